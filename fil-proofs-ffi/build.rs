@@ -0,0 +1,33 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Set to copy the generated header over the checked in `include/fil_proofs.h`.
+const UPDATE_HEADER_ENV: &str = "FIL_PROOFS_FFI_UPDATE_HEADER";
+
+// Generates the C header of the ABI into `OUT_DIR`. The header is checked in at
+// `include/fil_proofs.h`, which is only overwritten if `FIL_PROOFS_FFI_UPDATE_HEADER` is set, so
+// that building the crate never modifies the source tree.
+fn main() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("missing manifest dir"));
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("missing out dir"));
+
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed={}", UPDATE_HEADER_ENV);
+
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+        .expect("invalid cbindgen.toml");
+    let header = out_dir.join("fil_proofs.h");
+    cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("failed to generate C header")
+        .write_to_file(&header);
+
+    if env::var_os(UPDATE_HEADER_ENV).is_some() {
+        fs::copy(&header, crate_dir.join("include").join("fil_proofs.h"))
+            .expect("failed to update include/fil_proofs.h");
+    }
+}
@@ -0,0 +1,374 @@
+#ifndef FIL_PROOFS_H
+#define FIL_PROOFS_H
+
+/* Generated by cbindgen from fil-proofs-ffi, do not edit. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#define FIL_API_VERSION_1_0_0 0
+
+#define FIL_API_VERSION_1_1_0 1
+
+#define FIL_POST_TYPE_WINNING 0
+
+#define FIL_POST_TYPE_WINDOW 1
+
+// The outcome of a call.
+typedef enum FilStatus {
+  FIL_STATUS_OK = 0,
+  // An argument is null, not valid UTF-8 or otherwise malformed.
+  FIL_STATUS_INVALID_ARGUMENT = 1,
+  // The operation failed, e.g. because a file could not be read.
+  FIL_STATUS_ERROR = 2,
+  // The operation panicked. This is a bug in the library.
+  FIL_STATUS_PANIC = 3,
+} FilStatus;
+
+// A byte buffer owned by the library, e.g. a proof.
+typedef struct FilBytes FilBytes;
+
+// The output of `fil_seal_commit_phase1`.
+typedef struct FilCommitPhase1Output FilCommitPhase1Output;
+
+// The PoRep config of a sector size, porep id and API version.
+typedef struct FilPoRepConfig FilPoRepConfig;
+
+// The PoSt config of a sector size, PoSt type and API version.
+typedef struct FilPoStConfig FilPoStConfig;
+
+// The output of `fil_seal_pre_commit_phase1`.
+typedef struct FilPreCommitPhase1Output FilPreCommitPhase1Output;
+
+// The sealed sectors a PoSt is generated over.
+typedef struct FilPrivateReplicas FilPrivateReplicas;
+
+// The sectors a PoSt is verified against.
+typedef struct FilPublicReplicas FilPublicReplicas;
+
+// A piece of a sector, as returned by `fil_add_piece`.
+typedef struct FilPieceInfo {
+  uint8_t commitment[32];
+  // The unpadded size of the piece in bytes.
+  uint64_t size;
+} FilPieceInfo;
+
+// The commitments of a sealed sector, as returned by `fil_seal_pre_commit_phase2`.
+typedef struct FilPreCommitOutput {
+  uint8_t comm_r[32];
+  uint8_t comm_d[32];
+} FilPreCommitOutput;
+
+// Selects the sectors challenged by a Winning PoSt out of a set of `sector_set_size` sectors,
+// writing their indices to `out`. `out_len` must be the sector count of the config, 1 for
+// Winning PoSt.
+//
+// # Safety
+//
+// `config` must be a live handle, `randomness` and `prover_id` must point to 32 bytes and `out`
+// must point to `out_len` writable values.
+FilStatus fil_generate_winning_post_sector_challenge(const FilPoStConfig *config,
+                                                     const uint8_t (*randomness)[32],
+                                                     uint64_t sector_set_size,
+                                                     const uint8_t (*prover_id)[32],
+                                                     uint64_t *out,
+                                                     size_t out_len);
+
+// Generates a Winning PoSt over the challenged sectors in `replicas`.
+//
+// # Safety
+//
+// `config` and `replicas` must be live handles, `randomness` and `prover_id` must point to 32
+// bytes and `out` must be writable.
+FilStatus fil_generate_winning_post(const FilPoStConfig *config,
+                                    const uint8_t (*randomness)[32],
+                                    const uint8_t (*prover_id)[32],
+                                    const FilPrivateReplicas *replicas,
+                                    FilBytes **out);
+
+// Verifies a Winning PoSt over the sectors in `replicas`, and writes whether it is valid to
+// `out`.
+//
+// # Safety
+//
+// `config` and `replicas` must be live handles, `randomness` and `prover_id` must point to 32
+// bytes, `proof` must point to `proof_len` bytes and `out` must be writable.
+FilStatus fil_verify_winning_post(const FilPoStConfig *config,
+                                  const uint8_t (*randomness)[32],
+                                  const uint8_t (*prover_id)[32],
+                                  const FilPublicReplicas *replicas,
+                                  const uint8_t *proof,
+                                  size_t proof_len,
+                                  bool *out);
+
+// Generates a Window PoSt over all sectors in `replicas`, whose sector ids must be unique.
+//
+// # Safety
+//
+// `config` and `replicas` must be live handles, `randomness` and `prover_id` must point to 32
+// bytes and `out` must be writable.
+FilStatus fil_generate_window_post(const FilPoStConfig *config,
+                                   const uint8_t (*randomness)[32],
+                                   const uint8_t (*prover_id)[32],
+                                   const FilPrivateReplicas *replicas,
+                                   FilBytes **out);
+
+// Verifies a Window PoSt over the sectors in `replicas`, whose sector ids must be unique, and
+// writes whether it is valid to `out`.
+//
+// # Safety
+//
+// `config` and `replicas` must be live handles, `randomness` and `prover_id` must point to 32
+// bytes, `proof` must point to `proof_len` bytes and `out` must be writable.
+FilStatus fil_verify_window_post(const FilPoStConfig *config,
+                                 const uint8_t (*randomness)[32],
+                                 const uint8_t (*prover_id)[32],
+                                 const FilPublicReplicas *replicas,
+                                 const uint8_t *proof,
+                                 size_t proof_len,
+                                 bool *out);
+
+// Writes the piece at `source_path` with fr32 padding to the end of the staged sector at
+// `staged_path`, which already holds pieces of the unpadded sizes in `existing_piece_sizes`.
+// The staged sector is created if it does not exist.
+//
+// # Safety
+//
+// The paths must be nul terminated, `existing_piece_sizes` must point to
+// `existing_piece_sizes_len` values and `out` must be writable.
+FilStatus fil_add_piece(const char *source_path,
+                        const char *staged_path,
+                        uint64_t piece_size,
+                        const uint64_t *existing_piece_sizes,
+                        size_t existing_piece_sizes_len,
+                        FilPieceInfo *out);
+
+// Runs the first phase of sealing the staged sector at `staged_path` into `sealed_path`, which
+// must exist. `cache_path` must be an existing directory.
+//
+// # Safety
+//
+// `config` must be a live handle, the paths must be nul terminated, `prover_id` and `ticket`
+// must point to 32 bytes, `pieces` must point to `pieces_len` values and `out` must be
+// writable.
+FilStatus fil_seal_pre_commit_phase1(const FilPoRepConfig *config,
+                                     const char *cache_path,
+                                     const char *staged_path,
+                                     const char *sealed_path,
+                                     const uint8_t (*prover_id)[32],
+                                     uint64_t sector_id,
+                                     const uint8_t (*ticket)[32],
+                                     const FilPieceInfo *pieces,
+                                     size_t pieces_len,
+                                     FilPreCommitPhase1Output **out);
+
+// Runs the second phase of sealing, which builds the trees of the sealed sector and computes
+// its commitments.
+//
+// # Safety
+//
+// `config` and `phase1_output` must be live handles, the paths must be nul terminated and
+// `out` must be writable.
+FilStatus fil_seal_pre_commit_phase2(const FilPoRepConfig *config,
+                                     const FilPreCommitPhase1Output *phase1_output,
+                                     const char *cache_path,
+                                     const char *sealed_path,
+                                     FilPreCommitOutput *out);
+
+// Runs the first phase of proving a sealed sector, which generates the vanilla proofs for the
+// interactive randomness `seed`.
+//
+// # Safety
+//
+// `config` must be a live handle, the paths must be nul terminated, `prover_id`, `ticket` and
+// `seed` must point to 32 bytes, `pre_commit` must be readable, `pieces` must point to
+// `pieces_len` values and `out` must be writable.
+FilStatus fil_seal_commit_phase1(const FilPoRepConfig *config,
+                                 const char *cache_path,
+                                 const char *sealed_path,
+                                 const uint8_t (*prover_id)[32],
+                                 uint64_t sector_id,
+                                 const uint8_t (*ticket)[32],
+                                 const uint8_t (*seed)[32],
+                                 const FilPreCommitOutput *pre_commit,
+                                 const FilPieceInfo *pieces,
+                                 size_t pieces_len,
+                                 FilCommitPhase1Output **out);
+
+// Runs the second phase of proving a sealed sector, which generates the SNARK proof to be
+// verified with `fil_verify_seal`.
+//
+// # Safety
+//
+// `config` and `phase1_output` must be live handles, `prover_id` must point to 32 bytes and
+// `out` must be writable.
+FilStatus fil_seal_commit_phase2(const FilPoRepConfig *config,
+                                 const FilCommitPhase1Output *phase1_output,
+                                 const uint8_t (*prover_id)[32],
+                                 uint64_t sector_id,
+                                 FilBytes **out);
+
+// Verifies the seal proof of a sector, and writes whether it is valid to `out`.
+//
+// # Safety
+//
+// `config` must be a live handle, `comm_r`, `comm_d`, `prover_id`, `ticket` and `seed` must
+// point to 32 bytes, `proof` must point to `proof_len` bytes and `out` must be writable.
+FilStatus fil_verify_seal(const FilPoRepConfig *config,
+                          const uint8_t (*comm_r)[32],
+                          const uint8_t (*comm_d)[32],
+                          const uint8_t (*prover_id)[32],
+                          uint64_t sector_id,
+                          const uint8_t (*ticket)[32],
+                          const uint8_t (*seed)[32],
+                          const uint8_t *proof,
+                          size_t proof_len,
+                          bool *out);
+
+// Serializes `phase1_output`, e.g. to resume sealing in another process.
+//
+// # Safety
+//
+// `phase1_output` must be a live handle and `out` must be writable.
+FilStatus fil_pre_commit_phase1_output_to_bytes(const FilPreCommitPhase1Output *phase1_output,
+                                                FilBytes **out);
+
+// Restores the output of `fil_seal_pre_commit_phase1` for a sector of `config` from bytes
+// returned by `fil_pre_commit_phase1_output_to_bytes`.
+//
+// # Safety
+//
+// `config` must be a live handle, `data` must point to `len` bytes and `out` must be writable.
+FilStatus fil_pre_commit_phase1_output_from_bytes(const FilPoRepConfig *config,
+                                                  const uint8_t *data,
+                                                  size_t len,
+                                                  FilPreCommitPhase1Output **out);
+
+// # Safety
+//
+// `phase1_output` must be a handle returned by this library which was not freed yet, or null.
+void fil_pre_commit_phase1_output_free(FilPreCommitPhase1Output *phase1_output);
+
+// Serializes `phase1_output`, e.g. to generate the SNARK proof in another process.
+//
+// # Safety
+//
+// `phase1_output` must be a live handle and `out` must be writable.
+FilStatus fil_commit_phase1_output_to_bytes(const FilCommitPhase1Output *phase1_output,
+                                            FilBytes **out);
+
+// Restores the output of `fil_seal_commit_phase1` for a sector of `config` from bytes returned
+// by `fil_commit_phase1_output_to_bytes`.
+//
+// # Safety
+//
+// `config` must be a live handle, `data` must point to `len` bytes and `out` must be writable.
+FilStatus fil_commit_phase1_output_from_bytes(const FilPoRepConfig *config,
+                                              const uint8_t *data,
+                                              size_t len,
+                                              FilCommitPhase1Output **out);
+
+// # Safety
+//
+// `phase1_output` must be a handle returned by this library which was not freed yet, or null.
+void fil_commit_phase1_output_free(FilCommitPhase1Output *phase1_output);
+
+// Creates the PoRep config of a sector, see `FIL_API_VERSION_*` for `api_version`.
+//
+// # Safety
+//
+// `porep_id` must point to 32 bytes and `out` to a writable handle pointer.
+FilStatus fil_porep_config_new(uint64_t sector_size,
+                               const uint8_t (*porep_id)[32],
+                               uint32_t api_version,
+                               FilPoRepConfig **out);
+
+// # Safety
+//
+// `config` must be a handle returned by `fil_porep_config_new` which was not freed yet, or
+// null.
+void fil_porep_config_free(FilPoRepConfig *config);
+
+// Creates the config of Winning or Window PoSts over sectors of `sector_size`, see
+// `FIL_POST_TYPE_*` for `post_type` and `FIL_API_VERSION_*` for `api_version`.
+//
+// # Safety
+//
+// `out` must point to a writable handle pointer.
+FilStatus fil_post_config_new(uint64_t sector_size,
+                              uint32_t post_type,
+                              uint32_t api_version,
+                              FilPoStConfig **out);
+
+// # Safety
+//
+// `config` must be a handle returned by `fil_post_config_new` which was not freed yet, or
+// null.
+void fil_post_config_free(FilPoStConfig *config);
+
+// Creates an empty set of sealed sectors to generate a PoSt over.
+FilPrivateReplicas *fil_private_replicas_new(void);
+
+// Adds a sealed sector, whose replica is at `replica_path` and whose cache is at `cache_dir`.
+//
+// # Safety
+//
+// `replicas` must be a live handle, `comm_r` must point to 32 bytes and the paths must be nul
+// terminated.
+FilStatus fil_private_replicas_push(FilPrivateReplicas *replicas,
+                                    uint64_t sector_id,
+                                    const uint8_t (*comm_r)[32],
+                                    const char *cache_dir,
+                                    const char *replica_path);
+
+// # Safety
+//
+// `replicas` must be a handle returned by `fil_private_replicas_new` which was not freed yet,
+// or null.
+void fil_private_replicas_free(FilPrivateReplicas *replicas);
+
+// Creates an empty set of sectors to verify a PoSt against.
+FilPublicReplicas *fil_public_replicas_new(void);
+
+// Adds a sector with replica commitment `comm_r`.
+//
+// # Safety
+//
+// `replicas` must be a live handle and `comm_r` must point to 32 bytes.
+FilStatus fil_public_replicas_push(FilPublicReplicas *replicas,
+                                   uint64_t sector_id,
+                                   const uint8_t (*comm_r)[32]);
+
+// # Safety
+//
+// `replicas` must be a handle returned by `fil_public_replicas_new` which was not freed yet,
+// or null.
+void fil_public_replicas_free(FilPublicReplicas *replicas);
+
+// Returns the message of the last failed call on this thread, or null if no call failed yet.
+//
+// The string is owned by the library and stays valid until the next failed call on this
+// thread.
+const char *fil_last_error_message(void);
+
+// Returns a pointer to the contents of `bytes`, which stays valid until it is freed.
+//
+// # Safety
+//
+// `bytes` must be a live handle returned by this library.
+const uint8_t *fil_bytes_data(const FilBytes *bytes);
+
+// Returns the length of `bytes`, or 0 if it is null.
+//
+// # Safety
+//
+// `bytes` must be a live handle returned by this library, or null.
+size_t fil_bytes_len(const FilBytes *bytes);
+
+// # Safety
+//
+// `bytes` must be a handle returned by this library which was not freed yet, or null.
+void fil_bytes_free(FilBytes *bytes);
+
+#endif /* FIL_PROOFS_H */
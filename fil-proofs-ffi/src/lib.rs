@@ -0,0 +1,30 @@
+//! A C ABI for the sealing and PoSt APIs of `filecoin-proofs`, so that consumers in other
+//! languages don't need to maintain their own shim. The header is checked in at
+//! `include/fil_proofs.h`, see the README on how to regenerate it.
+//!
+//! All functions which can fail return a `FilStatus`. If it is not `FIL_STATUS_OK`, a
+//! description of the error can be retrieved with `fil_last_error_message`. Results are written
+//! to out-parameters, which are left untouched on failure. The accessors `fil_bytes_data` and
+//! `fil_bytes_len`, the `*_new` functions without arguments and the `*_free` functions cannot
+//! fail and return their result directly, or nothing.
+//!
+//! Configs, intermediate outputs and byte buffers are passed as opaque handles. Every handle
+//! returned by the library is owned by the caller and must be released with the matching
+//! `*_free` function exactly once. Handles are never consumed by the functions they are passed
+//! to.
+//!
+//! Sector sizes are dispatched to their tree shape by the library, so callers only ever deal
+//! with plain sector sizes.
+
+#![deny(clippy::all, clippy::perf, clippy::correctness, rust_2018_idioms)]
+#![warn(clippy::unwrap_used)]
+
+mod post;
+mod seal;
+mod types;
+mod util;
+
+pub use post::*;
+pub use seal::*;
+pub use types::*;
+pub use util::*;
@@ -0,0 +1,301 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use filecoin_proofs::{
+    generate_window_post, generate_winning_post, generate_winning_post_sector_challenge,
+    verify_window_post, verify_winning_post, with_shape, ChallengeSeed, MerkleTreeTrait,
+    PoStConfig, PoStType, PrivateReplicaInfo, ProverId, PublicReplicaInfo,
+};
+use storage_proofs_core::sector::SectorId;
+
+use crate::types::{FilPoStConfig, FilPrivateReplicas, FilPublicReplicas, PrivateReplica};
+use crate::util::{
+    arg, ffi_call, into_handle, invalid_argument, out_arg, slice_arg, FilBytes, FilStatus,
+};
+
+fn check_post_type(config: &PoStConfig, typ: PoStType) -> Result<()> {
+    if config.typ != typ {
+        return Err(invalid_argument(format!(
+            "{:?} PoSt config used for a {:?} PoSt",
+            config.typ, typ
+        )));
+    }
+
+    Ok(())
+}
+
+fn private_replicas<Tree: 'static + MerkleTreeTrait>(
+    replicas: &[PrivateReplica],
+) -> Result<Vec<(SectorId, PrivateReplicaInfo<Tree>)>> {
+    replicas
+        .iter()
+        .map(|replica| {
+            let info = PrivateReplicaInfo::new(
+                replica.replica_path.clone(),
+                replica.comm_r,
+                replica.cache_dir.clone(),
+            )?;
+            Ok((replica.sector_id, info))
+        })
+        .collect()
+}
+
+fn public_replicas(replicas: &FilPublicReplicas) -> Result<Vec<(SectorId, PublicReplicaInfo)>> {
+    replicas
+        .0
+        .iter()
+        .map(|(sector_id, comm_r)| Ok((*sector_id, PublicReplicaInfo::new(*comm_r)?)))
+        .collect()
+}
+
+/// Collects the replicas of a Window PoSt by sector id, which must be unique.
+fn replica_map<T>(replicas: Vec<(SectorId, T)>) -> Result<BTreeMap<SectorId, T>> {
+    let mut map = BTreeMap::new();
+    for (sector_id, replica) in replicas {
+        if map.insert(sector_id, replica).is_some() {
+            return Err(invalid_argument(format!(
+                "duplicate sector id {}",
+                u64::from(sector_id)
+            )));
+        }
+    }
+
+    Ok(map)
+}
+
+fn winning_post_sector_challenge<Tree: 'static + MerkleTreeTrait>(
+    config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    sector_set_size: u64,
+    prover_id: ProverId,
+) -> Result<Vec<u64>> {
+    generate_winning_post_sector_challenge::<Tree>(config, randomness, sector_set_size, prover_id)
+}
+
+/// Selects the sectors challenged by a Winning PoSt out of a set of `sector_set_size` sectors,
+/// writing their indices to `out`. `out_len` must be the sector count of the config, 1 for
+/// Winning PoSt.
+///
+/// # Safety
+///
+/// `config` must be a live handle, `randomness` and `prover_id` must point to 32 bytes and `out`
+/// must point to `out_len` writable values.
+#[no_mangle]
+pub unsafe extern "C" fn fil_generate_winning_post_sector_challenge(
+    config: *const FilPoStConfig,
+    randomness: *const [u8; 32],
+    sector_set_size: u64,
+    prover_id: *const [u8; 32],
+    out: *mut u64,
+    out_len: usize,
+) -> FilStatus {
+    ffi_call("fil_generate_winning_post_sector_challenge", || {
+        out_arg(out, "out")?;
+        let config = &arg(config, "config")?.0;
+        check_post_type(config, PoStType::Winning)?;
+        if out_len != config.sector_count {
+            return Err(invalid_argument(format!(
+                "out has room for {} challenges, but {} are generated",
+                out_len, config.sector_count
+            )));
+        }
+
+        let challenges = with_shape!(
+            u64::from(config.sector_size),
+            winning_post_sector_challenge,
+            config,
+            arg(randomness, "randomness")?,
+            sector_set_size,
+            *arg(prover_id, "prover_id")?
+        )?;
+        std::slice::from_raw_parts_mut(out, out_len).copy_from_slice(&challenges);
+
+        Ok(())
+    })
+}
+
+fn winning_post<Tree: 'static + MerkleTreeTrait>(
+    config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &[PrivateReplica],
+    prover_id: ProverId,
+) -> Result<Vec<u8>> {
+    let replicas = private_replicas::<Tree>(replicas)?;
+
+    generate_winning_post::<Tree>(config, randomness, &replicas, prover_id)
+}
+
+/// Generates a Winning PoSt over the challenged sectors in `replicas`.
+///
+/// # Safety
+///
+/// `config` and `replicas` must be live handles, `randomness` and `prover_id` must point to 32
+/// bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn fil_generate_winning_post(
+    config: *const FilPoStConfig,
+    randomness: *const [u8; 32],
+    prover_id: *const [u8; 32],
+    replicas: *const FilPrivateReplicas,
+    out: *mut *mut FilBytes,
+) -> FilStatus {
+    ffi_call("fil_generate_winning_post", || {
+        out_arg(out, "out")?;
+        let config = &arg(config, "config")?.0;
+        check_post_type(config, PoStType::Winning)?;
+
+        let proof = with_shape!(
+            u64::from(config.sector_size),
+            winning_post,
+            config,
+            arg(randomness, "randomness")?,
+            &arg(replicas, "replicas")?.0,
+            *arg(prover_id, "prover_id")?
+        )?;
+        out.write(into_handle(FilBytes(proof)));
+
+        Ok(())
+    })
+}
+
+fn verify_winning<Tree: 'static + MerkleTreeTrait>(
+    config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &FilPublicReplicas,
+    prover_id: ProverId,
+    proof: &[u8],
+) -> Result<bool> {
+    let replicas = public_replicas(replicas)?;
+
+    verify_winning_post::<Tree>(config, randomness, &replicas, prover_id, proof)
+}
+
+/// Verifies a Winning PoSt over the sectors in `replicas`, and writes whether it is valid to
+/// `out`.
+///
+/// # Safety
+///
+/// `config` and `replicas` must be live handles, `randomness` and `prover_id` must point to 32
+/// bytes, `proof` must point to `proof_len` bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn fil_verify_winning_post(
+    config: *const FilPoStConfig,
+    randomness: *const [u8; 32],
+    prover_id: *const [u8; 32],
+    replicas: *const FilPublicReplicas,
+    proof: *const u8,
+    proof_len: usize,
+    out: *mut bool,
+) -> FilStatus {
+    ffi_call("fil_verify_winning_post", || {
+        out_arg(out, "out")?;
+        let config = &arg(config, "config")?.0;
+        check_post_type(config, PoStType::Winning)?;
+
+        let valid = with_shape!(
+            u64::from(config.sector_size),
+            verify_winning,
+            config,
+            arg(randomness, "randomness")?,
+            arg(replicas, "replicas")?,
+            *arg(prover_id, "prover_id")?,
+            slice_arg(proof, proof_len, "proof")?
+        )?;
+        out.write(valid);
+
+        Ok(())
+    })
+}
+
+fn window_post<Tree: 'static + MerkleTreeTrait>(
+    config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &[PrivateReplica],
+    prover_id: ProverId,
+) -> Result<Vec<u8>> {
+    let replicas = replica_map(private_replicas::<Tree>(replicas)?)?;
+
+    generate_window_post::<Tree>(config, randomness, &replicas, prover_id)
+}
+
+/// Generates a Window PoSt over all sectors in `replicas`, whose sector ids must be unique.
+///
+/// # Safety
+///
+/// `config` and `replicas` must be live handles, `randomness` and `prover_id` must point to 32
+/// bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn fil_generate_window_post(
+    config: *const FilPoStConfig,
+    randomness: *const [u8; 32],
+    prover_id: *const [u8; 32],
+    replicas: *const FilPrivateReplicas,
+    out: *mut *mut FilBytes,
+) -> FilStatus {
+    ffi_call("fil_generate_window_post", || {
+        out_arg(out, "out")?;
+        let config = &arg(config, "config")?.0;
+        check_post_type(config, PoStType::Window)?;
+
+        let proof = with_shape!(
+            u64::from(config.sector_size),
+            window_post,
+            config,
+            arg(randomness, "randomness")?,
+            &arg(replicas, "replicas")?.0,
+            *arg(prover_id, "prover_id")?
+        )?;
+        out.write(into_handle(FilBytes(proof)));
+
+        Ok(())
+    })
+}
+
+fn verify_window<Tree: 'static + MerkleTreeTrait>(
+    config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &FilPublicReplicas,
+    prover_id: ProverId,
+    proof: &[u8],
+) -> Result<bool> {
+    let replicas = replica_map(public_replicas(replicas)?)?;
+
+    verify_window_post::<Tree>(config, randomness, &replicas, prover_id, proof)
+}
+
+/// Verifies a Window PoSt over the sectors in `replicas`, whose sector ids must be unique, and
+/// writes whether it is valid to `out`.
+///
+/// # Safety
+///
+/// `config` and `replicas` must be live handles, `randomness` and `prover_id` must point to 32
+/// bytes, `proof` must point to `proof_len` bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn fil_verify_window_post(
+    config: *const FilPoStConfig,
+    randomness: *const [u8; 32],
+    prover_id: *const [u8; 32],
+    replicas: *const FilPublicReplicas,
+    proof: *const u8,
+    proof_len: usize,
+    out: *mut bool,
+) -> FilStatus {
+    ffi_call("fil_verify_window_post", || {
+        out_arg(out, "out")?;
+        let config = &arg(config, "config")?.0;
+        check_post_type(config, PoStType::Window)?;
+
+        let valid = with_shape!(
+            u64::from(config.sector_size),
+            verify_window,
+            config,
+            arg(randomness, "randomness")?,
+            arg(replicas, "replicas")?,
+            *arg(prover_id, "prover_id")?,
+            slice_arg(proof, proof_len, "proof")?
+        )?;
+        out.write(valid);
+
+        Ok(())
+    })
+}
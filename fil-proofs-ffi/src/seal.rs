@@ -0,0 +1,554 @@
+use std::fs::{File, OpenOptions};
+use std::os::raw::c_char;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use filecoin_proofs::{
+    add_piece, seal_commit_phase1, seal_commit_phase2, seal_pre_commit_phase1,
+    seal_pre_commit_phase2, verify_seal, with_shape, MerkleTreeTrait, PieceInfo, PoRepConfig,
+    ProverId, SealCommitPhase1Output, SealPreCommitOutput, SealPreCommitPhase1Output, Ticket,
+    UnpaddedBytesAmount,
+};
+use storage_proofs_core::sector::SectorId;
+
+use crate::types::{FilPieceInfo, FilPoRepConfig};
+use crate::util::{
+    arg, ffi_call, free_handle, into_handle, invalid_argument, out_arg, path_arg, slice_arg,
+    FilBytes, FilStatus,
+};
+
+/// The commitments of a sealed sector, as returned by `fil_seal_pre_commit_phase2`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilPreCommitOutput {
+    pub comm_r: [u8; 32],
+    pub comm_d: [u8; 32],
+}
+
+/// The output of `fil_seal_pre_commit_phase1`.
+pub struct FilPreCommitPhase1Output {
+    sector_size: u64,
+    /// The JSON serialization of the `SealPreCommitPhase1Output` of the sector's tree shape.
+    json: Vec<u8>,
+}
+
+/// The output of `fil_seal_commit_phase1`.
+pub struct FilCommitPhase1Output {
+    sector_size: u64,
+    /// The JSON serialization of the `SealCommitPhase1Output` of the sector's tree shape.
+    json: Vec<u8>,
+}
+
+fn check_sector_size(config: &PoRepConfig, sector_size: u64) -> Result<()> {
+    if u64::from(config.sector_size) != sector_size {
+        return Err(invalid_argument(format!(
+            "output of a {} byte sector used with a config for {} byte sectors",
+            sector_size,
+            u64::from(config.sector_size)
+        )));
+    }
+
+    Ok(())
+}
+
+unsafe fn piece_infos_arg(
+    pieces: *const FilPieceInfo,
+    pieces_len: usize,
+) -> Result<Vec<PieceInfo>> {
+    Ok(slice_arg(pieces, pieces_len, "pieces")?
+        .iter()
+        .map(|piece| PieceInfo::from(*piece))
+        .collect())
+}
+
+/// Writes the piece at `source_path` with fr32 padding to the end of the staged sector at
+/// `staged_path`, which already holds pieces of the unpadded sizes in `existing_piece_sizes`.
+/// The staged sector is created if it does not exist.
+///
+/// # Safety
+///
+/// The paths must be nul terminated, `existing_piece_sizes` must point to
+/// `existing_piece_sizes_len` values and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn fil_add_piece(
+    source_path: *const c_char,
+    staged_path: *const c_char,
+    piece_size: u64,
+    existing_piece_sizes: *const u64,
+    existing_piece_sizes_len: usize,
+    out: *mut FilPieceInfo,
+) -> FilStatus {
+    ffi_call("fil_add_piece", || {
+        out_arg(out, "out")?;
+        let source_path = path_arg(source_path, "source_path")?;
+        let staged_path = path_arg(staged_path, "staged_path")?;
+        let piece_lengths: Vec<_> = slice_arg(
+            existing_piece_sizes,
+            existing_piece_sizes_len,
+            "existing_piece_sizes",
+        )?
+        .iter()
+        .map(|size| UnpaddedBytesAmount(*size))
+        .collect();
+
+        let source = File::open(&source_path)
+            .with_context(|| format!("could not open {:?}", source_path))?;
+        let target = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&staged_path)
+            .with_context(|| format!("could not open {:?}", staged_path))?;
+
+        let (piece_info, _) = add_piece(
+            source,
+            target,
+            UnpaddedBytesAmount(piece_size),
+            &piece_lengths,
+        )?;
+        out.write(FilPieceInfo::from(piece_info));
+
+        Ok(())
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn pre_commit_phase1<Tree: 'static + MerkleTreeTrait>(
+    config: &PoRepConfig,
+    cache_path: &Path,
+    staged_path: &Path,
+    sealed_path: &Path,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    piece_infos: &[PieceInfo],
+) -> Result<Vec<u8>> {
+    let output = seal_pre_commit_phase1::<_, _, _, Tree>(
+        *config,
+        cache_path,
+        staged_path,
+        sealed_path,
+        prover_id,
+        sector_id,
+        ticket,
+        piece_infos,
+    )?;
+
+    Ok(serde_json::to_vec(&output)?)
+}
+
+/// Runs the first phase of sealing the staged sector at `staged_path` into `sealed_path`, which
+/// must exist. `cache_path` must be an existing directory.
+///
+/// # Safety
+///
+/// `config` must be a live handle, the paths must be nul terminated, `prover_id` and `ticket`
+/// must point to 32 bytes, `pieces` must point to `pieces_len` values and `out` must be
+/// writable.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn fil_seal_pre_commit_phase1(
+    config: *const FilPoRepConfig,
+    cache_path: *const c_char,
+    staged_path: *const c_char,
+    sealed_path: *const c_char,
+    prover_id: *const [u8; 32],
+    sector_id: u64,
+    ticket: *const [u8; 32],
+    pieces: *const FilPieceInfo,
+    pieces_len: usize,
+    out: *mut *mut FilPreCommitPhase1Output,
+) -> FilStatus {
+    ffi_call("fil_seal_pre_commit_phase1", || {
+        out_arg(out, "out")?;
+        let config = &arg(config, "config")?.0;
+        let cache_path = path_arg(cache_path, "cache_path")?;
+        let staged_path = path_arg(staged_path, "staged_path")?;
+        let sealed_path = path_arg(sealed_path, "sealed_path")?;
+        let prover_id = *arg(prover_id, "prover_id")?;
+        let ticket = *arg(ticket, "ticket")?;
+        let piece_infos = piece_infos_arg(pieces, pieces_len)?;
+
+        let sector_size = u64::from(config.sector_size);
+        let json = with_shape!(
+            sector_size,
+            pre_commit_phase1,
+            config,
+            &cache_path,
+            &staged_path,
+            &sealed_path,
+            prover_id,
+            SectorId::from(sector_id),
+            ticket,
+            &piece_infos
+        )?;
+        out.write(into_handle(FilPreCommitPhase1Output { sector_size, json }));
+
+        Ok(())
+    })
+}
+
+fn pre_commit_phase2<Tree: 'static + MerkleTreeTrait>(
+    config: &PoRepConfig,
+    phase1_output: &FilPreCommitPhase1Output,
+    cache_path: &Path,
+    sealed_path: &Path,
+) -> Result<SealPreCommitOutput> {
+    let phase1_output: SealPreCommitPhase1Output<Tree> =
+        serde_json::from_slice(&phase1_output.json)?;
+
+    seal_pre_commit_phase2(*config, phase1_output, cache_path, sealed_path)
+}
+
+/// Runs the second phase of sealing, which builds the trees of the sealed sector and computes
+/// its commitments.
+///
+/// # Safety
+///
+/// `config` and `phase1_output` must be live handles, the paths must be nul terminated and
+/// `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn fil_seal_pre_commit_phase2(
+    config: *const FilPoRepConfig,
+    phase1_output: *const FilPreCommitPhase1Output,
+    cache_path: *const c_char,
+    sealed_path: *const c_char,
+    out: *mut FilPreCommitOutput,
+) -> FilStatus {
+    ffi_call("fil_seal_pre_commit_phase2", || {
+        out_arg(out, "out")?;
+        let config = &arg(config, "config")?.0;
+        let phase1_output = arg(phase1_output, "phase1_output")?;
+        let cache_path = path_arg(cache_path, "cache_path")?;
+        let sealed_path = path_arg(sealed_path, "sealed_path")?;
+        check_sector_size(config, phase1_output.sector_size)?;
+
+        let output = with_shape!(
+            phase1_output.sector_size,
+            pre_commit_phase2,
+            config,
+            phase1_output,
+            &cache_path,
+            &sealed_path
+        )?;
+        out.write(FilPreCommitOutput {
+            comm_r: output.comm_r,
+            comm_d: output.comm_d,
+        });
+
+        Ok(())
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn commit_phase1<Tree: 'static + MerkleTreeTrait>(
+    config: &PoRepConfig,
+    cache_path: &Path,
+    sealed_path: &Path,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    pre_commit: SealPreCommitOutput,
+    piece_infos: &[PieceInfo],
+) -> Result<Vec<u8>> {
+    let output = seal_commit_phase1::<_, Tree>(
+        *config,
+        cache_path,
+        sealed_path,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        pre_commit,
+        piece_infos,
+    )?;
+
+    Ok(serde_json::to_vec(&output)?)
+}
+
+/// Runs the first phase of proving a sealed sector, which generates the vanilla proofs for the
+/// interactive randomness `seed`.
+///
+/// # Safety
+///
+/// `config` must be a live handle, the paths must be nul terminated, `prover_id`, `ticket` and
+/// `seed` must point to 32 bytes, `pre_commit` must be readable, `pieces` must point to
+/// `pieces_len` values and `out` must be writable.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn fil_seal_commit_phase1(
+    config: *const FilPoRepConfig,
+    cache_path: *const c_char,
+    sealed_path: *const c_char,
+    prover_id: *const [u8; 32],
+    sector_id: u64,
+    ticket: *const [u8; 32],
+    seed: *const [u8; 32],
+    pre_commit: *const FilPreCommitOutput,
+    pieces: *const FilPieceInfo,
+    pieces_len: usize,
+    out: *mut *mut FilCommitPhase1Output,
+) -> FilStatus {
+    ffi_call("fil_seal_commit_phase1", || {
+        out_arg(out, "out")?;
+        let config = &arg(config, "config")?.0;
+        let cache_path = path_arg(cache_path, "cache_path")?;
+        let sealed_path = path_arg(sealed_path, "sealed_path")?;
+        let prover_id = *arg(prover_id, "prover_id")?;
+        let ticket = *arg(ticket, "ticket")?;
+        let seed = *arg(seed, "seed")?;
+        let pre_commit = arg(pre_commit, "pre_commit")?;
+        let piece_infos = piece_infos_arg(pieces, pieces_len)?;
+
+        let sector_size = u64::from(config.sector_size);
+        let json = with_shape!(
+            sector_size,
+            commit_phase1,
+            config,
+            &cache_path,
+            &sealed_path,
+            prover_id,
+            SectorId::from(sector_id),
+            ticket,
+            seed,
+            SealPreCommitOutput {
+                comm_r: pre_commit.comm_r,
+                comm_d: pre_commit.comm_d,
+            },
+            &piece_infos
+        )?;
+        out.write(into_handle(FilCommitPhase1Output { sector_size, json }));
+
+        Ok(())
+    })
+}
+
+fn commit_phase2<Tree: 'static + MerkleTreeTrait>(
+    config: &PoRepConfig,
+    phase1_output: &FilCommitPhase1Output,
+    prover_id: ProverId,
+    sector_id: SectorId,
+) -> Result<Vec<u8>> {
+    let phase1_output: SealCommitPhase1Output<Tree> = serde_json::from_slice(&phase1_output.json)?;
+    let output = seal_commit_phase2(*config, phase1_output, prover_id, sector_id)?;
+
+    Ok(output.proof)
+}
+
+/// Runs the second phase of proving a sealed sector, which generates the SNARK proof to be
+/// verified with `fil_verify_seal`.
+///
+/// # Safety
+///
+/// `config` and `phase1_output` must be live handles, `prover_id` must point to 32 bytes and
+/// `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn fil_seal_commit_phase2(
+    config: *const FilPoRepConfig,
+    phase1_output: *const FilCommitPhase1Output,
+    prover_id: *const [u8; 32],
+    sector_id: u64,
+    out: *mut *mut FilBytes,
+) -> FilStatus {
+    ffi_call("fil_seal_commit_phase2", || {
+        out_arg(out, "out")?;
+        let config = &arg(config, "config")?.0;
+        let phase1_output = arg(phase1_output, "phase1_output")?;
+        let prover_id = *arg(prover_id, "prover_id")?;
+        check_sector_size(config, phase1_output.sector_size)?;
+
+        let proof = with_shape!(
+            phase1_output.sector_size,
+            commit_phase2,
+            config,
+            phase1_output,
+            prover_id,
+            SectorId::from(sector_id)
+        )?;
+        out.write(into_handle(FilBytes(proof)));
+
+        Ok(())
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn verify<Tree: 'static + MerkleTreeTrait>(
+    config: &PoRepConfig,
+    comm_r: [u8; 32],
+    comm_d: [u8; 32],
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    proof: &[u8],
+) -> Result<bool> {
+    verify_seal::<Tree>(
+        *config, comm_r, comm_d, prover_id, sector_id, ticket, seed, proof,
+    )
+}
+
+/// Verifies the seal proof of a sector, and writes whether it is valid to `out`.
+///
+/// # Safety
+///
+/// `config` must be a live handle, `comm_r`, `comm_d`, `prover_id`, `ticket` and `seed` must
+/// point to 32 bytes, `proof` must point to `proof_len` bytes and `out` must be writable.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn fil_verify_seal(
+    config: *const FilPoRepConfig,
+    comm_r: *const [u8; 32],
+    comm_d: *const [u8; 32],
+    prover_id: *const [u8; 32],
+    sector_id: u64,
+    ticket: *const [u8; 32],
+    seed: *const [u8; 32],
+    proof: *const u8,
+    proof_len: usize,
+    out: *mut bool,
+) -> FilStatus {
+    ffi_call("fil_verify_seal", || {
+        out_arg(out, "out")?;
+        let config = &arg(config, "config")?.0;
+
+        let valid = with_shape!(
+            u64::from(config.sector_size),
+            verify,
+            config,
+            *arg(comm_r, "comm_r")?,
+            *arg(comm_d, "comm_d")?,
+            *arg(prover_id, "prover_id")?,
+            SectorId::from(sector_id),
+            *arg(ticket, "ticket")?,
+            *arg(seed, "seed")?,
+            slice_arg(proof, proof_len, "proof")?
+        )?;
+        out.write(valid);
+
+        Ok(())
+    })
+}
+
+/// Serializes `phase1_output`, e.g. to resume sealing in another process.
+///
+/// # Safety
+///
+/// `phase1_output` must be a live handle and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn fil_pre_commit_phase1_output_to_bytes(
+    phase1_output: *const FilPreCommitPhase1Output,
+    out: *mut *mut FilBytes,
+) -> FilStatus {
+    ffi_call("fil_pre_commit_phase1_output_to_bytes", || {
+        out_arg(out, "out")?;
+        let phase1_output = arg(phase1_output, "phase1_output")?;
+        out.write(into_handle(FilBytes(phase1_output.json.clone())));
+
+        Ok(())
+    })
+}
+
+fn check_pre_commit_phase1_output<Tree: 'static + MerkleTreeTrait>(json: &[u8]) -> Result<()> {
+    serde_json::from_slice::<SealPreCommitPhase1Output<Tree>>(json)
+        .map(|_| ())
+        .map_err(|err| invalid_argument(format!("invalid pre commit phase 1 output: {}", err)))
+}
+
+/// Restores the output of `fil_seal_pre_commit_phase1` for a sector of `config` from bytes
+/// returned by `fil_pre_commit_phase1_output_to_bytes`.
+///
+/// # Safety
+///
+/// `config` must be a live handle, `data` must point to `len` bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn fil_pre_commit_phase1_output_from_bytes(
+    config: *const FilPoRepConfig,
+    data: *const u8,
+    len: usize,
+    out: *mut *mut FilPreCommitPhase1Output,
+) -> FilStatus {
+    ffi_call("fil_pre_commit_phase1_output_from_bytes", || {
+        out_arg(out, "out")?;
+        let sector_size = u64::from(arg(config, "config")?.0.sector_size);
+        let json = slice_arg(data, len, "data")?;
+
+        with_shape!(sector_size, check_pre_commit_phase1_output, json)?;
+        out.write(into_handle(FilPreCommitPhase1Output {
+            sector_size,
+            json: json.to_vec(),
+        }));
+
+        Ok(())
+    })
+}
+
+/// # Safety
+///
+/// `phase1_output` must be a handle returned by this library which was not freed yet, or null.
+#[no_mangle]
+pub unsafe extern "C" fn fil_pre_commit_phase1_output_free(
+    phase1_output: *mut FilPreCommitPhase1Output,
+) {
+    free_handle(phase1_output)
+}
+
+/// Serializes `phase1_output`, e.g. to generate the SNARK proof in another process.
+///
+/// # Safety
+///
+/// `phase1_output` must be a live handle and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn fil_commit_phase1_output_to_bytes(
+    phase1_output: *const FilCommitPhase1Output,
+    out: *mut *mut FilBytes,
+) -> FilStatus {
+    ffi_call("fil_commit_phase1_output_to_bytes", || {
+        out_arg(out, "out")?;
+        let phase1_output = arg(phase1_output, "phase1_output")?;
+        out.write(into_handle(FilBytes(phase1_output.json.clone())));
+
+        Ok(())
+    })
+}
+
+fn check_commit_phase1_output<Tree: 'static + MerkleTreeTrait>(json: &[u8]) -> Result<()> {
+    serde_json::from_slice::<SealCommitPhase1Output<Tree>>(json)
+        .map(|_| ())
+        .map_err(|err| invalid_argument(format!("invalid commit phase 1 output: {}", err)))
+}
+
+/// Restores the output of `fil_seal_commit_phase1` for a sector of `config` from bytes returned
+/// by `fil_commit_phase1_output_to_bytes`.
+///
+/// # Safety
+///
+/// `config` must be a live handle, `data` must point to `len` bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn fil_commit_phase1_output_from_bytes(
+    config: *const FilPoRepConfig,
+    data: *const u8,
+    len: usize,
+    out: *mut *mut FilCommitPhase1Output,
+) -> FilStatus {
+    ffi_call("fil_commit_phase1_output_from_bytes", || {
+        out_arg(out, "out")?;
+        let sector_size = u64::from(arg(config, "config")?.0.sector_size);
+        let json = slice_arg(data, len, "data")?;
+
+        with_shape!(sector_size, check_commit_phase1_output, json)?;
+        out.write(into_handle(FilCommitPhase1Output {
+            sector_size,
+            json: json.to_vec(),
+        }));
+
+        Ok(())
+    })
+}
+
+/// # Safety
+///
+/// `phase1_output` must be a handle returned by this library which was not freed yet, or null.
+#[no_mangle]
+pub unsafe extern "C" fn fil_commit_phase1_output_free(phase1_output: *mut FilCommitPhase1Output) {
+    free_handle(phase1_output)
+}
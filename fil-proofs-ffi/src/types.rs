@@ -0,0 +1,250 @@
+use std::os::raw::c_char;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use filecoin_proofs::{
+    sector_shape, Commitment, PieceInfo, PoRepConfig, PoRepProofPartitions, PoStConfig, PoStType,
+    SectorSize, UnpaddedBytesAmount, WINDOW_POST_CHALLENGE_COUNT, WINNING_POST_CHALLENGE_COUNT,
+    WINNING_POST_SECTOR_COUNT,
+};
+use storage_proofs_core::{api_version::ApiVersion, sector::SectorId};
+
+use crate::util::{
+    arg, arg_mut, ffi_call, free_handle, into_handle, invalid_argument, out_arg, path_arg,
+    FilStatus,
+};
+
+pub const FIL_API_VERSION_1_0_0: u32 = 0;
+pub const FIL_API_VERSION_1_1_0: u32 = 1;
+
+pub const FIL_POST_TYPE_WINNING: u32 = 0;
+pub const FIL_POST_TYPE_WINDOW: u32 = 1;
+
+fn api_version(api_version: u32) -> Result<ApiVersion> {
+    match api_version {
+        FIL_API_VERSION_1_0_0 => Ok(ApiVersion::V1_0_0),
+        FIL_API_VERSION_1_1_0 => Ok(ApiVersion::V1_1_0),
+        other => Err(invalid_argument(format!("unknown api version {}", other))),
+    }
+}
+
+fn checked_sector_shape(sector_size: u64) -> Result<()> {
+    sector_shape(sector_size)
+        .map(|_| ())
+        .map_err(|_| invalid_argument(format!("unsupported sector size {}", sector_size)))
+}
+
+/// A piece of a sector, as returned by `fil_add_piece`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilPieceInfo {
+    pub commitment: [u8; 32],
+    /// The unpadded size of the piece in bytes.
+    pub size: u64,
+}
+
+impl From<FilPieceInfo> for PieceInfo {
+    fn from(piece_info: FilPieceInfo) -> Self {
+        PieceInfo {
+            commitment: piece_info.commitment,
+            size: UnpaddedBytesAmount(piece_info.size),
+        }
+    }
+}
+
+impl From<PieceInfo> for FilPieceInfo {
+    fn from(piece_info: PieceInfo) -> Self {
+        FilPieceInfo {
+            commitment: piece_info.commitment,
+            size: u64::from(piece_info.size),
+        }
+    }
+}
+
+/// The PoRep config of a sector size, porep id and API version.
+pub struct FilPoRepConfig(pub(crate) PoRepConfig);
+
+/// Creates the PoRep config of a sector, see `FIL_API_VERSION_*` for `api_version`.
+///
+/// # Safety
+///
+/// `porep_id` must point to 32 bytes and `out` to a writable handle pointer.
+#[no_mangle]
+pub unsafe extern "C" fn fil_porep_config_new(
+    sector_size: u64,
+    porep_id: *const [u8; 32],
+    api_version: u32,
+    out: *mut *mut FilPoRepConfig,
+) -> FilStatus {
+    ffi_call("fil_porep_config_new", || {
+        out_arg(out, "out")?;
+        checked_sector_shape(sector_size)?;
+
+        let config = PoRepConfig {
+            sector_size: SectorSize(sector_size),
+            partitions: PoRepProofPartitions(sector_shape(sector_size)?.porep_partitions),
+            porep_id: *arg(porep_id, "porep_id")?,
+            api_version: self::api_version(api_version)?,
+        };
+        out.write(into_handle(FilPoRepConfig(config)));
+
+        Ok(())
+    })
+}
+
+/// # Safety
+///
+/// `config` must be a handle returned by `fil_porep_config_new` which was not freed yet, or
+/// null.
+#[no_mangle]
+pub unsafe extern "C" fn fil_porep_config_free(config: *mut FilPoRepConfig) {
+    free_handle(config)
+}
+
+/// The PoSt config of a sector size, PoSt type and API version.
+pub struct FilPoStConfig(pub(crate) PoStConfig);
+
+/// Creates the config of Winning or Window PoSts over sectors of `sector_size`, see
+/// `FIL_POST_TYPE_*` for `post_type` and `FIL_API_VERSION_*` for `api_version`.
+///
+/// # Safety
+///
+/// `out` must point to a writable handle pointer.
+#[no_mangle]
+pub unsafe extern "C" fn fil_post_config_new(
+    sector_size: u64,
+    post_type: u32,
+    api_version: u32,
+    out: *mut *mut FilPoStConfig,
+) -> FilStatus {
+    ffi_call("fil_post_config_new", || {
+        out_arg(out, "out")?;
+        checked_sector_shape(sector_size)?;
+
+        let (typ, challenge_count, sector_count) = match post_type {
+            FIL_POST_TYPE_WINNING => (
+                PoStType::Winning,
+                WINNING_POST_CHALLENGE_COUNT,
+                WINNING_POST_SECTOR_COUNT,
+            ),
+            FIL_POST_TYPE_WINDOW => (
+                PoStType::Window,
+                WINDOW_POST_CHALLENGE_COUNT,
+                sector_shape(sector_size)?.window_post_sector_count,
+            ),
+            other => return Err(invalid_argument(format!("unknown post type {}", other))),
+        };
+
+        let config = PoStConfig {
+            sector_size: SectorSize(sector_size),
+            challenge_count,
+            sector_count,
+            typ,
+            priority: false,
+            api_version: self::api_version(api_version)?,
+        };
+        out.write(into_handle(FilPoStConfig(config)));
+
+        Ok(())
+    })
+}
+
+/// # Safety
+///
+/// `config` must be a handle returned by `fil_post_config_new` which was not freed yet, or
+/// null.
+#[no_mangle]
+pub unsafe extern "C" fn fil_post_config_free(config: *mut FilPoStConfig) {
+    free_handle(config)
+}
+
+pub(crate) struct PrivateReplica {
+    pub(crate) sector_id: SectorId,
+    pub(crate) comm_r: Commitment,
+    pub(crate) cache_dir: PathBuf,
+    pub(crate) replica_path: PathBuf,
+}
+
+/// The sealed sectors a PoSt is generated over.
+pub struct FilPrivateReplicas(pub(crate) Vec<PrivateReplica>);
+
+/// Creates an empty set of sealed sectors to generate a PoSt over.
+#[no_mangle]
+pub extern "C" fn fil_private_replicas_new() -> *mut FilPrivateReplicas {
+    into_handle(FilPrivateReplicas(Vec::new()))
+}
+
+/// Adds a sealed sector, whose replica is at `replica_path` and whose cache is at `cache_dir`.
+///
+/// # Safety
+///
+/// `replicas` must be a live handle, `comm_r` must point to 32 bytes and the paths must be nul
+/// terminated.
+#[no_mangle]
+pub unsafe extern "C" fn fil_private_replicas_push(
+    replicas: *mut FilPrivateReplicas,
+    sector_id: u64,
+    comm_r: *const [u8; 32],
+    cache_dir: *const c_char,
+    replica_path: *const c_char,
+) -> FilStatus {
+    ffi_call("fil_private_replicas_push", || {
+        let replicas = arg_mut(replicas, "replicas")?;
+        replicas.0.push(PrivateReplica {
+            sector_id: SectorId::from(sector_id),
+            comm_r: *arg(comm_r, "comm_r")?,
+            cache_dir: path_arg(cache_dir, "cache_dir")?,
+            replica_path: path_arg(replica_path, "replica_path")?,
+        });
+
+        Ok(())
+    })
+}
+
+/// # Safety
+///
+/// `replicas` must be a handle returned by `fil_private_replicas_new` which was not freed yet,
+/// or null.
+#[no_mangle]
+pub unsafe extern "C" fn fil_private_replicas_free(replicas: *mut FilPrivateReplicas) {
+    free_handle(replicas)
+}
+
+/// The sectors a PoSt is verified against.
+pub struct FilPublicReplicas(pub(crate) Vec<(SectorId, Commitment)>);
+
+/// Creates an empty set of sectors to verify a PoSt against.
+#[no_mangle]
+pub extern "C" fn fil_public_replicas_new() -> *mut FilPublicReplicas {
+    into_handle(FilPublicReplicas(Vec::new()))
+}
+
+/// Adds a sector with replica commitment `comm_r`.
+///
+/// # Safety
+///
+/// `replicas` must be a live handle and `comm_r` must point to 32 bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fil_public_replicas_push(
+    replicas: *mut FilPublicReplicas,
+    sector_id: u64,
+    comm_r: *const [u8; 32],
+) -> FilStatus {
+    ffi_call("fil_public_replicas_push", || {
+        let replicas = arg_mut(replicas, "replicas")?;
+        replicas
+            .0
+            .push((SectorId::from(sector_id), *arg(comm_r, "comm_r")?));
+
+        Ok(())
+    })
+}
+
+/// # Safety
+///
+/// `replicas` must be a handle returned by `fil_public_replicas_new` which was not freed yet,
+/// or null.
+#[no_mangle]
+pub unsafe extern "C" fn fil_public_replicas_free(replicas: *mut FilPublicReplicas) {
+    free_handle(replicas)
+}
@@ -0,0 +1,240 @@
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+
+use anyhow::{Error, Result};
+use log::error;
+
+/// The outcome of a call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilStatus {
+    Ok = 0,
+    /// An argument is null, not valid UTF-8 or otherwise malformed.
+    InvalidArgument = 1,
+    /// The operation failed, e.g. because a file could not be read.
+    Error = 2,
+    /// The operation panicked. This is a bug in the library.
+    Panic = 3,
+}
+
+/// An error caused by the arguments of a call, rather than by the operation itself.
+#[derive(Debug)]
+pub(crate) struct InvalidArgument(pub(crate) String);
+
+impl fmt::Display for InvalidArgument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for InvalidArgument {}
+
+pub(crate) fn invalid_argument(message: impl Into<String>) -> Error {
+    InvalidArgument(message.into()).into()
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+fn set_last_error(message: String) {
+    // Interior nul bytes would truncate the message, so they are dropped.
+    let message = CString::new(message.replace('\0', "")).expect("nul bytes were removed");
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+}
+
+/// Returns the message of the last failed call on this thread, or null if no call failed yet.
+///
+/// The string is owned by the library and stays valid until the next failed call on this
+/// thread.
+#[no_mangle]
+pub extern "C" fn fil_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last_error| match &*last_error.borrow() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// Runs the body of the call `name`, converting errors and panics into a status.
+pub(crate) fn ffi_call<F: FnOnce() -> Result<()>>(name: &str, f: F) -> FilStatus {
+    let (status, message) = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return FilStatus::Ok,
+        Ok(Err(err)) if err.is::<InvalidArgument>() => (FilStatus::InvalidArgument, err),
+        Ok(Err(err)) => (FilStatus::Error, err),
+        Err(panic) => {
+            let message = if let Some(message) = panic.downcast_ref::<&str>() {
+                message.to_string()
+            } else if let Some(message) = panic.downcast_ref::<String>() {
+                message.clone()
+            } else {
+                "unknown panic".to_string()
+            };
+            (FilStatus::Panic, Error::msg(message))
+        }
+    };
+
+    let message = format!("{}: {:#}", name, message);
+    error!("{}", message);
+    set_last_error(message);
+
+    status
+}
+
+/// Dereferences a pointer argument.
+pub(crate) unsafe fn arg<'a, T>(ptr: *const T, name: &str) -> Result<&'a T> {
+    ptr.as_ref()
+        .ok_or_else(|| invalid_argument(format!("{} is null", name)))
+}
+
+/// Dereferences a pointer to a handle which is modified by the call.
+pub(crate) unsafe fn arg_mut<'a, T>(ptr: *mut T, name: &str) -> Result<&'a mut T> {
+    ptr.as_mut()
+        .ok_or_else(|| invalid_argument(format!("{} is null", name)))
+}
+
+/// Borrows an array argument of `len` elements. `ptr` may only be null if `len` is 0.
+pub(crate) unsafe fn slice_arg<'a, T>(ptr: *const T, len: usize, name: &str) -> Result<&'a [T]> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    if ptr.is_null() {
+        return Err(invalid_argument(format!("{} is null", name)));
+    }
+
+    Ok(std::slice::from_raw_parts(ptr, len))
+}
+
+/// Converts a nul terminated UTF-8 path argument.
+pub(crate) unsafe fn path_arg(ptr: *const c_char, name: &str) -> Result<PathBuf> {
+    if ptr.is_null() {
+        return Err(invalid_argument(format!("{} is null", name)));
+    }
+
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map(PathBuf::from)
+        .map_err(|_| invalid_argument(format!("{} is not valid UTF-8", name)))
+}
+
+/// Checks an out-parameter up front, so that it is not found to be null only after the work is
+/// done.
+pub(crate) fn out_arg<T>(ptr: *mut T, name: &str) -> Result<()> {
+    if ptr.is_null() {
+        return Err(invalid_argument(format!("{} is null", name)));
+    }
+
+    Ok(())
+}
+
+/// Moves `value` to the heap, to be released by the matching `*_free` function.
+pub(crate) fn into_handle<T>(value: T) -> *mut T {
+    Box::into_raw(Box::new(value))
+}
+
+/// Releases a handle returned by `into_handle`. Null is ignored.
+pub(crate) unsafe fn free_handle<T>(ptr: *mut T) {
+    if !ptr.is_null() {
+        drop(Box::from_raw(ptr));
+    }
+}
+
+/// A byte buffer owned by the library, e.g. a proof.
+pub struct FilBytes(pub(crate) Vec<u8>);
+
+/// Returns a pointer to the contents of `bytes`, which stays valid until it is freed.
+///
+/// # Safety
+///
+/// `bytes` must be a live handle returned by this library.
+#[no_mangle]
+pub unsafe extern "C" fn fil_bytes_data(bytes: *const FilBytes) -> *const u8 {
+    match bytes.as_ref() {
+        Some(bytes) => bytes.0.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Returns the length of `bytes`, or 0 if it is null.
+///
+/// # Safety
+///
+/// `bytes` must be a live handle returned by this library, or null.
+#[no_mangle]
+pub unsafe extern "C" fn fil_bytes_len(bytes: *const FilBytes) -> usize {
+    bytes.as_ref().map(|bytes| bytes.0.len()).unwrap_or(0)
+}
+
+/// # Safety
+///
+/// `bytes` must be a handle returned by this library which was not freed yet, or null.
+#[no_mangle]
+pub unsafe extern "C" fn fil_bytes_free(bytes: *mut FilBytes) {
+    free_handle(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anyhow::bail;
+
+    unsafe fn last_error() -> String {
+        CStr::from_ptr(fil_last_error_message())
+            .to_str()
+            .expect("invalid error message")
+            .to_string()
+    }
+
+    #[test]
+    fn test_ffi_call_statuses() {
+        assert_eq!(ffi_call("ok", || Ok(())), FilStatus::Ok);
+
+        assert_eq!(
+            ffi_call("invalid", || Err(invalid_argument("bad size"))),
+            FilStatus::InvalidArgument
+        );
+        assert_eq!(unsafe { last_error() }, "invalid: bad size");
+
+        assert_eq!(
+            ffi_call("failing", || bail!("disk\0 error")),
+            FilStatus::Error
+        );
+        assert_eq!(unsafe { last_error() }, "failing: disk error");
+
+        assert_eq!(
+            ffi_call("panicking", || panic!("bug {}", 1)),
+            FilStatus::Panic
+        );
+        assert_eq!(unsafe { last_error() }, "panicking: bug 1");
+
+        // Successful calls leave the last error alone.
+        assert_eq!(ffi_call("ok", || Ok(())), FilStatus::Ok);
+        assert_eq!(unsafe { last_error() }, "panicking: bug 1");
+    }
+
+    #[test]
+    fn test_args() {
+        unsafe {
+            assert!(arg::<u8>(ptr::null(), "x").is_err());
+            assert_eq!(*arg(&1u8, "x").expect("arg failure"), 1);
+
+            assert!(slice_arg::<u8>(ptr::null(), 0, "xs")
+                .expect("slice_arg failure")
+                .is_empty());
+            assert!(slice_arg::<u8>(ptr::null(), 1, "xs").is_err());
+
+            let path = CString::new("/tmp/sector").expect("CString failure");
+            assert_eq!(
+                path_arg(path.as_ptr(), "path").expect("path_arg failure"),
+                PathBuf::from("/tmp/sector")
+            );
+            let invalid = [0xffu8 as c_char, 0];
+            assert!(path_arg(invalid.as_ptr(), "path").is_err());
+        }
+    }
+}
@@ -0,0 +1,477 @@
+use std::ffi::{CStr, CString};
+use std::io::Write;
+use std::path::Path;
+use std::ptr;
+use std::slice;
+
+use fil_proofs_ffi::*;
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use tempfile::{tempdir, NamedTempFile};
+
+const SECTOR_SIZE_2_KIB: u64 = 2048;
+const PIECE_SIZE_2_KIB: u64 = 2032;
+
+const ARBITRARY_POREP_ID: [u8; 32] = [128; 32];
+
+const TEST_SEED: [u8; 16] = [
+    0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06, 0xbc, 0xe5,
+];
+
+fn c_path(path: &Path) -> CString {
+    CString::new(path.to_str().expect("invalid path")).expect("CString failure")
+}
+
+unsafe fn last_error() -> String {
+    CStr::from_ptr(fil_last_error_message())
+        .to_str()
+        .expect("invalid error message")
+        .to_string()
+}
+
+unsafe fn bytes(bytes: *const FilBytes) -> Vec<u8> {
+    slice::from_raw_parts(fil_bytes_data(bytes), fil_bytes_len(bytes)).to_vec()
+}
+
+/// Returns 32 random bytes which are a valid field element.
+fn random_fr_bytes<R: Rng>(rng: &mut R) -> [u8; 32] {
+    let mut bytes: [u8; 32] = rng.gen();
+    bytes[31] = 0;
+    bytes
+}
+
+#[test]
+#[ignore]
+fn test_seal_and_prove_2kib() {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let prover_id = random_fr_bytes(rng);
+    let ticket: [u8; 32] = rng.gen();
+    let seed: [u8; 32] = rng.gen();
+    let randomness: [u8; 32] = rng.gen();
+    let sector_id = rng.gen::<u64>();
+
+    let piece_bytes: Vec<u8> = (0..PIECE_SIZE_2_KIB).map(|_| rng.gen()).collect();
+    let mut piece_file = NamedTempFile::new().expect("failed to create piece file");
+    piece_file
+        .write_all(&piece_bytes)
+        .expect("failed to write piece file");
+    piece_file.flush().expect("failed to flush piece file");
+
+    let staged_file = NamedTempFile::new().expect("failed to create staged file");
+    let sealed_file = NamedTempFile::new().expect("failed to create sealed file");
+    let cache_dir = tempdir().expect("failed to create cache dir");
+
+    let piece_path = c_path(piece_file.path());
+    let staged_path = c_path(staged_file.path());
+    let sealed_path = c_path(sealed_file.path());
+    let cache_path = c_path(cache_dir.path());
+
+    unsafe {
+        let mut porep_config = ptr::null_mut();
+        assert_eq!(
+            fil_porep_config_new(
+                SECTOR_SIZE_2_KIB,
+                &ARBITRARY_POREP_ID,
+                FIL_API_VERSION_1_1_0,
+                &mut porep_config
+            ),
+            FilStatus::Ok
+        );
+
+        let mut piece_info = FilPieceInfo {
+            commitment: [0; 32],
+            size: 0,
+        };
+        assert_eq!(
+            fil_add_piece(
+                piece_path.as_ptr(),
+                staged_path.as_ptr(),
+                PIECE_SIZE_2_KIB,
+                ptr::null(),
+                0,
+                &mut piece_info
+            ),
+            FilStatus::Ok
+        );
+        assert_eq!(piece_info.size, PIECE_SIZE_2_KIB);
+
+        let mut pre_commit_phase1 = ptr::null_mut();
+        assert_eq!(
+            fil_seal_pre_commit_phase1(
+                porep_config,
+                cache_path.as_ptr(),
+                staged_path.as_ptr(),
+                sealed_path.as_ptr(),
+                &prover_id,
+                sector_id,
+                &ticket,
+                &piece_info,
+                1,
+                &mut pre_commit_phase1
+            ),
+            FilStatus::Ok,
+            "{}",
+            last_error()
+        );
+
+        // The output survives a roundtrip through its serialization.
+        let mut serialized = ptr::null_mut();
+        assert_eq!(
+            fil_pre_commit_phase1_output_to_bytes(pre_commit_phase1, &mut serialized),
+            FilStatus::Ok
+        );
+        fil_pre_commit_phase1_output_free(pre_commit_phase1);
+        let mut pre_commit_phase1 = ptr::null_mut();
+        assert_eq!(
+            fil_pre_commit_phase1_output_from_bytes(
+                porep_config,
+                fil_bytes_data(serialized),
+                fil_bytes_len(serialized),
+                &mut pre_commit_phase1
+            ),
+            FilStatus::Ok
+        );
+        fil_bytes_free(serialized);
+
+        let mut pre_commit = FilPreCommitOutput {
+            comm_r: [0; 32],
+            comm_d: [0; 32],
+        };
+        assert_eq!(
+            fil_seal_pre_commit_phase2(
+                porep_config,
+                pre_commit_phase1,
+                cache_path.as_ptr(),
+                sealed_path.as_ptr(),
+                &mut pre_commit
+            ),
+            FilStatus::Ok,
+            "{}",
+            last_error()
+        );
+        fil_pre_commit_phase1_output_free(pre_commit_phase1);
+
+        let mut commit_phase1 = ptr::null_mut();
+        assert_eq!(
+            fil_seal_commit_phase1(
+                porep_config,
+                cache_path.as_ptr(),
+                sealed_path.as_ptr(),
+                &prover_id,
+                sector_id,
+                &ticket,
+                &seed,
+                &pre_commit,
+                &piece_info,
+                1,
+                &mut commit_phase1
+            ),
+            FilStatus::Ok,
+            "{}",
+            last_error()
+        );
+
+        let mut serialized = ptr::null_mut();
+        assert_eq!(
+            fil_commit_phase1_output_to_bytes(commit_phase1, &mut serialized),
+            FilStatus::Ok
+        );
+        fil_commit_phase1_output_free(commit_phase1);
+        let mut commit_phase1 = ptr::null_mut();
+        assert_eq!(
+            fil_commit_phase1_output_from_bytes(
+                porep_config,
+                fil_bytes_data(serialized),
+                fil_bytes_len(serialized),
+                &mut commit_phase1
+            ),
+            FilStatus::Ok
+        );
+        fil_bytes_free(serialized);
+
+        let mut seal_proof = ptr::null_mut();
+        assert_eq!(
+            fil_seal_commit_phase2(
+                porep_config,
+                commit_phase1,
+                &prover_id,
+                sector_id,
+                &mut seal_proof
+            ),
+            FilStatus::Ok,
+            "{}",
+            last_error()
+        );
+        fil_commit_phase1_output_free(commit_phase1);
+
+        let proof = bytes(seal_proof);
+        fil_bytes_free(seal_proof);
+
+        let mut valid = false;
+        assert_eq!(
+            fil_verify_seal(
+                porep_config,
+                &pre_commit.comm_r,
+                &pre_commit.comm_d,
+                &prover_id,
+                sector_id,
+                &ticket,
+                &seed,
+                proof.as_ptr(),
+                proof.len(),
+                &mut valid
+            ),
+            FilStatus::Ok,
+            "{}",
+            last_error()
+        );
+        assert!(valid, "seal proof is invalid");
+
+        let mut valid = true;
+        assert_eq!(
+            fil_verify_seal(
+                porep_config,
+                &pre_commit.comm_r,
+                &pre_commit.comm_d,
+                &prover_id,
+                sector_id.wrapping_add(1),
+                &ticket,
+                &seed,
+                proof.as_ptr(),
+                proof.len(),
+                &mut valid
+            ),
+            FilStatus::Ok,
+            "{}",
+            last_error()
+        );
+        assert!(!valid, "seal proof of another sector is valid");
+
+        fil_porep_config_free(porep_config);
+
+        let mut post_config = ptr::null_mut();
+        assert_eq!(
+            fil_post_config_new(
+                SECTOR_SIZE_2_KIB,
+                FIL_POST_TYPE_WINNING,
+                FIL_API_VERSION_1_1_0,
+                &mut post_config
+            ),
+            FilStatus::Ok
+        );
+
+        let mut challenged = [u64::max_value(); 1];
+        assert_eq!(
+            fil_generate_winning_post_sector_challenge(
+                post_config,
+                &randomness,
+                1,
+                &prover_id,
+                challenged.as_mut_ptr(),
+                challenged.len()
+            ),
+            FilStatus::Ok,
+            "{}",
+            last_error()
+        );
+        assert_eq!(challenged, [0]);
+
+        let private_replicas = fil_private_replicas_new();
+        assert_eq!(
+            fil_private_replicas_push(
+                private_replicas,
+                sector_id,
+                &pre_commit.comm_r,
+                cache_path.as_ptr(),
+                sealed_path.as_ptr()
+            ),
+            FilStatus::Ok
+        );
+
+        let mut post_proof = ptr::null_mut();
+        assert_eq!(
+            fil_generate_winning_post(
+                post_config,
+                &randomness,
+                &prover_id,
+                private_replicas,
+                &mut post_proof
+            ),
+            FilStatus::Ok,
+            "{}",
+            last_error()
+        );
+        fil_private_replicas_free(private_replicas);
+
+        let proof = bytes(post_proof);
+        fil_bytes_free(post_proof);
+
+        let public_replicas = fil_public_replicas_new();
+        assert_eq!(
+            fil_public_replicas_push(public_replicas, sector_id, &pre_commit.comm_r),
+            FilStatus::Ok
+        );
+
+        let mut valid = false;
+        assert_eq!(
+            fil_verify_winning_post(
+                post_config,
+                &randomness,
+                &prover_id,
+                public_replicas,
+                proof.as_ptr(),
+                proof.len(),
+                &mut valid
+            ),
+            FilStatus::Ok,
+            "{}",
+            last_error()
+        );
+        assert!(valid, "winning post proof is invalid");
+
+        // A Winning PoSt config can't be used for a Window PoSt.
+        let mut valid = false;
+        assert_eq!(
+            fil_verify_window_post(
+                post_config,
+                &randomness,
+                &prover_id,
+                public_replicas,
+                proof.as_ptr(),
+                proof.len(),
+                &mut valid
+            ),
+            FilStatus::InvalidArgument
+        );
+
+        fil_public_replicas_free(public_replicas);
+        fil_post_config_free(post_config);
+    }
+}
+
+#[test]
+fn test_invalid_arguments() {
+    unsafe {
+        let mut config = ptr::null_mut();
+        assert_eq!(
+            fil_porep_config_new(
+                1234,
+                &ARBITRARY_POREP_ID,
+                FIL_API_VERSION_1_1_0,
+                &mut config
+            ),
+            FilStatus::InvalidArgument
+        );
+        assert!(config.is_null());
+        assert_eq!(
+            last_error(),
+            "fil_porep_config_new: unsupported sector size 1234"
+        );
+
+        assert_eq!(
+            fil_post_config_new(SECTOR_SIZE_2_KIB, 2, FIL_API_VERSION_1_1_0, ptr::null_mut()),
+            FilStatus::InvalidArgument
+        );
+        assert_eq!(last_error(), "fil_post_config_new: out is null");
+
+        let mut config = ptr::null_mut();
+        assert_eq!(
+            fil_post_config_new(SECTOR_SIZE_2_KIB, 2, FIL_API_VERSION_1_1_0, &mut config),
+            FilStatus::InvalidArgument
+        );
+        assert_eq!(last_error(), "fil_post_config_new: unknown post type 2");
+
+        assert_eq!(
+            fil_porep_config_new(SECTOR_SIZE_2_KIB, &ARBITRARY_POREP_ID, 7, &mut config),
+            FilStatus::InvalidArgument
+        );
+        assert_eq!(last_error(), "fil_porep_config_new: unknown api version 7");
+
+        let mut config = ptr::null_mut();
+        assert_eq!(
+            fil_porep_config_new(
+                SECTOR_SIZE_2_KIB,
+                &ARBITRARY_POREP_ID,
+                FIL_API_VERSION_1_1_0,
+                &mut config
+            ),
+            FilStatus::Ok
+        );
+
+        let garbage = b"not an output";
+        let mut output = ptr::null_mut();
+        assert_eq!(
+            fil_pre_commit_phase1_output_from_bytes(
+                config,
+                garbage.as_ptr(),
+                garbage.len(),
+                &mut output
+            ),
+            FilStatus::InvalidArgument
+        );
+        assert!(output.is_null());
+
+        let missing = c_path(Path::new("/nonexistent/piece"));
+        let mut piece_info = FilPieceInfo {
+            commitment: [0; 32],
+            size: 0,
+        };
+        assert_eq!(
+            fil_add_piece(
+                missing.as_ptr(),
+                missing.as_ptr(),
+                PIECE_SIZE_2_KIB,
+                ptr::null(),
+                0,
+                &mut piece_info
+            ),
+            FilStatus::Error
+        );
+        assert!(last_error().starts_with("fil_add_piece: could not open"));
+
+        fil_porep_config_free(config);
+
+        let mut post_config = ptr::null_mut();
+        assert_eq!(
+            fil_post_config_new(
+                SECTOR_SIZE_2_KIB,
+                FIL_POST_TYPE_WINDOW,
+                FIL_API_VERSION_1_1_0,
+                &mut post_config
+            ),
+            FilStatus::Ok
+        );
+        let public_replicas = fil_public_replicas_new();
+        for _ in 0..2 {
+            assert_eq!(
+                fil_public_replicas_push(public_replicas, 1, &[1; 32]),
+                FilStatus::Ok
+            );
+        }
+        let mut valid = true;
+        assert_eq!(
+            fil_verify_window_post(
+                post_config,
+                &[0; 32],
+                &[0; 32],
+                public_replicas,
+                garbage.as_ptr(),
+                garbage.len(),
+                &mut valid
+            ),
+            FilStatus::InvalidArgument
+        );
+        assert_eq!(
+            last_error(),
+            "fil_verify_window_post: duplicate sector id 1"
+        );
+        fil_public_replicas_free(public_replicas);
+        fil_post_config_free(post_config);
+
+        // Freeing null is a no-op.
+        fil_porep_config_free(ptr::null_mut());
+        fil_bytes_free(ptr::null_mut());
+        assert_eq!(fil_bytes_len(ptr::null()), 0);
+    }
+}
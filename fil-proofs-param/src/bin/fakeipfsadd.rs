@@ -18,6 +18,12 @@ enum Cli {
         file_path: String,
         #[structopt(short = "Q", help = "Simulates the -Q argument to `ipfs add`.")]
         quieter: bool,
+        #[structopt(
+            short = "n",
+            long = "only-hash",
+            help = "Simulates the -n argument to `ipfs add`."
+        )]
+        only_hash: bool,
     },
 }
 
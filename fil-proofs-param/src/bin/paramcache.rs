@@ -5,10 +5,11 @@ use std::str::FromStr;
 use dialoguer::{theme::ColorfulTheme, MultiSelect};
 use filecoin_proofs::{
     constants::{
-        DefaultPieceHasher, POREP_PARTITIONS, PUBLISHED_SECTOR_SIZES, WINDOW_POST_CHALLENGE_COUNT,
-        WINDOW_POST_SECTOR_COUNT, WINNING_POST_CHALLENGE_COUNT, WINNING_POST_SECTOR_COUNT,
+        DefaultPieceHasher, PUBLISHED_SECTOR_SIZES, WINDOW_POST_CHALLENGE_COUNT,
+        WINNING_POST_CHALLENGE_COUNT, WINNING_POST_SECTOR_COUNT,
     },
     parameters::{public_params, window_post_public_params, winning_post_public_params},
+    registry::sector_shape,
     types::{PaddedBytesAmount, PoRepConfig, PoRepProofPartitions, PoStConfig, SectorSize},
     with_shape, PoStType,
 };
@@ -160,11 +161,9 @@ fn generate_params_post(sector_size: u64, api_version: ApiVersion) {
         &PoStConfig {
             sector_size: SectorSize(sector_size),
             challenge_count: WINDOW_POST_CHALLENGE_COUNT,
-            sector_count: *WINDOW_POST_SECTOR_COUNT
-                .read()
-                .expect("WINDOW_POST_SECTOR_COUNT poisoned")
-                .get(&sector_size)
-                .expect("unknown sector size"),
+            sector_count: sector_shape(sector_size)
+                .expect("unknown sector size")
+                .window_post_sector_count,
             typ: PoStType::Window,
             priority: true,
             api_version,
@@ -179,11 +178,9 @@ fn generate_params_porep(sector_size: u64, api_version: ApiVersion) {
         PoRepConfig {
             sector_size: SectorSize(sector_size),
             partitions: PoRepProofPartitions(
-                *POREP_PARTITIONS
-                    .read()
-                    .expect("POREP_PARTITIONS poisoned")
-                    .get(&sector_size)
-                    .expect("unknown sector size"),
+                sector_shape(sector_size)
+                    .expect("unknown sector size")
+                    .porep_partitions,
             ),
             porep_id: [0; 32],
             api_version,
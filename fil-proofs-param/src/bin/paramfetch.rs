@@ -1,11 +1,16 @@
 use std::env;
 use std::fs::{create_dir_all, rename, File};
 use std::io::{self, copy, stderr, stdout, Read, Stdout, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{exit, Command};
 
 use anyhow::{ensure, Context, Result};
 use dialoguer::{theme::ColorfulTheme, MultiSelect, Select};
+use fil_proofs_param::{
+    bundle::install_bundle,
+    fetch::HttpFetcher,
+    verify::{repair_cache, verify_cache, VerifyReport},
+};
 use filecoin_proofs::param::{
     get_digest_for_file_within_cache, get_full_path_for_file_within_cache, has_extension,
 };
@@ -15,19 +20,24 @@ use lazy_static::lazy_static;
 use log::{error, info, trace, warn};
 use pbr::{ProgressBar, Units};
 use reqwest::{blocking::Client, header, Proxy, Url};
-use storage_proofs_core::parameter_cache::{
-    parameter_cache_dir, parameter_cache_dir_name, ParameterMap, GROTH_PARAMETER_EXT,
+use storage_proofs_core::{
+    parameter_cache::{
+        parameter_cache_dir, parameter_cache_dir_name, ParameterData, ParameterMap,
+        GROTH_PARAMETER_EXT,
+    },
+    settings::ProofsContext,
 };
 use structopt::StructOpt;
 use tar::Archive;
 
 lazy_static! {
     static ref CLI_ABOUT: String = format!(
-        "Downloads missing or outdated Groth parameter files from ipfs using ipget.\n\n\
+        "Downloads missing or outdated Groth parameter files from ipfs using ipget, or over \
+        HTTP(S) from gateways or mirrors.\n\n\
 
         Set the $FIL_PROOFS_PARAMETER_CACHE env-var to specify the path to the parameter cache
         directory (location where params are written), otherwise params will be written to '{}'.",
-        parameter_cache_dir_name(),
+        parameter_cache_dir_name(&ProofsContext::default()),
     );
 }
 
@@ -154,19 +164,20 @@ fn get_filenames_requiring_download(
         .into_iter()
         .filter(|filename| {
             trace!("determining if file is out of date: {}", filename);
-            let path = get_full_path_for_file_within_cache(filename);
+            let path = get_full_path_for_file_within_cache(&ProofsContext::default(), filename);
             if !path.exists() {
                 trace!("file not found, marking for download");
                 return true;
             };
             trace!("params file found");
-            let calculated_digest = match get_digest_for_file_within_cache(&filename) {
-                Ok(digest) => digest,
-                Err(e) => {
-                    warn!("failed to hash file {}, marking for download", e);
-                    return true;
-                }
-            };
+            let calculated_digest =
+                match get_digest_for_file_within_cache(&ProofsContext::default(), &filename) {
+                    Ok(digest) => digest,
+                    Err(e) => {
+                        warn!("failed to hash file {}, marking for download", e);
+                        return true;
+                    }
+                };
             let expected_digest = &parameter_map[filename].digest;
             if &calculated_digest == expected_digest {
                 trace!("file is up to date");
@@ -215,6 +226,91 @@ fn download_file_with_ipget(
     Ok(())
 }
 
+/// The backend used to download files.
+enum Fetcher {
+    Ipget(PathBuf),
+    Http(HttpFetcher),
+}
+
+impl Fetcher {
+    fn fetch(
+        &self,
+        cache_dir: &Path,
+        filename: &str,
+        data: &ParameterData,
+        ipget_args: &Option<String>,
+        verbose: bool,
+    ) -> Result<()> {
+        match self {
+            Fetcher::Http(http_fetcher) => {
+                info!("downloading params file over http: {}", filename);
+                http_fetcher.fetch(cache_dir, filename, data).map(|_| ())
+            }
+            Fetcher::Ipget(ipget_path) => {
+                info!("downloading params file with ipget: {}", filename);
+                let path = cache_dir.join(filename);
+                download_file_with_ipget(&data.cid, &path, ipget_path, ipget_args, verbose)
+            }
+        }
+    }
+}
+
+/// Set up the download backend selected on the command line.
+fn build_fetcher(cli: &Cli) -> Fetcher {
+    if cli.http {
+        let http_fetcher = if cli.mirrors.is_empty() {
+            HttpFetcher::with_default_mirrors()
+        } else {
+            HttpFetcher::new(cli.mirrors.clone())
+        }
+        .expect("invalid mirrors");
+        trace!("using mirrors: {:?}", http_fetcher.url_templates());
+        Fetcher::Http(http_fetcher)
+    } else {
+        let ipget_path = match cli.ipget_bin {
+            Some(ref ipget_path) => {
+                let ipget_path = PathBuf::from(ipget_path);
+                if !ipget_path.exists() {
+                    error!(
+                        "provided ipget binary not found: {}, exiting",
+                        ipget_path.display()
+                    );
+                    exit(1);
+                }
+                ipget_path
+            }
+            None => {
+                let ipget_version = cli
+                    .ipget_version
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_IPGET_VERSION.to_string());
+                let ipget_path = PathBuf::from(ipget_path(&ipget_version));
+                if !ipget_path.exists() {
+                    info!("ipget binary not found: {}", ipget_path.display());
+                    download_ipget(&ipget_version, cli.verbose).expect("ipget download failed");
+                }
+                ipget_path
+            }
+        };
+        trace!("using ipget binary: {}", ipget_path.display());
+        Fetcher::Ipget(ipget_path)
+    }
+}
+
+/// Log the outcome of verifying the param cache.
+fn log_report(report: &VerifyReport) {
+    info!("{} files are valid", report.valid.len());
+    for filename in &report.missing {
+        warn!("missing file: {}", filename);
+    }
+    for filename in &report.mismatched {
+        warn!("file has unexpected digest: {}", filename);
+    }
+    for filename in &report.unknown {
+        info!("unknown file: {}", filename);
+    }
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(name = "paramfetch", version = "1.1", about = CLI_ABOUT.as_str())]
 struct Cli {
@@ -248,6 +344,46 @@ struct Cli {
     sector_sizes: Option<Vec<u64>>,
     #[structopt(long, short = "v")]
     verbose: bool,
+    #[structopt(
+        long = "from-bundle",
+        value_name = "PATH",
+        conflicts_with_all = &["json", "verify-only", "repair", "http"],
+        help = "Install the files of a bundle written by `parampublish --bundle` (a directory or \
+            tarball) into the parameter cache, after verifying their digests."
+    )]
+    from_bundle: Option<PathBuf>,
+    #[structopt(
+        long = "verify-only",
+        conflicts_with = "repair",
+        help = "Hash the cached files in parallel and report missing, mismatched and unknown \
+            files, without downloading anything."
+    )]
+    verify_only: bool,
+    #[structopt(
+        long,
+        conflicts_with = "verify-only",
+        help = "Hash the cached files in parallel and download only the missing or mismatched \
+            ones, without prompting."
+    )]
+    repair: bool,
+    #[structopt(
+        long,
+        conflicts_with_all = &["ipget-bin", "ipget-version", "ipget-args"],
+        help = "Download files over HTTP(S) instead of using ipget. Interrupted downloads are \
+            resumed on retry."
+    )]
+    http: bool,
+    #[structopt(
+        long = "mirror",
+        value_name = "URL TEMPLATE",
+        number_of_values = 1,
+        requires = "http",
+        long_help = "A gateway or mirror to download from when using --http, in which {cid} and \
+            {filename} are replaced with the values from the JSON file. May be given multiple \
+            times, mirrors are tried in order. Defaults to https://proofs.filecoin.io/ipfs/{cid} \
+            and https://ipfs.io/ipfs/{cid}."
+    )]
+    mirrors: Vec<String>,
     #[structopt(
         long = "ipget-bin",
         short = "i",
@@ -280,11 +416,28 @@ pub fn main() {
 
     let cli = Cli::from_args();
 
+    if let Some(ref bundle_path) = cli.from_bundle {
+        let cache_dir = parameter_cache_dir(&ProofsContext::default());
+        info!(
+            "installing bundle {} into {}",
+            bundle_path.display(),
+            cache_dir.display()
+        );
+        match install_bundle(bundle_path, &cache_dir) {
+            Ok(filenames) => info!("installed {} files, exiting", filenames.len()),
+            Err(e) => {
+                error!("failed to install bundle:\n{:?}\nexiting", e);
+                exit(1);
+            }
+        }
+        return;
+    }
+
     // Parse parameters.json file.
     let parameter_map: ParameterMap = match cli.json {
-        Some(json_path) => {
+        Some(ref json_path) => {
             trace!("using json file: {}", json_path);
-            let mut json_file = File::open(&json_path)
+            let mut json_file = File::open(json_path)
                 .map_err(|e| {
                     error!("failed to open json file, exiting\n{:?}", e);
                     exit(1);
@@ -328,6 +481,36 @@ pub fn main() {
         });
     }
 
+    let cache_dir = parameter_cache_dir(&ProofsContext::default());
+
+    if cli.verify_only || cli.repair {
+        info!(
+            "verifying {} files in {}",
+            filenames.len(),
+            cache_dir.display()
+        );
+        let mut report = verify_cache(&cache_dir, &parameter_map, &filenames)
+            .expect("failed to verify param cache");
+        log_report(&report);
+
+        if cli.repair && !report.is_intact() {
+            let fetcher = build_fetcher(&cli);
+            create_dir_all(&cache_dir).expect("failed to create param cache dir");
+            report = repair_cache(&cache_dir, &parameter_map, &report, |filename, data| {
+                fetcher.fetch(&cache_dir, filename, data, &cli.ipget_args, cli.verbose)
+            })
+            .expect("failed to repair param cache");
+            log_report(&report);
+        }
+
+        if !report.is_intact() {
+            error!("param cache is not intact, exiting");
+            exit(1);
+        }
+        info!("param cache is intact, exiting");
+        return;
+    }
+
     // Determine which files are outdated.
     filenames = get_filenames_requiring_download(&parameter_map, filenames);
     if filenames.is_empty() {
@@ -368,43 +551,17 @@ pub fn main() {
         return;
     }
 
-    let ipget_path = match cli.ipget_bin {
-        Some(ipget_path) => {
-            let ipget_path = PathBuf::from(ipget_path);
-            if !ipget_path.exists() {
-                error!(
-                    "provided ipget binary not found: {}, exiting",
-                    ipget_path.display()
-                );
-                exit(1);
-            }
-            ipget_path
-        }
-        None => {
-            let ipget_version = cli
-                .ipget_version
-                .unwrap_or(DEFAULT_IPGET_VERSION.to_string());
-            let ipget_path = PathBuf::from(ipget_path(&ipget_version));
-            if !ipget_path.exists() {
-                info!("ipget binary not found: {}", ipget_path.display());
-                download_ipget(&ipget_version, cli.verbose).expect("ipget download failed");
-            }
-            ipget_path
-        }
-    };
-    trace!("using ipget binary: {}", ipget_path.display());
+    let fetcher = build_fetcher(&cli);
 
     trace!("creating param cache dir(s) if they don't exist");
-    create_dir_all(parameter_cache_dir()).expect("failed to create param cache dir");
+    create_dir_all(&cache_dir).expect("failed to create param cache dir");
 
     loop {
         for filename in &filenames {
-            info!("downloading params file with ipget: {}", filename);
-            let path = get_full_path_for_file_within_cache(filename);
-            match download_file_with_ipget(
-                &parameter_map[filename].cid,
-                &path,
-                &ipget_path,
+            match fetcher.fetch(
+                &cache_dir,
+                filename,
+                &parameter_map[filename],
                 &cli.ipget_args,
                 cli.verbose,
             ) {
@@ -2,11 +2,12 @@ use std::collections::BTreeMap;
 use std::env;
 use std::fs::{read_dir, File};
 use std::io::{stderr, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{exit, Command};
 
 use anyhow::{ensure, Context, Result};
 use dialoguer::{theme::ColorfulTheme, MultiSelect, Select};
+use fil_proofs_param::bundle::write_bundle;
 use filecoin_proofs::{
     param::{
         add_extension, filename_to_parameter_id, get_digest_for_file_within_cache,
@@ -19,9 +20,12 @@ use humansize::{file_size_opts, FileSize};
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use log::{error, info, trace, warn};
-use storage_proofs_core::parameter_cache::{
-    parameter_cache_dir, parameter_cache_dir_name, ParameterData, ParameterMap,
-    GROTH_PARAMETER_EXT, PARAMETER_METADATA_EXT, VERIFYING_KEY_EXT,
+use storage_proofs_core::{
+    parameter_cache::{
+        get_parameter_data_from_id, parameter_cache_dir, parameter_cache_dir_name, ParameterData,
+        ParameterMap, GROTH_PARAMETER_EXT, PARAMETER_METADATA_EXT, VERIFYING_KEY_EXT,
+    },
+    settings::ProofsContext,
 };
 use structopt::StructOpt;
 
@@ -29,7 +33,7 @@ lazy_static! {
     static ref CLI_ABOUT: String = format!(
         "Publish param files found in the cache directory specified by the env-var \
         $FIL_PROOFS_PARAMETER_CACHE (or if the env-var is not set, the dir: {}) to ipfs",
-        parameter_cache_dir_name(),
+        parameter_cache_dir_name(&ProofsContext::default()),
     );
 }
 
@@ -79,7 +83,7 @@ fn is_well_formed_filename(filename: &str) -> bool {
 }
 
 fn get_filenames_in_cache_dir() -> Vec<String> {
-    let path = parameter_cache_dir();
+    let path = parameter_cache_dir(&ProofsContext::default());
     if !path.exists() {
         warn!("param cache dir does not exist (no files to publish), exiting");
         exit(0);
@@ -100,9 +104,37 @@ fn get_filenames_in_cache_dir() -> Vec<String> {
 }
 
 fn publish_file(ipfs_bin: &str, filename: &str) -> Result<String> {
-    let path = get_full_path_for_file_within_cache(filename);
+    ipfs_add(ipfs_bin, filename, false)
+}
+
+/// Determine the cid of a file which is bundled rather than published. The cid is computed with
+/// `ipfs add --only-hash` if the ipfs binary exists, otherwise it is taken from the built-in
+/// parameters.json (or left empty if the file is not listed there).
+fn bundled_file_cid(ipfs_bin: &str, filename: &str) -> Result<String> {
+    if Path::new(ipfs_bin).exists() {
+        return ipfs_add(ipfs_bin, filename, true);
+    }
+    match get_parameter_data_from_id(filename) {
+        Some(data) => Ok(data.cid.clone()),
+        None => {
+            warn!(
+                "ipfs binary not found and file is unknown, leaving cid empty: {}",
+                filename
+            );
+            Ok(String::new())
+        }
+    }
+}
+
+fn ipfs_add(ipfs_bin: &str, filename: &str, only_hash: bool) -> Result<String> {
+    let path = get_full_path_for_file_within_cache(&ProofsContext::default(), filename);
+    let mut args = vec!["add", "-Q"];
+    if only_hash {
+        args.push("--only-hash");
+    }
+    args.push(path.to_str().unwrap());
     let output = Command::new(ipfs_bin)
-        .args(&["add", "-Q", path.to_str().unwrap()])
+        .args(&args)
         .output()
         .expect("failed to run ipfs subprocess");
     stderr()
@@ -150,6 +182,15 @@ struct Cli {
         help = "The path to write the parameters.json file."
     )]
     json_path: String,
+    #[structopt(
+        long,
+        value_name = "PATH",
+        long_help = "Write the selected files, their .meta files and a parameters.json manifest \
+            to a bundle for offline installation with `paramfetch --from-bundle`, instead of \
+            publishing them to ipfs. The bundle is written as a tarball if PATH ends in .tar, \
+            .tar.gz or .tgz, and as a directory otherwise."
+    )]
+    bundle: Option<PathBuf>,
 }
 
 pub fn main() {
@@ -161,11 +202,14 @@ pub fn main() {
 
     let cache_dir = match env::var("FIL_PROOFS_PARAMETER_CACHE") {
         Ok(s) => s,
-        _ => format!("{}", parameter_cache_dir().display()),
+        _ => format!(
+            "{}",
+            parameter_cache_dir(&ProofsContext::default()).display()
+        ),
     };
     info!("using param cache dir: {}", cache_dir);
 
-    if !Path::new(&cli.ipfs_bin).exists() {
+    if cli.bundle.is_none() && !Path::new(&cli.ipfs_bin).exists() {
         error!("ipfs binary not found: `{}`, exiting", cli.ipfs_bin);
         exit(1);
     }
@@ -196,10 +240,11 @@ pub fn main() {
     };
 
     // Read each param file's sector-size from its .meta file.
-    let meta_map = parameter_id_to_metadata_map(&ids).unwrap_or_else(|e| {
-        error!("failed to parse .meta file:\n{:?}\nexiting", e);
-        exit(1);
-    });
+    let meta_map =
+        parameter_id_to_metadata_map(&ProofsContext::default(), &ids).unwrap_or_else(|e| {
+            error!("failed to parse .meta file:\n{:?}\nexiting", e);
+            exit(1);
+        });
 
     // Store every param-id's .params and .vk file info.
     let mut infos = Vec::<FileInfo>::with_capacity(2 * ids.len());
@@ -312,16 +357,25 @@ pub fn main() {
     }
     trace!("{} files to publish", n_files_to_publish);
 
-    // Publish files to ipfs.
+    // Publish files to ipfs, or determine their cid's if they are bundled.
     let mut param_map: ParameterMap = BTreeMap::new();
 
     for info in infos {
-        trace!("publishing file to ipfs: {}", info.filename);
-        match publish_file(&cli.ipfs_bin, &info.filename) {
+        let cid = if cli.bundle.is_some() {
+            trace!("determining cid of bundled file: {}", info.filename);
+            bundled_file_cid(&cli.ipfs_bin, &info.filename)
+        } else {
+            trace!("publishing file to ipfs: {}", info.filename);
+            publish_file(&cli.ipfs_bin, &info.filename)
+        };
+        match cid {
             Ok(cid) => {
-                info!("successfully published file to ipfs, cid={}", cid);
+                if cli.bundle.is_none() {
+                    info!("successfully published file to ipfs, cid={}", cid);
+                }
                 let digest =
-                    get_digest_for_file_within_cache(&info.filename).expect("failed to hash file");
+                    get_digest_for_file_within_cache(&ProofsContext::default(), &info.filename)
+                        .expect("failed to hash file");
                 trace!("successfully hashed file: {}", digest);
                 let param_data = ParameterData {
                     cid,
@@ -338,6 +392,21 @@ pub fn main() {
     }
     info!("finished publishing files");
 
+    if let Some(ref bundle_path) = cli.bundle {
+        match write_bundle(Path::new(&cache_dir), &param_map, bundle_path) {
+            Ok(filenames) => info!(
+                "successfully wrote {} files to bundle: {}",
+                filenames.len(),
+                bundle_path.display()
+            ),
+            Err(e) => {
+                error!("failed to write bundle:\n{:?}\nexiting", e);
+                exit(1);
+            }
+        }
+        return;
+    }
+
     // Write parameters.json file containing published ipfs cid's.
     if let Err(e) = write_param_map_to_disk(&param_map, &cli.json_path) {
         error!("failed to write json file:\n{:?}\nexiting", e);
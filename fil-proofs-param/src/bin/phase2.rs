@@ -0,0 +1,295 @@
+use std::fs::{create_dir_all, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::str::FromStr;
+
+use anyhow::{bail, ensure, Context, Result};
+use fil_proofs_param::phase2::MPCParameters;
+use filecoin_proofs::{
+    constants::{
+        DefaultPieceHasher, WINDOW_POST_CHALLENGE_COUNT, WINNING_POST_CHALLENGE_COUNT,
+        WINNING_POST_SECTOR_COUNT,
+    },
+    param::get_digest_for_file,
+    parameters::{public_params, window_post_public_params, winning_post_public_params},
+    registry::sector_shape,
+    types::{PaddedBytesAmount, PoRepConfig, PoRepProofPartitions, PoStConfig, SectorSize},
+    with_shape, PoStType,
+};
+use log::info;
+use rand::rngs::OsRng;
+use storage_proofs_core::{
+    api_version::ApiVersion,
+    compound_proof::CompoundProof,
+    merkle::MerkleTreeTrait,
+    parameter_cache::{
+        parameter_cache_dir, parameter_cache_metadata_path, parameter_cache_params_path,
+        parameter_cache_verifying_key_path, CacheEntryMetadata,
+    },
+    settings::ProofsContext,
+};
+use storage_proofs_porep::stacked::{StackedCircuit, StackedCompound, StackedDrg};
+use storage_proofs_post::fallback::{FallbackPoSt, FallbackPoStCircuit, FallbackPoStCompound};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+struct CircuitOpt {
+    /// The circuit: porep, winning or window.
+    #[structopt(long)]
+    circuit: String,
+    #[structopt(long)]
+    sector_size: u64,
+    #[structopt(default_value = "1.1.0", long)]
+    api_version: String,
+    /// The directory containing the `phase1radix2m{exp}` files.
+    #[structopt(long, parse(from_os_str))]
+    phase1_dir: PathBuf,
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "phase2",
+    about = "Runs phase 2 of the trusted setup of SDR PoRep, Winning-PoSt and Window-PoSt groth params"
+)]
+enum Opt {
+    /// Derives the initial parameters of a circuit from phase 1.
+    New {
+        #[structopt(flatten)]
+        circuit: CircuitOpt,
+        #[structopt(short, long, parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// Adds a contribution to the parameters and prints its receipt.
+    Contribute {
+        #[structopt(short, long, parse(from_os_str))]
+        input: PathBuf,
+        #[structopt(short, long, parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// Verifies that the parameters are the result of a single contribution to the previous ones.
+    VerifyContribution {
+        #[structopt(long, parse(from_os_str))]
+        before: PathBuf,
+        #[structopt(long, parse(from_os_str))]
+        after: PathBuf,
+    },
+    /// Verifies all contributions to the parameters of a circuit and prints their receipts.
+    Verify {
+        #[structopt(flatten)]
+        circuit: CircuitOpt,
+        #[structopt(short, long, parse(from_os_str))]
+        input: PathBuf,
+    },
+    /// Verifies all contributions and writes the final groth params, verifying key and metadata
+    /// to the parameter cache.
+    Finalize {
+        #[structopt(flatten)]
+        circuit: CircuitOpt,
+        #[structopt(short, long, parse(from_os_str))]
+        input: PathBuf,
+    },
+}
+
+fn porep_config(sector_size: u64, api_version: ApiVersion) -> Result<PoRepConfig> {
+    Ok(PoRepConfig {
+        sector_size: SectorSize(sector_size),
+        partitions: PoRepProofPartitions(sector_shape(sector_size)?.porep_partitions),
+        porep_id: [0; 32],
+        api_version,
+    })
+}
+
+fn post_config(sector_size: u64, typ: PoStType, api_version: ApiVersion) -> Result<PoStConfig> {
+    let (challenge_count, sector_count) = match typ {
+        PoStType::Winning => (WINNING_POST_CHALLENGE_COUNT, WINNING_POST_SECTOR_COUNT),
+        PoStType::Window => (
+            WINDOW_POST_CHALLENGE_COUNT,
+            sector_shape(sector_size)?.window_post_sector_count,
+        ),
+    };
+
+    Ok(PoStConfig {
+        sector_size: SectorSize(sector_size),
+        challenge_count,
+        sector_count,
+        typ,
+        priority: true,
+        api_version,
+    })
+}
+
+/// Derives the initial parameters of the circuit from phase 1. Returns them together with the
+/// parameter cache identifier of the circuit.
+fn initial_params<Tree: 'static + MerkleTreeTrait>(
+    opt: &CircuitOpt,
+) -> Result<(MPCParameters, String)> {
+    let api_version = ApiVersion::from_str(&opt.api_version)?;
+
+    match opt.circuit.as_str() {
+        "porep" => {
+            let porep_config = porep_config(opt.sector_size, api_version)?;
+            let public_params = public_params::<Tree>(
+                PaddedBytesAmount::from(porep_config),
+                usize::from(PoRepProofPartitions::from(porep_config)),
+                porep_config.porep_id,
+                porep_config.api_version,
+            )?;
+            let circuit = <StackedCompound<Tree, DefaultPieceHasher> as CompoundProof<
+                StackedDrg<Tree, DefaultPieceHasher>,
+                StackedCircuit<Tree, DefaultPieceHasher>,
+            >>::blank_circuit(&public_params);
+
+            Ok((
+                MPCParameters::new(circuit, &opt.phase1_dir)?,
+                porep_config.get_cache_identifier::<Tree>()?,
+            ))
+        }
+        "winning" | "window" => {
+            let (post_config, public_params) = if opt.circuit == "winning" {
+                let post_config = post_config(opt.sector_size, PoStType::Winning, api_version)?;
+                let public_params = winning_post_public_params::<Tree>(&post_config)?;
+                (post_config, public_params)
+            } else {
+                let post_config = post_config(opt.sector_size, PoStType::Window, api_version)?;
+                let public_params = window_post_public_params::<Tree>(&post_config)?;
+                (post_config, public_params)
+            };
+            let circuit = <FallbackPoStCompound<Tree> as CompoundProof<
+                FallbackPoSt<Tree>,
+                FallbackPoStCircuit<Tree>,
+            >>::blank_circuit(&public_params);
+
+            Ok((
+                MPCParameters::new(circuit, &opt.phase1_dir)?,
+                post_config.get_cache_identifier::<Tree>()?,
+            ))
+        }
+        other => bail!("unknown circuit: {}", other),
+    }
+}
+
+fn initial_params_for(opt: &CircuitOpt) -> Result<(MPCParameters, String)> {
+    sector_shape(opt.sector_size)?;
+    with_shape!(opt.sector_size, initial_params, opt)
+}
+
+fn read_params(path: &Path, checked: bool) -> Result<MPCParameters> {
+    info!("reading parameters from {:?}", path);
+    let file = File::open(path).with_context(|| format!("could not open {:?}", path))?;
+    MPCParameters::read(BufReader::new(file), checked)
+        .with_context(|| format!("invalid parameters {:?}", path))
+}
+
+fn write_file<F>(path: &Path, f: F) -> Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<()>,
+{
+    let file = File::create(path).with_context(|| format!("could not create {:?}", path))?;
+    let mut writer = BufWriter::new(file);
+    f(&mut writer)?;
+    writer.flush()?;
+    info!("wrote {:?}", path);
+
+    Ok(())
+}
+
+/// Verifies the full chain of contributions to the parameters at `input`, and prints their
+/// receipts.
+fn verify(circuit: &CircuitOpt, input: &Path) -> Result<(MPCParameters, String)> {
+    let (initial, cache_id) = initial_params_for(circuit)?;
+    let params = read_params(input, true)?;
+    let receipts = params.verify_contributions(&initial)?;
+
+    println!("{} contributions to {} verified:", receipts.len(), cache_id);
+    for (i, receipt) in receipts.iter().enumerate() {
+        println!("{:>4}: {}", i, hex::encode(&receipt[..]));
+    }
+
+    Ok((params, cache_id))
+}
+
+fn finalize(circuit: &CircuitOpt, input: &Path) -> Result<()> {
+    let (params, cache_id) = verify(circuit, input)?;
+    let params = params.into_params();
+
+    let ctx = ProofsContext::default();
+    create_dir_all(parameter_cache_dir(&ctx))?;
+    let params_path = parameter_cache_params_path(&ctx, &cache_id);
+    let vk_path = parameter_cache_verifying_key_path(&ctx, &cache_id);
+    let meta_path = parameter_cache_metadata_path(&ctx, &cache_id);
+    for path in &[&params_path, &vk_path, &meta_path] {
+        ensure!(!path.exists(), "refusing to overwrite {:?}", path);
+    }
+
+    write_file(&params_path, |w| Ok(params.write(w)?))?;
+    write_file(&vk_path, |w| Ok(params.vk.write(w)?))?;
+    write_file(&meta_path, |w| {
+        let meta = CacheEntryMetadata {
+            sector_size: circuit.sector_size,
+        };
+        Ok(serde_json::to_writer(w, &meta)?)
+    })?;
+
+    for path in &[&params_path, &vk_path] {
+        println!("{:?}: digest {}", path, get_digest_for_file(path)?);
+    }
+
+    Ok(())
+}
+
+fn run(opt: Opt) -> Result<()> {
+    match opt {
+        Opt::New { circuit, output } => {
+            let (params, cache_id) = initial_params_for(&circuit)?;
+            write_file(&output, |w| Ok(params.write(w)?))?;
+            println!(
+                "initial parameters for {} written to {:?}, circuit hash: {}",
+                cache_id,
+                output,
+                hex::encode(&params.cs_hash()[..])
+            );
+        }
+        Opt::Contribute { input, output } => {
+            // Contributors rely on the coordinator verifying each contribution.
+            let mut params = read_params(&input, false)?;
+            let receipt = params.contribute(&mut OsRng);
+            write_file(&output, |w| Ok(params.write(w)?))?;
+            println!(
+                "contribution {} written to {:?}, receipt: {}",
+                params.contributions().len() - 1,
+                output,
+                hex::encode(&receipt[..])
+            );
+        }
+        Opt::VerifyContribution { before, after } => {
+            let before = read_params(&before, true)?;
+            let after = read_params(&after, true)?;
+            let receipt = after.verify_contribution(&before)?;
+            println!(
+                "contribution {} verified, receipt: {}",
+                after.contributions().len() - 1,
+                hex::encode(&receipt[..])
+            );
+        }
+        Opt::Verify { circuit, input } => {
+            verify(&circuit, &input)?;
+        }
+        Opt::Finalize { circuit, input } => finalize(&circuit, &input)?,
+    }
+
+    Ok(())
+}
+
+// Run this from the command-line to take part in the trusted setup of new groth params.
+pub fn main() {
+    fil_logger::init();
+
+    let opt = Opt::from_args();
+    info!("phase2: {:?}", opt);
+
+    if let Err(err) = run(opt) {
+        eprintln!("phase2 failed: {:?}", err);
+        exit(1);
+    }
+}
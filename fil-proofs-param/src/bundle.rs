@@ -0,0 +1,258 @@
+use std::fs::{self, create_dir_all, File};
+use std::io::{self, Read};
+use std::path::Path;
+
+use anyhow::{bail, ensure, Context, Result};
+use filecoin_proofs::param::{
+    add_extension, filename_to_parameter_id, get_digest_for_file, has_extension,
+};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use log::{info, trace, warn};
+use storage_proofs_core::parameter_cache::{
+    CacheEntryMetadata, ParameterMap, PARAMETER_METADATA_EXT,
+};
+use tar::{Archive, Builder, Header};
+
+use crate::fetch::partial_path;
+
+/// Name of the manifest within a bundle, in the format of `parameters.json`.
+pub const BUNDLE_MANIFEST: &str = "parameters.json";
+
+/// Returns `true` if `path` names a tarball (`.tar`, `.tar.gz` or `.tgz`) rather than a
+/// directory.
+pub fn is_tarball(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    name.ends_with(".tar") || is_gzipped(path)
+}
+
+fn is_gzipped(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    name.ends_with(".tar.gz") || name.ends_with(".tgz")
+}
+
+/// Writes the files listed in `manifest`, their `.meta` files and the manifest itself from
+/// `cache_dir` into a bundle at `path`, which is either a directory or a tarball.
+///
+/// Returns the names of all files written, excluding the manifest.
+pub fn write_bundle(cache_dir: &Path, manifest: &ParameterMap, path: &Path) -> Result<Vec<String>> {
+    let mut filenames: Vec<String> = manifest.keys().cloned().collect();
+    for filename in manifest.keys() {
+        if let Some(id) = filename_to_parameter_id(filename) {
+            let meta_filename = add_extension(&id, PARAMETER_METADATA_EXT);
+            if cache_dir.join(&meta_filename).exists() && !filenames.contains(&meta_filename) {
+                filenames.push(meta_filename);
+            }
+        }
+    }
+    filenames.sort();
+
+    let manifest_bytes = serde_json::to_vec_pretty(manifest)?;
+
+    if is_tarball(path) {
+        let file = File::create(path).with_context(|| format!("could not create {:?}", path))?;
+        if is_gzipped(path) {
+            let encoder = write_tarball(
+                GzEncoder::new(file, Compression::default()),
+                cache_dir,
+                &filenames,
+                &manifest_bytes,
+            )?;
+            encoder.finish()?.sync_all()?;
+        } else {
+            write_tarball(file, cache_dir, &filenames, &manifest_bytes)?.sync_all()?;
+        }
+    } else {
+        create_dir_all(path).with_context(|| format!("could not create {:?}", path))?;
+        for filename in &filenames {
+            trace!("copying {} into bundle", filename);
+            fs::copy(cache_dir.join(filename), path.join(filename))
+                .with_context(|| format!("could not copy {} into bundle", filename))?;
+        }
+        fs::write(path.join(BUNDLE_MANIFEST), &manifest_bytes)?;
+    }
+
+    info!("wrote {} files to bundle {:?}", filenames.len(), path);
+
+    Ok(filenames)
+}
+
+fn write_tarball<W: io::Write>(
+    writer: W,
+    cache_dir: &Path,
+    filenames: &[String],
+    manifest_bytes: &[u8],
+) -> Result<W> {
+    let mut builder = Builder::new(writer);
+
+    // The manifest goes first, so readers know what to expect.
+    let mut header = Header::new_gnu();
+    header.set_size(manifest_bytes.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, BUNDLE_MANIFEST, manifest_bytes)?;
+
+    for filename in filenames {
+        trace!("appending {} to bundle", filename);
+        builder
+            .append_path_with_name(cache_dir.join(filename), filename)
+            .with_context(|| format!("could not append {} to bundle", filename))?;
+    }
+
+    Ok(builder.into_inner()?)
+}
+
+/// Installs the bundle at `path`, a directory or tarball written by `write_bundle`, into
+/// `cache_dir`.
+///
+/// Every file is first copied to `<filename>.partial` in `cache_dir`, and only renamed to its final
+/// name once its digest matches the bundle manifest. `.meta` files must parse as metadata. Returns
+/// the names of the installed files, or an error if any file was missing or invalid; valid files
+/// are installed regardless.
+pub fn install_bundle(path: &Path, cache_dir: &Path) -> Result<Vec<String>> {
+    create_dir_all(cache_dir).with_context(|| format!("could not create {:?}", cache_dir))?;
+
+    let (manifest, copied) = if is_tarball(path) {
+        let file = File::open(path).with_context(|| format!("could not open {:?}", path))?;
+        if is_gzipped(path) {
+            unpack_tarball(GzDecoder::new(file), cache_dir)?
+        } else {
+            unpack_tarball(file, cache_dir)?
+        }
+    } else {
+        let manifest_path = path.join(BUNDLE_MANIFEST);
+        let manifest: ParameterMap = serde_json::from_reader(
+            File::open(&manifest_path)
+                .with_context(|| format!("could not open {:?}", manifest_path))?,
+        )?;
+
+        let mut copied = Vec::new();
+        for entry in fs::read_dir(path)? {
+            let filename = entry?.file_name().to_string_lossy().into_owned();
+            if is_bundled_file(&manifest, &filename) {
+                trace!("copying {} from bundle", filename);
+                fs::copy(
+                    path.join(&filename),
+                    partial_path(&cache_dir.join(&filename)),
+                )?;
+                copied.push(filename);
+            }
+        }
+        (manifest, copied)
+    };
+
+    let mut installed = Vec::new();
+    let mut invalid = Vec::new();
+    for filename in copied {
+        let partial = partial_path(&cache_dir.join(&filename));
+        match verify_bundled_file(&manifest, &filename, &partial) {
+            Ok(()) => {
+                fs::rename(&partial, cache_dir.join(&filename))?;
+                trace!("installed {}", filename);
+                installed.push(filename);
+            }
+            Err(e) => {
+                warn!("not installing {}: {:?}", filename, e);
+                fs::remove_file(&partial)?;
+                invalid.push(filename);
+            }
+        }
+    }
+
+    let missing: Vec<&String> = manifest
+        .keys()
+        .filter(|filename| !installed.contains(filename) && !invalid.contains(filename))
+        .collect();
+
+    ensure!(
+        invalid.is_empty() && missing.is_empty(),
+        "bundle is incomplete: invalid files {:?}, missing files {:?}",
+        invalid,
+        missing
+    );
+
+    info!("installed {} files from bundle {:?}", installed.len(), path);
+
+    Ok(installed)
+}
+
+/// Returns `true` for the files of a bundle which get installed into the cache.
+fn is_bundled_file(manifest: &ParameterMap, filename: &str) -> bool {
+    manifest.contains_key(filename) || has_extension(filename, PARAMETER_METADATA_EXT)
+}
+
+/// Streams the files of a tarball into partial files in `cache_dir` and returns the manifest,
+/// along with the names of the copied files.
+fn unpack_tarball<R: Read>(reader: R, cache_dir: &Path) -> Result<(ParameterMap, Vec<String>)> {
+    let mut archive = Archive::new(reader);
+    let mut manifest: Option<ParameterMap> = None;
+    let mut copied = Vec::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let filename = entry.path()?.to_string_lossy().into_owned();
+
+        if filename == BUNDLE_MANIFEST {
+            manifest = Some(serde_json::from_reader(&mut entry)?);
+            continue;
+        }
+
+        // Only plain files are expected, any other path could escape the cache dir.
+        let is_plain_file = entry.header().entry_type().is_file()
+            && Path::new(&filename)
+                .file_name()
+                .and_then(|name| name.to_str())
+                == Some(filename.as_str());
+        if !is_plain_file {
+            warn!("ignoring unexpected bundle entry: {}", filename);
+            continue;
+        }
+
+        trace!("unpacking {} from bundle", filename);
+        let partial = partial_path(&cache_dir.join(&filename));
+        io::copy(&mut entry, &mut File::create(&partial)?)?;
+        copied.push(filename);
+    }
+
+    let manifest = match manifest {
+        Some(manifest) => manifest,
+        None => {
+            for filename in &copied {
+                fs::remove_file(partial_path(&cache_dir.join(filename)))?;
+            }
+            bail!("bundle does not contain {}", BUNDLE_MANIFEST);
+        }
+    };
+
+    // Tarballs may contain files which are neither listed nor metadata, which are discarded.
+    let mut bundled = Vec::with_capacity(copied.len());
+    for filename in copied {
+        if is_bundled_file(&manifest, &filename) {
+            bundled.push(filename);
+        } else {
+            warn!("ignoring unlisted bundle entry: {}", filename);
+            fs::remove_file(partial_path(&cache_dir.join(&filename)))?;
+        }
+    }
+
+    Ok((manifest, bundled))
+}
+
+fn verify_bundled_file(manifest: &ParameterMap, filename: &str, path: &Path) -> Result<()> {
+    match manifest.get(filename) {
+        Some(data) => {
+            let digest = get_digest_for_file(path)?;
+            ensure!(
+                digest == data.digest,
+                "digest mismatch: expected {}, got {}",
+                data.digest,
+                digest
+            );
+        }
+        None => {
+            let _: CacheEntryMetadata = serde_json::from_reader(File::open(path)?)
+                .with_context(|| format!("invalid metadata file {}", filename))?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,182 @@
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Context, Result};
+use filecoin_proofs::param::get_digest_for_file;
+use log::{info, trace, warn};
+use reqwest::{
+    blocking::{Client, Response},
+    header, Proxy, StatusCode,
+};
+use storage_proofs_core::parameter_cache::ParameterData;
+
+/// Mirrors used when no url templates are configured.
+pub const DEFAULT_URL_TEMPLATES: [&str; 2] = [
+    "https://proofs.filecoin.io/ipfs/{cid}",
+    "https://ipfs.io/ipfs/{cid}",
+];
+
+/// Extension appended to files which are still being downloaded.
+pub const PARTIAL_EXT: &str = "partial";
+
+/// Downloads parameter files over HTTP(S) from a list of gateways or mirrors.
+///
+/// Each mirror is given as a url template, in which `{cid}` and `{filename}` are replaced with the
+/// values from the parameter manifest, e.g. `https://ipfs.io/ipfs/{cid}` or
+/// `https://example.com/params/{filename}`.
+#[derive(Debug)]
+pub struct HttpFetcher {
+    client: Client,
+    url_templates: Vec<String>,
+}
+
+impl HttpFetcher {
+    pub fn new(url_templates: Vec<String>) -> Result<Self> {
+        ensure!(
+            !url_templates.is_empty(),
+            "at least one url template is required"
+        );
+        for template in &url_templates {
+            ensure!(
+                template.contains("{cid}") || template.contains("{filename}"),
+                "url template {} contains neither {{cid}} nor {{filename}}",
+                template
+            );
+        }
+
+        let client = Client::builder()
+            .proxy(Proxy::custom(move |url| env_proxy::for_url(&url).to_url()))
+            // Parameter files can be many GiB, so only fail on connection errors.
+            .timeout(None)
+            .build()?;
+
+        Ok(HttpFetcher {
+            client,
+            url_templates,
+        })
+    }
+
+    /// Creates a fetcher using `DEFAULT_URL_TEMPLATES`.
+    pub fn with_default_mirrors() -> Result<Self> {
+        Self::new(
+            DEFAULT_URL_TEMPLATES
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        )
+    }
+
+    pub fn url_templates(&self) -> &[String] {
+        &self.url_templates
+    }
+
+    /// Downloads `filename` into `cache_dir`, trying each mirror in turn.
+    ///
+    /// Data is written to `<filename>.partial` first, so an interrupted download is resumed with
+    /// a range request on the next call. The file is only renamed to its final path once its
+    /// digest matches the one in `data`.
+    pub fn fetch(&self, cache_dir: &Path, filename: &str, data: &ParameterData) -> Result<PathBuf> {
+        let path = cache_dir.join(filename);
+        let partial_path = partial_path(&path);
+
+        for template in &self.url_templates {
+            let url = expand_url_template(template, filename, data);
+            info!("fetching {} from {}", filename, url);
+
+            match self.fetch_into(&url, &partial_path, &data.digest) {
+                Ok(()) => {
+                    fs::rename(&partial_path, &path).with_context(|| {
+                        format!("could not rename {:?} to {:?}", partial_path, path)
+                    })?;
+                    info!("fetched {}", filename);
+                    return Ok(path);
+                }
+                Err(err) => warn!("failed to fetch {} from {}: {:?}", filename, url, err),
+            }
+        }
+
+        bail!(
+            "failed to fetch {} from {} mirror(s)",
+            filename,
+            self.url_templates.len()
+        );
+    }
+
+    /// Downloads `url` into `partial_path`, resuming from its current length, and verifies the
+    /// digest of the result.
+    fn fetch_into(&self, url: &str, partial_path: &Path, digest: &str) -> Result<()> {
+        let offset = fs::metadata(partial_path).map(|m| m.len()).unwrap_or(0);
+
+        let mut request = self.client.get(url);
+        if offset > 0 {
+            trace!("resuming download at byte {}", offset);
+            request = request.header(header::RANGE, format!("bytes={}-", offset));
+        }
+        let mut resp = request.send()?;
+        trace!("received response: {}", resp.status());
+
+        match resp.status() {
+            StatusCode::PARTIAL_CONTENT => {
+                if content_range_start(&resp) != Some(offset) {
+                    // We can't tell where this range belongs, so start over next time.
+                    fs::remove_file(partial_path)?;
+                    bail!("server responded with an unexpected range");
+                }
+                let mut file = OpenOptions::new().append(true).open(partial_path)?;
+                io::copy(&mut resp, &mut file)?;
+                file.sync_all()?;
+            }
+            // The partial file already holds every byte the server has.
+            StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => {}
+            status if status.is_success() => {
+                // Either a fresh download, or the server ignored the range request.
+                let mut file = File::create(partial_path)?;
+                io::copy(&mut resp, &mut file)?;
+                file.sync_all()?;
+            }
+            status => bail!("unexpected response status: {}", status),
+        }
+
+        let calculated_digest = get_digest_for_file(partial_path)?;
+        if calculated_digest != digest {
+            // Resuming can't fix a corrupt file, so discard it.
+            fs::remove_file(partial_path)?;
+            bail!(
+                "digest mismatch: expected {}, got {}",
+                digest,
+                calculated_digest
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the path a download of `path` is written to until it has been verified.
+pub fn partial_path(path: &Path) -> PathBuf {
+    let mut filename = path.file_name().unwrap_or_default().to_os_string();
+    filename.push(".");
+    filename.push(PARTIAL_EXT);
+    path.with_file_name(filename)
+}
+
+/// Fills in the `{cid}` and `{filename}` placeholders of a url template.
+pub fn expand_url_template(template: &str, filename: &str, data: &ParameterData) -> String {
+    template
+        .replace("{cid}", &data.cid)
+        .replace("{filename}", filename)
+}
+
+/// Parses the first byte position of a `Content-Range: bytes <start>-<end>/<len>` header.
+fn content_range_start(resp: &Response) -> Option<u64> {
+    resp.headers()
+        .get(header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .strip_prefix("bytes ")?
+        .split('-')
+        .next()?
+        .parse()
+        .ok()
+}
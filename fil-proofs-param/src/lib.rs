@@ -1,2 +1,7 @@
 #![deny(clippy::all, clippy::perf, clippy::correctness)]
 #![warn(clippy::unwrap_used)]
+
+pub mod bundle;
+pub mod fetch;
+pub mod phase2;
+pub mod verify;
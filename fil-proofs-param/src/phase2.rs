@@ -0,0 +1,1003 @@
+//! Phase 2 of a multi-party trusted setup ("MPC ceremony") for the Groth16 parameters of a
+//! circuit, following Bowe, Gabizon and Miers, <https://eprint.iacr.org/2017/1050>.
+//!
+//! Phase 1 is independent of the circuit and is read from a `phase1radix2m{exp}` file, as produced
+//! by the powers of tau ceremony for domains of size `2^exp`. It contains the uncompressed points
+//! `alpha_g1`, `beta_g1`, `beta_g2`, followed by the Lagrange coefficients `coeffs_g1`,
+//! `coeffs_g2`, `alpha_coeffs_g1` and `beta_coeffs_g1` (`2^exp` points each) and the `2^exp - 1`
+//! points of the `h` query.
+//!
+//! `MPCParameters::new` derives initial parameters for a circuit from phase 1, with `delta = 1`.
+//! Each participant then calls `MPCParameters::contribute`, which multiplies `delta` by a secret
+//! and divides the `h` and `l` queries by it. The participant publishes a `PublicKey` proving
+//! knowledge of the secret, whose hash serves as their receipt. The final parameters are secure as
+//! long as a single participant destroyed their secret.
+//!
+//! # File format
+//!
+//! | Field           | Type     | Description                                               |
+//! |-----------------|----------|-----------------------------------------------------------|
+//! | `params`        |          | the current parameters, as written by `Parameters::write` |
+//! | `cs_hash`       | 64 bytes | BLAKE2b-512 of the initial parameters                     |
+//! | `contributions` | `u32` BE | number of contributions                                   |
+//! | public keys     |          | one `PublicKey` per contribution, oldest first            |
+//!
+//! A public key is the uncompressed points `delta_after`, `s`, `s_delta` and `r_delta`, followed
+//! by its 64 byte `transcript`.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{anyhow, ensure, Context, Result};
+use bellperson::{
+    bls::{Bls12, Engine, Fr, G1Affine, G2Affine, G1, G2},
+    groth16::{Parameters, VerifyingKey},
+    Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable,
+};
+use blake2b_simd::State as Blake2b;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use ff::{Field, PrimeField};
+use groupy::{CurveAffine, CurveProjective, EncodedPoint};
+use log::info;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaChaRng;
+use rayon::prelude::*;
+
+/// Length of a BLAKE2b-512 digest, used for the circuit hash, transcripts and receipts.
+pub const HASH_BYTES: usize = 64;
+
+/// Number of points each rayon task processes when merging query pairs.
+const MERGE_CHUNK_SIZE: usize = 1024;
+
+pub type Hash = [u8; HASH_BYTES];
+
+/// The `(coefficient, constraint)` pairs of a variable in one of the A, B or C polynomials.
+type Terms = Vec<(Fr, usize)>;
+
+/// Returns the name of the phase 1 file for domains of size `2^exp`.
+pub fn phase1_filename(exp: u32) -> String {
+    format!("phase1radix2m{}", exp)
+}
+
+/// The output of phase 1 for a domain of size `2^exp`. See the module documentation for its
+/// format.
+#[derive(Clone, PartialEq)]
+pub struct Phase1 {
+    pub alpha_g1: G1Affine,
+    pub beta_g1: G1Affine,
+    pub beta_g2: G2Affine,
+    pub coeffs_g1: Vec<G1Affine>,
+    pub coeffs_g2: Vec<G2Affine>,
+    pub alpha_coeffs_g1: Vec<G1Affine>,
+    pub beta_coeffs_g1: Vec<G1Affine>,
+    pub h: Vec<G1Affine>,
+}
+
+impl Phase1 {
+    /// Reads phase 1 for a domain of size `2^exp`. Phase 1 is the public output of a verified
+    /// ceremony, so points are not checked to be in the correct subgroup.
+    pub fn read<R: Read>(mut reader: R, exp: u32) -> Result<Self> {
+        let m = 1usize << exp;
+        let reader = &mut reader;
+
+        Ok(Phase1 {
+            alpha_g1: read_point(reader, false)?,
+            beta_g1: read_point(reader, false)?,
+            beta_g2: read_point(reader, false)?,
+            coeffs_g1: read_points(reader, m, false)?,
+            coeffs_g2: read_points(reader, m, false)?,
+            alpha_coeffs_g1: read_points(reader, m, false)?,
+            beta_coeffs_g1: read_points(reader, m, false)?,
+            h: read_points(reader, m - 1, false)?,
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let writer = &mut writer;
+        write_point(writer, &self.alpha_g1)?;
+        write_point(writer, &self.beta_g1)?;
+        write_point(writer, &self.beta_g2)?;
+        write_points(writer, &self.coeffs_g1)?;
+        write_points(writer, &self.coeffs_g2)?;
+        write_points(writer, &self.alpha_coeffs_g1)?;
+        write_points(writer, &self.beta_coeffs_g1)?;
+        write_points(writer, &self.h)
+    }
+}
+
+/// The proof of knowledge of a participant's secret `delta`, binding it to the transcript of all
+/// previous contributions.
+#[derive(Clone)]
+pub struct PublicKey {
+    /// `delta_g1` after the contribution.
+    pub delta_after: G1Affine,
+    /// A random point and its multiple by `delta`.
+    pub s: G1Affine,
+    pub s_delta: G1Affine,
+    /// The multiple by `delta` of the G2 point derived from `transcript`.
+    pub r_delta: G2Affine,
+    /// BLAKE2b-512 of `cs_hash`, all previous public keys, `s` and `s_delta`.
+    pub transcript: Hash,
+}
+
+impl PartialEq for PublicKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.delta_after == other.delta_after
+            && self.s == other.s
+            && self.s_delta == other.s_delta
+            && self.r_delta == other.r_delta
+            && self.transcript[..] == other.transcript[..]
+    }
+}
+
+impl PublicKey {
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let writer = &mut writer;
+        write_point(writer, &self.delta_after)?;
+        write_point(writer, &self.s)?;
+        write_point(writer, &self.s_delta)?;
+        write_point(writer, &self.r_delta)?;
+        writer.write_all(&self.transcript)
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> Result<Self> {
+        let reader = &mut reader;
+        let delta_after = read_point(reader, true)?;
+        let s = read_point(reader, true)?;
+        let s_delta = read_point(reader, true)?;
+        let r_delta = read_point(reader, true)?;
+        let mut transcript = [0; HASH_BYTES];
+        reader.read_exact(&mut transcript)?;
+
+        Ok(PublicKey {
+            delta_after,
+            s,
+            s_delta,
+            r_delta,
+            transcript,
+        })
+    }
+
+    /// The receipt of this contribution, BLAKE2b-512 of the public key.
+    pub fn hash(&self) -> Hash {
+        let mut hasher = Blake2b::new();
+        self.write(&mut hasher).expect("hashing failure");
+        into_hash(&hasher)
+    }
+}
+
+/// The parameters of a circuit during phase 2, together with all contributions so far.
+#[derive(Clone)]
+pub struct MPCParameters {
+    params: Parameters<Bls12>,
+    cs_hash: Hash,
+    contributions: Vec<PublicKey>,
+}
+
+impl PartialEq for MPCParameters {
+    fn eq(&self, other: &Self) -> bool {
+        self.params == other.params
+            && self.cs_hash[..] == other.cs_hash[..]
+            && self.contributions == other.contributions
+    }
+}
+
+impl MPCParameters {
+    /// Derives the initial parameters of `circuit` from the phase 1 file for its domain size in
+    /// `phase1_dir`. The result is deterministic, so verifiers recompute it to check the chain of
+    /// contributions.
+    pub fn new<C: Circuit<Bls12>>(circuit: C, phase1_dir: &Path) -> Result<Self> {
+        let assembly = KeypairAssembly::synthesize(circuit)?;
+        let exp = assembly.domain_exp()?;
+
+        let path = phase1_dir.join(phase1_filename(exp));
+        info!("reading phase 1 from {:?}", path);
+        let file = File::open(&path).with_context(|| format!("could not open {:?}", path))?;
+        let phase1 = Phase1::read(BufReader::new(file), exp)
+            .with_context(|| format!("invalid phase 1 file {:?}", path))?;
+
+        Self::from_phase1(assembly, &phase1)
+    }
+
+    /// Like `new`, with phase 1 already in memory.
+    pub fn new_with_phase1<C: Circuit<Bls12>>(circuit: C, phase1: &Phase1) -> Result<Self> {
+        Self::from_phase1(KeypairAssembly::synthesize(circuit)?, phase1)
+    }
+
+    fn from_phase1(assembly: KeypairAssembly, phase1: &Phase1) -> Result<Self> {
+        let exp = assembly.domain_exp()?;
+        ensure!(
+            phase1.coeffs_g1.len() == 1 << exp
+                && phase1.coeffs_g2.len() == 1 << exp
+                && phase1.alpha_coeffs_g1.len() == 1 << exp
+                && phase1.beta_coeffs_g1.len() == 1 << exp
+                && phase1.h.len() == (1 << exp) - 1,
+            "phase 1 does not match the domain size 2^{} of the circuit",
+            exp
+        );
+        info!(
+            "deriving initial parameters: {} inputs, {} aux, {} constraints",
+            assembly.num_inputs, assembly.num_aux, assembly.num_constraints
+        );
+
+        let evaluate_all = |at: &[Terms], bt: &[Terms], ct: &[Terms]| -> Vec<_> {
+            at.par_iter()
+                .zip(bt.par_iter())
+                .zip(ct.par_iter())
+                .map(|((at, bt), ct)| {
+                    let a_g1 = evaluate(at, &phase1.coeffs_g1);
+                    let b_g1 = evaluate(bt, &phase1.coeffs_g1);
+                    let b_g2 = evaluate(bt, &phase1.coeffs_g2);
+
+                    // beta * A(tau) + alpha * B(tau) + C(tau)
+                    let mut ext = evaluate(at, &phase1.beta_coeffs_g1);
+                    ext.add_assign(&evaluate(bt, &phase1.alpha_coeffs_g1));
+                    ext.add_assign(&evaluate(ct, &phase1.coeffs_g1));
+
+                    (
+                        a_g1.into_affine(),
+                        b_g1.into_affine(),
+                        b_g2.into_affine(),
+                        ext.into_affine(),
+                    )
+                })
+                .collect()
+        };
+
+        let inputs = evaluate_all(
+            &assembly.at_inputs,
+            &assembly.bt_inputs,
+            &assembly.ct_inputs,
+        );
+        let aux = evaluate_all(&assembly.at_aux, &assembly.bt_aux, &assembly.ct_aux);
+
+        // Points at infinity are filtered from the A and B queries, as the prover skips variables
+        // which do not occur in them.
+        let all = || inputs.iter().chain(aux.iter());
+        let a = all().map(|e| e.0).filter(|p| !p.is_zero()).collect();
+        let b_g1 = all().map(|e| e.1).filter(|p| !p.is_zero()).collect();
+        let b_g2 = all().map(|e| e.2).filter(|p| !p.is_zero()).collect();
+        let ic = inputs.iter().map(|e| e.3).collect();
+        let l = aux.iter().map(|e| e.3).collect();
+
+        let params = Parameters {
+            vk: VerifyingKey {
+                alpha_g1: phase1.alpha_g1,
+                beta_g1: phase1.beta_g1,
+                beta_g2: phase1.beta_g2,
+                gamma_g2: G2Affine::one(),
+                delta_g1: G1Affine::one(),
+                delta_g2: G2Affine::one(),
+                ic,
+            },
+            h: Arc::new(phase1.h.clone()),
+            l: Arc::new(l),
+            a: Arc::new(a),
+            b_g1: Arc::new(b_g1),
+            b_g2: Arc::new(b_g2),
+        };
+
+        let mut hasher = Blake2b::new();
+        params.write(&mut hasher).expect("hashing failure");
+        let cs_hash = into_hash(&hasher);
+
+        Ok(MPCParameters {
+            params,
+            cs_hash,
+            contributions: Vec::new(),
+        })
+    }
+
+    /// Reads parameters written by `write`. Unless `checked`, the points of the parameters are
+    /// not checked to be in the correct subgroup, which is only safe for files from a trusted
+    /// source. Public keys are always checked.
+    pub fn read<R: Read>(mut reader: R, checked: bool) -> Result<Self> {
+        let params = Parameters::read(&mut reader, checked).context("invalid parameters")?;
+
+        let mut cs_hash = [0; HASH_BYTES];
+        reader.read_exact(&mut cs_hash)?;
+
+        let count = reader.read_u32::<BigEndian>()?;
+        let contributions = (0..count)
+            .map(|i| {
+                PublicKey::read(&mut reader).with_context(|| format!("invalid contribution {}", i))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(MPCParameters {
+            params,
+            cs_hash,
+            contributions,
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        self.params.write(&mut writer)?;
+        writer.write_all(&self.cs_hash)?;
+        writer.write_u32::<BigEndian>(self.contributions.len() as u32)?;
+        for pubkey in &self.contributions {
+            pubkey.write(&mut writer)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn params(&self) -> &Parameters<Bls12> {
+        &self.params
+    }
+
+    pub fn into_params(self) -> Parameters<Bls12> {
+        self.params
+    }
+
+    /// The hash of the initial parameters, identifying the circuit and phase 1.
+    pub fn cs_hash(&self) -> &Hash {
+        &self.cs_hash
+    }
+
+    pub fn contributions(&self) -> &[PublicKey] {
+        &self.contributions
+    }
+
+    /// Contributes a secret `delta` drawn from `rng`, which is discarded afterwards. Returns the
+    /// receipt of the contribution.
+    pub fn contribute<R: RngCore>(&mut self, rng: &mut R) -> Hash {
+        let delta = loop {
+            let delta = Fr::random(rng);
+            if !delta.is_zero() {
+                break delta;
+            }
+        };
+        let delta_inv = delta.inverse().expect("nonzero delta is invertible");
+
+        let s = G1::random(rng).into_affine();
+        let s_delta = s.mul(delta.into_repr()).into_affine();
+        let transcript = transcript(&self.cs_hash, &self.contributions, &s, &s_delta);
+        let r_delta = hash_to_g2(&transcript)
+            .into_affine()
+            .mul(delta.into_repr())
+            .into_affine();
+
+        info!("contributing: dividing h and l queries by delta");
+        let vk = &mut self.params.vk;
+        vk.delta_g1 = vk.delta_g1.mul(delta.into_repr()).into_affine();
+        vk.delta_g2 = vk.delta_g2.mul(delta.into_repr()).into_affine();
+        self.params.h = Arc::new(batch_mul(&self.params.h, delta_inv));
+        self.params.l = Arc::new(batch_mul(&self.params.l, delta_inv));
+
+        let pubkey = PublicKey {
+            delta_after: self.params.vk.delta_g1,
+            s,
+            s_delta,
+            r_delta,
+            transcript,
+        };
+        let receipt = pubkey.hash();
+        self.contributions.push(pubkey);
+
+        receipt
+    }
+
+    /// Verifies that `self` is the result of a single contribution to `before`. Returns the
+    /// receipt of that contribution.
+    pub fn verify_contribution(&self, before: &MPCParameters) -> Result<Hash> {
+        ensure!(
+            self.cs_hash[..] == before.cs_hash[..],
+            "parameters are for different circuits"
+        );
+        ensure_same_circuit(&self.params, &before.params)?;
+        ensure!(
+            self.contributions.len() == before.contributions.len() + 1
+                && self.contributions[..before.contributions.len()] == before.contributions[..],
+            "parameters do not extend the previous contributions by exactly one"
+        );
+
+        let index = before.contributions.len();
+        let pubkey = &self.contributions[index];
+        verify_public_key(
+            &self.cs_hash,
+            &self.contributions[..index],
+            pubkey,
+            &before.params.vk.delta_g1,
+        )
+        .with_context(|| format!("invalid contribution {}", index))?;
+        verify_delta(&self.params, &before.params, pubkey)?;
+
+        Ok(pubkey.hash())
+    }
+
+    /// Verifies the full chain of contributions from `initial`, the parameters recomputed with
+    /// `new` from the circuit and phase 1. Returns the receipts of all contributions, oldest
+    /// first.
+    pub fn verify_contributions(&self, initial: &MPCParameters) -> Result<Vec<Hash>> {
+        ensure!(
+            initial.contributions.is_empty()
+                && initial.params.vk.delta_g1 == G1Affine::one()
+                && initial.params.vk.delta_g2 == G2Affine::one(),
+            "invalid initial parameters"
+        );
+        ensure!(
+            self.cs_hash[..] == initial.cs_hash[..],
+            "parameters were not derived from this circuit and phase 1"
+        );
+        ensure_same_circuit(&self.params, &initial.params)?;
+        ensure!(
+            !self.contributions.is_empty(),
+            "parameters without contributions are insecure"
+        );
+
+        let mut delta_g1 = initial.params.vk.delta_g1;
+        let mut receipts = Vec::with_capacity(self.contributions.len());
+        for (i, pubkey) in self.contributions.iter().enumerate() {
+            verify_public_key(&self.cs_hash, &self.contributions[..i], pubkey, &delta_g1)
+                .with_context(|| format!("invalid contribution {}", i))?;
+            delta_g1 = pubkey.delta_after;
+            receipts.push(pubkey.hash());
+        }
+
+        let last = self.contributions.last().expect("no contributions");
+        verify_delta(&self.params, &initial.params, last)?;
+
+        Ok(receipts)
+    }
+}
+
+/// Checks the proof of knowledge in `pubkey`, and that it moved `delta_g1` from `delta_before`
+/// to `pubkey.delta_after`.
+fn verify_public_key(
+    cs_hash: &Hash,
+    previous: &[PublicKey],
+    pubkey: &PublicKey,
+    delta_before: &G1Affine,
+) -> Result<()> {
+    ensure!(
+        !pubkey.s.is_zero() && !pubkey.s_delta.is_zero() && !pubkey.delta_after.is_zero(),
+        "degenerate public key"
+    );
+
+    let transcript = transcript(cs_hash, previous, &pubkey.s, &pubkey.s_delta);
+    ensure!(
+        transcript[..] == pubkey.transcript[..],
+        "transcript does not match the previous contributions"
+    );
+
+    let r = hash_to_g2(&transcript).into_affine();
+    ensure!(
+        same_ratio((pubkey.s, pubkey.s_delta), (r, pubkey.r_delta)),
+        "invalid proof of knowledge of delta"
+    );
+    ensure!(
+        same_ratio((*delta_before, pubkey.delta_after), (r, pubkey.r_delta)),
+        "delta was not updated by the contributed secret"
+    );
+
+    Ok(())
+}
+
+/// Checks that `after` is `before` with `delta` multiplied, and the `h` and `l` queries divided,
+/// by the same secret, ending at `last.delta_after`.
+fn verify_delta(
+    after: &Parameters<Bls12>,
+    before: &Parameters<Bls12>,
+    last: &PublicKey,
+) -> Result<()> {
+    ensure!(
+        after.vk.delta_g1 == last.delta_after,
+        "delta_g1 does not match the last contribution"
+    );
+    ensure!(
+        same_ratio(
+            (G1Affine::one(), after.vk.delta_g1),
+            (G2Affine::one(), after.vk.delta_g2)
+        ),
+        "delta_g1 and delta_g2 are inconsistent"
+    );
+
+    let delta_g2 = (after.vk.delta_g2, before.vk.delta_g2);
+    ensure!(
+        same_ratio(merge_pairs(&before.h, &after.h), delta_g2),
+        "h query was not updated consistently with delta"
+    );
+    ensure!(
+        same_ratio(merge_pairs(&before.l, &after.l), delta_g2),
+        "l query was not updated consistently with delta"
+    );
+
+    Ok(())
+}
+
+/// Checks that `a` and `b` only differ in `delta` and the `h` and `l` queries.
+fn ensure_same_circuit(a: &Parameters<Bls12>, b: &Parameters<Bls12>) -> Result<()> {
+    ensure!(
+        a.vk.alpha_g1 == b.vk.alpha_g1
+            && a.vk.beta_g1 == b.vk.beta_g1
+            && a.vk.beta_g2 == b.vk.beta_g2
+            && a.vk.gamma_g2 == b.vk.gamma_g2
+            && a.vk.ic == b.vk.ic
+            && a.a == b.a
+            && a.b_g1 == b.b_g1
+            && a.b_g2 == b.b_g2
+            && a.h.len() == b.h.len()
+            && a.l.len() == b.l.len(),
+        "parameters differ in more than delta and the h and l queries"
+    );
+
+    Ok(())
+}
+
+/// BLAKE2b-512 of `cs_hash`, the `previous` public keys, `s` and `s_delta`.
+fn transcript(cs_hash: &Hash, previous: &[PublicKey], s: &G1Affine, s_delta: &G1Affine) -> Hash {
+    let mut hasher = Blake2b::new();
+    hasher.update(cs_hash);
+    for pubkey in previous {
+        pubkey.write(&mut hasher).expect("hashing failure");
+    }
+    hasher.update(s.into_uncompressed().as_ref());
+    hasher.update(s_delta.into_uncompressed().as_ref());
+    into_hash(&hasher)
+}
+
+/// Derives a G2 point from `digest`, with unknown discrete logarithm.
+fn hash_to_g2(digest: &Hash) -> G2 {
+    let mut seed = [0; 32];
+    seed.copy_from_slice(&digest[..32]);
+    G2::random(&mut ChaChaRng::from_seed(seed))
+}
+
+fn into_hash(hasher: &Blake2b) -> Hash {
+    let mut hash = [0; HASH_BYTES];
+    hash.copy_from_slice(hasher.finalize().as_bytes());
+    hash
+}
+
+/// Returns whether `g1.1 / g1.0 == g2.1 / g2.0`.
+fn same_ratio(g1: (G1Affine, G1Affine), g2: (G2Affine, G2Affine)) -> bool {
+    Bls12::pairing(g1.0, g2.1) == Bls12::pairing(g1.1, g2.0)
+}
+
+/// Combines `v1` and `v2` with the same random coefficients, so that `same_ratio` of the result
+/// checks all pairs `(v1[i], v2[i])` at once, except with negligible probability.
+fn merge_pairs(v1: &[G1Affine], v2: &[G1Affine]) -> (G1Affine, G1Affine) {
+    assert_eq!(v1.len(), v2.len());
+
+    let (s, sx) = v1
+        .par_chunks(MERGE_CHUNK_SIZE)
+        .zip(v2.par_chunks(MERGE_CHUNK_SIZE))
+        .map(|(v1, v2)| {
+            let rng = &mut rand::thread_rng();
+            let mut s = G1::zero();
+            let mut sx = G1::zero();
+            for (p1, p2) in v1.iter().zip(v2.iter()) {
+                let rho = Fr::random(rng).into_repr();
+                s.add_assign(&p1.mul(rho));
+                sx.add_assign(&p2.mul(rho));
+            }
+            (s, sx)
+        })
+        .reduce(
+            || (G1::zero(), G1::zero()),
+            |(mut s, mut sx), (s2, sx2)| {
+                s.add_assign(&s2);
+                sx.add_assign(&sx2);
+                (s, sx)
+            },
+        );
+
+    (s.into_affine(), sx.into_affine())
+}
+
+fn batch_mul(points: &[G1Affine], scalar: Fr) -> Vec<G1Affine> {
+    let scalar = scalar.into_repr();
+    points
+        .par_iter()
+        .map(|p| p.mul(scalar).into_affine())
+        .collect()
+}
+
+/// Evaluates a polynomial given by its Lagrange `terms` at tau, in the group of `coeffs`.
+fn evaluate<G: CurveAffine<Scalar = Fr>>(terms: &[(Fr, usize)], coeffs: &[G]) -> G::Projective {
+    let mut acc = G::Projective::zero();
+    for (coeff, constraint) in terms {
+        if *coeff == Fr::one() {
+            acc.add_assign_mixed(&coeffs[*constraint]);
+        } else {
+            acc.add_assign(&coeffs[*constraint].mul(coeff.into_repr()));
+        }
+    }
+    acc
+}
+
+fn read_point<G: CurveAffine, R: Read>(reader: &mut R, checked: bool) -> Result<G> {
+    let mut encoded = G::Uncompressed::empty();
+    reader.read_exact(encoded.as_mut())?;
+    let point = if checked {
+        encoded.into_affine()
+    } else {
+        encoded.into_affine_unchecked()
+    };
+
+    point.map_err(|e| anyhow!("invalid point: {}", e))
+}
+
+fn read_points<G: CurveAffine, R: Read>(reader: &mut R, n: usize, checked: bool) -> Result<Vec<G>> {
+    (0..n).map(|_| read_point(reader, checked)).collect()
+}
+
+fn write_point<G: CurveAffine, W: Write>(writer: &mut W, point: &G) -> io::Result<()> {
+    writer.write_all(point.into_uncompressed().as_ref())
+}
+
+fn write_points<G: CurveAffine, W: Write>(writer: &mut W, points: &[G]) -> io::Result<()> {
+    points.iter().try_for_each(|p| write_point(writer, p))
+}
+
+/// A constraint system recording, for every variable, its coefficients in the A, B and C
+/// polynomials of each constraint.
+#[derive(Default)]
+struct KeypairAssembly {
+    num_inputs: usize,
+    num_aux: usize,
+    num_constraints: usize,
+    at_inputs: Vec<Terms>,
+    bt_inputs: Vec<Terms>,
+    ct_inputs: Vec<Terms>,
+    at_aux: Vec<Terms>,
+    bt_aux: Vec<Terms>,
+    ct_aux: Vec<Terms>,
+}
+
+impl KeypairAssembly {
+    fn synthesize<C: Circuit<Bls12>>(circuit: C) -> Result<Self> {
+        let mut assembly = KeypairAssembly::default();
+        assembly.alloc_input(|| "ONE", || Ok(Fr::one()))?;
+        circuit
+            .synthesize(&mut assembly)
+            .context("failed to synthesize circuit")?;
+
+        // Inputs must be constrained manually, so that they are linearly independent. This
+        // matches the constraints added by the prover.
+        for i in 0..assembly.num_inputs {
+            assembly.enforce(
+                || "",
+                |lc| lc + Variable::new_unchecked(Index::Input(i)),
+                |lc| lc,
+                |lc| lc,
+            );
+        }
+
+        Ok(assembly)
+    }
+
+    /// Returns `exp` of the smallest domain `2^exp` holding all constraints.
+    fn domain_exp(&self) -> Result<u32> {
+        let exp = self.num_constraints.next_power_of_two().trailing_zeros();
+        ensure!(exp <= Fr::S, "circuit has too many constraints");
+
+        Ok(exp)
+    }
+}
+
+fn record_terms(
+    lc: &LinearCombination<Bls12>,
+    inputs: &mut [Terms],
+    aux: &mut [Terms],
+    constraint: usize,
+) {
+    for (var, coeff) in lc.iter() {
+        match var.get_unchecked() {
+            Index::Input(i) => inputs[i].push((*coeff, constraint)),
+            Index::Aux(i) => aux[i].push((*coeff, constraint)),
+        }
+    }
+}
+
+impl ConstraintSystem<Bls12> for KeypairAssembly {
+    type Root = Self;
+
+    fn alloc<F, A, AR>(&mut self, _annotation: A, _f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let index = self.num_aux;
+        self.num_aux += 1;
+        self.at_aux.push(Vec::new());
+        self.bt_aux.push(Vec::new());
+        self.ct_aux.push(Vec::new());
+
+        Ok(Variable::new_unchecked(Index::Aux(index)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, _annotation: A, _f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Fr, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let index = self.num_inputs;
+        self.num_inputs += 1;
+        self.at_inputs.push(Vec::new());
+        self.bt_inputs.push(Vec::new());
+        self.ct_inputs.push(Vec::new());
+
+        Ok(Variable::new_unchecked(Index::Input(index)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, _annotation: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<Bls12>) -> LinearCombination<Bls12>,
+        LB: FnOnce(LinearCombination<Bls12>) -> LinearCombination<Bls12>,
+        LC: FnOnce(LinearCombination<Bls12>) -> LinearCombination<Bls12>,
+    {
+        let constraint = self.num_constraints;
+        self.num_constraints += 1;
+
+        record_terms(
+            &a(LinearCombination::zero()),
+            &mut self.at_inputs,
+            &mut self.at_aux,
+            constraint,
+        );
+        record_terms(
+            &b(LinearCombination::zero()),
+            &mut self.bt_inputs,
+            &mut self.bt_aux,
+            constraint,
+        );
+        record_terms(
+            &c(LinearCombination::zero()),
+            &mut self.ct_inputs,
+            &mut self.ct_aux,
+            constraint,
+        );
+    }
+
+    fn push_namespace<NR, N>(&mut self, _name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self) {}
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bellperson::groth16::{create_random_proof, prepare_verifying_key, verify_proof};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    const TEST_SEED: [u8; 16] = [
+        0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06, 0xbc,
+        0xe5,
+    ];
+
+    /// Proves knowledge of `x` such that `x^3 + x + 5 = y`, with `y` public.
+    #[derive(Clone)]
+    struct Cubic(Option<Fr>);
+
+    impl Circuit<Bls12> for Cubic {
+        fn synthesize<CS: ConstraintSystem<Bls12>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let x_value = self.0;
+            let x_sq_value = x_value.map(|mut x| {
+                x.square();
+                x
+            });
+            let x_cu_value = x_sq_value.and_then(|mut x_sq| {
+                x_sq.mul_assign(&x_value?);
+                Some(x_sq)
+            });
+            let y_value = x_cu_value.and_then(|mut y| {
+                y.add_assign(&x_value?);
+                y.add_assign(&Fr::from_str("5")?);
+                Some(y)
+            });
+
+            let x = cs.alloc(|| "x", || x_value.ok_or(SynthesisError::AssignmentMissing))?;
+            let x_sq = cs.alloc(
+                || "x_sq",
+                || x_sq_value.ok_or(SynthesisError::AssignmentMissing),
+            )?;
+            let x_cu = cs.alloc(
+                || "x_cu",
+                || x_cu_value.ok_or(SynthesisError::AssignmentMissing),
+            )?;
+            let y = cs.alloc_input(|| "y", || y_value.ok_or(SynthesisError::AssignmentMissing))?;
+
+            let five = Fr::from_str("5").expect("invalid field element");
+            cs.enforce(|| "x_sq", |lc| lc + x, |lc| lc + x, |lc| lc + x_sq);
+            cs.enforce(|| "x_cu", |lc| lc + x_sq, |lc| lc + x, |lc| lc + x_cu);
+            cs.enforce(
+                || "y",
+                |lc| lc + x_cu + x + (five, CS::one()),
+                |lc| lc + CS::one(),
+                |lc| lc + y,
+            );
+
+            Ok(())
+        }
+    }
+
+    /// Phase 1 for known, and therefore insecure, secrets.
+    fn insecure_phase1<R: RngCore>(exp: u32, rng: &mut R) -> Phase1 {
+        let tau = Fr::random(rng);
+        let alpha = Fr::random(rng);
+        let beta = Fr::random(rng);
+        let m = 1usize << exp;
+
+        let mut omega = Fr::root_of_unity();
+        for _ in exp..Fr::S {
+            omega.square();
+        }
+
+        // Z(tau) = tau^m - 1
+        let mut z = tau.pow(&[m as u64]);
+        z.sub_assign(&Fr::one());
+
+        // L_j(tau) = Z(tau) * omega^j / (m * (tau - omega^j))
+        let m_inv = Fr::from_str(&m.to_string())
+            .expect("invalid field element")
+            .inverse()
+            .expect("m is invertible");
+        let mut omega_j = Fr::one();
+        let mut lagrange = Vec::with_capacity(m);
+        for _ in 0..m {
+            let mut denominator = tau;
+            denominator.sub_assign(&omega_j);
+            let mut l = denominator.inverse().expect("tau is not a root of unity");
+            l.mul_assign(&z);
+            l.mul_assign(&omega_j);
+            l.mul_assign(&m_inv);
+            lagrange.push(l);
+            omega_j.mul_assign(&omega);
+        }
+
+        let g1 = |s: &Fr| G1Affine::one().mul(s.into_repr()).into_affine();
+        let g2 = |s: &Fr| G2Affine::one().mul(s.into_repr()).into_affine();
+        let times = |a: &Fr, b: &Fr| {
+            let mut c = *a;
+            c.mul_assign(b);
+            c
+        };
+
+        let mut tau_i = Fr::one();
+        let mut h = Vec::with_capacity(m - 1);
+        for _ in 0..m - 1 {
+            h.push(g1(&times(&tau_i, &z)));
+            tau_i.mul_assign(&tau);
+        }
+
+        Phase1 {
+            alpha_g1: g1(&alpha),
+            beta_g1: g1(&beta),
+            beta_g2: g2(&beta),
+            coeffs_g1: lagrange.iter().map(g1).collect(),
+            coeffs_g2: lagrange.iter().map(g2).collect(),
+            alpha_coeffs_g1: lagrange.iter().map(|l| g1(&times(l, &alpha))).collect(),
+            beta_coeffs_g1: lagrange.iter().map(|l| g1(&times(l, &beta))).collect(),
+            h,
+        }
+    }
+
+    fn setup(rng: &mut XorShiftRng) -> (Phase1, MPCParameters) {
+        let assembly = KeypairAssembly::synthesize(Cubic(None)).expect("synthesis failure");
+        let exp = assembly.domain_exp().expect("invalid domain");
+        let phase1 = insecure_phase1(exp, rng);
+        let initial =
+            MPCParameters::new_with_phase1(Cubic(None), &phase1).expect("initialization failure");
+
+        (phase1, initial)
+    }
+
+    #[test]
+    fn test_ceremony() {
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+        let (_, initial) = setup(rng);
+        assert!(initial.contributions().is_empty());
+
+        let mut params = initial.clone();
+        let receipt1 = params.contribute(rng);
+        let after1 = params.clone();
+        let receipt2 = params.contribute(rng);
+
+        assert_eq!(
+            &after1
+                .verify_contribution(&initial)
+                .expect("invalid contribution")[..],
+            &receipt1[..]
+        );
+        assert_eq!(
+            &params
+                .verify_contribution(&after1)
+                .expect("invalid contribution")[..],
+            &receipt2[..]
+        );
+        assert!(params.verify_contribution(&initial).is_err());
+
+        let receipts = params
+            .verify_contributions(&initial)
+            .expect("invalid contributions");
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(&receipts[0][..], &receipt1[..]);
+        assert_eq!(&receipts[1][..], &receipt2[..]);
+        assert!(initial.verify_contributions(&initial).is_err());
+
+        // The final parameters prove and verify.
+        let params = params.into_params();
+        let pvk = prepare_verifying_key(&params.vk);
+        let x = Fr::from_str("3").expect("invalid field element");
+        let proof = create_random_proof(Cubic(Some(x)), &params, rng).expect("proving failure");
+        let y = Fr::from_str("35").expect("invalid field element");
+        assert!(verify_proof(&pvk, &proof, &[y]).expect("verification failure"));
+        assert!(!verify_proof(&pvk, &proof, &[x]).expect("verification failure"));
+    }
+
+    #[test]
+    fn test_rejects_tampered_contribution() {
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+        let (_, initial) = setup(rng);
+
+        let mut params = initial.clone();
+        params.contribute(rng);
+
+        // Changing delta without the matching h and l queries.
+        let mut tampered = params.clone();
+        tampered.params.vk.delta_g1 = initial.params.vk.delta_g1;
+        assert!(tampered.verify_contribution(&initial).is_err());
+
+        // Replacing a query point.
+        let mut tampered = params.clone();
+        let mut l = tampered.params.l.to_vec();
+        l[0] = G1Affine::one();
+        tampered.params.l = Arc::new(l);
+        assert!(tampered.verify_contribution(&initial).is_err());
+        assert!(tampered.verify_contributions(&initial).is_err());
+
+        // Rewriting the transcript.
+        let mut tampered = params;
+        tampered.contributions[0].transcript[0] ^= 1;
+        assert!(tampered.verify_contributions(&initial).is_err());
+    }
+
+    #[test]
+    fn test_serialization_roundtrip() {
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+        let (phase1, mut params) = setup(rng);
+        params.contribute(rng);
+
+        let mut bytes = Vec::new();
+        params.write(&mut bytes).expect("write failure");
+        let read = MPCParameters::read(&bytes[..], true).expect("read failure");
+        assert!(read == params);
+
+        let exp = phase1.h.len().next_power_of_two().trailing_zeros();
+        let mut bytes = Vec::new();
+        phase1.write(&mut bytes).expect("write failure");
+        let read = Phase1::read(&bytes[..], exp).expect("read failure");
+        assert!(read == phase1);
+    }
+
+    #[test]
+    fn test_rejects_mismatched_phase1() {
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+        let (phase1, _) = setup(rng);
+        let exp = phase1.h.len().next_power_of_two().trailing_zeros();
+        let larger = insecure_phase1(exp + 1, rng);
+        assert!(MPCParameters::new_with_phase1(Cubic(None), &larger).is_err());
+    }
+}
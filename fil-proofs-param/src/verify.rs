@@ -0,0 +1,157 @@
+use std::fs::{self, read_dir};
+use std::path::Path;
+
+use anyhow::{ensure, Context, Result};
+use filecoin_proofs::param::{get_digest_for_file, has_extension};
+use log::{info, trace, warn};
+use rayon::prelude::*;
+use storage_proofs_core::parameter_cache::{
+    ParameterData, ParameterMap, GROTH_PARAMETER_EXT, VERIFYING_KEY_EXT,
+};
+
+/// The state of a parameter cache compared to a manifest.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Files whose digest matches the manifest.
+    pub valid: Vec<String>,
+    /// Files listed in the manifest which are not in the cache.
+    pub missing: Vec<String>,
+    /// Files whose digest does not match the manifest, or which could not be read.
+    pub mismatched: Vec<String>,
+    /// Parameter and verifying key files in the cache which are not listed in the manifest.
+    pub unknown: Vec<String>,
+}
+
+impl VerifyReport {
+    /// Returns true if every checked file is present and valid. Unknown files are ignored.
+    pub fn is_intact(&self) -> bool {
+        self.missing.is_empty() && self.mismatched.is_empty()
+    }
+
+    /// Returns the missing and mismatched files, which need to be fetched again.
+    pub fn needs_fetch(&self) -> Vec<String> {
+        let mut filenames: Vec<String> = self
+            .missing
+            .iter()
+            .chain(self.mismatched.iter())
+            .cloned()
+            .collect();
+        filenames.sort();
+        filenames
+    }
+}
+
+/// Checks `filenames`, which must be listed in `parameter_map`, against their digests. Files are
+/// hashed in parallel.
+///
+/// Files in `cache_dir` which are not listed in `parameter_map` at all are reported as unknown.
+pub fn verify_cache(
+    cache_dir: &Path,
+    parameter_map: &ParameterMap,
+    filenames: &[String],
+) -> Result<VerifyReport> {
+    enum Status {
+        Valid,
+        Missing,
+        Mismatched,
+    }
+
+    for filename in filenames {
+        ensure!(
+            parameter_map.contains_key(filename),
+            "file is not listed in the manifest: {}",
+            filename
+        );
+    }
+
+    let statuses: Vec<(&String, Status)> = filenames
+        .par_iter()
+        .map(|filename| {
+            let path = cache_dir.join(filename);
+            if !path.exists() {
+                trace!("file not found: {}", filename);
+                return (filename, Status::Missing);
+            }
+
+            trace!("hashing file: {}", filename);
+            let expected_digest = &parameter_map[filename].digest;
+            match get_digest_for_file(&path) {
+                Ok(ref digest) if digest == expected_digest => (filename, Status::Valid),
+                Ok(digest) => {
+                    warn!(
+                        "file has unexpected digest: {} (expected {}, got {})",
+                        filename, expected_digest, digest
+                    );
+                    (filename, Status::Mismatched)
+                }
+                Err(e) => {
+                    warn!("failed to hash file {}: {:?}", filename, e);
+                    (filename, Status::Mismatched)
+                }
+            }
+        })
+        .collect();
+
+    let mut report = VerifyReport::default();
+    for (filename, status) in statuses {
+        let list = match status {
+            Status::Valid => &mut report.valid,
+            Status::Missing => &mut report.missing,
+            Status::Mismatched => &mut report.mismatched,
+        };
+        list.push(filename.clone());
+    }
+
+    if cache_dir.exists() {
+        for entry in read_dir(cache_dir)
+            .with_context(|| format!("could not read cache dir {:?}", cache_dir))?
+        {
+            let filename = entry?.file_name().to_string_lossy().into_owned();
+            let is_param_file = has_extension(&filename, GROTH_PARAMETER_EXT)
+                || has_extension(&filename, VERIFYING_KEY_EXT);
+            if is_param_file && !parameter_map.contains_key(&filename) {
+                report.unknown.push(filename);
+            }
+        }
+        report.unknown.sort();
+    }
+
+    Ok(report)
+}
+
+/// Fetches the missing and mismatched files of `report` with `fetch` and verifies them again.
+///
+/// Mismatched files are moved aside to `<filename>-invalid-digest` before being fetched. The
+/// returned report only covers the files which needed to be fetched.
+pub fn repair_cache<F>(
+    cache_dir: &Path,
+    parameter_map: &ParameterMap,
+    report: &VerifyReport,
+    fetch: F,
+) -> Result<VerifyReport>
+where
+    F: Fn(&str, &ParameterData) -> Result<()>,
+{
+    for filename in &report.mismatched {
+        let path = cache_dir.join(filename);
+        if path.exists() {
+            let new_path = cache_dir.join(format!("{}-invalid-digest", filename));
+            trace!("moving invalid params to: {}", new_path.display());
+            fs::rename(&path, &new_path)
+                .with_context(|| format!("could not move {:?} to {:?}", path, new_path))?;
+        }
+    }
+
+    let filenames = report.needs_fetch();
+    for filename in &filenames {
+        info!("repairing file: {}", filename);
+        if let Err(e) = fetch(filename, &parameter_map[filename]) {
+            warn!("failed to fetch {}: {:?}", filename, e);
+        }
+    }
+
+    let mut repaired = verify_cache(cache_dir, parameter_map, &filenames)?;
+    repaired.unknown = report.unknown.clone();
+
+    Ok(repaired)
+}
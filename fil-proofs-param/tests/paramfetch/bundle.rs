@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::Path;
+
+use fil_proofs_param::bundle::{install_bundle, write_bundle, BUNDLE_MANIFEST};
+use storage_proofs_core::parameter_cache::{CacheEntryMetadata, ParameterMap};
+use tempfile::tempdir;
+
+use super::rand_parameter_file;
+
+/// Populate `cache_dir` with a `.params`, `.vk` and `.meta` file triple and return the manifest
+/// of the `.params` and `.vk` files.
+fn populate_cache(cache_dir: &Path) -> ParameterMap {
+    let mut manifest = ParameterMap::new();
+
+    for filename in &["v1-aaa.params", "v1-aaa.vk"] {
+        let (bytes, data) = rand_parameter_file(1024, &format!("cid-{}", filename));
+        fs::write(cache_dir.join(filename), &bytes).expect("failed to write file");
+        manifest.insert(filename.to_string(), data);
+    }
+
+    let meta = CacheEntryMetadata { sector_size: 2048 };
+    fs::write(
+        cache_dir.join("v1-aaa.meta"),
+        serde_json::to_vec(&meta).expect("failed to serialize metadata"),
+    )
+    .expect("failed to write file");
+
+    manifest
+}
+
+fn assert_roundtrip(bundle_name: &str) {
+    let src_dir = tempdir().expect("failed to create cache dir");
+    let manifest = populate_cache(src_dir.path());
+
+    let bundle_dir = tempdir().expect("failed to create bundle dir");
+    let bundle_path = bundle_dir.path().join(bundle_name);
+    let written =
+        write_bundle(src_dir.path(), &manifest, &bundle_path).expect("write_bundle failed");
+    assert_eq!(written, vec!["v1-aaa.meta", "v1-aaa.params", "v1-aaa.vk"]);
+
+    let dst_dir = tempdir().expect("failed to create cache dir");
+    let mut installed = install_bundle(&bundle_path, dst_dir.path()).expect("install failed");
+    installed.sort();
+    assert_eq!(installed, written);
+
+    for filename in &written {
+        assert_eq!(
+            fs::read(dst_dir.path().join(filename)).expect("failed to read installed file"),
+            fs::read(src_dir.path().join(filename)).expect("failed to read source file"),
+        );
+    }
+    assert!(!dst_dir.path().join(BUNDLE_MANIFEST).exists());
+}
+
+#[test]
+fn bundle_directory_roundtrip() {
+    assert_roundtrip("bundle");
+}
+
+#[test]
+fn bundle_tarball_roundtrip() {
+    assert_roundtrip("bundle.tar");
+}
+
+#[test]
+fn bundle_gzipped_tarball_roundtrip() {
+    assert_roundtrip("bundle.tar.gz");
+}
+
+#[test]
+fn bundle_with_corrupted_file_is_rejected() {
+    let src_dir = tempdir().expect("failed to create cache dir");
+    let manifest = populate_cache(src_dir.path());
+
+    let bundle_dir = tempdir().expect("failed to create bundle dir");
+    let bundle_path = bundle_dir.path().join("bundle");
+    write_bundle(src_dir.path(), &manifest, &bundle_path).expect("write_bundle failed");
+    fs::write(bundle_path.join("v1-aaa.params"), b"corrupted").expect("failed to write file");
+
+    let dst_dir = tempdir().expect("failed to create cache dir");
+    assert!(install_bundle(&bundle_path, dst_dir.path()).is_err());
+
+    // The valid files are installed, the corrupted one leaves nothing behind.
+    assert!(dst_dir.path().join("v1-aaa.vk").exists());
+    assert!(!dst_dir.path().join("v1-aaa.params").exists());
+    assert!(!dst_dir.path().join("v1-aaa.params.partial").exists());
+}
+
+#[test]
+fn bundle_with_missing_file_is_rejected() {
+    let src_dir = tempdir().expect("failed to create cache dir");
+    let manifest = populate_cache(src_dir.path());
+
+    let bundle_dir = tempdir().expect("failed to create bundle dir");
+    let bundle_path = bundle_dir.path().join("bundle");
+    write_bundle(src_dir.path(), &manifest, &bundle_path).expect("write_bundle failed");
+    fs::remove_file(bundle_path.join("v1-aaa.vk")).expect("failed to remove file");
+
+    let dst_dir = tempdir().expect("failed to create cache dir");
+    assert!(install_bundle(&bundle_path, dst_dir.path()).is_err());
+}
@@ -0,0 +1,246 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use fil_proofs_param::fetch::{partial_path, HttpFetcher};
+use tempfile::tempdir;
+
+use super::rand_parameter_file;
+
+/// A request as seen by the test server: its path and `Range` header, if any.
+type Request = (String, Option<String>);
+
+/// A minimal HTTP server, standing in for a gateway, which serves files by path and supports
+/// range requests.
+struct TestServer {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl TestServer {
+    fn start(files: BTreeMap<String, Vec<u8>>) -> TestServer {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind test server");
+        let addr = listener.local_addr().expect("failed to get local addr");
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let seen = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.expect("failed to accept connection");
+                handle(stream, &files, &seen).expect("failed to handle request");
+            }
+        });
+
+        TestServer { addr, requests }
+    }
+
+    fn url_template(&self, path: &str) -> String {
+        format!("http://{}/{}", self.addr, path)
+    }
+
+    fn requests(&self) -> Vec<Request> {
+        self.requests.lock().expect("requests poisoned").clone()
+    }
+}
+
+fn handle(
+    mut stream: TcpStream,
+    files: &BTreeMap<String, Vec<u8>>,
+    requests: &Mutex<Vec<Request>>,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or_default()
+        .to_string();
+
+    let mut range = None;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let mut parts = line.splitn(2, ':');
+        let name = parts.next().unwrap_or_default();
+        if name.eq_ignore_ascii_case("range") {
+            range = parts.next().map(|value| value.trim().to_string());
+        }
+    }
+
+    // Record the request before responding, so the client always observes it.
+    requests
+        .lock()
+        .expect("requests poisoned")
+        .push((path.clone(), range.clone()));
+
+    let (status, headers, body): (&str, String, &[u8]) = match files.get(&path) {
+        None => ("404 Not Found", String::new(), &[]),
+        Some(bytes) => match range.as_ref().and_then(|r| parse_range_start(r)) {
+            None => ("200 OK", String::new(), &bytes[..]),
+            Some(start) if start >= bytes.len() => (
+                "416 Range Not Satisfiable",
+                format!("Content-Range: bytes */{}\r\n", bytes.len()),
+                &[],
+            ),
+            Some(start) => (
+                "206 Partial Content",
+                format!(
+                    "Content-Range: bytes {}-{}/{}\r\n",
+                    start,
+                    bytes.len() - 1,
+                    bytes.len()
+                ),
+                &bytes[start..],
+            ),
+        },
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        headers,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+fn parse_range_start(range: &str) -> Option<usize> {
+    range
+        .strip_prefix("bytes=")?
+        .split('-')
+        .next()?
+        .parse()
+        .ok()
+}
+
+#[test]
+fn fetches_and_verifies_file() {
+    let (bytes, data) = rand_parameter_file(4096, "QmAAA");
+    let mut files = BTreeMap::new();
+    files.insert("/ipfs/QmAAA".to_string(), bytes.clone());
+    let server = TestServer::start(files);
+
+    let cache_dir = tempdir().expect("failed to create cache dir");
+    let fetcher = HttpFetcher::new(vec![server.url_template("ipfs/{cid}")])
+        .expect("failed to create fetcher");
+
+    let path = fetcher
+        .fetch(cache_dir.path(), "aaa.vk", &data)
+        .expect("fetch failed");
+
+    assert_eq!(path, cache_dir.path().join("aaa.vk"));
+    assert_eq!(fs::read(&path).expect("failed to read file"), bytes);
+    assert!(!partial_path(&path).exists());
+    assert_eq!(server.requests(), vec![("/ipfs/QmAAA".to_string(), None)]);
+}
+
+#[test]
+fn resumes_partial_download() {
+    let (bytes, data) = rand_parameter_file(4096, "QmAAA");
+    let mut files = BTreeMap::new();
+    files.insert("/aaa.params".to_string(), bytes.clone());
+    let server = TestServer::start(files);
+
+    let cache_dir = tempdir().expect("failed to create cache dir");
+    let path = cache_dir.path().join("aaa.params");
+    File::create(partial_path(&path))
+        .and_then(|mut file| file.write_all(&bytes[..1000]))
+        .expect("failed to write partial file");
+
+    let fetcher = HttpFetcher::new(vec![server.url_template("{filename}")])
+        .expect("failed to create fetcher");
+    fetcher
+        .fetch(cache_dir.path(), "aaa.params", &data)
+        .expect("fetch failed");
+
+    assert_eq!(fs::read(&path).expect("failed to read file"), bytes);
+    assert_eq!(
+        server.requests(),
+        vec![("/aaa.params".to_string(), Some("bytes=1000-".to_string()))]
+    );
+}
+
+#[test]
+fn verifies_complete_partial_download() {
+    let (bytes, data) = rand_parameter_file(4096, "QmAAA");
+    let mut files = BTreeMap::new();
+    files.insert("/aaa.params".to_string(), bytes.clone());
+    let server = TestServer::start(files);
+
+    let cache_dir = tempdir().expect("failed to create cache dir");
+    let path = cache_dir.path().join("aaa.params");
+    fs::write(partial_path(&path), &bytes).expect("failed to write partial file");
+
+    let fetcher = HttpFetcher::new(vec![server.url_template("{filename}")])
+        .expect("failed to create fetcher");
+    fetcher
+        .fetch(cache_dir.path(), "aaa.params", &data)
+        .expect("fetch failed");
+
+    assert_eq!(fs::read(&path).expect("failed to read file"), bytes);
+    assert!(!partial_path(&path).exists());
+}
+
+#[test]
+fn rejects_digest_mismatch() {
+    let (bytes, mut data) = rand_parameter_file(4096, "QmAAA");
+    data.digest = "obviouslywrong".to_string();
+    let mut files = BTreeMap::new();
+    files.insert("/ipfs/QmAAA".to_string(), bytes);
+    let server = TestServer::start(files);
+
+    let cache_dir = tempdir().expect("failed to create cache dir");
+    let fetcher = HttpFetcher::new(vec![server.url_template("ipfs/{cid}")])
+        .expect("failed to create fetcher");
+
+    assert!(fetcher.fetch(cache_dir.path(), "aaa.vk", &data).is_err());
+
+    let path = cache_dir.path().join("aaa.vk");
+    assert!(!path.exists());
+    assert!(!partial_path(&path).exists());
+}
+
+#[test]
+fn falls_back_to_next_mirror() {
+    let (bytes, data) = rand_parameter_file(4096, "QmAAA");
+    let mut files = BTreeMap::new();
+    files.insert("/mirror/aaa.vk".to_string(), bytes.clone());
+    let server = TestServer::start(files);
+
+    let cache_dir = tempdir().expect("failed to create cache dir");
+    let fetcher = HttpFetcher::new(vec![
+        server.url_template("ipfs/{cid}"),
+        server.url_template("mirror/{filename}"),
+    ])
+    .expect("failed to create fetcher");
+
+    let path = fetcher
+        .fetch(cache_dir.path(), "aaa.vk", &data)
+        .expect("fetch failed");
+
+    assert_eq!(fs::read(&path).expect("failed to read file"), bytes);
+    assert_eq!(
+        server.requests(),
+        vec![
+            ("/ipfs/QmAAA".to_string(), None),
+            ("/mirror/aaa.vk".to_string(), None),
+        ]
+    );
+}
+
+#[test]
+fn rejects_url_template_without_placeholder() {
+    assert!(HttpFetcher::new(vec!["http://127.0.0.1/params".to_string()]).is_err());
+    assert!(HttpFetcher::new(Vec::new()).is_err());
+}
@@ -5,12 +5,15 @@ use std::path::PathBuf;
 
 use blake2b_simd::State as Blake2b;
 use failure::Error as FailureError;
-use rand::{thread_rng, Rng};
+use rand::{thread_rng, Rng, RngCore};
 use storage_proofs_core::parameter_cache::{ParameterData, ParameterMap};
 
 use crate::support::tmp_manifest;
 
+mod bundle;
+mod http;
 mod session;
+mod verify;
 
 use session::ParamFetchSessionBuilder;
 
@@ -19,17 +22,33 @@ use session::ParamFetchSessionBuilder;
 /// parampublish implementation.
 fn rand_bytes_with_blake2b() -> Result<(Vec<u8>, String), FailureError> {
     let bytes = thread_rng().gen::<[u8; 32]>();
+    let checksum = blake2b_checksum(&bytes)?;
 
+    Ok((bytes.iter().cloned().collect(), checksum))
+}
+
+/// Produce `len` random bytes and the parameter manifest entry for them.
+fn rand_parameter_file(len: usize, cid: &str) -> (Vec<u8>, ParameterData) {
+    let mut bytes = vec![0u8; len];
+    thread_rng().fill_bytes(&mut bytes);
+
+    let data = ParameterData {
+        cid: cid.to_string(),
+        digest: blake2b_checksum(&bytes).expect("failed to hash bytes"),
+        sector_size: 2048,
+    };
+
+    (bytes, data)
+}
+
+fn blake2b_checksum(bytes: &[u8]) -> Result<String, FailureError> {
     let mut hasher = Blake2b::new();
 
-    let mut as_slice = &bytes[..];
+    let mut as_slice = bytes;
 
     io::copy(&mut as_slice, &mut hasher)?;
 
-    Ok((
-        bytes.iter().cloned().collect(),
-        hasher.finalize().to_hex()[..32].into(),
-    ))
+    Ok(hasher.finalize().to_hex()[..32].into())
 }
 
 #[test]
@@ -0,0 +1,109 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+
+use fil_proofs_param::verify::{repair_cache, verify_cache, VerifyReport};
+use storage_proofs_core::parameter_cache::ParameterMap;
+use tempfile::tempdir;
+
+use super::rand_parameter_file;
+
+/// A manifest of `aaa.params` (valid), `bbb.params` (missing) and `ccc.vk` (mismatched), along
+/// with the expected contents of each file.
+fn manifest() -> (ParameterMap, BTreeMap<String, Vec<u8>>) {
+    let mut manifest = ParameterMap::new();
+    let mut contents = BTreeMap::new();
+
+    for filename in &["aaa.params", "bbb.params", "ccc.vk"] {
+        let (bytes, data) = rand_parameter_file(1024, "");
+        manifest.insert(filename.to_string(), data);
+        contents.insert(filename.to_string(), bytes);
+    }
+
+    (manifest, contents)
+}
+
+#[test]
+fn reports_missing_mismatched_and_unknown_files() {
+    let (manifest, contents) = manifest();
+    let cache_dir = tempdir().expect("failed to create cache dir");
+
+    fs::write(cache_dir.path().join("aaa.params"), &contents["aaa.params"])
+        .expect("failed to write file");
+    fs::write(cache_dir.path().join("ccc.vk"), b"corrupted").expect("failed to write file");
+    fs::write(cache_dir.path().join("zzz.vk"), b"unknown").expect("failed to write file");
+    fs::write(cache_dir.path().join("zzz.meta"), b"ignored").expect("failed to write file");
+
+    let filenames: Vec<String> = manifest.keys().cloned().collect();
+    let report =
+        verify_cache(cache_dir.path(), &manifest, &filenames).expect("verify_cache failed");
+
+    assert_eq!(
+        report,
+        VerifyReport {
+            valid: vec!["aaa.params".to_string()],
+            missing: vec!["bbb.params".to_string()],
+            mismatched: vec!["ccc.vk".to_string()],
+            unknown: vec!["zzz.vk".to_string()],
+        }
+    );
+    assert!(!report.is_intact());
+    assert_eq!(
+        report.needs_fetch(),
+        vec!["bbb.params".to_string(), "ccc.vk".to_string()]
+    );
+}
+
+#[test]
+fn only_checks_selected_files() {
+    let (manifest, contents) = manifest();
+    let cache_dir = tempdir().expect("failed to create cache dir");
+
+    fs::write(cache_dir.path().join("aaa.params"), &contents["aaa.params"])
+        .expect("failed to write file");
+
+    let report = verify_cache(cache_dir.path(), &manifest, &["aaa.params".to_string()])
+        .expect("verify_cache failed");
+
+    assert!(report.is_intact());
+    assert_eq!(report.valid, vec!["aaa.params".to_string()]);
+    assert!(report.unknown.is_empty());
+
+    assert!(verify_cache(cache_dir.path(), &manifest, &["zzz.params".to_string()]).is_err());
+}
+
+#[test]
+fn repair_fetches_only_bad_files() {
+    let (manifest, contents) = manifest();
+    let cache_dir = tempdir().expect("failed to create cache dir");
+
+    fs::write(cache_dir.path().join("aaa.params"), &contents["aaa.params"])
+        .expect("failed to write file");
+    fs::write(cache_dir.path().join("ccc.vk"), b"corrupted").expect("failed to write file");
+
+    let filenames: Vec<String> = manifest.keys().cloned().collect();
+    let report =
+        verify_cache(cache_dir.path(), &manifest, &filenames).expect("verify_cache failed");
+
+    let fetched = RefCell::new(Vec::new());
+    let repaired = repair_cache(cache_dir.path(), &manifest, &report, |filename, _data| {
+        fetched.borrow_mut().push(filename.to_string());
+        fs::write(cache_dir.path().join(filename), &contents[filename])?;
+        Ok(())
+    })
+    .expect("repair_cache failed");
+
+    assert_eq!(
+        fetched.into_inner(),
+        vec!["bbb.params".to_string(), "ccc.vk".to_string()]
+    );
+    assert!(repaired.is_intact());
+    assert_eq!(
+        repaired.valid,
+        vec!["bbb.params".to_string(), "ccc.vk".to_string()]
+    );
+    assert_eq!(
+        fs::read(cache_dir.path().join("ccc.vk-invalid-digest")).expect("failed to read file"),
+        b"corrupted"
+    );
+}
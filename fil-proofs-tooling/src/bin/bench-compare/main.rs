@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+use std::process::exit;
+
+use anyhow::{ensure, Result};
+use byte_unit::Byte;
+use fil_proofs_tooling::compare::{compare, BenchResult, Comparison, MeasurementKey, Thresholds};
+use log::{info, warn};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "bench-compare",
+    about = "Compares benchy and prodbench results against a baseline, failing on regressions"
+)]
+struct Opt {
+    /// The baseline result, followed by the results to compare against it.
+    #[structopt(parse(from_os_str), required = true, min_values = 2)]
+    results: Vec<PathBuf>,
+    /// Relative changes of at most this many percent are noise.
+    #[structopt(long, default_value = "2")]
+    noise: f64,
+    /// Changes of timings of at most this many milliseconds are noise.
+    #[structopt(long, default_value = "10")]
+    noise_floor_ms: f64,
+    /// Fail if a measurement got worse by more than this many percent.
+    #[structopt(long, default_value = "5")]
+    tolerance: f64,
+}
+
+fn describe(result: &BenchResult) -> String {
+    match &result.git_hash {
+        Some(hash) => format!(
+            "{:?} (git {})",
+            result.path,
+            hash.chars().take(8).collect::<String>()
+        ),
+        None => format!("{:?}", result.path),
+    }
+}
+
+fn sector_size(key: &MeasurementKey) -> String {
+    key.sector_size
+        .map(|size| {
+            Byte::from_bytes(size.into())
+                .get_appropriate_unit(true)
+                .to_string()
+        })
+        .unwrap_or_else(|| "-".into())
+}
+
+fn print_comparison(baseline: &BenchResult, candidate: &BenchResult, comparison: &Comparison) {
+    println!("{} against {}", describe(candidate), describe(baseline));
+    println!(
+        "{:<12} {:<56} {:>14} {:>14} {:>10}  verdict",
+        "sector-size", "measurement", "baseline", "candidate", "delta"
+    );
+    for m in &comparison.measurements {
+        println!(
+            "{:<12} {:<56} {:>14.2} {:>14.2} {:>+9.2}%  {}",
+            sector_size(&m.key),
+            m.key.name,
+            m.baseline,
+            m.candidate,
+            m.delta_percent,
+            m.verdict
+        );
+    }
+    for key in &comparison.only_in_baseline {
+        println!(
+            "{:<12} {:<56} only in the baseline",
+            sector_size(key),
+            key.name
+        );
+    }
+    for key in &comparison.only_in_candidate {
+        println!(
+            "{:<12} {:<56} only in the candidate",
+            sector_size(key),
+            key.name
+        );
+    }
+    println!();
+}
+
+/// Returns the number of failures, regressions and measurements missing from a result.
+fn run(opt: Opt) -> Result<usize> {
+    ensure!(
+        opt.results.len() >= 2,
+        "a baseline and at least one result to compare are required"
+    );
+    let thresholds = Thresholds {
+        noise_percent: opt.noise,
+        noise_floor_ms: opt.noise_floor_ms,
+        tolerance_percent: opt.tolerance,
+    };
+    thresholds.validate()?;
+
+    let results = opt
+        .results
+        .iter()
+        .map(BenchResult::load)
+        .collect::<Result<Vec<_>>>()?;
+    let (baseline, candidates) = results.split_first().expect("no baseline");
+
+    let mut failures = 0;
+    for candidate in candidates {
+        if candidate.processor != baseline.processor {
+            warn!(
+                "{:?} ran on {:?}, but the baseline on {:?}",
+                candidate.path, candidate.processor, baseline.processor
+            );
+        }
+
+        let comparison = compare(baseline, candidate, &thresholds);
+        print_comparison(baseline, candidate, &comparison);
+        ensure!(
+            !comparison.measurements.is_empty(),
+            "{:?} has no measurements in common with the baseline",
+            candidate.path
+        );
+        failures += comparison.failures();
+    }
+
+    Ok(failures)
+}
+
+fn main() {
+    fil_logger::init();
+
+    let opt = Opt::from_args();
+    info!("bench-compare: {:?}", opt);
+
+    let tolerance = opt.tolerance;
+    match run(opt) {
+        Ok(0) => {}
+        Ok(failures) => {
+            eprintln!(
+                "bench-compare: {} measurements regressed by more than {}% or are missing",
+                failures, tolerance
+            );
+            exit(1);
+        }
+        Err(err) => {
+            eprintln!("bench-compare failed: {:?}", err);
+            exit(1);
+        }
+    }
+}
@@ -2,6 +2,7 @@
 //#![warn(clippy::unwrap_used)]
 
 use std::io::{stdin, stdout};
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use anyhow::Result;
@@ -14,7 +15,10 @@ use crate::prodbench::ProdbenchInputs;
 
 mod hash_fns;
 mod merkleproofs;
+mod pieces;
+mod pipeline;
 mod prodbench;
+mod unseal;
 mod window_post;
 mod winning_post;
 
@@ -97,6 +101,23 @@ fn main() -> Result<()> {
                 .takes_value(true),
         );
 
+    let window_post_farm_cmd = SubCommand::with_name("window-post-farm")
+        .about("Benchmark Window PoST over the sectors of a farm generated by fakefarm")
+        .arg(
+            Arg::with_name("manifest")
+                .long("manifest")
+                .required(true)
+                .help("The manifest.json of the farm")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("api_version")
+                .long("api-version")
+                .help("The api_version to use (default: 1.0.0)")
+                .default_value("1.0.0")
+                .takes_value(true),
+        );
+
     let winning_post_cmd = SubCommand::with_name("winning-post")
         .about("Benchmark Winning PoST")
         .arg(
@@ -115,6 +136,60 @@ fn main() -> Result<()> {
                 .takes_value(true),
         );
 
+    let size_arg = Arg::with_name("size")
+        .long("size")
+        .required(true)
+        .help("The sector size (e.g. 2KiB)")
+        .takes_value(true);
+    let preserved_cache_arg = Arg::with_name("cache")
+        .long("cache")
+        .required(false)
+        .help("Re-use the sector preserved in this directory by window-post --preserve-cache")
+        .takes_value(true);
+
+    let add_piece_cmd = SubCommand::with_name("add-piece")
+        .about("Benchmark add_piece of a piece filling a sector")
+        .arg(size_arg.clone())
+        .arg(preserved_cache_arg.clone());
+
+    let piece_commitment_cmd = SubCommand::with_name("piece-commitment")
+        .about("Benchmark generate_piece_commitment of a piece filling a sector")
+        .arg(size_arg.clone())
+        .arg(preserved_cache_arg.clone());
+
+    let fr32_cmd = SubCommand::with_name("fr32")
+        .about("Benchmark fr32 padding and unpadding of a piece filling a sector")
+        .arg(size_arg.clone())
+        .arg(preserved_cache_arg.clone());
+
+    let unseal_cmd = SubCommand::with_name("unseal")
+        .about("Benchmark get_unsealed_range and unseal_range")
+        .arg(size_arg)
+        .arg(preserved_cache_arg)
+        .arg(
+            Arg::with_name("offset")
+                .long("offset")
+                .required(false)
+                .help("The unpadded offset of the range to unseal")
+                .default_value("0")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("num-bytes")
+                .long("num-bytes")
+                .required(false)
+                .help("The unpadded length of the range to unseal (default: up to the sector end)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("api_version")
+                .long("api-version")
+                .required(true)
+                .help("The api_version to use (default: 1.0.0)")
+                .default_value("1.0.0")
+                .takes_value(true),
+        );
+
     let hash_cmd = SubCommand::with_name("hash-constraints")
         .about("Benchmark hash function inside of a circuit");
 
@@ -181,8 +256,13 @@ fn main() -> Result<()> {
     let matches = App::new("benchy")
         .version("0.1")
         .subcommand(window_post_cmd)
+        .subcommand(window_post_farm_cmd)
         .subcommand(winning_post_cmd)
         .subcommand(hash_cmd)
+        .subcommand(add_piece_cmd)
+        .subcommand(piece_commitment_cmd)
+        .subcommand(fr32_cmd)
+        .subcommand(unseal_cmd)
         .subcommand(prodbench_cmd)
         .subcommand(merkleproof_cmd)
         .get_matches();
@@ -211,6 +291,11 @@ fn main() -> Result<()> {
                 test_resume,
             )?;
         }
+        ("window-post-farm", Some(m)) => {
+            let manifest = PathBuf::from(value_t!(m, "manifest", String)?);
+            let api_version = ApiVersion::from_str(&value_t!(m, "api_version", String)?)?;
+            window_post::run_farm(manifest, api_version)?;
+        }
         ("winning-post", Some(m)) => {
             let sector_size = Byte::from_str(value_t!(m, "size", String)?)?.get_bytes() as usize;
             let api_version = ApiVersion::from_str(&value_t!(m, "api_version", String)?)?;
@@ -219,6 +304,33 @@ fn main() -> Result<()> {
         ("hash-constraints", Some(_m)) => {
             hash_fns::run()?;
         }
+        ("add-piece", Some(m)) => {
+            let sector_size = Byte::from_str(value_t!(m, "size", String)?)?.get_bytes() as u64;
+            let cache_dir = m.value_of("cache").map(PathBuf::from);
+            pieces::run_add_piece(sector_size, cache_dir)?;
+        }
+        ("piece-commitment", Some(m)) => {
+            let sector_size = Byte::from_str(value_t!(m, "size", String)?)?.get_bytes() as u64;
+            let cache_dir = m.value_of("cache").map(PathBuf::from);
+            pieces::run_piece_commitment(sector_size, cache_dir)?;
+        }
+        ("fr32", Some(m)) => {
+            let sector_size = Byte::from_str(value_t!(m, "size", String)?)?.get_bytes() as u64;
+            let cache_dir = m.value_of("cache").map(PathBuf::from);
+            pieces::run_fr32(sector_size, cache_dir)?;
+        }
+        ("unseal", Some(m)) => {
+            let sector_size = Byte::from_str(value_t!(m, "size", String)?)?.get_bytes() as u64;
+            let cache_dir = m.value_of("cache").map(PathBuf::from);
+            let offset = value_t!(m, "offset", u64)?;
+            let num_bytes = if m.is_present("num-bytes") {
+                Some(value_t!(m, "num-bytes", u64)?)
+            } else {
+                None
+            };
+            let api_version = ApiVersion::from_str(&value_t!(m, "api_version", String)?)?;
+            unseal::run(sector_size, api_version, cache_dir, offset, num_bytes)?;
+        }
         ("merkleproofs", Some(m)) => {
             let size = Byte::from_str(value_t!(m, "size", String)?)?.get_bytes() as usize;
 
@@ -0,0 +1,263 @@
+use std::cmp::min;
+use std::fs::File;
+use std::io::{copy, sink, stdout, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{ensure, Context, Result};
+use fil_proofs_tooling::shared::create_piece;
+use fil_proofs_tooling::{measure, Metadata};
+use filecoin_proofs::{
+    add_piece, generate_piece_commitment, sector_shape, PaddedBytesAmount, UnpaddedBytesAmount,
+};
+use fr32::{write_unpadded, Fr32Reader};
+use log::info;
+use memmap::MmapOptions;
+use serde::Serialize;
+use tempfile::NamedTempFile;
+
+use crate::window_post::PIECE_FILE;
+
+/// Pieces are unpadded and compared in chunks of this many unpadded bytes, a multiple of the 127
+/// bytes padded into 128, so that sectors need not fit into memory.
+const UNPAD_CHUNK_BYTES: usize = 127 << 13;
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Inputs {
+    sector_size: u64,
+    piece_size: u64,
+    preserved_cache: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct AddPieceOutputs {
+    add_piece_cpu_time_ms: u64,
+    add_piece_wall_time_ms: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct PieceCommitmentOutputs {
+    generate_piece_commitment_cpu_time_ms: u64,
+    generate_piece_commitment_wall_time_ms: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Fr32Outputs {
+    pad_cpu_time_ms: u64,
+    pad_wall_time_ms: u64,
+    pad_throughput_mib_per_s: f64,
+    unpad_cpu_time_ms: u64,
+    unpad_wall_time_ms: u64,
+    unpad_throughput_mib_per_s: f64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Report<T: Serialize> {
+    inputs: Inputs,
+    outputs: T,
+}
+
+impl<T: Serialize> Report<T> {
+    /// Print all results to stdout
+    pub fn print(&self) {
+        let wrapped = Metadata::wrap(&self).expect("failed to retrieve metadata");
+        serde_json::to_writer(stdout(), &wrapped).expect("cannot write report JSON to stdout");
+    }
+}
+
+/// A piece filling a sector of `sector_size`, either the piece of the sector preserved in
+/// `cache_dir` by `benchy window-post --preserve-cache`, or a random one which lives as long as
+/// the returned temp file.
+fn piece_file(
+    sector_size: u64,
+    cache_dir: Option<&Path>,
+) -> Result<(PathBuf, Option<NamedTempFile>)> {
+    let piece_size = UnpaddedBytesAmount::from(PaddedBytesAmount(sector_size));
+
+    match cache_dir {
+        Some(cache_dir) => {
+            let path = cache_dir.join(PIECE_FILE);
+            let len = path
+                .metadata()
+                .with_context(|| format!("could not read preserved piece {:?}", path))?
+                .len();
+            ensure!(
+                len == u64::from(piece_size),
+                "preserved piece {:?} has {} bytes, but sectors of {} bytes hold {} bytes",
+                path,
+                len,
+                sector_size,
+                u64::from(piece_size)
+            );
+            info!("Re-using preserved piece {:?}", path);
+
+            Ok((path, None))
+        }
+        None => {
+            let file = create_piece(piece_size);
+            Ok((file.path().to_path_buf(), Some(file)))
+        }
+    }
+}
+
+fn inputs(sector_size: u64, cache_dir: Option<&Path>) -> Inputs {
+    Inputs {
+        sector_size,
+        piece_size: u64::from(UnpaddedBytesAmount::from(PaddedBytesAmount(sector_size))),
+        preserved_cache: cache_dir.is_some(),
+    }
+}
+
+fn throughput_mib_per_s(bytes: u64, wall_time_ms: u64) -> f64 {
+    let seconds = std::cmp::max(wall_time_ms, 1) as f64 / 1000.0;
+    bytes as f64 / (1024.0 * 1024.0) / seconds
+}
+
+pub fn run_add_piece(sector_size: u64, cache_dir: Option<PathBuf>) -> Result<()> {
+    info!(
+        "Benchy add_piece: sector-size={}, cache={:?}",
+        sector_size, cache_dir
+    );
+    sector_shape(sector_size)?;
+
+    let cache_dir = cache_dir.as_deref();
+    let (piece_path, _piece_file) = piece_file(sector_size, cache_dir)?;
+    let piece_size = UnpaddedBytesAmount::from(PaddedBytesAmount(sector_size));
+
+    // Stage next to the preserved sector, so the same disk is measured.
+    let staged_file = match cache_dir {
+        Some(cache_dir) => NamedTempFile::new_in(cache_dir)?,
+        None => NamedTempFile::new()?,
+    };
+
+    let source = File::open(&piece_path)?;
+    let target = staged_file.reopen()?;
+    let add_piece_measurement = measure(|| add_piece(source, target, piece_size, &[]))?;
+
+    Report {
+        inputs: inputs(sector_size, cache_dir),
+        outputs: AddPieceOutputs {
+            add_piece_cpu_time_ms: add_piece_measurement.cpu_time.as_millis() as u64,
+            add_piece_wall_time_ms: add_piece_measurement.wall_time.as_millis() as u64,
+        },
+    }
+    .print();
+
+    Ok(())
+}
+
+pub fn run_piece_commitment(sector_size: u64, cache_dir: Option<PathBuf>) -> Result<()> {
+    info!(
+        "Benchy generate_piece_commitment: sector-size={}, cache={:?}",
+        sector_size, cache_dir
+    );
+    sector_shape(sector_size)?;
+
+    let cache_dir = cache_dir.as_deref();
+    let (piece_path, _piece_file) = piece_file(sector_size, cache_dir)?;
+    let piece_size = UnpaddedBytesAmount::from(PaddedBytesAmount(sector_size));
+
+    let source = File::open(&piece_path)?;
+    let measurement = measure(|| generate_piece_commitment(source, piece_size))?;
+
+    Report {
+        inputs: inputs(sector_size, cache_dir),
+        outputs: PieceCommitmentOutputs {
+            generate_piece_commitment_cpu_time_ms: measurement.cpu_time.as_millis() as u64,
+            generate_piece_commitment_wall_time_ms: measurement.wall_time.as_millis() as u64,
+        },
+    }
+    .print();
+
+    Ok(())
+}
+
+pub fn run_fr32(sector_size: u64, cache_dir: Option<PathBuf>) -> Result<()> {
+    info!(
+        "Benchy fr32: sector-size={}, cache={:?}",
+        sector_size, cache_dir
+    );
+    sector_shape(sector_size)?;
+
+    let cache_dir = cache_dir.as_deref();
+    let (piece_path, _piece_file) = piece_file(sector_size, cache_dir)?;
+    let piece_size = u64::from(UnpaddedBytesAmount::from(PaddedBytesAmount(sector_size)));
+
+    // Padding streams from disk, like `add_piece`, while unpadding works on a sector mapped into
+    // memory, like unsealing.
+    let source = BufReader::new(File::open(&piece_path)?);
+    let pad_measurement = measure(|| Ok(copy(&mut Fr32Reader::new(source), &mut sink())?))?;
+    ensure!(
+        pad_measurement.return_value == sector_size,
+        "padded {} bytes into {} bytes instead of {}",
+        piece_size,
+        pad_measurement.return_value,
+        sector_size
+    );
+
+    let padded_file = match cache_dir {
+        Some(cache_dir) => NamedTempFile::new_in(cache_dir)?,
+        None => NamedTempFile::new()?,
+    };
+    {
+        let mut writer = BufWriter::new(padded_file.reopen()?);
+        copy(
+            &mut Fr32Reader::new(BufReader::new(File::open(&piece_path)?)),
+            &mut writer,
+        )?;
+        writer.flush()?;
+    }
+    let padded = unsafe { MmapOptions::new().map(padded_file.as_file())? };
+
+    // Only unpadding is measured, each chunk is compared with the piece in between.
+    let mut piece = BufReader::new(File::open(&piece_path)?);
+    let mut unpadded = Vec::with_capacity(UNPAD_CHUNK_BYTES);
+    let mut expected = vec![0u8; UNPAD_CHUNK_BYTES];
+    let mut unpad_cpu_time = Duration::default();
+    let mut unpad_wall_time = Duration::default();
+    let mut offset = 0;
+    while offset < piece_size {
+        let len = min(piece_size - offset, UNPAD_CHUNK_BYTES as u64) as usize;
+        unpadded.clear();
+        let measurement = measure(|| {
+            Ok(write_unpadded(
+                &padded,
+                &mut unpadded,
+                offset as usize,
+                len,
+            )?)
+        })?;
+        unpad_cpu_time += measurement.cpu_time;
+        unpad_wall_time += measurement.wall_time;
+
+        piece.read_exact(&mut expected[..len])?;
+        ensure!(
+            unpadded == expected[..len],
+            "unpadding did not restore the piece at offset {}",
+            offset
+        );
+        offset += len as u64;
+    }
+
+    let pad_wall_time_ms = pad_measurement.wall_time.as_millis() as u64;
+    let unpad_wall_time_ms = unpad_wall_time.as_millis() as u64;
+    Report {
+        inputs: inputs(sector_size, cache_dir),
+        outputs: Fr32Outputs {
+            pad_cpu_time_ms: pad_measurement.cpu_time.as_millis() as u64,
+            pad_wall_time_ms,
+            pad_throughput_mib_per_s: throughput_mib_per_s(piece_size, pad_wall_time_ms),
+            unpad_cpu_time_ms: unpad_cpu_time.as_millis() as u64,
+            unpad_wall_time_ms,
+            unpad_throughput_mib_per_s: throughput_mib_per_s(piece_size, unpad_wall_time_ms),
+        },
+    }
+    .print();
+
+    Ok(())
+}
@@ -0,0 +1,570 @@
+//! A sealing pipeline for `prodbench`, where sectors arrive one after the other and the phases of
+//! different sectors overlap, like they do on a storage provider's machine.
+//!
+//! Every phase has a pool of workers, which take sectors from the queue of the phase and hand
+//! them to the queue of the next phase. The time a sector waits in a queue is its queueing delay
+//! for that phase.
+
+use std::fs::read_to_string;
+use std::io::{Seek, SeekFrom};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, ensure, Context, Result};
+use fil_proofs_tooling::shared::{create_piece, PROVER_ID, RANDOMNESS, TICKET_BYTES};
+use filecoin_proofs::{
+    add_piece, clear_cache, seal_commit_phase1, seal_commit_phase2, seal_pre_commit_phase1,
+    seal_pre_commit_phase2, validate_cache_for_commit, validate_cache_for_precommit_phase2,
+    DefaultOctLCTree, PaddedBytesAmount, PieceInfo, PoRepConfig, SealCommitPhase1Output,
+    SealPreCommitOutput, SealPreCommitPhase1Output, UnpaddedBytesAmount,
+};
+use log::info;
+use rand::random;
+use serde::{Deserialize, Serialize};
+use storage_proofs_core::sector::SectorId;
+use tempfile::{tempdir, NamedTempFile, TempDir};
+
+type PipelineTree = DefaultOctLCTree;
+
+/// How often the memory usage is sampled.
+const MEMORY_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The schedule of the pipeline, read from the `pipeline` section of the prodbench inputs.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PipelineConfig {
+    /// The number of sectors going through each phase at the same time.
+    pub add_piece_concurrency: usize,
+    pub precommit_phase1_concurrency: usize,
+    pub precommit_phase2_concurrency: usize,
+    pub commit_phase1_concurrency: usize,
+    pub commit_phase2_concurrency: usize,
+    /// The time between two sectors entering the pipeline.
+    pub sector_interval_ms: u64,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        PipelineConfig {
+            add_piece_concurrency: 1,
+            precommit_phase1_concurrency: 1,
+            precommit_phase2_concurrency: 1,
+            commit_phase1_concurrency: 1,
+            commit_phase2_concurrency: 1,
+            sector_interval_ms: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    AddPiece,
+    PreCommitPhase1,
+    PreCommitPhase2,
+    CommitPhase1,
+    CommitPhase2,
+}
+
+const PHASES: [Phase; 5] = [
+    Phase::AddPiece,
+    Phase::PreCommitPhase1,
+    Phase::PreCommitPhase2,
+    Phase::CommitPhase1,
+    Phase::CommitPhase2,
+];
+
+impl Phase {
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn concurrency(self, config: &PipelineConfig) -> usize {
+        match self {
+            Phase::AddPiece => config.add_piece_concurrency,
+            Phase::PreCommitPhase1 => config.precommit_phase1_concurrency,
+            Phase::PreCommitPhase2 => config.precommit_phase2_concurrency,
+            Phase::CommitPhase1 => config.commit_phase1_concurrency,
+            Phase::CommitPhase2 => config.commit_phase2_concurrency,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PhaseOutputs {
+    concurrency: usize,
+    sectors: usize,
+    mean_wall_time_ms: u64,
+    max_wall_time_ms: u64,
+    mean_queue_time_ms: u64,
+    max_queue_time_ms: u64,
+    /// Sectors completed per hour, from the first sector starting the phase to the last one
+    /// completing it.
+    sectors_per_hour: f64,
+    /// The peak resident memory of the whole process while the phase was running, if known.
+    /// This includes the memory of the phases running at the same time, so the peaks of
+    /// overlapping phases don't add up.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    peak_process_memory_bytes: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PipelineOutputs {
+    wall_time_ms: u64,
+    sectors_per_hour: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    peak_memory_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    add_piece: Option<PhaseOutputs>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    precommit_phase1: Option<PhaseOutputs>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    precommit_phase2: Option<PhaseOutputs>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    commit_phase1: Option<PhaseOutputs>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    commit_phase2: Option<PhaseOutputs>,
+}
+
+/// A sector on its way through the pipeline, holding the outputs of the phases it went through.
+struct Sector {
+    sector_id: SectorId,
+    piece_file: NamedTempFile,
+    staged_file: NamedTempFile,
+    sealed_file: NamedTempFile,
+    cache_dir: TempDir,
+    piece_infos: Vec<PieceInfo>,
+    pre_commit_phase1: Option<SealPreCommitPhase1Output<PipelineTree>>,
+    pre_commit: Option<SealPreCommitOutput>,
+    commit_phase1: Option<SealCommitPhase1Output<PipelineTree>>,
+    /// When the sector entered the queue of its next phase.
+    queued_at: Instant,
+}
+
+impl Sector {
+    fn new(porep_config: PoRepConfig) -> Result<Self> {
+        let piece_bytes = UnpaddedBytesAmount::from(PaddedBytesAmount::from(porep_config));
+
+        Ok(Sector {
+            sector_id: SectorId::from(random::<u64>()),
+            piece_file: create_piece(piece_bytes),
+            staged_file: NamedTempFile::new()?,
+            sealed_file: NamedTempFile::new()?,
+            cache_dir: tempdir()?,
+            piece_infos: Vec::new(),
+            pre_commit_phase1: None,
+            pre_commit: None,
+            commit_phase1: None,
+            queued_at: Instant::now(),
+        })
+    }
+
+    fn run_phase(&mut self, phase: Phase, porep_config: PoRepConfig) -> Result<()> {
+        let cache_path = self.cache_dir.path();
+        let sealed_path = self.sealed_file.path();
+
+        match phase {
+            Phase::AddPiece => {
+                let piece_bytes = UnpaddedBytesAmount::from(PaddedBytesAmount::from(porep_config));
+                self.piece_file.seek(SeekFrom::Start(0))?;
+                let (piece_info, _) = add_piece(
+                    &mut self.piece_file,
+                    &mut self.staged_file,
+                    piece_bytes,
+                    &[],
+                )?;
+                self.piece_infos.push(piece_info);
+            }
+            Phase::PreCommitPhase1 => {
+                self.pre_commit_phase1 = Some(seal_pre_commit_phase1(
+                    porep_config,
+                    cache_path,
+                    self.staged_file.path(),
+                    sealed_path,
+                    PROVER_ID,
+                    self.sector_id,
+                    TICKET_BYTES,
+                    &self.piece_infos,
+                )?);
+            }
+            Phase::PreCommitPhase2 => {
+                let phase1 = self
+                    .pre_commit_phase1
+                    .take()
+                    .expect("pre commit phase1 output missing");
+                validate_cache_for_precommit_phase2(cache_path, sealed_path, &phase1)?;
+                self.pre_commit = Some(seal_pre_commit_phase2(
+                    porep_config,
+                    phase1,
+                    cache_path,
+                    sealed_path,
+                )?);
+            }
+            Phase::CommitPhase1 => {
+                let pre_commit = self.pre_commit.take().expect("pre commit output missing");
+                validate_cache_for_commit::<_, _, PipelineTree>(cache_path, sealed_path)?;
+                self.commit_phase1 = Some(seal_commit_phase1(
+                    porep_config,
+                    cache_path,
+                    sealed_path,
+                    PROVER_ID,
+                    self.sector_id,
+                    TICKET_BYTES,
+                    RANDOMNESS,
+                    pre_commit,
+                    &self.piece_infos,
+                )?);
+                clear_cache::<PipelineTree>(cache_path)?;
+            }
+            Phase::CommitPhase2 => {
+                let phase1 = self
+                    .commit_phase1
+                    .take()
+                    .expect("commit phase1 output missing");
+                seal_commit_phase2(porep_config, phase1, PROVER_ID, self.sector_id)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PhaseTiming {
+    phase: Phase,
+    queued: Duration,
+    started_at: Instant,
+    finished_at: Instant,
+}
+
+/// State shared by the workers and the memory sampler.
+#[derive(Default)]
+struct Shared {
+    timings: Mutex<Vec<PhaseTiming>>,
+    /// The number of sectors each phase is working on.
+    active: [AtomicUsize; 5],
+    /// The peak resident memory of the process sampled while each phase was active.
+    peak_memory: [AtomicU64; 5],
+    done: AtomicBool,
+}
+
+/// Reads a size in kB from `/proc/self/status`, which only exists on Linux.
+fn proc_status_bytes(key: &str) -> Option<u64> {
+    let status = read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with(key))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+
+    Some(kib * 1024)
+}
+
+fn sample_memory(shared: &Shared) {
+    while !shared.done.load(Ordering::SeqCst) {
+        if let Some(rss) = proc_status_bytes("VmRSS:") {
+            for phase in &PHASES {
+                if shared.active[phase.index()].load(Ordering::SeqCst) > 0 {
+                    shared.peak_memory[phase.index()].fetch_max(rss, Ordering::SeqCst);
+                }
+            }
+        }
+        thread::sleep(MEMORY_SAMPLE_INTERVAL);
+    }
+}
+
+fn run_worker(
+    phase: Phase,
+    porep_config: PoRepConfig,
+    queue: Arc<Mutex<Receiver<Sector>>>,
+    next: Option<Sender<Sector>>,
+    shared: Arc<Shared>,
+) -> Result<()> {
+    loop {
+        // The lock is released before the phase runs, so the other workers can take sectors.
+        let received = queue.lock().expect("failed to acquire queue lock").recv();
+        let mut sector = match received {
+            Ok(sector) => sector,
+            // The previous phase is done.
+            Err(_) => return Ok(()),
+        };
+
+        let started_at = Instant::now();
+        shared.active[phase.index()].fetch_add(1, Ordering::SeqCst);
+        let result = sector.run_phase(phase, porep_config);
+        shared.active[phase.index()].fetch_sub(1, Ordering::SeqCst);
+        result.with_context(|| format!("{:?} of sector {:?} failed", phase, sector.sector_id))?;
+        let finished_at = Instant::now();
+
+        info!("{:?} of sector {:?} done", phase, sector.sector_id);
+        shared
+            .timings
+            .lock()
+            .expect("failed to acquire timings lock")
+            .push(PhaseTiming {
+                phase,
+                queued: started_at - sector.queued_at,
+                started_at,
+                finished_at,
+            });
+
+        if let Some(next) = &next {
+            sector.queued_at = finished_at;
+            next.send(sector)
+                .map_err(|_| anyhow!("the workers of the phase after {:?} are gone", phase))?;
+        }
+    }
+}
+
+fn phase_outputs(
+    phase: Phase,
+    config: &PipelineConfig,
+    timings: &[PhaseTiming],
+    shared: &Shared,
+) -> Option<PhaseOutputs> {
+    let timings: Vec<_> = timings.iter().filter(|t| t.phase == phase).collect();
+    let first_start = timings.iter().map(|t| t.started_at).min()?;
+    let last_finish = timings.iter().map(|t| t.finished_at).max()?;
+
+    let count = timings.len();
+    let wall_times: Vec<_> = timings
+        .iter()
+        .map(|t| t.finished_at - t.started_at)
+        .collect();
+    let mean = |durations: &[Duration]| {
+        (durations.iter().sum::<Duration>() / durations.len() as u32).as_millis() as u64
+    };
+    let queue_times: Vec<_> = timings.iter().map(|t| t.queued).collect();
+    let peak_memory = shared.peak_memory[phase.index()].load(Ordering::SeqCst);
+
+    Some(PhaseOutputs {
+        concurrency: phase.concurrency(config),
+        sectors: count,
+        mean_wall_time_ms: mean(&wall_times),
+        max_wall_time_ms: wall_times.iter().max()?.as_millis() as u64,
+        mean_queue_time_ms: mean(&queue_times),
+        max_queue_time_ms: queue_times.iter().max()?.as_millis() as u64,
+        sectors_per_hour: sectors_per_hour(count, last_finish - first_start),
+        peak_process_memory_bytes: if peak_memory > 0 {
+            Some(peak_memory)
+        } else {
+            None
+        },
+    })
+}
+
+fn sectors_per_hour(sectors: usize, elapsed: Duration) -> f64 {
+    let hours = elapsed.as_secs_f64().max(0.001) / 3600.0;
+    sectors as f64 / hours
+}
+
+/// Runs `num_sectors` sectors through the phases up to and including `last_phase`.
+pub fn run(
+    config: &PipelineConfig,
+    porep_config: PoRepConfig,
+    num_sectors: usize,
+    last_phase: Phase,
+) -> Result<PipelineOutputs> {
+    let phases: Vec<Phase> = PHASES
+        .iter()
+        .copied()
+        .filter(|phase| *phase <= last_phase)
+        .collect();
+    for phase in &phases {
+        ensure!(
+            phase.concurrency(config) > 0,
+            "the concurrency of {:?} must be at least 1",
+            phase
+        );
+    }
+    info!(
+        "running a pipeline of {} sectors through {:?}: {:?}",
+        num_sectors, phases, config
+    );
+
+    // Pieces are generated before the clock starts, so the pipeline is not throttled by them,
+    // and before any thread is spawned, so that failing to generate them leaves none running.
+    info!("generating the pieces of {} sectors", num_sectors);
+    let sectors = (0..num_sectors)
+        .map(|_| Sector::new(porep_config))
+        .collect::<Result<Vec<_>>>()?;
+
+    let shared = Arc::new(Shared::default());
+    let sampler = {
+        let shared = shared.clone();
+        thread::spawn(move || sample_memory(&shared))
+    };
+
+    // Build the phases back to front, so each worker gets the queue of the next phase.
+    let mut workers: Vec<JoinHandle<Result<()>>> = Vec::new();
+    let mut next: Option<Sender<Sector>> = None;
+    for phase in phases.iter().rev() {
+        let (sender, receiver) = channel();
+        let queue = Arc::new(Mutex::new(receiver));
+        for _ in 0..phase.concurrency(config) {
+            let (phase, queue, next, shared) =
+                (*phase, queue.clone(), next.clone(), shared.clone());
+            workers.push(thread::spawn(move || {
+                run_worker(phase, porep_config, queue, next, shared)
+            }));
+        }
+        next = Some(sender);
+    }
+    let first_queue = next.expect("no phases");
+
+    let started_at = Instant::now();
+    for (i, mut sector) in sectors.into_iter().enumerate() {
+        if i > 0 {
+            thread::sleep(Duration::from_millis(config.sector_interval_ms));
+        }
+        info!("sector {}/{} enters the pipeline", i + 1, num_sectors);
+        sector.queued_at = Instant::now();
+        if first_queue.send(sector).is_err() {
+            // All workers of the first phase failed, their error is reported below.
+            break;
+        }
+    }
+    // Closing the first queue lets the workers wind down phase by phase.
+    drop(first_queue);
+
+    let mut result = Ok(());
+    for worker in workers {
+        let worker_result = worker
+            .join()
+            .unwrap_or_else(|_| Err(anyhow!("pipeline worker panicked")));
+        if result.is_ok() {
+            result = worker_result;
+        }
+    }
+    let wall_time = started_at.elapsed();
+
+    shared.done.store(true, Ordering::SeqCst);
+    sampler.join().expect("memory sampler panicked");
+    result?;
+
+    let timings = shared
+        .timings
+        .lock()
+        .expect("failed to acquire timings lock")
+        .clone();
+    // Phases after `last_phase` have no timings, and therefore no outputs.
+    let outputs = |phase: Phase| phase_outputs(phase, config, &timings, &shared);
+
+    Ok(PipelineOutputs {
+        wall_time_ms: wall_time.as_millis() as u64,
+        sectors_per_hour: sectors_per_hour(num_sectors, wall_time),
+        peak_memory_bytes: proc_status_bytes("VmHWM:"),
+        add_piece: outputs(Phase::AddPiece),
+        precommit_phase1: outputs(Phase::PreCommitPhase1),
+        precommit_phase2: outputs(Phase::PreCommitPhase2),
+        commit_phase1: outputs(Phase::CommitPhase1),
+        commit_phase2: outputs(Phase::CommitPhase2),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use filecoin_proofs::{sector_shape, PoRepProofPartitions, SectorSize};
+    use storage_proofs_core::api_version::ApiVersion;
+
+    #[test]
+    fn test_sectors_per_hour() {
+        assert_eq!(sectors_per_hour(2, Duration::from_secs(3600)), 2.0);
+        assert_eq!(sectors_per_hour(3, Duration::from_secs(1800)), 6.0);
+        // Instant phases don't divide by zero.
+        assert_eq!(sectors_per_hour(1, Duration::from_secs(0)), 3_600_000.0);
+    }
+
+    #[test]
+    fn test_phase_outputs() {
+        let config = PipelineConfig {
+            add_piece_concurrency: 2,
+            ..PipelineConfig::default()
+        };
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let timings = vec![
+            PhaseTiming {
+                phase: Phase::AddPiece,
+                queued: Duration::from_secs(0),
+                started_at: at(0),
+                finished_at: at(10),
+            },
+            PhaseTiming {
+                phase: Phase::PreCommitPhase1,
+                queued: Duration::from_secs(1),
+                started_at: at(10),
+                finished_at: at(100),
+            },
+            PhaseTiming {
+                phase: Phase::AddPiece,
+                queued: Duration::from_secs(2),
+                started_at: at(5),
+                finished_at: at(25),
+            },
+        ];
+        let shared = Shared::default();
+        shared.peak_memory[Phase::AddPiece.index()].store(4096, Ordering::SeqCst);
+
+        let add_piece =
+            phase_outputs(Phase::AddPiece, &config, &timings, &shared).expect("missing outputs");
+        assert_eq!(add_piece.concurrency, 2);
+        assert_eq!(add_piece.sectors, 2);
+        assert_eq!(add_piece.mean_wall_time_ms, 15_000);
+        assert_eq!(add_piece.max_wall_time_ms, 20_000);
+        assert_eq!(add_piece.mean_queue_time_ms, 1_000);
+        assert_eq!(add_piece.max_queue_time_ms, 2_000);
+        // 2 sectors within the 25 seconds from the first start to the last finish.
+        assert!((add_piece.sectors_per_hour - 288.0).abs() < 1e-9);
+        assert_eq!(add_piece.peak_process_memory_bytes, Some(4096));
+
+        let precommit_phase1 = phase_outputs(Phase::PreCommitPhase1, &config, &timings, &shared)
+            .expect("missing outputs");
+        assert_eq!(precommit_phase1.sectors, 1);
+        assert_eq!(precommit_phase1.peak_process_memory_bytes, None);
+
+        assert!(phase_outputs(Phase::CommitPhase2, &config, &timings, &shared).is_none());
+    }
+
+    #[test]
+    fn test_run_2kib() {
+        let sector_size = 2048;
+        let porep_config = PoRepConfig {
+            sector_size: SectorSize(sector_size),
+            partitions: PoRepProofPartitions(
+                sector_shape(sector_size)
+                    .expect("sector_shape failure")
+                    .porep_partitions,
+            ),
+            porep_id: [5; 32],
+            api_version: ApiVersion::V1_1_0,
+        };
+        let config = PipelineConfig {
+            precommit_phase1_concurrency: 2,
+            ..PipelineConfig::default()
+        };
+
+        // Commit phase 2 would need the groth parameters.
+        let outputs = run(&config, porep_config, 2, Phase::CommitPhase1).expect("pipeline failure");
+
+        assert!(outputs.sectors_per_hour > 0.0);
+        for phase in &[
+            &outputs.add_piece,
+            &outputs.precommit_phase1,
+            &outputs.precommit_phase2,
+            &outputs.commit_phase1,
+        ] {
+            assert_eq!(phase.as_ref().expect("missing phase outputs").sectors, 2);
+        }
+        assert_eq!(
+            outputs
+                .precommit_phase1
+                .as_ref()
+                .expect("missing phase outputs")
+                .concurrency,
+            2
+        );
+        assert!(outputs.commit_phase2.is_none());
+    }
+}
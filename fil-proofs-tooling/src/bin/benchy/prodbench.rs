@@ -9,10 +9,10 @@ use fil_proofs_tooling::{
 };
 use filecoin_hashers::sha256::Sha256Hasher;
 use filecoin_proofs::{
-    clear_cache, parameters::public_params, seal_commit_phase1, seal_commit_phase2,
-    validate_cache_for_commit, DefaultOctLCTree, DefaultOctTree, PaddedBytesAmount, PoRepConfig,
-    PoRepProofPartitions, SectorSize, DRG_DEGREE, EXP_DEGREE, LAYERS, POREP_MINIMUM_CHALLENGES,
-    POREP_PARTITIONS,
+    clear_cache, override_sector_shape, parameters::public_params, seal_commit_phase1,
+    seal_commit_phase2, sector_shape, validate_cache_for_commit, DefaultOctLCTree, DefaultOctTree,
+    PaddedBytesAmount, PoRepConfig, PoRepProofPartitions, SectorShapeConfig, SectorSize,
+    DRG_DEGREE, EXP_DEGREE,
 };
 use log::info;
 use rand::SeedableRng;
@@ -26,6 +26,8 @@ use storage_proofs_core::{
 };
 use storage_proofs_porep::stacked::{LayerChallenges, SetupParams, StackedCompound, StackedDrg};
 
+use crate::pipeline::{self, Phase, PipelineConfig, PipelineOutputs};
+
 const SEED: [u8; 16] = [
     0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06, 0xbc, 0xe5,
 ];
@@ -50,6 +52,9 @@ pub struct ProdbenchInputs {
     /// How many sectors should be created in parallel.
     num_sectors: u64,
     api_version: String,
+    /// Seal the sectors in a pipeline instead of all at once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pipeline: Option<PipelineConfig>,
 }
 
 impl ProdbenchInputs {
@@ -95,6 +100,8 @@ pub struct ProdbenchOutputs {
     generate_piece_commitment_wall_time_ms: u64,
     #[serde(flatten)]
     circuits: CircuitOutputs,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pipeline: Option<PipelineOutputs>,
 }
 
 #[cfg(not(feature = "measurements"))]
@@ -158,18 +165,14 @@ fn augment_with_op_measurements(mut output: &mut ProdbenchOutputs) {
 }
 
 fn configure_global_config(inputs: &ProdbenchInputs) {
-    LAYERS
-        .write()
-        .expect("LAYERS poisoned")
-        .insert(inputs.sector_size_bytes(), inputs.stacked_layers as usize);
-    POREP_PARTITIONS
-        .write()
-        .expect("POREP_PARTITIONS poisoned")
-        .insert(inputs.sector_size_bytes(), inputs.porep_partitions);
-    POREP_MINIMUM_CHALLENGES
-        .write()
-        .expect("POREP_MINIMUM_CHALLENGES poisoned")
-        .insert(inputs.sector_size_bytes(), inputs.porep_challenges);
+    let shape = sector_shape(inputs.sector_size_bytes()).expect("unknown sector size");
+    override_sector_shape(SectorShapeConfig {
+        layers: inputs.stacked_layers as usize,
+        porep_partitions: inputs.porep_partitions,
+        porep_minimum_challenges: inputs.porep_challenges,
+        ..shape
+    })
+    .expect("override_sector_shape failure");
 }
 
 pub fn run(
@@ -188,6 +191,44 @@ pub fn run(
 
     assert!(inputs.num_sectors > 0, "Missing num_sectors");
 
+    if let Some(pipeline_config) = &inputs.pipeline {
+        let last_phase = if only_add_piece {
+            Phase::AddPiece
+        } else if only_replicate || skip_seal_proof {
+            Phase::PreCommitPhase2
+        } else {
+            generate_params(&inputs);
+            Phase::CommitPhase2
+        };
+        let porep_config = PoRepConfig {
+            sector_size,
+            partitions: PoRepProofPartitions(
+                sector_shape(u64::from(sector_size))
+                    .expect("unknown sector size")
+                    .porep_partitions,
+            ),
+            porep_id: arbitrary_porep_id,
+            api_version: inputs.api_version(),
+        };
+
+        outputs.pipeline = Some(
+            pipeline::run(
+                pipeline_config,
+                porep_config,
+                inputs.num_sectors as usize,
+                last_phase,
+            )
+            .expect("failed to run the sealing pipeline"),
+        );
+
+        augment_with_op_measurements(&mut outputs);
+        if !(only_add_piece || only_replicate) {
+            outputs.circuits = run_measure_circuits(&inputs);
+        }
+        return Metadata::wrap(ProdbenchReport { inputs, outputs })
+            .expect("failed to retrieve metadata");
+    }
+
     let (cfg, repls) = create_replicas::<DefaultOctLCTree>(
         sector_size,
         inputs.num_sectors as usize,
@@ -300,11 +341,9 @@ fn measure_porep_circuit(i: &ProdbenchInputs) -> usize {
 fn generate_params(i: &ProdbenchInputs) {
     let sector_size = SectorSize(i.sector_size_bytes());
     let partitions = PoRepProofPartitions(
-        *POREP_PARTITIONS
-            .read()
-            .expect("POREP_PARTITIONS poisoned")
-            .get(&i.sector_size_bytes())
-            .expect("unknown sector size"),
+        sector_shape(i.sector_size_bytes())
+            .expect("unknown sector size")
+            .porep_partitions,
     );
     info!(
         "generating params: porep: (size: {:?}, partitions: {:?})",
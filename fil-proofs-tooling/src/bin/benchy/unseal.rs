@@ -0,0 +1,259 @@
+use std::cmp::min;
+use std::fs::{read, File};
+use std::io::{stdout, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{ensure, Context, Result};
+use bincode::deserialize;
+use fil_proofs_tooling::shared::{create_replicas, PROVER_ID, TICKET_BYTES};
+use fil_proofs_tooling::{measure, Metadata};
+use filecoin_proofs::{
+    get_unsealed_range, sector_shape, unseal_range, with_shape, Commitment, MerkleTreeTrait,
+    PaddedBytesAmount, SealPreCommitOutput, SectorSize, UnpaddedByteIndex, UnpaddedBytesAmount,
+};
+use log::info;
+use serde::Serialize;
+use storage_proofs_core::{api_version::ApiVersion, sector::SectorId};
+use tempfile::NamedTempFile;
+
+use crate::window_post::{
+    get_porep_config, PIECE_FILE, PRECOMMIT_PHASE2_OUTPUT_FILE, SEALED_FILE, SECTOR_ID,
+};
+
+/// Unsealed data is compared with the piece in chunks of this size, so that sectors need not fit
+/// into memory.
+const COMPARE_CHUNK_BYTES: usize = 1 << 20;
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Inputs {
+    sector_size: u64,
+    offset: u64,
+    num_bytes: u64,
+    preserved_cache: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Outputs {
+    get_unsealed_range_cpu_time_ms: u64,
+    get_unsealed_range_wall_time_ms: u64,
+    unseal_range_cpu_time_ms: u64,
+    unseal_range_wall_time_ms: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Report {
+    inputs: Inputs,
+    outputs: Outputs,
+}
+
+impl Report {
+    /// Print all results to stdout
+    pub fn print(&self) {
+        let wrapped = Metadata::wrap(&self).expect("failed to retrieve metadata");
+        serde_json::to_writer(stdout(), &wrapped).expect("cannot write report JSON to stdout");
+    }
+}
+
+/// A sealed sector to unseal.
+struct Sealed {
+    sector_id: SectorId,
+    porep_id: [u8; 32],
+    comm_d: Commitment,
+    cache_dir: PathBuf,
+    sealed_path: PathBuf,
+    /// The unsealed data, if known.
+    piece_path: Option<PathBuf>,
+}
+
+/// Loads the sector preserved in `cache_dir` by `benchy window-post --preserve-cache`.
+fn preserved_sector(sector_size: u64, api_version: ApiVersion, cache_dir: &Path) -> Result<Sealed> {
+    let pre_commit_path = cache_dir.join(PRECOMMIT_PHASE2_OUTPUT_FILE);
+    let pre_commit_bytes = read(&pre_commit_path)
+        .with_context(|| format!("could not read file {:?}", pre_commit_path))?;
+    let pre_commit: SealPreCommitOutput = deserialize(&pre_commit_bytes)?;
+
+    Ok(Sealed {
+        sector_id: SectorId::from(SECTOR_ID),
+        porep_id: get_porep_config(sector_size, api_version).porep_id,
+        comm_d: pre_commit.comm_d,
+        cache_dir: cache_dir.to_path_buf(),
+        sealed_path: cache_dir.join(SEALED_FILE),
+        piece_path: Some(cache_dir.join(PIECE_FILE)),
+    })
+}
+
+/// Whether the `len` bytes of `path` at `offset` equal the start of `other`.
+fn range_matches(path: &Path, offset: u64, other: &Path, len: u64) -> Result<bool> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut reader = BufReader::new(file);
+    let mut other = BufReader::new(File::open(other)?);
+
+    let mut chunk = vec![0u8; COMPARE_CHUNK_BYTES];
+    let mut other_chunk = vec![0u8; COMPARE_CHUNK_BYTES];
+    let mut compared = 0;
+    while compared < len {
+        let chunk_len = min(len - compared, COMPARE_CHUNK_BYTES as u64) as usize;
+        reader.read_exact(&mut chunk[..chunk_len])?;
+        other.read_exact(&mut other_chunk[..chunk_len])?;
+        if chunk[..chunk_len] != other_chunk[..chunk_len] {
+            return Ok(false);
+        }
+        compared += chunk_len as u64;
+    }
+
+    Ok(true)
+}
+
+fn run_unseal_bench<Tree: 'static + MerkleTreeTrait>(
+    sector_size: u64,
+    api_version: ApiVersion,
+    cache_dir: Option<PathBuf>,
+    offset: u64,
+    num_bytes: Option<u64>,
+) -> Result<()> {
+    let piece_size = u64::from(UnpaddedBytesAmount::from(PaddedBytesAmount(sector_size)));
+    let num_bytes = num_bytes.unwrap_or(piece_size - offset.min(piece_size));
+    ensure!(
+        offset
+            .checked_add(num_bytes)
+            .map_or(false, |end| end <= piece_size),
+        "range of {} bytes at {} exceeds the {} bytes of the sector",
+        num_bytes,
+        offset,
+        piece_size
+    );
+
+    let sealed = match &cache_dir {
+        Some(cache_dir) => preserved_sector(sector_size, api_version, cache_dir)?,
+        None => {
+            let arbitrary_porep_id = [55; 32];
+            let (_, replicas) = create_replicas::<Tree>(
+                SectorSize(sector_size),
+                1,
+                false,
+                arbitrary_porep_id,
+                api_version,
+            );
+            let (mut replica_outputs, pre_commit_outputs) =
+                replicas.expect("create_replicas failed to seal");
+            let (sector_id, replica_output) =
+                replica_outputs.pop().expect("no replica was created");
+            let replica_info = replica_output.private_replica_info;
+
+            Sealed {
+                sector_id,
+                porep_id: arbitrary_porep_id,
+                comm_d: pre_commit_outputs.return_value[0].comm_d,
+                cache_dir: replica_info.cache_dir_path().to_path_buf(),
+                sealed_path: replica_info.replica_path().to_path_buf(),
+                // `create_replicas` does not keep the pieces it seals.
+                piece_path: None,
+            }
+        }
+    };
+
+    let mut porep_config = get_porep_config(sector_size, api_version);
+    porep_config.porep_id = sealed.porep_id;
+
+    let unsealed_file = NamedTempFile::new()?;
+    let get_unsealed_range_measurement = measure(|| {
+        get_unsealed_range::<_, Tree>(
+            porep_config,
+            sealed.cache_dir.clone(),
+            sealed.sealed_path.clone(),
+            unsealed_file.path().to_path_buf(),
+            PROVER_ID,
+            sealed.sector_id,
+            sealed.comm_d,
+            TICKET_BYTES,
+            UnpaddedByteIndex(offset),
+            UnpaddedBytesAmount(num_bytes),
+        )
+    })?;
+    ensure!(
+        u64::from(get_unsealed_range_measurement.return_value) == num_bytes,
+        "unsealed {} bytes instead of {}",
+        u64::from(get_unsealed_range_measurement.return_value),
+        num_bytes
+    );
+
+    let unseal_range_file = NamedTempFile::new()?;
+    let mut unsealed = BufWriter::new(unseal_range_file.reopen()?);
+    let sealed_file = BufReader::new(File::open(&sealed.sealed_path)?);
+    let unseal_range_measurement = measure(|| {
+        let written = unseal_range::<_, _, _, Tree>(
+            porep_config,
+            &sealed.cache_dir,
+            sealed_file,
+            &mut unsealed,
+            PROVER_ID,
+            sealed.sector_id,
+            sealed.comm_d,
+            TICKET_BYTES,
+            UnpaddedByteIndex(offset),
+            UnpaddedBytesAmount(num_bytes),
+        )?;
+        unsealed.flush()?;
+
+        Ok(written)
+    })?;
+
+    if let Some(piece_path) = &sealed.piece_path {
+        ensure!(
+            range_matches(piece_path, offset, unseal_range_file.path(), num_bytes)?,
+            "unsealed data does not match the piece"
+        );
+        ensure!(
+            range_matches(piece_path, offset, unsealed_file.path(), num_bytes)?,
+            "unsealed file does not match the piece"
+        );
+    }
+
+    Report {
+        inputs: Inputs {
+            sector_size,
+            offset,
+            num_bytes,
+            preserved_cache: cache_dir.is_some(),
+        },
+        outputs: Outputs {
+            get_unsealed_range_cpu_time_ms: get_unsealed_range_measurement.cpu_time.as_millis()
+                as u64,
+            get_unsealed_range_wall_time_ms: get_unsealed_range_measurement.wall_time.as_millis()
+                as u64,
+            unseal_range_cpu_time_ms: unseal_range_measurement.cpu_time.as_millis() as u64,
+            unseal_range_wall_time_ms: unseal_range_measurement.wall_time.as_millis() as u64,
+        },
+    }
+    .print();
+
+    Ok(())
+}
+
+pub fn run(
+    sector_size: u64,
+    api_version: ApiVersion,
+    cache_dir: Option<PathBuf>,
+    offset: u64,
+    num_bytes: Option<u64>,
+) -> Result<()> {
+    info!(
+        "Benchy unseal: sector-size={}, api_version={}, cache={:?}, offset={}, num_bytes={:?}",
+        sector_size, api_version, cache_dir, offset, num_bytes
+    );
+    sector_shape(sector_size)?;
+
+    with_shape!(
+        sector_size,
+        run_unseal_bench,
+        sector_size,
+        api_version,
+        cache_dir,
+        offset,
+        num_bytes,
+    )
+}
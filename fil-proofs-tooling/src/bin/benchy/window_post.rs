@@ -6,12 +6,11 @@ use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::{ensure, Context};
 use bincode::{deserialize, serialize};
+use fil_proofs_tooling::farm::FarmManifest;
 use fil_proofs_tooling::measure::FuncMeasurement;
 use fil_proofs_tooling::shared::{PROVER_ID, RANDOMNESS, TICKET_BYTES};
 use fil_proofs_tooling::{measure, Metadata};
-use filecoin_proofs::constants::{
-    POREP_PARTITIONS, WINDOW_POST_CHALLENGE_COUNT, WINDOW_POST_SECTOR_COUNT,
-};
+use filecoin_proofs::constants::WINDOW_POST_CHALLENGE_COUNT;
 use filecoin_proofs::types::{
     PaddedBytesAmount, PieceInfo, PoRepConfig, PoRepProofPartitions, PoStConfig,
     SealCommitPhase1Output, SealPreCommitOutput, SealPreCommitPhase1Output, SectorSize,
@@ -19,9 +18,9 @@ use filecoin_proofs::types::{
 };
 use filecoin_proofs::{
     add_piece, generate_piece_commitment, generate_window_post, seal_commit_phase1,
-    seal_commit_phase2, seal_pre_commit_phase1, seal_pre_commit_phase2, validate_cache_for_commit,
-    validate_cache_for_precommit_phase2, verify_window_post, with_shape, PoStType,
-    PrivateReplicaInfo, PublicReplicaInfo,
+    seal_commit_phase2, seal_pre_commit_phase1, seal_pre_commit_phase2, sector_shape,
+    validate_cache_for_commit, validate_cache_for_precommit_phase2, verify_window_post, with_shape,
+    PoStType, PrivateReplicaInfo, PublicReplicaInfo,
 };
 use log::info;
 use serde::{Deserialize, Serialize};
@@ -29,14 +28,14 @@ use storage_proofs_core::api_version::ApiVersion;
 use storage_proofs_core::merkle::MerkleTreeTrait;
 use storage_proofs_core::sector::SectorId;
 
-const SECTOR_ID: u64 = 0;
+pub(crate) const SECTOR_ID: u64 = 0;
 
-const PIECE_FILE: &str = "piece-file";
+pub(crate) const PIECE_FILE: &str = "piece-file";
 const PIECE_INFOS_FILE: &str = "piece-infos-file";
 const STAGED_FILE: &str = "staged-file";
-const SEALED_FILE: &str = "sealed-file";
+pub(crate) const SEALED_FILE: &str = "sealed-file";
 const PRECOMMIT_PHASE1_OUTPUT_FILE: &str = "precommit-phase1-output";
-const PRECOMMIT_PHASE2_OUTPUT_FILE: &str = "precommit-phase2-output";
+pub(crate) const PRECOMMIT_PHASE2_OUTPUT_FILE: &str = "precommit-phase2-output";
 const COMMIT_PHASE1_OUTPUT_FILE: &str = "commit-phase1-output";
 
 #[derive(Serialize, Deserialize)]
@@ -81,18 +80,47 @@ impl Report {
     }
 }
 
-fn get_porep_config(sector_size: u64, api_version: ApiVersion) -> PoRepConfig {
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct FarmInputs {
+    sector_size: u64,
+    sector_count: usize,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct FarmOutputs {
+    gen_window_post_cpu_time_ms: u64,
+    gen_window_post_wall_time_ms: u64,
+    verify_window_post_cpu_time_ms: u64,
+    verify_window_post_wall_time_ms: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct FarmReport {
+    inputs: FarmInputs,
+    outputs: FarmOutputs,
+}
+
+impl FarmReport {
+    /// Print all results to stdout
+    pub fn print(&self) {
+        let wrapped = Metadata::wrap(&self).expect("failed to retrieve metadata");
+        serde_json::to_writer(stdout(), &wrapped).expect("cannot write report JSON to stdout");
+    }
+}
+
+pub(crate) fn get_porep_config(sector_size: u64, api_version: ApiVersion) -> PoRepConfig {
     let arbitrary_porep_id = [99; 32];
 
     // Replicate the staged sector, write the replica file to `sealed_path`.
     PoRepConfig {
         sector_size: SectorSize(sector_size),
         partitions: PoRepProofPartitions(
-            *POREP_PARTITIONS
-                .read()
-                .expect("POREP_PARTITONS poisoned")
-                .get(&(sector_size))
-                .expect("unknown sector size"),
+            sector_shape(sector_size)
+                .expect("unknown sector size")
+                .porep_partitions,
         ),
         porep_id: arbitrary_porep_id,
         api_version,
@@ -512,11 +540,9 @@ pub fn run_window_post_bench<Tree: 'static + MerkleTreeTrait>(
     let post_config = PoStConfig {
         sector_size: SectorSize(sector_size),
         challenge_count: WINDOW_POST_CHALLENGE_COUNT,
-        sector_count: *WINDOW_POST_SECTOR_COUNT
-            .read()
-            .expect("WINDOW_POST_SECTOR_COUNT poisoned")
-            .get(&sector_size)
-            .expect("unknown sector size"),
+        sector_count: sector_shape(sector_size)
+            .expect("unknown sector size")
+            .window_post_sector_count,
         typ: PoStType::Window,
         priority: true,
         api_version,
@@ -642,3 +668,78 @@ pub fn run(
         test_resume,
     )
 }
+
+fn run_farm_window_post_bench<Tree: 'static + MerkleTreeTrait>(
+    manifest: FarmManifest,
+    api_version: ApiVersion,
+) -> anyhow::Result<()> {
+    let priv_replica_info = manifest.private_replicas::<Tree>()?;
+    let pub_replica_info = manifest.public_replicas()?;
+
+    let post_config = PoStConfig {
+        sector_size: SectorSize(manifest.sector_size),
+        challenge_count: WINDOW_POST_CHALLENGE_COUNT,
+        sector_count: sector_shape(manifest.sector_size)?.window_post_sector_count,
+        typ: PoStType::Window,
+        priority: true,
+        api_version,
+    };
+
+    let gen_window_post_measurement = measure(|| {
+        generate_window_post::<Tree>(&post_config, &RANDOMNESS, &priv_replica_info, PROVER_ID)
+    })
+    .expect("failed to generate window post");
+
+    let proof = &gen_window_post_measurement.return_value;
+
+    let verify_window_post_measurement = measure(|| {
+        verify_window_post::<Tree>(
+            &post_config,
+            &RANDOMNESS,
+            &pub_replica_info,
+            PROVER_ID,
+            &proof,
+        )
+    })
+    .expect("failed to verify window post proof");
+    ensure!(
+        verify_window_post_measurement.return_value,
+        "window post proof is invalid"
+    );
+
+    let report = FarmReport {
+        inputs: FarmInputs {
+            sector_size: manifest.sector_size,
+            sector_count: manifest.sectors.len(),
+        },
+        outputs: FarmOutputs {
+            gen_window_post_cpu_time_ms: gen_window_post_measurement.cpu_time.as_millis() as u64,
+            gen_window_post_wall_time_ms: gen_window_post_measurement.wall_time.as_millis() as u64,
+            verify_window_post_cpu_time_ms: verify_window_post_measurement.cpu_time.as_millis()
+                as u64,
+            verify_window_post_wall_time_ms: verify_window_post_measurement.wall_time.as_millis()
+                as u64,
+        },
+    };
+
+    report.print();
+    Ok(())
+}
+
+/// Benchmarks Window PoSt over the sectors of a farm generated by `fakefarm`.
+pub fn run_farm(manifest_path: PathBuf, api_version: ApiVersion) -> anyhow::Result<()> {
+    info!(
+        "Benchy Window PoSt over farm: manifest={:?}, api_version={}",
+        manifest_path, api_version
+    );
+
+    let manifest = FarmManifest::load(&manifest_path)?;
+    ensure!(!manifest.sectors.is_empty(), "the farm has no sectors");
+
+    with_shape!(
+        manifest.sector_size,
+        run_farm_window_post_bench,
+        manifest,
+        api_version
+    )
+}
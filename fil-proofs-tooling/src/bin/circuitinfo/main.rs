@@ -4,10 +4,9 @@ use bellperson::{bls::Bls12, util_cs::bench_cs::BenchCS, Circuit};
 use dialoguer::{theme::ColorfulTheme, MultiSelect};
 use filecoin_proofs::{
     parameters::{public_params, window_post_public_params, winning_post_public_params},
-    with_shape, DefaultPieceHasher, PaddedBytesAmount, PoRepConfig, PoRepProofPartitions,
-    PoStConfig, PoStType, SectorSize, POREP_PARTITIONS, PUBLISHED_SECTOR_SIZES,
-    WINDOW_POST_CHALLENGE_COUNT, WINDOW_POST_SECTOR_COUNT, WINNING_POST_CHALLENGE_COUNT,
-    WINNING_POST_SECTOR_COUNT,
+    sector_shape, with_shape, DefaultPieceHasher, PaddedBytesAmount, PoRepConfig,
+    PoRepProofPartitions, PoStConfig, PoStType, SectorSize, PUBLISHED_SECTOR_SIZES,
+    WINDOW_POST_CHALLENGE_COUNT, WINNING_POST_CHALLENGE_COUNT, WINNING_POST_SECTOR_COUNT,
 };
 use humansize::{file_size_opts, FileSize};
 use log::{info, warn};
@@ -119,11 +118,9 @@ fn window_post_info(sector_size: u64, api_version: ApiVersion) -> CircuitInfo {
         &PoStConfig {
             sector_size: SectorSize(sector_size),
             challenge_count: WINDOW_POST_CHALLENGE_COUNT,
-            sector_count: *WINDOW_POST_SECTOR_COUNT
-                .read()
-                .expect("WINDOW_POST_SECTOR_COUNT poisoned")
-                .get(&sector_size)
-                .expect("unknown sector size"),
+            sector_count: sector_shape(sector_size)
+                .expect("unknown sector size")
+                .window_post_sector_count,
             typ: PoStType::Window,
             priority: true,
             api_version,
@@ -133,11 +130,9 @@ fn window_post_info(sector_size: u64, api_version: ApiVersion) -> CircuitInfo {
 
 fn porep_info(sector_size: u64, api_version: ApiVersion) -> (CircuitInfo, usize) {
     let partitions = PoRepProofPartitions(
-        *POREP_PARTITIONS
-            .read()
-            .expect("POREP_PARTITIONS poisoned")
-            .get(&sector_size)
-            .expect("unknown sector size"),
+        sector_shape(sector_size)
+            .expect("unknown sector size")
+            .porep_partitions,
     );
     let info = with_shape!(
         sector_size,
@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+use std::process::exit;
+
+use anyhow::{Context, Result};
+use byte_unit::Byte;
+use fil_proofs_tooling::farm::{generate_farm, FarmConfig, MANIFEST_FILE};
+use filecoin_proofs::{sector_shape, with_shape};
+use log::info;
+use rayon::ThreadPoolBuilder;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "fakefarm",
+    about = "Generates a directory of fake sectors and a manifest of them, to load test PoSt"
+)]
+struct Opt {
+    /// The directory to generate the farm into.
+    #[structopt(long, parse(from_os_str))]
+    dir: PathBuf,
+    /// The sector size (e.g. 32GiB).
+    #[structopt(long)]
+    size: String,
+    /// The number of sectors to generate.
+    #[structopt(long)]
+    count: u64,
+    /// The id of the first sector, the others are numbered consecutively.
+    #[structopt(long, default_value = "0")]
+    first_sector_id: u64,
+    /// The seed the sector commitments are derived from.
+    #[structopt(long, default_value = "0")]
+    seed: u64,
+    /// Write sparse all-zero replicas and hard link one shared tree_r_last into every sector's
+    /// cache. Much faster and smaller, but all sectors hold the same data.
+    #[structopt(long)]
+    sparse_replicas: bool,
+    /// The number of sectors generated at the same time, defaults to the number of cores.
+    #[structopt(long)]
+    parallel: Option<usize>,
+}
+
+fn run(opt: Opt) -> Result<()> {
+    let sector_size = Byte::from_str(&opt.size)
+        .with_context(|| format!("invalid sector size {}", opt.size))?
+        .get_bytes() as u64;
+    sector_shape(sector_size)?;
+
+    let config = FarmConfig {
+        sector_size,
+        sector_count: opt.count,
+        first_sector_id: opt.first_sector_id,
+        seed: opt.seed,
+        sparse_replicas: opt.sparse_replicas,
+    };
+
+    let mut pool = ThreadPoolBuilder::new();
+    if let Some(parallel) = opt.parallel {
+        pool = pool.num_threads(parallel);
+    }
+    let manifest = pool
+        .build()?
+        .install(|| with_shape!(sector_size, generate_farm, &opt.dir, &config))?;
+
+    info!(
+        "generated {} sectors, manifest at {:?}",
+        manifest.sectors.len(),
+        opt.dir.join(MANIFEST_FILE)
+    );
+
+    Ok(())
+}
+
+fn main() {
+    fil_logger::init();
+
+    let opt = Opt::from_args();
+    info!("fakefarm: {:?}", opt);
+
+    if let Err(err) = run(opt) {
+        eprintln!("fakefarm failed: {:?}", err);
+        exit(1);
+    }
+}
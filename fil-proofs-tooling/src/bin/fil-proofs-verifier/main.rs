@@ -0,0 +1,798 @@
+use std::collections::BTreeMap;
+use std::fs::remove_file;
+use std::io::{self, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, ensure, Context, Result};
+use filecoin_proofs::{
+    preload_verifying_keys, sector_shape, verify_batch_seal, verify_seal, verify_window_post,
+    verify_winning_post, with_shape, Commitment, MerkleTreeTrait, ParameterSet, PoRepConfig,
+    PoRepProofPartitions, PoStConfig, PoStType, ProverId, PublicReplicaInfo, SectorSize, Ticket,
+    WINDOW_POST_CHALLENGE_COUNT, WINNING_POST_CHALLENGE_COUNT, WINNING_POST_SECTOR_COUNT,
+};
+use log::{info, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use storage_proofs_core::{api_version::ApiVersion, sector::SectorId};
+use structopt::StructOpt;
+
+/// Frames larger than this are rejected without reading them.
+const MAX_FRAME_BYTES: usize = 64 << 20;
+
+/// Frames are read in chunks of this size, so memory is only allocated for bytes received.
+const READ_CHUNK_BYTES: usize = 64 << 10;
+
+/// How often idle connections and the listener check for a shutdown.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "fil-proofs-verifier",
+    about = "Serves proof verification requests over a Unix domain socket, keeping verifying keys in memory"
+)]
+struct Opt {
+    /// The path of the Unix domain socket to listen on.
+    #[structopt(long, parse(from_os_str))]
+    socket: PathBuf,
+    /// The max number of verifications run at the same time.
+    #[structopt(long, default_value = "4")]
+    max_concurrent_verifications: usize,
+    /// The max number of open connections. Further connections are closed right away.
+    #[structopt(long, default_value = "64")]
+    max_connections: usize,
+    /// Connections which take longer than this to send a frame, once it started, are closed.
+    #[structopt(long, default_value = "30")]
+    frame_timeout_secs: u64,
+    /// How long to wait for the requests in flight on shutdown, before exiting anyway.
+    #[structopt(long, default_value = "60")]
+    shutdown_timeout_secs: u64,
+    /// Load the PoRep and PoSt verifying keys of these sector sizes on startup.
+    #[structopt(long)]
+    preload_sector_size: Vec<u64>,
+    /// The API version of the preloaded verifying keys.
+    #[structopt(long, default_value = "1.1.0")]
+    preload_api_version: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "snake_case")]
+enum Request {
+    VerifySeal(VerifySealRequest),
+    VerifyBatchSeal(VerifyBatchSealRequest),
+    VerifyWinningPost(VerifyPoStRequest),
+    VerifyWindowPost(VerifyPoStRequest),
+}
+
+#[derive(Debug, Deserialize)]
+struct VerifySealRequest {
+    sector_size: u64,
+    api_version: String,
+    porep_id: [u8; 32],
+    #[serde(flatten)]
+    seal: Seal,
+}
+
+#[derive(Debug, Deserialize)]
+struct VerifyBatchSealRequest {
+    sector_size: u64,
+    api_version: String,
+    porep_id: [u8; 32],
+    seals: Vec<Seal>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Seal {
+    comm_r: Commitment,
+    comm_d: Commitment,
+    prover_id: ProverId,
+    sector_id: u64,
+    ticket: Ticket,
+    seed: Ticket,
+    proof: Vec<u8>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VerifyPoStRequest {
+    sector_size: u64,
+    api_version: String,
+    randomness: [u8; 32],
+    prover_id: ProverId,
+    sectors: Vec<PoStSector>,
+    proof: Vec<u8>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PoStSector {
+    sector_id: u64,
+    comm_r: Commitment,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Response {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    valid: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl From<Result<bool>> for Response {
+    fn from(result: Result<bool>) -> Self {
+        match result {
+            Ok(valid) => Response {
+                valid: Some(valid),
+                error: None,
+            },
+            Err(err) => Response {
+                valid: None,
+                error: Some(format!("{:#}", err)),
+            },
+        }
+    }
+}
+
+/// The encoding of a frame. Responses are encoded like the request they answer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Json,
+    Cbor,
+}
+
+impl Encoding {
+    /// A JSON request is an object, while a CBOR request is a map, which never starts with `{`.
+    fn detect(frame: &[u8]) -> Self {
+        match frame.first() {
+            Some(b'{') => Encoding::Json,
+            _ => Encoding::Cbor,
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(self, frame: &[u8]) -> Result<T> {
+        Ok(match self {
+            Encoding::Json => serde_json::from_slice(frame)?,
+            Encoding::Cbor => serde_cbor::from_slice(frame)?,
+        })
+    }
+
+    fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>> {
+        Ok(match self {
+            Encoding::Json => serde_json::to_vec(value)?,
+            Encoding::Cbor => serde_cbor::to_vec(value)?,
+        })
+    }
+}
+
+/// Reads a frame, a big-endian `u32` length followed by as many bytes. Returns `None` if the
+/// connection was closed, or `shutdown` was set while waiting for the next frame. Once a frame
+/// started, it fails if the rest of it does not arrive within `timeout`, or on a shutdown.
+fn read_frame(
+    stream: &mut UnixStream,
+    shutdown: &AtomicBool,
+    timeout: Duration,
+) -> Result<Option<Vec<u8>>> {
+    let mut len_bytes = [0u8; 4];
+    let mut read = 0;
+    let mut deadline = None;
+    while read < len_bytes.len() {
+        match stream.read(&mut len_bytes[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => bail!("connection closed within a frame"),
+            Ok(n) => read += n,
+            Err(err) if is_timeout(&err) || err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        if read == 0 {
+            if shutdown.load(Ordering::SeqCst) {
+                return Ok(None);
+            }
+        } else {
+            let deadline = *deadline.get_or_insert_with(|| Instant::now() + timeout);
+            check_frame_progress(deadline, shutdown)?;
+        }
+    }
+    let deadline = deadline.expect("frame deadline not set");
+
+    let len = u32::from_be_bytes(len_bytes) as usize;
+    ensure!(
+        len <= MAX_FRAME_BYTES,
+        "frame of {} bytes exceeds the limit of {} bytes",
+        len,
+        MAX_FRAME_BYTES
+    );
+
+    let mut frame = Vec::with_capacity(std::cmp::min(len, READ_CHUNK_BYTES));
+    let mut chunk = vec![0u8; READ_CHUNK_BYTES];
+    while frame.len() < len {
+        let remaining = std::cmp::min(len - frame.len(), chunk.len());
+        match stream.read(&mut chunk[..remaining]) {
+            Ok(0) => bail!("connection closed within a frame"),
+            Ok(n) => frame.extend_from_slice(&chunk[..n]),
+            Err(err) if is_timeout(&err) || err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        check_frame_progress(deadline, shutdown)?;
+    }
+
+    Ok(Some(frame))
+}
+
+/// Fails if a frame still being read is past its deadline, or if the verifier is shutting down.
+fn check_frame_progress(deadline: Instant, shutdown: &AtomicBool) -> Result<()> {
+    ensure!(
+        !shutdown.load(Ordering::SeqCst),
+        "shutting down within a frame"
+    );
+    ensure!(Instant::now() < deadline, "timed out within a frame");
+
+    Ok(())
+}
+
+fn write_frame<W: Write>(writer: &mut W, frame: &[u8]) -> Result<()> {
+    ensure!(frame.len() <= MAX_FRAME_BYTES, "response frame too large");
+    writer.write_all(&(frame.len() as u32).to_be_bytes())?;
+    writer.write_all(frame)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn is_timeout(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::WouldBlock || err.kind() == io::ErrorKind::TimedOut
+}
+
+/// Bounds the number of verifications running at the same time.
+struct Limiter {
+    running: Mutex<usize>,
+    done: Condvar,
+    max: usize,
+}
+
+impl Limiter {
+    fn new(max: usize) -> Self {
+        Limiter {
+            running: Mutex::new(0),
+            done: Condvar::new(),
+            max: std::cmp::max(max, 1),
+        }
+    }
+
+    fn run<T, F: FnOnce() -> T>(&self, f: F) -> T {
+        {
+            let mut running = self.running.lock().expect("limiter poisoned");
+            while *running >= self.max {
+                running = self.done.wait(running).expect("limiter poisoned");
+            }
+            *running += 1;
+        }
+        let _permit = Permit(self);
+
+        f()
+    }
+}
+
+/// Releases a slot of the limiter when dropped, even if the verification panics.
+struct Permit<'a>(&'a Limiter);
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        // Don't panic while unwinding if a panic poisoned the lock.
+        let mut running = match self.0.running.lock() {
+            Ok(running) => running,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *running -= 1;
+        self.0.done.notify_one();
+    }
+}
+
+fn porep_config(sector_size: u64, api_version: &str, porep_id: [u8; 32]) -> Result<PoRepConfig> {
+    Ok(PoRepConfig {
+        sector_size: SectorSize(sector_size),
+        partitions: PoRepProofPartitions(sector_shape(sector_size)?.porep_partitions),
+        porep_id,
+        api_version: ApiVersion::from_str(api_version)?,
+    })
+}
+
+fn post_config(sector_size: u64, typ: PoStType, api_version: &str) -> Result<PoStConfig> {
+    let (challenge_count, sector_count) = match typ {
+        PoStType::Winning => (WINNING_POST_CHALLENGE_COUNT, WINNING_POST_SECTOR_COUNT),
+        PoStType::Window => (
+            WINDOW_POST_CHALLENGE_COUNT,
+            sector_shape(sector_size)?.window_post_sector_count,
+        ),
+    };
+
+    Ok(PoStConfig {
+        sector_size: SectorSize(sector_size),
+        challenge_count,
+        sector_count,
+        typ,
+        priority: false,
+        api_version: ApiVersion::from_str(api_version)?,
+    })
+}
+
+fn verify_seal_request<Tree: 'static + MerkleTreeTrait>(
+    request: &VerifySealRequest,
+) -> Result<bool> {
+    let porep_config = porep_config(request.sector_size, &request.api_version, request.porep_id)?;
+    let seal = &request.seal;
+
+    verify_seal::<Tree>(
+        porep_config,
+        seal.comm_r,
+        seal.comm_d,
+        seal.prover_id,
+        SectorId::from(seal.sector_id),
+        seal.ticket,
+        seal.seed,
+        &seal.proof,
+    )
+}
+
+fn verify_batch_seal_request<Tree: 'static + MerkleTreeTrait>(
+    request: &VerifyBatchSealRequest,
+) -> Result<bool> {
+    let porep_config = porep_config(request.sector_size, &request.api_version, request.porep_id)?;
+    let seals = &request.seals;
+
+    verify_batch_seal::<Tree>(
+        porep_config,
+        &seals.iter().map(|seal| seal.comm_r).collect::<Vec<_>>(),
+        &seals.iter().map(|seal| seal.comm_d).collect::<Vec<_>>(),
+        &seals.iter().map(|seal| seal.prover_id).collect::<Vec<_>>(),
+        &seals
+            .iter()
+            .map(|seal| SectorId::from(seal.sector_id))
+            .collect::<Vec<_>>(),
+        &seals.iter().map(|seal| seal.ticket).collect::<Vec<_>>(),
+        &seals.iter().map(|seal| seal.seed).collect::<Vec<_>>(),
+        &seals
+            .iter()
+            .map(|seal| seal.proof.as_slice())
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn verify_post_request<Tree: 'static + MerkleTreeTrait>(
+    request: &VerifyPoStRequest,
+    typ: PoStType,
+) -> Result<bool> {
+    let post_config = post_config(request.sector_size, typ, &request.api_version)?;
+
+    match typ {
+        PoStType::Winning => {
+            let replicas = request
+                .sectors
+                .iter()
+                .map(|sector| {
+                    Ok((
+                        SectorId::from(sector.sector_id),
+                        PublicReplicaInfo::new(sector.comm_r)?,
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            verify_winning_post::<Tree>(
+                &post_config,
+                &request.randomness,
+                &replicas,
+                request.prover_id,
+                &request.proof,
+            )
+        }
+        PoStType::Window => {
+            let replicas = request
+                .sectors
+                .iter()
+                .map(|sector| {
+                    Ok((
+                        SectorId::from(sector.sector_id),
+                        PublicReplicaInfo::new(sector.comm_r)?,
+                    ))
+                })
+                .collect::<Result<BTreeMap<_, _>>>()?;
+            ensure!(
+                replicas.len() == request.sectors.len(),
+                "duplicate sector ids"
+            );
+
+            verify_window_post::<Tree>(
+                &post_config,
+                &request.randomness,
+                &replicas,
+                request.prover_id,
+                &request.proof,
+            )
+        }
+    }
+}
+
+fn handle_request(request: &Request) -> Result<bool> {
+    let sector_size = match request {
+        Request::VerifySeal(request) => request.sector_size,
+        Request::VerifyBatchSeal(request) => request.sector_size,
+        Request::VerifyWinningPost(request) | Request::VerifyWindowPost(request) => {
+            request.sector_size
+        }
+    };
+    // `with_shape!` panics on unknown sector sizes.
+    sector_shape(sector_size)?;
+
+    match request {
+        Request::VerifySeal(request) => with_shape!(sector_size, verify_seal_request, request),
+        Request::VerifyBatchSeal(request) => {
+            ensure!(!request.seals.is_empty(), "no seals to verify");
+            with_shape!(sector_size, verify_batch_seal_request, request)
+        }
+        Request::VerifyWinningPost(request) => {
+            with_shape!(sector_size, verify_post_request, request, PoStType::Winning)
+        }
+        Request::VerifyWindowPost(request) => {
+            with_shape!(sector_size, verify_post_request, request, PoStType::Window)
+        }
+    }
+}
+
+fn handle_frame(frame: &[u8], limiter: &Limiter) -> Result<Vec<u8>> {
+    let encoding = Encoding::detect(frame);
+    let response = match encoding.decode::<Request>(frame) {
+        Ok(request) => limiter.run(|| Response::from(handle_request(&request))),
+        Err(err) => Response::from(Err(err.context("invalid request"))),
+    };
+
+    encoding.encode(&response)
+}
+
+fn serve_connection(
+    mut stream: UnixStream,
+    limiter: &Limiter,
+    shutdown: &AtomicBool,
+    frame_timeout: Duration,
+) -> Result<()> {
+    // Wake up regularly while idle, to notice a shutdown.
+    stream.set_read_timeout(Some(SHUTDOWN_POLL_INTERVAL))?;
+
+    // Requests of a connection are answered in order.
+    while let Some(frame) = read_frame(&mut stream, shutdown, frame_timeout)? {
+        let response = handle_frame(&frame, limiter)?;
+        write_frame(&mut stream, &response)?;
+    }
+
+    Ok(())
+}
+
+fn preload(opt: &Opt) -> Result<()> {
+    let mut parameter_sets = Vec::new();
+    for &sector_size in &opt.preload_sector_size {
+        parameter_sets.push(ParameterSet::PoRep(porep_config(
+            sector_size,
+            &opt.preload_api_version,
+            [0; 32],
+        )?));
+        for typ in &[PoStType::Winning, PoStType::Window] {
+            parameter_sets.push(ParameterSet::PoSt(post_config(
+                sector_size,
+                *typ,
+                &opt.preload_api_version,
+            )?));
+        }
+    }
+
+    preload_verifying_keys(&parameter_sets)
+}
+
+fn bind(path: &Path) -> Result<UnixListener> {
+    // A socket left behind by a previous run is replaced, unless it is still in use.
+    if path.exists() {
+        ensure!(
+            UnixStream::connect(path).is_err(),
+            "{:?} is in use by another verifier",
+            path
+        );
+        remove_file(path).with_context(|| format!("could not remove {:?}", path))?;
+    }
+
+    UnixListener::bind(path).with_context(|| format!("could not bind {:?}", path))
+}
+
+fn run(opt: Opt) -> Result<()> {
+    preload(&opt)?;
+
+    let shutdown = Arc::new(AtomicBool::new(false));
+    {
+        let shutdown = shutdown.clone();
+        ctrlc::set_handler(move || {
+            info!("shutting down");
+            shutdown.store(true, Ordering::SeqCst);
+        })?;
+    }
+
+    let listener = bind(&opt.socket)?;
+    info!("listening on {:?}", opt.socket);
+
+    serve(listener, &opt, &shutdown)?;
+    remove_file(&opt.socket).with_context(|| format!("could not remove {:?}", opt.socket))?;
+
+    Ok(())
+}
+
+/// Serves the connections of `listener` until `shutdown` is set, then waits for the requests in
+/// flight for at most `--shutdown-timeout-secs`.
+fn serve(listener: UnixListener, opt: &Opt, shutdown: &Arc<AtomicBool>) -> Result<()> {
+    listener.set_nonblocking(true)?;
+
+    let limiter = Arc::new(Limiter::new(opt.max_concurrent_verifications));
+    let connections = Arc::new(AtomicUsize::new(0));
+    let frame_timeout = Duration::from_secs(opt.frame_timeout_secs);
+
+    while !shutdown.load(Ordering::SeqCst) {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(err) if is_timeout(&err) => {
+                thread::sleep(SHUTDOWN_POLL_INTERVAL);
+                continue;
+            }
+            Err(err) => {
+                // Errors such as running out of file descriptors persist for a while, so retrying
+                // right away would only spin.
+                warn!("accept failed: {}", err);
+                thread::sleep(SHUTDOWN_POLL_INTERVAL);
+                continue;
+            }
+        };
+
+        if connections.load(Ordering::SeqCst) >= opt.max_connections {
+            warn!("too many connections, closing new connection");
+            continue;
+        }
+        stream.set_nonblocking(false)?;
+
+        let guard = ConnectionGuard::new(&connections);
+        let limiter = limiter.clone();
+        let shutdown = shutdown.clone();
+        thread::spawn(move || {
+            let _guard = guard;
+            if let Err(err) = serve_connection(stream, &limiter, &shutdown, frame_timeout) {
+                warn!("connection failed: {:#}", err);
+            }
+        });
+    }
+
+    // Requests in flight are answered before exiting, unless a verification hangs.
+    let deadline = Instant::now() + Duration::from_secs(opt.shutdown_timeout_secs);
+    loop {
+        let open = connections.load(Ordering::SeqCst);
+        if open == 0 {
+            break;
+        }
+        if Instant::now() >= deadline {
+            warn!(
+                "{} connections still open after {}s, exiting anyway",
+                open, opt.shutdown_timeout_secs
+            );
+            break;
+        }
+        thread::sleep(SHUTDOWN_POLL_INTERVAL);
+    }
+
+    Ok(())
+}
+
+/// Counts an open connection until dropped, even if serving it panics.
+struct ConnectionGuard(Arc<AtomicUsize>);
+
+impl ConnectionGuard {
+    fn new(connections: &Arc<AtomicUsize>) -> Self {
+        connections.fetch_add(1, Ordering::SeqCst);
+        ConnectionGuard(connections.clone())
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Run this from the command-line to serve verification requests to local processes.
+pub fn main() {
+    fil_logger::init();
+
+    let opt = Opt::from_args();
+    info!("fil-proofs-verifier: {:?}", opt);
+
+    if let Err(err) = run(opt) {
+        eprintln!("fil-proofs-verifier failed: {:?}", err);
+        exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_roundtrip() {
+        let (mut client, mut server) = UnixStream::pair().expect("pair failure");
+        let shutdown = AtomicBool::new(false);
+
+        let timeout = Duration::from_secs(1);
+        let large = vec![7u8; 3 * READ_CHUNK_BYTES + 1];
+
+        write_frame(&mut client, b"{}").expect("write_frame failure");
+        write_frame(&mut client, b"").expect("write_frame failure");
+        let writer = thread::spawn(move || {
+            write_frame(&mut client, &large).expect("write_frame failure");
+        });
+
+        assert_eq!(
+            read_frame(&mut server, &shutdown, timeout).expect("read_frame failure"),
+            Some(b"{}".to_vec())
+        );
+        assert_eq!(
+            read_frame(&mut server, &shutdown, timeout).expect("read_frame failure"),
+            Some(Vec::new())
+        );
+        assert_eq!(
+            read_frame(&mut server, &shutdown, timeout).expect("read_frame failure"),
+            Some(vec![7u8; 3 * READ_CHUNK_BYTES + 1])
+        );
+        writer.join().expect("writer failure");
+        assert_eq!(
+            read_frame(&mut server, &shutdown, timeout).expect("read_frame failure"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_read_frame_stops_on_shutdown() {
+        let (mut client, mut server) = UnixStream::pair().expect("pair failure");
+        server
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .expect("set_read_timeout failure");
+        let timeout = Duration::from_millis(100);
+        let shutdown = AtomicBool::new(true);
+        assert_eq!(
+            read_frame(&mut server, &shutdown, timeout).expect("read_frame failure"),
+            None
+        );
+
+        // A frame which started is abandoned on a shutdown.
+        client
+            .write_all(&4u32.to_be_bytes())
+            .expect("write failure");
+        client.write_all(b"{").expect("write failure");
+        assert!(read_frame(&mut server, &shutdown, timeout).is_err());
+
+        let (mut client, mut server) = UnixStream::pair().expect("pair failure");
+        client
+            .write_all(&(MAX_FRAME_BYTES as u32 + 1).to_be_bytes())
+            .expect("write failure");
+        assert!(read_frame(&mut server, &AtomicBool::new(false), timeout).is_err());
+    }
+
+    #[test]
+    fn test_read_frame_times_out() {
+        let (mut client, mut server) = UnixStream::pair().expect("pair failure");
+        server
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .expect("set_read_timeout failure");
+        let shutdown = AtomicBool::new(false);
+
+        // A partial length and a partial body both time out, rather than waiting forever.
+        client.write_all(&[0, 0]).expect("write failure");
+        assert!(read_frame(&mut server, &shutdown, Duration::from_millis(50)).is_err());
+
+        let (mut client, mut server) = UnixStream::pair().expect("pair failure");
+        server
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .expect("set_read_timeout failure");
+        client
+            .write_all(&4u32.to_be_bytes())
+            .expect("write failure");
+        client.write_all(b"{}").expect("write failure");
+        assert!(read_frame(&mut server, &shutdown, Duration::from_millis(50)).is_err());
+    }
+
+    #[test]
+    fn test_serve() {
+        let dir = tempfile::tempdir().expect("tempdir failure");
+        let socket = dir.path().join("verifier.sock");
+        let opt = Opt {
+            socket: socket.clone(),
+            max_concurrent_verifications: 1,
+            max_connections: 4,
+            frame_timeout_secs: 1,
+            shutdown_timeout_secs: 1,
+            preload_sector_size: Vec::new(),
+            preload_api_version: "1.1.0".to_string(),
+        };
+        let listener = bind(&socket).expect("bind failure");
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let server = {
+            let shutdown = shutdown.clone();
+            thread::spawn(move || serve(listener, &opt, &shutdown))
+        };
+
+        let bytes = [0u8; 32];
+        let request = serde_json::json!({
+            "method": "verify_seal",
+            "params": {
+                "sector_size": 1234,
+                "api_version": "1.1.0",
+                "porep_id": bytes,
+                "comm_r": bytes,
+                "comm_d": bytes,
+                "prover_id": bytes,
+                "sector_id": 1,
+                "ticket": bytes,
+                "seed": bytes,
+                "proof": [],
+            },
+        });
+
+        let mut client = UnixStream::connect(&socket).expect("connect failure");
+        for encoding in &[Encoding::Json, Encoding::Cbor] {
+            let frame = encoding.encode(&request).expect("encode failure");
+            write_frame(&mut client, &frame).expect("write_frame failure");
+
+            let frame = read_frame(&mut client, &AtomicBool::new(false), Duration::from_secs(5))
+                .expect("read_frame failure")
+                .expect("connection closed");
+            let response: Response = encoding.decode(&frame).expect("decode failure");
+            assert_eq!(response.valid, None);
+            assert_eq!(
+                response.error.expect("missing error"),
+                "unknown sector size: 1234"
+            );
+        }
+
+        // Idle connections are closed on a shutdown.
+        shutdown.store(true, Ordering::SeqCst);
+        server
+            .join()
+            .expect("server failure")
+            .expect("serve failure");
+        assert_eq!(
+            read_frame(&mut client, &AtomicBool::new(false), Duration::from_secs(1))
+                .expect("read_frame failure"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_encodings() {
+        let response = Response {
+            valid: Some(true),
+            error: None,
+        };
+
+        for encoding in &[Encoding::Json, Encoding::Cbor] {
+            let frame = encoding.encode(&response).expect("encode failure");
+            assert_eq!(Encoding::detect(&frame), *encoding);
+            assert_eq!(
+                encoding.decode::<Response>(&frame).expect("decode failure"),
+                response
+            );
+        }
+
+        let limiter = Limiter::new(1);
+        let frame = handle_frame(b"{\"method\": \"verify_everything\"}", &limiter)
+            .expect("handle_frame failure");
+        let response: Response = Encoding::Json.decode(&frame).expect("decode failure");
+        assert_eq!(response.valid, None);
+        assert!(response
+            .error
+            .expect("missing error")
+            .starts_with("invalid request"));
+    }
+}
@@ -11,7 +11,9 @@ use filecoin_proofs::{
     SECTOR_SIZE_64_GIB, SECTOR_SIZE_8_MIB,
 };
 use serde::{Deserialize, Serialize};
-use storage_proofs_core::{api_version::ApiVersion, merkle::MerkleTreeTrait, proof::ProofScheme};
+use storage_proofs_core::{
+    api_version::ApiVersion, merkle::MerkleTreeTrait, proof::ProofScheme, settings::ProofsContext,
+};
 use storage_proofs_porep::stacked::{LayerChallenges, SetupParams, StackedDrg};
 
 const PARENT_CACHE_JSON_OUTPUT: &str = "./parent_cache.json";
@@ -48,7 +50,7 @@ fn gen_graph_cache<Tree: 'static + MerkleTreeTrait>(
     };
 
     let pp = StackedDrg::<Tree, Sha256Hasher>::setup(&sp).expect("failed to setup DRG");
-    let parent_cache = pp.graph.parent_cache()?;
+    let parent_cache = pp.graph.parent_cache(&ProofsContext::default())?;
 
     let data = ParentCacheSummary {
         digest: parent_cache.digest,
@@ -0,0 +1,183 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{ensure, Context, Result};
+use fil_proofs_tooling::shared::{create_replicas, PROVER_ID, RANDOMNESS, TICKET_BYTES};
+use filecoin_proofs::{
+    seal_commit_phase1, sector_shape, with_shape, write_porep_r1cs, write_post_r1cs,
+    write_seal_witness, MerkleTreeTrait, PoRepConfig, PoRepProofPartitions, PoStConfig, PoStType,
+    SectorSize, WINDOW_POST_CHALLENGE_COUNT, WINNING_POST_CHALLENGE_COUNT,
+    WINNING_POST_SECTOR_COUNT,
+};
+use humansize::{file_size_opts, FileSize};
+use log::info;
+use storage_proofs_core::{api_version::ApiVersion, r1cs::R1csInfo};
+use structopt::StructOpt;
+
+/// Sectors larger than this are not sealed for a witness export.
+const MAX_WITNESS_SECTOR_SIZE: u64 = 1 << 20;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "r1cs-export",
+    about = "Exports the constraint system of a circuit in the format documented in storage_proofs_core::r1cs"
+)]
+struct Opt {
+    /// The circuit to export: porep, winning or window.
+    #[structopt(long)]
+    circuit: String,
+    #[structopt(long)]
+    sector_size: u64,
+    #[structopt(default_value = "1.1.0", long)]
+    api_version: String,
+    /// The file the constraint system is written to.
+    #[structopt(short, long, parse(from_os_str))]
+    output: PathBuf,
+    /// The file the names of all variables and constraints are written to.
+    #[structopt(long, parse(from_os_str))]
+    annotations: Option<PathBuf>,
+    /// Seal a test sector and write the witness of its first PoRep partition to this file. Only
+    /// supported for the PoRep circuit of test sector sizes.
+    #[structopt(long, parse(from_os_str))]
+    witness: Option<PathBuf>,
+}
+
+fn create_file(path: &Path) -> Result<BufWriter<File>> {
+    let file = File::create(path).with_context(|| format!("could not create {:?}", path))?;
+    Ok(BufWriter::new(file))
+}
+
+fn porep_config(sector_size: u64, api_version: ApiVersion) -> Result<PoRepConfig> {
+    Ok(PoRepConfig {
+        sector_size: SectorSize(sector_size),
+        partitions: PoRepProofPartitions(sector_shape(sector_size)?.porep_partitions),
+        porep_id: [0; 32],
+        api_version,
+    })
+}
+
+fn post_config(sector_size: u64, typ: PoStType, api_version: ApiVersion) -> Result<PoStConfig> {
+    let (challenge_count, sector_count) = match typ {
+        PoStType::Winning => (WINNING_POST_CHALLENGE_COUNT, WINNING_POST_SECTOR_COUNT),
+        PoStType::Window => (
+            WINDOW_POST_CHALLENGE_COUNT,
+            sector_shape(sector_size)?.window_post_sector_count,
+        ),
+    };
+
+    Ok(PoStConfig {
+        sector_size: SectorSize(sector_size),
+        challenge_count,
+        sector_count,
+        typ,
+        priority: true,
+        api_version,
+    })
+}
+
+fn export_porep<Tree: 'static + MerkleTreeTrait>(opts: &Opt) -> Result<R1csInfo> {
+    let api_version = ApiVersion::from_str(&opts.api_version)?;
+    let porep_config = porep_config(opts.sector_size, api_version)?;
+    let annotations = opts.annotations.as_deref().map(create_file).transpose()?;
+
+    write_porep_r1cs::<Tree, _, _>(porep_config, create_file(&opts.output)?, annotations)
+}
+
+fn export_post<Tree: 'static + MerkleTreeTrait>(opts: &Opt, typ: PoStType) -> Result<R1csInfo> {
+    let api_version = ApiVersion::from_str(&opts.api_version)?;
+    let post_config = post_config(opts.sector_size, typ, api_version)?;
+    let annotations = opts.annotations.as_deref().map(create_file).transpose()?;
+
+    write_post_r1cs::<Tree, _, _>(&post_config, create_file(&opts.output)?, annotations)
+}
+
+fn export_seal_witness<Tree: 'static + MerkleTreeTrait>(
+    opts: &Opt,
+    path: &Path,
+) -> Result<R1csInfo> {
+    ensure!(
+        opts.sector_size <= MAX_WITNESS_SECTOR_SIZE,
+        "witness export is only supported for sector sizes up to {} bytes",
+        MAX_WITNESS_SECTOR_SIZE
+    );
+
+    let api_version = ApiVersion::from_str(&opts.api_version)?;
+    let (porep_config, replicas) =
+        create_replicas::<Tree>(SectorSize(opts.sector_size), 1, false, [0; 32], api_version);
+    let (mut replicas, pre_commit_outputs) = replicas.expect("failed to create replica");
+    let (sector_id, replica) = replicas.pop().expect("failed to create replica");
+    let pre_commit_output = pre_commit_outputs
+        .return_value
+        .into_iter()
+        .next()
+        .expect("failed to create replica");
+
+    let phase1_output = seal_commit_phase1::<_, Tree>(
+        porep_config,
+        &replica.private_replica_info.cache_dir_path(),
+        &replica.private_replica_info.replica_path(),
+        PROVER_ID,
+        sector_id,
+        TICKET_BYTES,
+        RANDOMNESS,
+        pre_commit_output,
+        &replica.piece_info,
+    )?;
+
+    write_seal_witness::<Tree, _>(porep_config, &phase1_output, 0, create_file(path)?)
+}
+
+fn export(opts: &Opt) -> Result<()> {
+    let human_size = opts
+        .sector_size
+        .file_size(file_size_opts::BINARY)
+        .expect("failed to format sector size");
+    sector_shape(opts.sector_size)?;
+
+    let r1cs_info = match opts.circuit.as_str() {
+        "porep" => with_shape!(opts.sector_size, export_porep, opts),
+        "winning" => with_shape!(opts.sector_size, export_post, opts, PoStType::Winning),
+        "window" => with_shape!(opts.sector_size, export_post, opts, PoStType::Window),
+        other => anyhow::bail!("unknown circuit: {}", other),
+    }?;
+    println!(
+        "{} {} constraints: {}, public inputs: {}, aux variables: {}, written to {:?}",
+        human_size,
+        opts.circuit,
+        r1cs_info.num_constraints,
+        r1cs_info.num_inputs,
+        r1cs_info.num_aux,
+        opts.output
+    );
+
+    if let Some(path) = &opts.witness {
+        ensure!(
+            opts.circuit == "porep",
+            "witness export is only supported for the porep circuit"
+        );
+        let witness_info = with_shape!(opts.sector_size, export_seal_witness, opts, path)?;
+        ensure!(
+            witness_info.num_inputs == r1cs_info.num_inputs
+                && witness_info.num_aux == r1cs_info.num_aux,
+            "witness does not match the constraint system"
+        );
+        println!("{} porep witness written to {:?}", human_size, path);
+    }
+
+    Ok(())
+}
+
+// Run this from the command-line to export the constraint system of a circuit.
+pub fn main() {
+    fil_logger::init();
+
+    let opts = Opt::from_args();
+    info!("exporting r1cs: {:?}", opts);
+
+    if let Err(err) = export(&opts) {
+        eprintln!("r1cs export failed: {:?}", err);
+        std::process::exit(1);
+    }
+}
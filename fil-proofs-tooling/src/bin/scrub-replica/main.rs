@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+use std::process::exit;
+
+use anyhow::{Context, Result};
+use byte_unit::Byte;
+use filecoin_proofs::{
+    scrub_replica, scrub_replica_sampled, sector_shape, with_shape, MerkleTreeTrait, ScrubReport,
+};
+use log::info;
+use rand::thread_rng;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "scrub-replica",
+    about = "Checks a sealed replica against its tree_r_last cache, reporting corrupted nodes"
+)]
+struct Opt {
+    /// The sector size, e.g. 32GiB.
+    #[structopt(long)]
+    size: String,
+    /// The sealed replica.
+    #[structopt(long, parse(from_os_str))]
+    replica: PathBuf,
+    /// The cache directory of the sector.
+    #[structopt(long, parse(from_os_str))]
+    cache: PathBuf,
+    /// Only check this many randomly chosen subtrees of the replica.
+    #[structopt(long)]
+    sample: Option<usize>,
+}
+
+fn scrub<Tree: 'static + MerkleTreeTrait>(opt: &Opt, sector_size: u64) -> Result<ScrubReport> {
+    match opt.sample {
+        Some(sample_count) => scrub_replica_sampled::<_, Tree>(
+            &mut thread_rng(),
+            sector_size.into(),
+            &opt.cache,
+            &opt.replica,
+            sample_count,
+        ),
+        None => scrub_replica::<Tree>(sector_size.into(), &opt.cache, &opt.replica),
+    }
+}
+
+fn run(opt: &Opt) -> Result<ScrubReport> {
+    let sector_size = Byte::from_str(&opt.size)
+        .with_context(|| format!("invalid sector size {}", opt.size))?
+        .get_bytes() as u64;
+    sector_shape(sector_size)?;
+
+    let report = with_shape!(sector_size, scrub, opt, sector_size)?;
+    serde_json::to_writer_pretty(std::io::stdout(), &report)
+        .context("could not write the report")?;
+    println!();
+
+    Ok(report)
+}
+
+fn main() {
+    fil_logger::init();
+
+    let opt = Opt::from_args();
+    info!("scrub-replica: {:?}", opt);
+
+    match run(&opt) {
+        Ok(report) if report.is_intact() => {}
+        Ok(report) => {
+            eprintln!(
+                "scrub-replica: {:?} is corrupted, {} corrupted node ranges{}",
+                opt.replica,
+                report.corrupted_nodes.len(),
+                if report.cache_consistent {
+                    ""
+                } else {
+                    ", and its tree_r_last cache is inconsistent"
+                }
+            );
+            exit(1);
+        }
+        Err(err) => {
+            eprintln!("scrub-replica failed: {:?}", err);
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,507 @@
+//! Comparison of the results printed by `benchy` and `prodbench`.
+//!
+//! A result is a report wrapped in [`Metadata`](crate::Metadata), or a list of reports. The
+//! measurements of a report are the numeric fields of its `outputs`, keyed by the sector size of
+//! its `inputs` and the field name, with the names of nested fields joined by `-`. Names are
+//! normalized to kebab case, as `prodbench` prints snake case, so the same operation lines up
+//! across both tools. A list of reports must not measure the same operation twice for a sector
+//! size, e.g. `prodbench` runs differing only in the number of sectors, as it would be ambiguous
+//! which of them to compare.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Context, Result};
+use byte_unit::Byte;
+use serde_json::Value;
+
+/// Identifies a measurement across results.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MeasurementKey {
+    /// The sector size of the report, if it has one.
+    pub sector_size: Option<u64>,
+    pub name: String,
+}
+
+/// The measurements of a result file.
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub path: PathBuf,
+    pub git_hash: Option<String>,
+    pub processor: Option<String>,
+    pub measurements: BTreeMap<MeasurementKey, f64>,
+}
+
+impl BenchResult {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("could not open {:?}", path))?;
+        let json: Value = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("invalid JSON in {:?}", path))?;
+
+        Self::from_json(path, &json).with_context(|| format!("invalid result {:?}", path))
+    }
+
+    pub fn from_json(path: &Path, json: &Value) -> Result<Self> {
+        let benchmarks = json.get("benchmarks").context("missing benchmarks")?;
+        let reports: Vec<&Value> = match benchmarks {
+            Value::Array(reports) => reports.iter().collect(),
+            report => vec![report],
+        };
+
+        let mut measurements = BTreeMap::new();
+        for report in reports {
+            // Reports without outputs, like the constraint counts of `benchy hash-constraints`,
+            // hold nothing to compare.
+            let outputs = match report.get("outputs") {
+                Some(Value::Object(outputs)) => outputs,
+                _ => continue,
+            };
+            let sector_size = report
+                .get("inputs")
+                .and_then(|inputs| {
+                    inputs
+                        .get("sector-size")
+                        .or_else(|| inputs.get("sector_size"))
+                })
+                .map(parse_sector_size)
+                .transpose()?;
+
+            for (name, value) in outputs {
+                insert_measurements(&mut measurements, sector_size, name, value)?;
+            }
+        }
+        ensure!(!measurements.is_empty(), "no measurements found");
+
+        let string_at = |pointer: &str| {
+            json.pointer(pointer)
+                .and_then(Value::as_str)
+                .map(String::from)
+        };
+
+        Ok(BenchResult {
+            path: path.to_path_buf(),
+            git_hash: string_at("/git/hash"),
+            processor: string_at("/system/processor"),
+            measurements,
+        })
+    }
+}
+
+/// Inserts the numeric leaves of `value`, named by the path to them, like
+/// `pipeline-commit-phase2-sectors-per-hour` for the pipeline outputs of `prodbench`. Fails if a
+/// measurement was already inserted by another report.
+fn insert_measurements(
+    measurements: &mut BTreeMap<MeasurementKey, f64>,
+    sector_size: Option<u64>,
+    name: &str,
+    value: &Value,
+) -> Result<()> {
+    let name = name.replace('_', "-");
+    match value {
+        Value::Object(fields) => {
+            for (field, value) in fields {
+                let field_name = format!("{}-{}", name, field);
+                insert_measurements(measurements, sector_size, &field_name, value)?;
+            }
+        }
+        value => {
+            if let Some(value) = value.as_f64() {
+                let key = MeasurementKey { sector_size, name };
+                ensure!(
+                    !measurements.contains_key(&key),
+                    "{} is measured by more than one report of sector size {:?}",
+                    key.name,
+                    key.sector_size
+                );
+                measurements.insert(key, value);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Sector sizes are printed as bytes by `benchy`, and as strings like `32GiB` by `prodbench`.
+fn parse_sector_size(value: &Value) -> Result<u64> {
+    match value {
+        Value::Number(number) => number.as_u64().context("invalid sector size"),
+        Value::String(size) => Ok(Byte::from_str(size)
+            .with_context(|| format!("invalid sector size {}", size))?
+            .get_bytes() as u64),
+        _ => bail!("invalid sector size {}", value),
+    }
+}
+
+/// Whether a measurement getting larger is an improvement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    LowerIsBetter,
+    HigherIsBetter,
+    /// Counts like the number of constraints, which are reported but never regress.
+    Neutral,
+}
+
+impl Direction {
+    /// Derived from the naming of the reports, which suffix timings with `-ms`, memory usage with
+    /// `-memory-bytes` and throughputs with `-per-s` or `-per-hour`.
+    pub fn of(name: &str) -> Self {
+        if name.ends_with("-ms") || name.ends_with("-memory-bytes") {
+            Direction::LowerIsBetter
+        } else if name.ends_with("-per-s") || name.ends_with("-per-hour") {
+            Direction::HigherIsBetter
+        } else {
+            Direction::Neutral
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Thresholds {
+    /// Relative changes of at most this many percent are noise.
+    pub noise_percent: f64,
+    /// Changes of timings of at most this many milliseconds are noise, whatever their relative
+    /// size.
+    pub noise_floor_ms: f64,
+    /// Measurements getting worse by more than this many percent are regressions.
+    pub tolerance_percent: f64,
+}
+
+impl Thresholds {
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            self.noise_percent >= 0.0 && self.noise_floor_ms >= 0.0,
+            "noise thresholds must not be negative"
+        );
+        ensure!(
+            self.noise_percent <= self.tolerance_percent,
+            "the noise threshold of {}% exceeds the tolerance of {}%",
+            self.noise_percent,
+            self.tolerance_percent
+        );
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Within the noise thresholds.
+    Unchanged,
+    Improved,
+    /// Worse, but within the tolerance.
+    Worse,
+    Regressed,
+    /// A neutral measurement changed beyond the noise threshold.
+    Changed,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = match self {
+            Verdict::Unchanged => "~",
+            Verdict::Improved => "improved",
+            Verdict::Worse => "worse",
+            Verdict::Regressed => "REGRESSED",
+            Verdict::Changed => "changed",
+        };
+        f.write_str(verdict)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MeasurementComparison {
+    pub key: MeasurementKey,
+    pub baseline: f64,
+    pub candidate: f64,
+    /// The change relative to the baseline, infinite if the baseline is zero.
+    pub delta_percent: f64,
+    pub verdict: Verdict,
+}
+
+/// The comparison of a candidate result against a baseline.
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub measurements: Vec<MeasurementComparison>,
+    pub only_in_baseline: Vec<MeasurementKey>,
+    pub only_in_candidate: Vec<MeasurementKey>,
+}
+
+impl Comparison {
+    pub fn regressions(&self) -> usize {
+        self.measurements
+            .iter()
+            .filter(|m| m.verdict == Verdict::Regressed)
+            .count()
+    }
+
+    /// The regressions, and the measurements of the baseline missing from the candidate, which
+    /// may have stopped working.
+    pub fn failures(&self) -> usize {
+        self.regressions() + self.only_in_baseline.len()
+    }
+}
+
+fn delta_percent(baseline: f64, candidate: f64) -> f64 {
+    if baseline == 0.0 {
+        if candidate == 0.0 {
+            0.0
+        } else {
+            f64::INFINITY.copysign(candidate)
+        }
+    } else {
+        (candidate - baseline) / baseline.abs() * 100.0
+    }
+}
+
+fn verdict(
+    key: &MeasurementKey,
+    baseline: f64,
+    candidate: f64,
+    thresholds: &Thresholds,
+) -> Verdict {
+    let direction = Direction::of(&key.name);
+    let delta = delta_percent(baseline, candidate);
+
+    // The noise floor is in milliseconds, so it only applies to timings.
+    let below_floor =
+        key.name.ends_with("-ms") && (candidate - baseline).abs() <= thresholds.noise_floor_ms;
+    if below_floor || delta.abs() <= thresholds.noise_percent {
+        return Verdict::Unchanged;
+    }
+
+    let worsening = match direction {
+        Direction::LowerIsBetter => delta,
+        Direction::HigherIsBetter => -delta,
+        Direction::Neutral => return Verdict::Changed,
+    };
+    if worsening < 0.0 {
+        Verdict::Improved
+    } else if worsening <= thresholds.tolerance_percent {
+        Verdict::Worse
+    } else {
+        Verdict::Regressed
+    }
+}
+
+/// Compares the measurements both results have in common.
+pub fn compare(
+    baseline: &BenchResult,
+    candidate: &BenchResult,
+    thresholds: &Thresholds,
+) -> Comparison {
+    let measurements = baseline
+        .measurements
+        .iter()
+        .filter_map(|(key, &baseline)| {
+            let candidate = *candidate.measurements.get(key)?;
+
+            Some(MeasurementComparison {
+                key: key.clone(),
+                baseline,
+                candidate,
+                delta_percent: delta_percent(baseline, candidate),
+                verdict: verdict(key, baseline, candidate, thresholds),
+            })
+        })
+        .collect();
+
+    let missing_from = |a: &BenchResult, b: &BenchResult| {
+        a.measurements
+            .keys()
+            .filter(|key| !b.measurements.contains_key(key))
+            .cloned()
+            .collect()
+    };
+
+    Comparison {
+        measurements,
+        only_in_baseline: missing_from(baseline, candidate),
+        only_in_candidate: missing_from(candidate, baseline),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    const THRESHOLDS: Thresholds = Thresholds {
+        noise_percent: 2.0,
+        noise_floor_ms: 10.0,
+        tolerance_percent: 5.0,
+    };
+
+    fn key(sector_size: u64, name: &str) -> MeasurementKey {
+        MeasurementKey {
+            sector_size: Some(sector_size),
+            name: name.into(),
+        }
+    }
+
+    fn benchy_result(commit_ms: u64, throughput: f64) -> BenchResult {
+        let json = json!({
+            "git": { "hash": "abc", "date": "2020-01-01T00:00:00Z" },
+            "system": { "processor": "cpu" },
+            "benchmarks": {
+                "inputs": { "sector-size": 2048 },
+                "outputs": {
+                    "seal-commit-phase2-wall-time-ms": commit_ms,
+                    "pad-throughput-mib-per-s": throughput,
+                    "porep-constraints": 100,
+                }
+            }
+        });
+
+        BenchResult::from_json(Path::new("benchy.json"), &json).expect("from_json failure")
+    }
+
+    #[test]
+    fn test_from_json() {
+        let result = benchy_result(1000, 10.0);
+        assert_eq!(result.git_hash.as_deref(), Some("abc"));
+        assert_eq!(result.processor.as_deref(), Some("cpu"));
+        assert_eq!(
+            result.measurements[&key(2048, "seal-commit-phase2-wall-time-ms")],
+            1000.0
+        );
+
+        // prodbench prints snake case and human readable sector sizes.
+        let json = json!({
+            "benchmarks": {
+                "inputs": { "sector_size": "2KiB", "num_sectors": 1 },
+                "outputs": { "seal_commit_phase2_wall_time_ms": 1200 }
+            }
+        });
+        let prodbench =
+            BenchResult::from_json(Path::new("prodbench.json"), &json).expect("from_json failure");
+        assert_eq!(
+            prodbench.measurements.keys().collect::<Vec<_>>(),
+            vec![&key(2048, "seal-commit-phase2-wall-time-ms")]
+        );
+
+        let json = json!({
+            "benchmarks": {
+                "inputs": { "sector_size": "2KiB" },
+                "outputs": {
+                    "pipeline": {
+                        "sectors_per_hour": 20.0,
+                        "commit_phase2": { "max_queue_time_ms": 10, "peak_process_memory_bytes": 4096 }
+                    }
+                }
+            }
+        });
+        let pipeline =
+            BenchResult::from_json(Path::new("pipeline.json"), &json).expect("from_json failure");
+        assert_eq!(
+            pipeline.measurements.keys().collect::<Vec<_>>(),
+            vec![
+                &key(2048, "pipeline-commit-phase2-max-queue-time-ms"),
+                &key(2048, "pipeline-commit-phase2-peak-process-memory-bytes"),
+                &key(2048, "pipeline-sectors-per-hour"),
+            ]
+        );
+        assert_eq!(
+            Direction::of("pipeline-commit-phase2-peak-process-memory-bytes"),
+            Direction::LowerIsBetter
+        );
+        assert_eq!(
+            Direction::of("pipeline-sectors-per-hour"),
+            Direction::HigherIsBetter
+        );
+
+        let json = json!({ "benchmarks": [{ "hash-fn": "sha256", "constraints": 1 }] });
+        assert!(BenchResult::from_json(Path::new("hashes.json"), &json).is_err());
+
+        // Reports of the same sector size with different inputs can't be told apart.
+        let json = json!({
+            "benchmarks": [
+                {
+                    "inputs": { "sector_size": "2KiB", "num_sectors": 1 },
+                    "outputs": { "seal_commit_phase2_wall_time_ms": 1200 }
+                },
+                {
+                    "inputs": { "sector_size": "2KiB", "num_sectors": 2 },
+                    "outputs": { "seal_commit_phase2_wall_time_ms": 2400 }
+                },
+                {
+                    "inputs": { "sector_size": "4KiB", "num_sectors": 1 },
+                    "outputs": { "seal_commit_phase2_wall_time_ms": 2400 }
+                }
+            ]
+        });
+        assert!(BenchResult::from_json(Path::new("prodbench.json"), &json).is_err());
+    }
+
+    #[test]
+    fn test_compare() {
+        let baseline = benchy_result(1000, 10.0);
+        let verdicts = |commit_ms, throughput| {
+            let comparison = compare(
+                &baseline,
+                &benchy_result(commit_ms, throughput),
+                &THRESHOLDS,
+            );
+            comparison
+                .measurements
+                .iter()
+                .map(|m| (m.key.name.clone(), m.verdict))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        let unchanged = verdicts(1015, 9.9);
+        assert_eq!(
+            unchanged["seal-commit-phase2-wall-time-ms"],
+            Verdict::Unchanged
+        );
+        assert_eq!(unchanged["pad-throughput-mib-per-s"], Verdict::Unchanged);
+        assert_eq!(unchanged["porep-constraints"], Verdict::Unchanged);
+
+        let worse = verdicts(1040, 10.5);
+        assert_eq!(worse["seal-commit-phase2-wall-time-ms"], Verdict::Worse);
+        assert_eq!(worse["pad-throughput-mib-per-s"], Verdict::Improved);
+
+        let regressed = verdicts(900, 9.0);
+        assert_eq!(
+            regressed["seal-commit-phase2-wall-time-ms"],
+            Verdict::Improved
+        );
+        assert_eq!(regressed["pad-throughput-mib-per-s"], Verdict::Regressed);
+
+        // Below the noise floor, large relative changes of short timings are noise.
+        let short = benchy_result(4, 10.0);
+        let comparison = compare(&short, &benchy_result(8, 10.0), &THRESHOLDS);
+        assert_eq!(comparison.regressions(), 0);
+
+        // Other measurements don't have a noise floor, however small their changes.
+        let memory = key(2048, "pipeline-commit-phase2-peak-process-memory-bytes");
+        assert_eq!(verdict(&memory, 4.0, 8.0, &THRESHOLDS), Verdict::Regressed);
+    }
+
+    #[test]
+    fn test_compare_missing_measurements() {
+        let baseline = benchy_result(1000, 10.0);
+        let json = json!({
+            "benchmarks": {
+                "inputs": { "sector-size": 4096 },
+                "outputs": { "seal-commit-phase2-wall-time-ms": 2000 }
+            }
+        });
+        let candidate =
+            BenchResult::from_json(Path::new("other.json"), &json).expect("from_json failure");
+
+        let comparison = compare(&baseline, &candidate, &THRESHOLDS);
+        assert!(comparison.measurements.is_empty());
+        assert_eq!(comparison.only_in_baseline.len(), 3);
+        assert_eq!(comparison.regressions(), 0);
+        assert_eq!(comparison.failures(), 3);
+        assert_eq!(
+            comparison.only_in_candidate,
+            vec![key(4096, "seal-commit-phase2-wall-time-ms")]
+        );
+    }
+}
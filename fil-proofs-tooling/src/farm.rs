@@ -0,0 +1,383 @@
+//! Farms of fake sectors, to load test PoSt over many sectors without sealing them.
+//!
+//! A farm lives in a directory holding `sealed/s-<sector id>` replicas, `cache/s-<sector id>`
+//! cache directories and a `manifest.json` listing them. Every sector gets a replica of field
+//! elements and a fake `comm_c`, both derived from the farm seed and the sector id, so
+//! regenerating a farm with the same seed yields the same sectors and commitments.
+//!
+//! With `sparse_replicas`, the replicas are sparse files reading as all zeros instead, which
+//! all share one `tree_r_last`, and only their `comm_c`, and therefore their `comm_r`, differ.
+
+use std::collections::BTreeMap;
+use std::fs::{self, create_dir_all, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{ensure, Context, Result};
+use filecoin_proofs::{
+    fauxrep2_aux, fauxrep_aux, fauxrep_with_replica_aux, sector_shape, Commitment, MerkleTreeTrait,
+    PaddedBytesAmount, PoRepConfig, PoRepProofPartitions, PrivateReplicaInfo, PublicReplicaInfo,
+    SectorSize,
+};
+use log::info;
+use rand::{RngCore, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use storage_proofs_core::{
+    api_version::ApiVersion, cache_key::CacheKey, sector::SectorId, util::NODE_SIZE,
+};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+const SEALED_DIR: &str = "sealed";
+const CACHE_DIR: &str = "cache";
+const TEMPLATE_DIR: &str = "template";
+
+/// Replicas are written in chunks of this size.
+const WRITE_CHUNK_BYTES: usize = 1 << 20;
+
+/// The fake sectors don't depend on the porep id, but the config requires one.
+const ARBITRARY_POREP_ID: [u8; 32] = [99; 32];
+
+/// A sector of a farm.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FarmSector {
+    pub sector_id: u64,
+    pub comm_r: Commitment,
+    pub replica_path: PathBuf,
+    pub cache_dir: PathBuf,
+}
+
+/// The sectors of a farm, as written to its `manifest.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FarmManifest {
+    pub sector_size: u64,
+    pub seed: u64,
+    pub sectors: Vec<FarmSector>,
+}
+
+impl FarmManifest {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("could not open {:?}", path))?;
+
+        serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("invalid farm manifest {:?}", path))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let file = File::create(path).with_context(|| format!("could not create {:?}", path))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)?;
+
+        Ok(())
+    }
+
+    /// The sectors to pass to `generate_window_post`.
+    pub fn private_replicas<Tree: 'static + MerkleTreeTrait>(
+        &self,
+    ) -> Result<BTreeMap<SectorId, PrivateReplicaInfo<Tree>>> {
+        self.sectors
+            .iter()
+            .map(|sector| {
+                let info = PrivateReplicaInfo::new(
+                    sector.replica_path.clone(),
+                    sector.comm_r,
+                    sector.cache_dir.clone(),
+                )?;
+                Ok((SectorId::from(sector.sector_id), info))
+            })
+            .collect()
+    }
+
+    /// The sectors to pass to `verify_window_post`.
+    pub fn public_replicas(&self) -> Result<BTreeMap<SectorId, PublicReplicaInfo>> {
+        self.sectors
+            .iter()
+            .map(|sector| {
+                let info = PublicReplicaInfo::new(sector.comm_r)?;
+                Ok((SectorId::from(sector.sector_id), info))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FarmConfig {
+    pub sector_size: u64,
+    pub sector_count: u64,
+    pub first_sector_id: u64,
+    pub seed: u64,
+    /// Write sparse replicas reading as all zeros, and build their `tree_r_last` once and hard
+    /// link it into every sector's cache. This saves most of the time and disk space, but all
+    /// sectors then hold the same data, so a PoSt over them reads far less from disk than one
+    /// over real sectors.
+    pub sparse_replicas: bool,
+}
+
+/// The rng a sector's fake commitments are drawn from.
+fn sector_rng(seed: u64, sector_id: u64) -> XorShiftRng {
+    let mut rng_seed = [0u8; 16];
+    rng_seed[..8].copy_from_slice(&seed.to_le_bytes());
+    rng_seed[8..].copy_from_slice(&sector_id.to_le_bytes());
+
+    XorShiftRng::from_seed(rng_seed)
+}
+
+fn porep_config(sector_size: u64) -> Result<PoRepConfig> {
+    Ok(PoRepConfig {
+        sector_size: SectorSize(sector_size),
+        partitions: PoRepProofPartitions(sector_shape(sector_size)?.porep_partitions),
+        porep_id: ARBITRARY_POREP_ID,
+        api_version: ApiVersion::V1_1_0,
+    })
+}
+
+/// Fills the replica at `path` with `sector_bytes` of field elements drawn from `rng`.
+fn write_replica<R: RngCore>(rng: &mut R, path: &Path, sector_bytes: u64) -> Result<()> {
+    let mut file = File::create(path).with_context(|| format!("could not create {:?}", path))?;
+    let mut chunk = vec![0u8; WRITE_CHUNK_BYTES];
+    let mut written = 0;
+    while written < sector_bytes {
+        let len = std::cmp::min(sector_bytes - written, chunk.len() as u64) as usize;
+        let chunk = &mut chunk[..len];
+        rng.fill_bytes(chunk);
+        // Clearing the two most significant bits keeps every node below the field modulus.
+        for node in chunk.chunks_mut(NODE_SIZE) {
+            node[NODE_SIZE - 1] &= 0b0011_1111;
+        }
+        file.write_all(chunk)
+            .with_context(|| format!("could not write {:?}", path))?;
+        written += len as u64;
+    }
+
+    Ok(())
+}
+
+/// Links, or if that fails copies, the files of `template_cache` except `p_aux` into
+/// `cache_dir`.
+fn link_template(template_cache: &Path, cache_dir: &Path) -> Result<()> {
+    let p_aux = CacheKey::PAux.to_string();
+    for entry in fs::read_dir(template_cache)? {
+        let entry = entry?;
+        if entry.file_name().to_str() == Some(p_aux.as_str()) {
+            continue;
+        }
+
+        let target = cache_dir.join(entry.file_name());
+        if fs::hard_link(entry.path(), &target).is_err() {
+            fs::copy(entry.path(), &target)
+                .with_context(|| format!("could not copy {:?} to {:?}", entry.path(), target))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn generate_sector<Tree: 'static + MerkleTreeTrait>(
+    dir: &Path,
+    config: &FarmConfig,
+    template_cache: Option<&Path>,
+    sector_id: u64,
+) -> Result<FarmSector> {
+    let replica_path = dir.join(SEALED_DIR).join(format!("s-{}", sector_id));
+    let cache_dir = dir.join(CACHE_DIR).join(format!("s-{}", sector_id));
+    create_dir_all(&cache_dir)?;
+
+    let mut rng = sector_rng(config.seed, sector_id);
+    let comm_r = match template_cache {
+        Some(template_cache) => {
+            // A sparse replica of the sector size reads as all zeros, like the one of `fauxrep`.
+            let sector_bytes = PaddedBytesAmount::from(porep_config(config.sector_size)?).0;
+            File::create(&replica_path)?.set_len(sector_bytes)?;
+            link_template(template_cache, &cache_dir)?;
+
+            fauxrep2_aux::<_, _, _, Tree>(
+                &mut rng,
+                &cache_dir,
+                template_cache.join(CacheKey::PAux.to_string()),
+            )?
+        }
+        None => {
+            let porep_config = porep_config(config.sector_size)?;
+            write_replica(
+                &mut rng,
+                &replica_path,
+                PaddedBytesAmount::from(porep_config).0,
+            )?;
+
+            fauxrep_with_replica_aux::<_, _, _, Tree>(
+                &mut rng,
+                porep_config,
+                &cache_dir,
+                &replica_path,
+            )?
+        }
+    };
+
+    Ok(FarmSector {
+        sector_id,
+        comm_r,
+        replica_path,
+        cache_dir,
+    })
+}
+
+/// Generates the sectors of a farm into `dir`, which must not hold a farm yet, in parallel on
+/// the current rayon thread pool. The manifest is written last, so a farm without one is
+/// incomplete.
+pub fn generate_farm<Tree: 'static + MerkleTreeTrait>(
+    dir: &Path,
+    config: &FarmConfig,
+) -> Result<FarmManifest> {
+    let manifest_path = dir.join(MANIFEST_FILE);
+    ensure!(!manifest_path.exists(), "{:?} already holds a farm", dir);
+    ensure!(
+        config
+            .first_sector_id
+            .checked_add(config.sector_count)
+            .is_some(),
+        "sector ids overflow"
+    );
+
+    create_dir_all(dir.join(SEALED_DIR))?;
+    create_dir_all(dir.join(CACHE_DIR))?;
+    // Absolute paths keep the manifest usable from any working directory.
+    let dir = dir
+        .canonicalize()
+        .with_context(|| format!("could not resolve {:?}", dir))?;
+
+    let template_cache = if config.sparse_replicas {
+        info!("building the shared tree_r_last");
+        let template_dir = dir.join(TEMPLATE_DIR);
+        create_dir_all(&template_dir)?;
+        fauxrep_aux::<_, _, _, Tree>(
+            &mut sector_rng(config.seed, u64::max_value()),
+            porep_config(config.sector_size)?,
+            &template_dir,
+            template_dir.join("replica"),
+        )?;
+        // Only the trees are shared, the replica is recreated for every sector.
+        fs::remove_file(template_dir.join("replica"))?;
+
+        Some(template_dir)
+    } else {
+        None
+    };
+
+    let generated = AtomicUsize::new(0);
+    let sectors = (config.first_sector_id..config.first_sector_id + config.sector_count)
+        .into_par_iter()
+        .map(|sector_id| {
+            let sector =
+                generate_sector::<Tree>(&dir, config, template_cache.as_deref(), sector_id)
+                    .with_context(|| format!("failed to generate sector {}", sector_id))?;
+            info!(
+                "generated sector {} ({}/{})",
+                sector_id,
+                generated.fetch_add(1, Ordering::SeqCst) + 1,
+                config.sector_count
+            );
+
+            Ok(sector)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let manifest = FarmManifest {
+        sector_size: config.sector_size,
+        seed: config.seed,
+        sectors,
+    };
+    manifest.save(&manifest_path)?;
+
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use filecoin_proofs::SectorShape2KiB;
+    use tempfile::tempdir;
+
+    const SECTOR_SIZE_2_KIB: u64 = 2048;
+
+    fn farm_config(sparse_replicas: bool) -> FarmConfig {
+        FarmConfig {
+            sector_size: SECTOR_SIZE_2_KIB,
+            sector_count: 3,
+            first_sector_id: 10,
+            seed: 7,
+            sparse_replicas,
+        }
+    }
+
+    fn comm_rs(manifest: &FarmManifest) -> Vec<(u64, Commitment)> {
+        manifest
+            .sectors
+            .iter()
+            .map(|sector| (sector.sector_id, sector.comm_r))
+            .collect()
+    }
+
+    #[test]
+    fn test_generate_farm_is_deterministic() {
+        let dir = tempdir().expect("tempdir failure");
+        let again_dir = tempdir().expect("tempdir failure");
+        let sparse_dir = tempdir().expect("tempdir failure");
+
+        let manifest = generate_farm::<SectorShape2KiB>(dir.path(), &farm_config(false))
+            .expect("generate_farm failure");
+        let again = generate_farm::<SectorShape2KiB>(again_dir.path(), &farm_config(false))
+            .expect("generate_farm failure");
+        let sparse = generate_farm::<SectorShape2KiB>(sparse_dir.path(), &farm_config(true))
+            .expect("generate_farm failure");
+
+        assert_eq!(
+            manifest
+                .sectors
+                .iter()
+                .map(|sector| sector.sector_id)
+                .collect::<Vec<_>>(),
+            vec![10, 11, 12]
+        );
+        assert_eq!(comm_rs(&manifest), comm_rs(&again));
+        assert_ne!(manifest.sectors[0].comm_r, manifest.sectors[1].comm_r);
+        assert_ne!(sparse.sectors[0].comm_r, sparse.sectors[1].comm_r);
+        assert_ne!(manifest.sectors[0].comm_r, sparse.sectors[0].comm_r);
+
+        // Every sector has its own replica, unless they are sparse.
+        let replica = |manifest: &FarmManifest, i: usize| {
+            fs::read(&manifest.sectors[i].replica_path).expect("read failure")
+        };
+        assert_eq!(replica(&manifest, 0), replica(&again, 0));
+        assert_ne!(replica(&manifest, 0), replica(&manifest, 1));
+        assert_eq!(replica(&sparse, 0), vec![0u8; SECTOR_SIZE_2_KIB as usize]);
+        assert_eq!(replica(&sparse, 0), replica(&sparse, 1));
+
+        let loaded = FarmManifest::load(dir.path().join(MANIFEST_FILE)).expect("load failure");
+        assert_eq!(loaded, manifest);
+
+        for manifest in &[manifest, sparse] {
+            assert_eq!(
+                manifest
+                    .private_replicas::<SectorShape2KiB>()
+                    .expect("private_replicas failure")
+                    .len(),
+                3
+            );
+            assert_eq!(
+                manifest
+                    .public_replicas()
+                    .expect("public_replicas failure")
+                    .len(),
+                3
+            );
+        }
+
+        // A farm is not overwritten.
+        assert!(generate_farm::<SectorShape2KiB>(dir.path(), &farm_config(false)).is_err());
+    }
+}
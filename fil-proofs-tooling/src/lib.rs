@@ -1,6 +1,8 @@
 #![deny(clippy::all, clippy::perf, clippy::correctness, rust_2018_idioms)]
 #![warn(clippy::unwrap_used)]
 
+pub mod compare;
+pub mod farm;
 pub mod measure;
 pub mod metadata;
 pub mod shared;
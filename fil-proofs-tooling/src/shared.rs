@@ -2,10 +2,10 @@ use std::cmp::min;
 use std::io::{BufWriter, Seek, SeekFrom, Write};
 
 use filecoin_proofs::{
-    add_piece, seal_pre_commit_phase1, seal_pre_commit_phase2, validate_cache_for_precommit_phase2,
-    MerkleTreeTrait, PaddedBytesAmount, PieceInfo, PoRepConfig, PoRepProofPartitions,
-    PrivateReplicaInfo, PublicReplicaInfo, SealPreCommitOutput, SectorSize, UnpaddedBytesAmount,
-    POREP_PARTITIONS,
+    add_piece, seal_pre_commit_phase1, seal_pre_commit_phase2, sector_shape,
+    validate_cache_for_precommit_phase2, MerkleTreeTrait, PaddedBytesAmount, PieceInfo,
+    PoRepConfig, PoRepProofPartitions, PrivateReplicaInfo, PublicReplicaInfo, SealPreCommitOutput,
+    SectorSize, UnpaddedBytesAmount,
 };
 use log::info;
 use rand::{random, thread_rng, RngCore};
@@ -101,11 +101,9 @@ pub fn create_replicas<Tree: 'static + MerkleTreeTrait>(
     let porep_config = PoRepConfig {
         sector_size,
         partitions: PoRepProofPartitions(
-            *POREP_PARTITIONS
-                .read()
-                .expect("poisoned read access")
-                .get(&u64::from(sector_size))
-                .expect("unknown sector size"),
+            sector_shape(u64::from(sector_size))
+                .expect("unknown sector size")
+                .porep_partitions,
         ),
         porep_id,
         api_version,
@@ -0,0 +1,371 @@
+//! A reusable conformance suite for `Hasher` implementations.
+//!
+//! Every `Hasher` has to keep its native hash functions, its `merkletree` algorithm and its
+//! circuit gadgets in agreement. The checks in this module verify that for any `Hasher`, and
+//! panic with a descriptive message on the first disagreement found.
+
+use bellperson::{
+    bls::{Bls12, Fr},
+    gadgets::num::AllocatedNum,
+    util_cs::test_cs::TestConstraintSystem,
+    ConstraintSystem,
+};
+use ff::PrimeField;
+use generic_array::typenum::{Unsigned, U16, U2, U4, U8};
+use merkletree::{
+    hash::Algorithm as LightAlgorithm,
+    merkle::{Element, MerkleTree},
+    store::VecStore,
+};
+use rand::RngCore;
+
+use crate::types::{Domain, HashFunction, Hasher, PoseidonArity};
+
+/// Runs every check in this module against `H`.
+pub fn check_hasher<H: Hasher, R: RngCore>(rng: &mut R) {
+    check_domain_round_trips::<H, R>(rng);
+    check_rejects_wrong_length::<H>();
+    // `check_rejects_non_field_bytes` is not part of the common suite: the SHA-256 and Blake2s
+    // domains hold raw digests and intentionally accept any 32 bytes, so only hashers whose
+    // domain is a field element run it.
+    check_native_consistency::<H, R>(rng);
+
+    check_leaf_circuit::<H, R>(rng);
+    check_hash2_circuit::<H, R>(rng);
+    check_multi_leaf_circuit::<H, U2, R>(rng);
+    check_multi_leaf_circuit::<H, U4, R>(rng);
+    check_multi_leaf_circuit::<H, U8, R>(rng);
+    check_multi_leaf_circuit::<H, U16, R>(rng);
+
+    check_merkle_root::<H, U2, R>(rng, 64);
+    check_merkle_root::<H, U4, R>(rng, 64);
+    check_merkle_root::<H, U8, R>(rng, 64);
+    check_merkle_root::<H, U16, R>(rng, 256);
+}
+
+/// Checks that a `Domain` survives every serialization it supports unchanged.
+pub fn check_domain_round_trips<H: Hasher, R: RngCore>(rng: &mut R) {
+    let name = H::name();
+    let byte_len = <H::Domain as Element>::byte_len();
+
+    for _ in 0..100 {
+        let domain = H::Domain::random(rng);
+
+        let bytes = domain.into_bytes();
+        assert_eq!(bytes.len(), byte_len, "{}: invalid into_bytes length", name);
+        assert_eq!(
+            bytes,
+            AsRef::<[u8]>::as_ref(&domain),
+            "{}: as_ref mismatch",
+            name
+        );
+        assert_eq!(
+            H::Domain::try_from_bytes(&bytes).expect("try_from_bytes failure"),
+            domain,
+            "{}: into_bytes/try_from_bytes round trip",
+            name
+        );
+
+        let mut written = vec![0u8; byte_len];
+        domain
+            .write_bytes(&mut written)
+            .expect("write_bytes failure");
+        assert_eq!(written, bytes, "{}: write_bytes mismatch", name);
+
+        let mut copied = vec![0u8; byte_len];
+        domain.copy_to_slice(&mut copied);
+        assert_eq!(copied, bytes, "{}: copy_to_slice mismatch", name);
+        assert_eq!(
+            <H::Domain as Element>::from_slice(&copied),
+            domain,
+            "{}: copy_to_slice/from_slice round trip",
+            name
+        );
+
+        let fr: Fr = domain.into();
+        assert_eq!(H::Domain::from(fr), domain, "{}: Fr round trip", name);
+        assert_eq!(
+            H::Domain::from(fr.into_repr()),
+            domain,
+            "{}: FrRepr round trip",
+            name
+        );
+
+        let json = serde_json::to_string(&domain).expect("serialize failure");
+        let from_json: H::Domain = serde_json::from_str(&json).expect("deserialize failure");
+        assert_eq!(from_json, domain, "{}: serde round trip", name);
+    }
+}
+
+/// Checks that `try_from_bytes` rejects input of the wrong length.
+pub fn check_rejects_wrong_length<H: Hasher>() {
+    let name = H::name();
+    let byte_len = <H::Domain as Element>::byte_len();
+
+    assert!(
+        H::Domain::try_from_bytes(&vec![0u8; byte_len - 1]).is_err(),
+        "{}: accepted too few bytes",
+        name
+    );
+    assert!(
+        H::Domain::try_from_bytes(&vec![0u8; byte_len + 1]).is_err(),
+        "{}: accepted too many bytes",
+        name
+    );
+}
+
+/// Checks that `try_from_bytes` rejects encodings of values which are not canonical field
+/// elements, for hashers whose domain is a field element.
+pub fn check_rejects_non_field_bytes<H: Hasher>() {
+    let name = H::name();
+    let byte_len = <H::Domain as Element>::byte_len();
+
+    // The field modulus itself, in little endian, is the smallest non-canonical value.
+    let mut modulus = Vec::with_capacity(byte_len);
+    for limb in Fr::char().as_ref() {
+        modulus.extend_from_slice(&limb.to_le_bytes());
+    }
+    let all_ones = vec![0xffu8; byte_len];
+    let mut high_bit = vec![0u8; byte_len];
+    high_bit[byte_len - 1] = 0b1000_0000;
+
+    for bytes in &[modulus, all_ones, high_bit] {
+        assert!(
+            H::Domain::try_from_bytes(bytes).is_err(),
+            "{}: accepted non-field bytes {:?}",
+            name,
+            bytes
+        );
+    }
+}
+
+/// Checks that the different native ways of hashing two elements agree.
+pub fn check_native_consistency<H: Hasher, R: RngCore>(rng: &mut R) {
+    let name = H::name();
+
+    for height in 0..4 {
+        let a = H::Domain::random(rng);
+        let b = H::Domain::random(rng);
+
+        let hash2 = H::Function::hash2(&a, &b);
+
+        let mut concatenated = a.into_bytes();
+        concatenated.extend(b.into_bytes());
+        assert_eq!(
+            H::Function::hash(&concatenated),
+            hash2,
+            "{}: hash/hash2 mismatch",
+            name
+        );
+
+        let mut function = H::Function::default();
+        assert_eq!(
+            function.node(a, b, height),
+            hash2,
+            "{}: node/hash2 mismatch",
+            name
+        );
+        function.reset();
+        assert_eq!(
+            function.multi_node(&[a, b], height),
+            hash2,
+            "{}: multi_node/hash2 mismatch",
+            name
+        );
+        function.reset();
+        assert_eq!(function.leaf(a), a, "{}: leaf must be the identity", name);
+    }
+}
+
+fn alloc_domains<H: Hasher, CS: ConstraintSystem<Bls12>>(
+    cs: &mut CS,
+    domains: &[H::Domain],
+) -> Vec<AllocatedNum<Bls12>> {
+    domains
+        .iter()
+        .enumerate()
+        .map(|(i, domain)| {
+            AllocatedNum::alloc(cs.namespace(|| format!("element {}", i)), || {
+                Ok((*domain).into())
+            })
+            .expect("alloc failure")
+        })
+        .collect()
+}
+
+fn assert_circuit_value<H: Hasher>(
+    cs: &TestConstraintSystem<Bls12>,
+    num: &AllocatedNum<Bls12>,
+    expected: H::Domain,
+    what: &str,
+) {
+    assert!(
+        cs.is_satisfied(),
+        "{}: {} constraints not satisfied: {:?}",
+        H::name(),
+        what,
+        cs.which_is_unsatisfied()
+    );
+
+    let expected: Fr = expected.into();
+    assert_eq!(
+        num.get_value().expect("get_value failure"),
+        expected,
+        "{}: {} circuit/native mismatch",
+        H::name(),
+        what
+    );
+}
+
+/// Checks that `hash_leaf_circuit` agrees with `LightAlgorithm::node`.
+pub fn check_leaf_circuit<H: Hasher, R: RngCore>(rng: &mut R) {
+    for height in 0..2 {
+        let left = H::Domain::random(rng);
+        let right = H::Domain::random(rng);
+
+        let mut cs = TestConstraintSystem::<Bls12>::new();
+        let nums = alloc_domains::<H, _>(&mut cs, &[left, right]);
+        let out = H::Function::hash_leaf_circuit(
+            cs.namespace(|| "hash_leaf_circuit"),
+            &nums[0],
+            &nums[1],
+            height,
+        )
+        .expect("hash_leaf_circuit failure");
+
+        let expected = H::Function::default().node(left, right, height);
+        assert_circuit_value::<H>(&cs, &out, expected, "hash_leaf_circuit");
+    }
+}
+
+/// Checks that `hash2_circuit` agrees with `HashFunction::hash2`.
+pub fn check_hash2_circuit<H: Hasher, R: RngCore>(rng: &mut R) {
+    let a = H::Domain::random(rng);
+    let b = H::Domain::random(rng);
+
+    let mut cs = TestConstraintSystem::<Bls12>::new();
+    let nums = alloc_domains::<H, _>(&mut cs, &[a, b]);
+    let out = H::Function::hash2_circuit(cs.namespace(|| "hash2_circuit"), &nums[0], &nums[1])
+        .expect("hash2_circuit failure");
+
+    assert_circuit_value::<H>(&cs, &out, H::Function::hash2(&a, &b), "hash2_circuit");
+}
+
+/// Checks that `hash_multi_leaf_circuit` agrees with `LightAlgorithm::multi_node` for `Arity`.
+pub fn check_multi_leaf_circuit<H: Hasher, Arity: 'static + PoseidonArity, R: RngCore>(
+    rng: &mut R,
+) {
+    let leaves: Vec<H::Domain> = (0..Arity::to_usize())
+        .map(|_| H::Domain::random(rng))
+        .collect();
+
+    let mut cs = TestConstraintSystem::<Bls12>::new();
+    let nums = alloc_domains::<H, _>(&mut cs, &leaves);
+    let out = H::Function::hash_multi_leaf_circuit::<Arity, _>(
+        cs.namespace(|| "hash_multi_leaf_circuit"),
+        &nums,
+        0,
+    )
+    .expect("hash_multi_leaf_circuit failure");
+
+    let expected = H::Function::default().multi_node(&leaves, 0);
+    assert_circuit_value::<H>(
+        &cs,
+        &out,
+        expected,
+        &format!("hash_multi_leaf_circuit (arity {})", Arity::to_usize()),
+    );
+}
+
+/// Checks that the root of a `merkletree` tree with `leafs` random leaves matches the root
+/// computed row by row with `LightAlgorithm::multi_node`.
+pub fn check_merkle_root<H: Hasher, Arity: 'static + PoseidonArity, R: RngCore>(
+    rng: &mut R,
+    leafs: usize,
+) {
+    let arity = Arity::to_usize();
+    let leaves: Vec<H::Domain> = (0..leafs).map(|_| H::Domain::random(rng)).collect();
+
+    let tree = MerkleTree::<H::Domain, H::Function, VecStore<H::Domain>, Arity>::new(
+        leaves.iter().copied(),
+    )
+    .expect("merkle tree new failure");
+
+    let mut function = H::Function::default();
+    let mut row: Vec<H::Domain> = leaves.iter().map(|leaf| function.leaf(*leaf)).collect();
+    let mut height = 0;
+    while row.len() > 1 {
+        assert_eq!(row.len() % arity, 0, "invalid number of leaves {}", leafs);
+        row = row
+            .chunks(arity)
+            .map(|children| {
+                function.reset();
+                let node = function.multi_node(children, height);
+                function.reset();
+                node
+            })
+            .collect();
+        height += 1;
+    }
+
+    assert_eq!(
+        tree.root(),
+        row[0],
+        "{}: merkle root mismatch (arity {})",
+        H::name(),
+        arity
+    );
+}
+
+/// Checks that `hash_md_circuit` agrees with `HashFunction::hash_md`, for hashers which implement
+/// the gadget.
+pub fn check_hash_md_circuit<H: Hasher, R: RngCore>(rng: &mut R, len: usize) {
+    let elements: Vec<H::Domain> = (0..len).map(|_| H::Domain::random(rng)).collect();
+
+    let mut cs = TestConstraintSystem::<Bls12>::new();
+    let nums = alloc_domains::<H, _>(&mut cs, &elements);
+    let out = H::Function::hash_md_circuit(&mut cs.namespace(|| "hash_md_circuit"), &nums)
+        .expect("hash_md_circuit failure");
+
+    assert_circuit_value::<H>(
+        &cs,
+        &out,
+        H::Function::hash_md(&elements),
+        "hash_md_circuit",
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::{blake2s::Blake2sHasher, poseidon::PoseidonHasher, sha256::Sha256Hasher};
+
+    const TEST_SEED: [u8; 16] = [
+        0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06, 0xbc,
+        0xe5,
+    ];
+
+    #[test]
+    fn test_sha256_conformance() {
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+        check_hasher::<Sha256Hasher, _>(rng);
+    }
+
+    #[test]
+    fn test_blake2s_conformance() {
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+        check_hasher::<Blake2sHasher, _>(rng);
+    }
+
+    #[test]
+    fn test_poseidon_conformance() {
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+        check_hasher::<PoseidonHasher, _>(rng);
+        check_rejects_non_field_bytes::<PoseidonHasher>();
+        check_hash_md_circuit::<PoseidonHasher, _>(rng, 71);
+    }
+}
@@ -3,6 +3,8 @@
 
 #[cfg(feature = "blake2s")]
 pub mod blake2s;
+#[cfg(all(feature = "poseidon", any(test, feature = "test-utils")))]
+pub mod conformance;
 #[cfg(feature = "poseidon")]
 pub mod poseidon;
 #[cfg(feature = "poseidon")]
@@ -140,6 +140,7 @@ impl Domain for PoseidonDomain {
         );
         let mut res: FrRepr = Default::default();
         res.read_le(raw)?;
+        ensure!(res < Fr::char(), "bytes are not a valid field element");
 
         Ok(PoseidonDomain(res))
     }
@@ -0,0 +1,288 @@
+//! Portable JSON representations of verifying keys, Groth16 proofs and public inputs, for use by
+//! verifiers which do not link against this crate.
+//!
+//! All field elements are encoded as `0x`-prefixed, big-endian hex strings of their canonical
+//! (non-Montgomery) value:
+//!
+//! * `Fr` elements (public inputs) are 32 bytes.
+//! * G1 points are `[x, y]`, with each coordinate 48 bytes.
+//! * G2 points are `[[x.c0, x.c1], [y.c0, y.c1]]`, with each coordinate 48 bytes, where `c0` is
+//!   the real and `c1` the imaginary part of the `Fq2` element.
+//! * The point at infinity is encoded with all coordinates zero.
+//!
+//! Points are validated to be on the curve and in the correct subgroup on import.
+
+use anyhow::{anyhow, ensure, Context, Result};
+use bellperson::{
+    bls::{Bls12, Fr, G1Affine, G2Affine},
+    groth16,
+};
+use fr32::{bytes_into_fr, fr_into_bytes};
+use groupy::{CurveAffine, EncodedPoint};
+use serde::{Deserialize, Serialize};
+
+use crate::constants::SINGLE_PARTITION_PROOF_LEN;
+
+const FQ_BYTES: usize = 48;
+const FR_BYTES: usize = 32;
+
+/// Bit set in the first byte of an uncompressed point encoding for the point at infinity.
+const INFINITY_FLAG: u8 = 0b0100_0000;
+
+/// A G1 point as `[x, y]`.
+pub type G1Json = [String; 2];
+
+/// A G2 point as `[[x.c0, x.c1], [y.c0, y.c1]]`.
+pub type G2Json = [[String; 2]; 2];
+
+/// A Groth16 verifying key. `ic` holds one point per public input, plus one for the constant
+/// term.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyingKeyJson {
+    pub alpha_g1: G1Json,
+    pub beta_g1: G1Json,
+    pub beta_g2: G2Json,
+    pub gamma_g2: G2Json,
+    pub delta_g1: G1Json,
+    pub delta_g2: G2Json,
+    pub ic: Vec<G1Json>,
+}
+
+/// A single Groth16 proof, i.e. the proof of one partition.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofJson {
+    pub a: G1Json,
+    pub b: G2Json,
+    pub c: G1Json,
+}
+
+pub fn export_verifying_key(vk: &groth16::VerifyingKey<Bls12>) -> VerifyingKeyJson {
+    VerifyingKeyJson {
+        alpha_g1: g1_to_json(&vk.alpha_g1),
+        beta_g1: g1_to_json(&vk.beta_g1),
+        beta_g2: g2_to_json(&vk.beta_g2),
+        gamma_g2: g2_to_json(&vk.gamma_g2),
+        delta_g1: g1_to_json(&vk.delta_g1),
+        delta_g2: g2_to_json(&vk.delta_g2),
+        ic: vk.ic.iter().map(g1_to_json).collect(),
+    }
+}
+
+pub fn import_verifying_key(json: &VerifyingKeyJson) -> Result<groth16::VerifyingKey<Bls12>> {
+    Ok(groth16::VerifyingKey {
+        alpha_g1: g1_from_json(&json.alpha_g1).context("invalid alpha_g1")?,
+        beta_g1: g1_from_json(&json.beta_g1).context("invalid beta_g1")?,
+        beta_g2: g2_from_json(&json.beta_g2).context("invalid beta_g2")?,
+        gamma_g2: g2_from_json(&json.gamma_g2).context("invalid gamma_g2")?,
+        delta_g1: g1_from_json(&json.delta_g1).context("invalid delta_g1")?,
+        delta_g2: g2_from_json(&json.delta_g2).context("invalid delta_g2")?,
+        ic: json
+            .ic
+            .iter()
+            .enumerate()
+            .map(|(i, point)| g1_from_json(point).with_context(|| format!("invalid ic[{}]", i)))
+            .collect::<Result<_>>()?,
+    })
+}
+
+/// Exports the proofs of each partition from proof bytes as returned by `seal_commit_phase2`,
+/// `generate_winning_post` or `generate_window_post`.
+pub fn export_proofs(proof_bytes: &[u8]) -> Result<Vec<ProofJson>> {
+    ensure!(
+        proof_bytes.len() % SINGLE_PARTITION_PROOF_LEN == 0,
+        "invalid proof length: {}",
+        proof_bytes.len()
+    );
+
+    let proofs = groth16::Proof::<Bls12>::read_many(
+        proof_bytes,
+        proof_bytes.len() / SINGLE_PARTITION_PROOF_LEN,
+    )?;
+
+    Ok(proofs
+        .iter()
+        .map(|proof| ProofJson {
+            a: g1_to_json(&proof.a),
+            b: g2_to_json(&proof.b),
+            c: g1_to_json(&proof.c),
+        })
+        .collect())
+}
+
+/// Imports the proofs of each partition into proof bytes as accepted by `verify_seal`,
+/// `verify_winning_post` or `verify_window_post`.
+pub fn import_proofs(proofs: &[ProofJson]) -> Result<Vec<u8>> {
+    let mut proof_bytes = Vec::with_capacity(proofs.len() * SINGLE_PARTITION_PROOF_LEN);
+    for (i, proof) in proofs.iter().enumerate() {
+        let proof = groth16::Proof::<Bls12> {
+            a: g1_from_json(&proof.a).with_context(|| format!("invalid a of proof {}", i))?,
+            b: g2_from_json(&proof.b).with_context(|| format!("invalid b of proof {}", i))?,
+            c: g1_from_json(&proof.c).with_context(|| format!("invalid c of proof {}", i))?,
+        };
+        proof.write(&mut proof_bytes)?;
+    }
+
+    Ok(proof_bytes)
+}
+
+/// Exports the public inputs of each partition, as returned by `seal_public_inputs`,
+/// `winning_post_public_inputs` or `window_post_public_inputs`.
+pub fn export_public_inputs(inputs: &[Vec<Fr>]) -> Vec<Vec<String>> {
+    inputs
+        .iter()
+        .map(|partition| partition.iter().map(fr_to_hex).collect())
+        .collect()
+}
+
+pub fn import_public_inputs(inputs: &[Vec<String>]) -> Result<Vec<Vec<Fr>>> {
+    inputs
+        .iter()
+        .map(|partition| partition.iter().map(|input| fr_from_hex(input)).collect())
+        .collect()
+}
+
+fn fr_to_hex(fr: &Fr) -> String {
+    let mut bytes = fr_into_bytes(fr);
+    bytes.reverse();
+    format!("0x{}", hex::encode(bytes))
+}
+
+fn fr_from_hex(s: &str) -> Result<Fr> {
+    let mut bytes = decode_hex(s, FR_BYTES)?;
+    bytes.reverse();
+    bytes_into_fr(&bytes).with_context(|| format!("invalid field element: {}", s))
+}
+
+fn decode_hex(s: &str, len: usize) -> Result<Vec<u8>> {
+    let bytes = hex::decode(s.strip_prefix("0x").unwrap_or(s))
+        .with_context(|| format!("invalid hex: {}", s))?;
+    ensure!(
+        bytes.len() == len,
+        "expected {} bytes of hex, got {}: {}",
+        len,
+        bytes.len(),
+        s
+    );
+
+    Ok(bytes)
+}
+
+fn encode_coordinates(encoded: &[u8]) -> Vec<String> {
+    let mut bytes = encoded.to_vec();
+    if bytes[0] & INFINITY_FLAG != 0 {
+        bytes.iter_mut().for_each(|b| *b = 0);
+    }
+    bytes
+        .chunks(FQ_BYTES)
+        .map(|chunk| format!("0x{}", hex::encode(chunk)))
+        .collect()
+}
+
+fn decode_coordinates(coordinates: &[&String], encoded: &mut [u8]) -> Result<()> {
+    for (coordinate, chunk) in coordinates.iter().zip(encoded.chunks_mut(FQ_BYTES)) {
+        chunk.copy_from_slice(&decode_hex(coordinate, FQ_BYTES)?);
+    }
+    if encoded.iter().all(|b| *b == 0) {
+        encoded[0] = INFINITY_FLAG;
+    }
+
+    Ok(())
+}
+
+// The uncompressed encoding is `x || y` for G1, and `x.c1 || x.c0 || y.c1 || y.c0` for G2.
+
+fn g1_to_json(point: &G1Affine) -> G1Json {
+    let coordinates = encode_coordinates(point.into_uncompressed().as_ref());
+    [coordinates[0].clone(), coordinates[1].clone()]
+}
+
+fn g1_from_json(json: &G1Json) -> Result<G1Affine> {
+    let mut encoded = <G1Affine as CurveAffine>::Uncompressed::empty();
+    decode_coordinates(&[&json[0], &json[1]], encoded.as_mut())?;
+    encoded
+        .into_affine()
+        .map_err(|e| anyhow!("invalid G1 point: {}", e))
+}
+
+fn g2_to_json(point: &G2Affine) -> G2Json {
+    let coordinates = encode_coordinates(point.into_uncompressed().as_ref());
+    [
+        [coordinates[1].clone(), coordinates[0].clone()],
+        [coordinates[3].clone(), coordinates[2].clone()],
+    ]
+}
+
+fn g2_from_json(json: &G2Json) -> Result<G2Affine> {
+    let mut encoded = <G2Affine as CurveAffine>::Uncompressed::empty();
+    decode_coordinates(
+        &[&json[0][1], &json[0][0], &json[1][1], &json[1][0]],
+        encoded.as_mut(),
+    )?;
+    encoded
+        .into_affine()
+        .map_err(|e| anyhow!("invalid G2 point: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ff::Field;
+    use groupy::CurveProjective;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::TEST_SEED;
+
+    #[test]
+    fn test_proof_roundtrip() {
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+        let mut proof_bytes = Vec::new();
+        for _ in 0..2 {
+            let proof = groth16::Proof::<Bls12> {
+                a: <G1Affine as CurveAffine>::Projective::random(rng).into_affine(),
+                b: <G2Affine as CurveAffine>::Projective::random(rng).into_affine(),
+                c: G1Affine::zero(),
+            };
+            proof.write(&mut proof_bytes).expect("write failure");
+        }
+
+        let exported = export_proofs(&proof_bytes).expect("export failure");
+        assert_eq!(exported.len(), 2);
+        assert_eq!(exported[0].c[0], format!("0x{}", "00".repeat(FQ_BYTES)));
+
+        let json = serde_json::to_string(&exported).expect("serialization failure");
+        let imported: Vec<ProofJson> = serde_json::from_str(&json).expect("parse failure");
+        assert_eq!(
+            import_proofs(&imported).expect("import failure"),
+            proof_bytes
+        );
+
+        assert!(export_proofs(&proof_bytes[1..]).is_err());
+    }
+
+    #[test]
+    fn test_public_inputs_roundtrip() {
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+        let inputs = vec![vec![Fr::random(rng), Fr::one()], vec![Fr::zero()]];
+        let exported = export_public_inputs(&inputs);
+        assert_eq!(exported[0][1], format!("0x{}01", "00".repeat(FR_BYTES - 1)));
+        assert_eq!(
+            import_public_inputs(&exported).expect("import failure"),
+            inputs
+        );
+
+        // The modulus itself is not a valid field element.
+        let modulus =
+            "0x73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001".to_string();
+        assert!(import_public_inputs(&[vec![modulus]]).is_err());
+    }
+
+    #[test]
+    fn test_rejects_point_not_on_curve() {
+        let one = format!("0x{}01", "00".repeat(FQ_BYTES - 1));
+        assert!(g1_from_json(&[one.clone(), one]).is_err());
+    }
+}
@@ -11,7 +11,7 @@ use storage_proofs_porep::stacked::StackedDrg;
 
 use crate::{
     constants::DefaultPieceHasher,
-    types::{Commitment, PaddedBytesAmount, PoRepConfig},
+    types::{Commitment, PaddedBytesAmount, PoRepConfig, ProofsContext},
 };
 
 pub fn fauxrep<R: AsRef<Path>, S: AsRef<Path>, Tree: 'static + MerkleTreeTrait>(
@@ -39,6 +39,7 @@ pub fn fauxrep_aux<R: Rng, S: AsRef<Path>, T: AsRef<Path>, Tree: 'static + Merkl
 
     let fake_comm_c = <Tree::Hasher as Hasher>::Domain::random(&mut rng);
     let (comm_r, p_aux) = StackedDrg::<Tree, DefaultPieceHasher>::fake_replicate_phase2(
+        &ProofsContext::default(),
         fake_comm_c,
         out_path,
         &cache_path,
@@ -58,12 +59,57 @@ pub fn fauxrep_aux<R: Rng, S: AsRef<Path>, T: AsRef<Path>, Tree: 'static + Merkl
     Ok(commitment)
 }
 
+/// Same as `fauxrep_aux`, but builds `tree_r_last` over the replica at `replica_path`, which
+/// must hold a sector of valid field elements, instead of replacing it with zeros.
+pub fn fauxrep_with_replica_aux<
+    R: Rng,
+    S: AsRef<Path>,
+    T: AsRef<Path>,
+    Tree: 'static + MerkleTreeTrait,
+>(
+    mut rng: &mut R,
+    porep_config: PoRepConfig,
+    cache_path: S,
+    replica_path: T,
+) -> Result<Commitment> {
+    let sector_bytes = PaddedBytesAmount::from(porep_config).0;
+
+    let fake_comm_c = <Tree::Hasher as Hasher>::Domain::random(&mut rng);
+    let (comm_r, p_aux) =
+        StackedDrg::<Tree, DefaultPieceHasher>::fake_replicate_phase2_with_replica(
+            &ProofsContext::default(),
+            fake_comm_c,
+            replica_path,
+            &cache_path,
+            sector_bytes as usize,
+        )?;
+
+    let p_aux_path = cache_path.as_ref().join(CacheKey::PAux.to_string());
+    let mut f_p_aux = File::create(&p_aux_path)
+        .with_context(|| format!("could not create file p_aux={:?}", p_aux_path))?;
+    let p_aux_bytes = serialize(&p_aux)?;
+    f_p_aux
+        .write_all(&p_aux_bytes)
+        .with_context(|| format!("could not write to file p_aux={:?}", p_aux_path))?;
+
+    let mut commitment = [0u8; 32];
+    commitment[..].copy_from_slice(&comm_r.into_bytes()[..]);
+    Ok(commitment)
+}
+
 pub fn fauxrep2<R: AsRef<Path>, S: AsRef<Path>, Tree: 'static + MerkleTreeTrait>(
     cache_path: R,
     existing_p_aux_path: S,
 ) -> Result<Commitment> {
     let mut rng = thread_rng();
+    fauxrep2_aux::<_, R, S, Tree>(&mut rng, cache_path, existing_p_aux_path)
+}
 
+pub fn fauxrep2_aux<R: Rng, S: AsRef<Path>, T: AsRef<Path>, Tree: 'static + MerkleTreeTrait>(
+    mut rng: &mut R,
+    cache_path: S,
+    existing_p_aux_path: T,
+) -> Result<Commitment> {
     let fake_comm_c = <Tree::Hasher as Hasher>::Domain::random(&mut rng);
 
     let (comm_r, p_aux) =
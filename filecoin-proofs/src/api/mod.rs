@@ -14,12 +14,9 @@ use storage_proofs_core::{
     merkle::get_base_tree_count,
     pieces::generate_piece_commitment_bytes_from_source,
     sector::SectorId,
-    util::default_rows_to_discard,
-};
-use storage_proofs_porep::{
-    stacked::{generate_replica_id, PersistentAux, StackedDrg, TemporaryAux},
-    PoRep,
+    util::default_rows_to_discard_with_context,
 };
+use storage_proofs_porep::stacked::{generate_replica_id, PersistentAux, StackedDrg, TemporaryAux};
 use typenum::Unsigned;
 
 use crate::{
@@ -32,23 +29,33 @@ use crate::{
     pieces::{get_piece_alignment, sum_piece_bytes_with_alignment},
     types::{
         Commitment, MerkleTreeTrait, PaddedBytesAmount, PieceInfo, PoRepConfig,
-        PoRepProofPartitions, ProverId, SealPreCommitPhase1Output, Ticket, UnpaddedByteIndex,
-        UnpaddedBytesAmount,
+        PoRepProofPartitions, ProofsContext, ProverId, SealPreCommitPhase1Output, Ticket,
+        UnpaddedByteIndex, UnpaddedBytesAmount,
     },
 };
 
+mod export;
 mod fake_seal;
 mod post_util;
+mod r1cs;
+mod scrub;
 mod seal;
 mod util;
+mod verifying_key;
 mod window_post;
+mod window_post_vanilla;
 mod winning_post;
 
+pub use export::*;
 pub use fake_seal::*;
 pub use post_util::*;
+pub use r1cs::*;
+pub use scrub::*;
 pub use seal::*;
 pub use util::*;
+pub use verifying_key::*;
 pub use window_post::*;
+pub use window_post_vanilla::*;
 pub use winning_post::*;
 
 /// Unseals the sector at `sealed_path` and returns the bytes for a piece
@@ -80,6 +87,40 @@ pub fn get_unsealed_range<T: Into<PathBuf> + AsRef<Path>, Tree: 'static + Merkle
     ticket: Ticket,
     offset: UnpaddedByteIndex,
     num_bytes: UnpaddedBytesAmount,
+) -> Result<UnpaddedBytesAmount> {
+    get_unsealed_range_with_context::<T, Tree>(
+        &ProofsContext::default(),
+        porep_config,
+        cache_path,
+        sealed_path,
+        output_path,
+        prover_id,
+        sector_id,
+        comm_d,
+        ticket,
+        offset,
+        num_bytes,
+    )
+}
+
+/// Same as `get_unsealed_range`, regenerating the labels with the parent cache and SDR settings
+/// of `ctx`.
+#[allow(clippy::too_many_arguments)]
+pub fn get_unsealed_range_with_context<
+    T: Into<PathBuf> + AsRef<Path>,
+    Tree: 'static + MerkleTreeTrait,
+>(
+    ctx: &ProofsContext,
+    porep_config: PoRepConfig,
+    cache_path: T,
+    sealed_path: T,
+    output_path: T,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    comm_d: Commitment,
+    ticket: Ticket,
+    offset: UnpaddedByteIndex,
+    num_bytes: UnpaddedBytesAmount,
 ) -> Result<UnpaddedBytesAmount> {
     info!("get_unsealed_range:start");
 
@@ -91,7 +132,8 @@ pub fn get_unsealed_range<T: Into<PathBuf> + AsRef<Path>, Tree: 'static + Merkle
 
     let buf_f_out = BufWriter::new(f_out);
 
-    let result = unseal_range::<_, _, _, Tree>(
+    let result = unseal_range_with_context::<_, _, _, Tree>(
+        ctx,
         porep_config,
         cache_path,
         f_in,
@@ -127,6 +169,43 @@ pub fn get_unsealed_range<T: Into<PathBuf> + AsRef<Path>, Tree: 'static + Merkle
 /// * `num_bytes` - the number of bytes that we want to read.
 #[allow(clippy::too_many_arguments)]
 pub fn unseal_range<P, R, W, Tree>(
+    porep_config: PoRepConfig,
+    cache_path: P,
+    sealed_sector: R,
+    unsealed_output: W,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    comm_d: Commitment,
+    ticket: Ticket,
+    offset: UnpaddedByteIndex,
+    num_bytes: UnpaddedBytesAmount,
+) -> Result<UnpaddedBytesAmount>
+where
+    P: Into<PathBuf> + AsRef<Path>,
+    R: Read,
+    W: Write,
+    Tree: 'static + MerkleTreeTrait,
+{
+    unseal_range_with_context::<P, R, W, Tree>(
+        &ProofsContext::default(),
+        porep_config,
+        cache_path,
+        sealed_sector,
+        unsealed_output,
+        prover_id,
+        sector_id,
+        comm_d,
+        ticket,
+        offset,
+        num_bytes,
+    )
+}
+
+/// Same as `unseal_range`. The labels are regenerated with the parent cache and SDR settings of
+/// `ctx`, and the data tree config uses its rows to discard.
+#[allow(clippy::too_many_arguments)]
+pub fn unseal_range_with_context<P, R, W, Tree>(
+    ctx: &ProofsContext,
     porep_config: PoRepConfig,
     cache_path: P,
     mut sealed_sector: R,
@@ -168,7 +247,8 @@ where
     let config = StoreConfig::new(
         cache_path.as_ref(),
         CacheKey::CommDTree.to_string(),
-        default_rows_to_discard(
+        default_rows_to_discard_with_context(
+            ctx,
             base_tree_leafs,
             <DefaultBinaryTree as MerkleTreeTrait>::Arity::to_usize(),
         ),
@@ -183,11 +263,17 @@ where
     let offset_padded: PaddedBytesAmount = UnpaddedBytesAmount::from(offset).into();
     let num_bytes_padded: PaddedBytesAmount = num_bytes.into();
 
-    let unsealed_all =
-        StackedDrg::<Tree, DefaultPieceHasher>::extract_all(&pp, &replica_id, &data, Some(config))?;
+    StackedDrg::<Tree, DefaultPieceHasher>::extract_and_invert_transform_layers(
+        ctx,
+        &pp.graph,
+        &pp.layer_challenges,
+        &replica_id,
+        &mut data,
+        config,
+    )?;
     let start: usize = offset_padded.into();
     let end = start + usize::from(num_bytes_padded);
-    let unsealed = &unsealed_all[start..end];
+    let unsealed = &data[start..end];
 
     // If the call to `extract_range` was successful, the `unsealed` vector must
     // have a length which equals `num_bytes_padded`. The byte at its 0-index
@@ -1,5 +1,6 @@
 use std::collections::BTreeMap;
 use std::fs;
+use std::ops::Range;
 use std::path::Path;
 
 use anyhow::{anyhow, ensure, Context, Result};
@@ -7,16 +8,24 @@ use bincode::deserialize;
 use filecoin_hashers::Hasher;
 use log::{info, trace};
 use storage_proofs_core::{
-    cache_key::CacheKey, merkle::MerkleTreeTrait, proof::ProofScheme, sector::SectorId,
+    cache_key::CacheKey,
+    compound_proof::{self, CompoundProof},
+    merkle::MerkleTreeTrait,
+    multi_proof::MultiProof,
+    proof::ProofScheme,
+    sector::SectorId,
+};
+use storage_proofs_post::fallback::{
+    self, generate_leaf_challenge, FallbackPoSt, FallbackPoStCompound, PublicSector, SectorProof,
 };
-use storage_proofs_post::fallback::{self, generate_leaf_challenge, FallbackPoSt, SectorProof};
 
 use crate::{
     api::as_safe_commitment,
+    caches::Bls12PreparedVerifyingKey,
     constants::DefaultPieceHasher,
     types::{
-        ChallengeSeed, FallbackPoStSectorProof, PoStConfig, PrivateReplicaInfo, ProverId,
-        TemporaryAux, VanillaProof,
+        ChallengeSeed, FallbackPoStSectorProof, PoStConfig, PrivateReplicaInfo, ProofsContext,
+        ProverId, PublicReplicaInfo, TemporaryAux, VanillaProof,
     },
     PoStType,
 };
@@ -128,11 +137,29 @@ pub fn generate_single_vanilla_proof<Tree: 'static + MerkleTreeTrait>(
     sector_id: SectorId,
     replica: &PrivateReplicaInfo<Tree>,
     challenges: &[u64],
+) -> Result<FallbackPoStSectorProof<Tree>> {
+    generate_single_vanilla_proof_with_context::<Tree>(
+        &ProofsContext::default(),
+        post_config,
+        sector_id,
+        replica,
+        challenges,
+    )
+}
+
+/// Same as `generate_single_vanilla_proof`, for a replica whose `tree_r_last` was cached with the
+/// rows to discard configured in `ctx`.
+pub fn generate_single_vanilla_proof_with_context<Tree: 'static + MerkleTreeTrait>(
+    ctx: &ProofsContext,
+    post_config: &PoStConfig,
+    sector_id: SectorId,
+    replica: &PrivateReplicaInfo<Tree>,
+    challenges: &[u64],
 ) -> Result<FallbackPoStSectorProof<Tree>> {
     info!("generate_single_vanilla_proof:start: {:?}", sector_id);
 
     let tree = &replica
-        .merkle_tree(post_config.sector_size)
+        .merkle_tree_with_context(ctx, post_config.sector_size)
         .with_context(|| {
             format!(
                 "generate_single_vanilla_proof: merkle_tree failed: {:?}",
@@ -160,12 +187,13 @@ pub fn generate_single_vanilla_proof<Tree: 'static + MerkleTreeTrait>(
     };
 
     let vanilla_proof =
-        fallback::vanilla_proof(sector_id, &priv_inputs, challenges).with_context(|| {
-            format!(
-                "generate_single_vanilla_proof: vanilla_proof failed: {:?}",
-                sector_id
-            )
-        })?;
+        fallback::vanilla_proof_with_context(ctx, sector_id, &priv_inputs, challenges)
+            .with_context(|| {
+                format!(
+                    "generate_single_vanilla_proof: vanilla_proof failed: {:?}",
+                    sector_id
+                )
+            })?;
 
     info!("generate_single_vanilla_proof:finish: {:?}", sector_id);
 
@@ -310,3 +338,139 @@ pub(crate) fn get_partitions_for_window_post(
         None
     }
 }
+
+/// The public inputs and proof of a single PoSt, prepared for batch verification.
+pub(crate) struct BatchPoSt<'a, Tree: MerkleTreeTrait> {
+    randomness: <Tree::Hasher as Hasher>::Domain,
+    prover_id: <Tree::Hasher as Hasher>::Domain,
+    sectors: Vec<PublicSector<<Tree::Hasher as Hasher>::Domain>>,
+    proof: MultiProof<'a>,
+}
+
+impl<'a, Tree: 'static + MerkleTreeTrait> BatchPoSt<'a, Tree> {
+    pub(crate) fn new<'b, I>(
+        randomness: &ChallengeSeed,
+        prover_id: ProverId,
+        sectors: I,
+        partitions: Option<usize>,
+        proof: &[u8],
+        verifying_key: &'a Bls12PreparedVerifyingKey,
+    ) -> Result<Self>
+    where
+        I: IntoIterator<Item = (SectorId, &'b PublicReplicaInfo)>,
+    {
+        let sectors = sectors
+            .into_iter()
+            .map(|(sector_id, replica)| {
+                let comm_r = replica
+                    .safe_comm_r()
+                    .with_context(|| format!("safe_comm_r failed: {:?}", sector_id))?;
+                Ok(PublicSector {
+                    id: sector_id,
+                    comm_r,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // Generating the public inputs of a proof over no sectors fails in the middle of the
+        // batch, so it is rejected here instead.
+        ensure!(!sectors.is_empty(), "no sectors to verify");
+
+        Ok(BatchPoSt {
+            randomness: as_safe_commitment(randomness, "randomness")?,
+            prover_id: as_safe_commitment(&prover_id, "prover_id")?,
+            sectors,
+            proof: MultiProof::new_from_bytes(partitions, proof, verifying_key)?,
+        })
+    }
+}
+
+/// Verifies `posts` in a single batch and returns whether each of them is valid.
+///
+/// A failing batch is split in halves until the invalid proofs are found, so a few invalid
+/// proofs among many valid ones only cost a few additional batches.
+pub(crate) fn batch_verify_posts<Tree: 'static + MerkleTreeTrait>(
+    pub_params: &compound_proof::PublicParams<'_, FallbackPoSt<'_, Tree>>,
+    posts: Vec<BatchPoSt<'_, Tree>>,
+    requirements: &fallback::ChallengeRequirements,
+) -> Result<Vec<bool>> {
+    let (inputs, proofs): (Vec<_>, Vec<_>) = posts
+        .into_iter()
+        .map(|post| ((post.randomness, post.prover_id, post.sectors), post.proof))
+        .unzip();
+    let pub_inputs: Vec<_> = inputs
+        .iter()
+        .map(|(randomness, prover_id, sectors)| fallback::PublicInputs {
+            randomness: *randomness,
+            prover_id: *prover_id,
+            sectors,
+            k: None,
+        })
+        .collect();
+
+    bisect_batch_verify(0..proofs.len(), &|range: Range<usize>| {
+        FallbackPoStCompound::<Tree>::batch_verify(
+            pub_params,
+            &pub_inputs[range.clone()],
+            &proofs[range],
+            requirements,
+        )
+    })
+}
+
+/// Returns whether each item in `range` is valid, where `verify_batch` checks whether all items
+/// in a range are.
+fn bisect_batch_verify<F>(range: Range<usize>, verify_batch: &F) -> Result<Vec<bool>>
+where
+    F: Fn(Range<usize>) -> Result<bool>,
+{
+    if range.start >= range.end {
+        return Ok(Vec::new());
+    }
+    if verify_batch(range.clone())? {
+        return Ok(vec![true; range.len()]);
+    }
+    if range.len() == 1 {
+        return Ok(vec![false]);
+    }
+
+    let mid = range.start + range.len() / 2;
+    let mut valid = bisect_batch_verify(range.start..mid, verify_batch)?;
+    valid.extend(bisect_batch_verify(mid..range.end, verify_batch)?);
+
+    Ok(valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+
+    #[test]
+    fn test_bisect_batch_verify() {
+        let items = [true, true, false, true, true, true, false, true];
+        let batches = Cell::new(0);
+        let verify_batch = |range: Range<usize>| {
+            batches.set(batches.get() + 1);
+            Ok(items[range].iter().all(|valid| *valid))
+        };
+
+        let valid = bisect_batch_verify(0..items.len(), &verify_batch).expect("bisect failure");
+        assert_eq!(valid, items);
+        assert!(batches.get() < 2 * items.len());
+
+        batches.set(0);
+        let valid = bisect_batch_verify(0..4, &|_| {
+            batches.set(batches.get() + 1);
+            Ok(true)
+        })
+        .expect("bisect failure");
+        assert_eq!(valid, vec![true; 4]);
+        assert_eq!(batches.get(), 1);
+
+        assert!(bisect_batch_verify(0..0, &verify_batch)
+            .expect("bisect failure")
+            .is_empty());
+        assert!(bisect_batch_verify(0..2, &|_| Err(anyhow!("broken batch"))).is_err());
+    }
+}
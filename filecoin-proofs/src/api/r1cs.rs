@@ -0,0 +1,151 @@
+use std::io::{Seek, Write};
+
+use anyhow::{ensure, Result};
+use filecoin_hashers::Domain;
+use log::info;
+use storage_proofs_core::{
+    compound_proof::CompoundProof,
+    merkle::MerkleTreeTrait,
+    r1cs::{write_r1cs, write_witness, R1csInfo},
+};
+use storage_proofs_porep::stacked::{self, StackedCircuit, StackedCompound, StackedDrg};
+use storage_proofs_post::fallback::{FallbackPoSt, FallbackPoStCircuit, FallbackPoStCompound};
+
+use crate::{
+    api::as_safe_commitment,
+    constants::{DefaultPieceDomain, DefaultPieceHasher},
+    parameters::{public_params, window_post_public_params, winning_post_public_params},
+    types::{
+        PaddedBytesAmount, PoRepConfig, PoRepProofPartitions, PoStConfig, PoStType,
+        SealCommitPhase1Output,
+    },
+};
+
+/// Writes the constraint system of a single PoRep partition for `porep_config` to `out`, in the
+/// format documented in `storage_proofs_core::r1cs`. Variable and constraint names are written to
+/// `annotations`, if given.
+pub fn write_porep_r1cs<Tree, W, A>(
+    porep_config: PoRepConfig,
+    out: W,
+    annotations: Option<A>,
+) -> Result<R1csInfo>
+where
+    Tree: 'static + MerkleTreeTrait,
+    W: Write + Seek + Send,
+    A: Write + Send,
+{
+    info!("write_porep_r1cs:start");
+
+    let public_params = public_params::<Tree>(
+        PaddedBytesAmount::from(porep_config),
+        usize::from(PoRepProofPartitions::from(porep_config)),
+        porep_config.porep_id,
+        porep_config.api_version,
+    )?;
+
+    let circuit: StackedCircuit<'_, Tree, DefaultPieceHasher> =
+        <StackedCompound<Tree, DefaultPieceHasher> as CompoundProof<
+            StackedDrg<'_, Tree, DefaultPieceHasher>,
+            _,
+        >>::blank_circuit(&public_params);
+    let r1cs_info = write_r1cs(circuit, out, annotations)?;
+
+    info!("write_porep_r1cs:finish: {:?}", r1cs_info);
+    Ok(r1cs_info)
+}
+
+/// Writes the constraint system of a single PoSt partition for `post_config` to `out`, in the
+/// format documented in `storage_proofs_core::r1cs`. Variable and constraint names are written to
+/// `annotations`, if given.
+pub fn write_post_r1cs<Tree, W, A>(
+    post_config: &PoStConfig,
+    out: W,
+    annotations: Option<A>,
+) -> Result<R1csInfo>
+where
+    Tree: 'static + MerkleTreeTrait,
+    W: Write + Seek + Send,
+    A: Write + Send,
+{
+    info!("write_post_r1cs:start");
+
+    let public_params = match post_config.typ {
+        PoStType::Winning => winning_post_public_params::<Tree>(post_config)?,
+        PoStType::Window => window_post_public_params::<Tree>(post_config)?,
+    };
+
+    let circuit: FallbackPoStCircuit<Tree> = <FallbackPoStCompound<Tree> as CompoundProof<
+        FallbackPoSt<'_, Tree>,
+        FallbackPoStCircuit<Tree>,
+    >>::blank_circuit(&public_params);
+    let r1cs_info = write_r1cs(circuit, out, annotations)?;
+
+    info!("write_post_r1cs:finish: {:?}", r1cs_info);
+    Ok(r1cs_info)
+}
+
+/// Writes the witness of PoRep partition `partition` of a sealed sector to `out`, in the format
+/// documented in `storage_proofs_core::r1cs`. Its variables match the constraint system written
+/// by `write_porep_r1cs` for the same `porep_config`.
+pub fn write_seal_witness<Tree, W>(
+    porep_config: PoRepConfig,
+    phase1_output: &SealCommitPhase1Output<Tree>,
+    partition: usize,
+    out: W,
+) -> Result<R1csInfo>
+where
+    Tree: 'static + MerkleTreeTrait,
+    W: Write,
+{
+    info!("write_seal_witness:start");
+
+    let SealCommitPhase1Output {
+        vanilla_proofs,
+        comm_d,
+        comm_r,
+        replica_id,
+        seed,
+        ..
+    } = phase1_output;
+
+    ensure!(
+        partition < vanilla_proofs.len(),
+        "invalid partition {}, sector has {} partitions",
+        partition,
+        vanilla_proofs.len()
+    );
+    ensure!(*comm_d != [0; 32], "Invalid all zero commitment (comm_d)");
+    ensure!(*comm_r != [0; 32], "Invalid all zero commitment (comm_r)");
+
+    let public_inputs = stacked::PublicInputs {
+        replica_id: *replica_id,
+        tau: Some(stacked::Tau {
+            comm_d: DefaultPieceDomain::try_from_bytes(comm_d)?,
+            comm_r: as_safe_commitment(comm_r, "comm_r")?,
+        }),
+        k: None,
+        seed: *seed,
+    };
+
+    let public_params = public_params::<Tree>(
+        PaddedBytesAmount::from(porep_config),
+        usize::from(PoRepProofPartitions::from(porep_config)),
+        porep_config.porep_id,
+        porep_config.api_version,
+    )?;
+
+    let circuit = <StackedCompound<Tree, DefaultPieceHasher> as CompoundProof<
+        StackedDrg<'_, Tree, DefaultPieceHasher>,
+        _,
+    >>::circuit(
+        &public_inputs,
+        Default::default(),
+        &vanilla_proofs[partition],
+        &public_params,
+        Some(partition),
+    )?;
+    let r1cs_info = write_witness(circuit, out)?;
+
+    info!("write_seal_witness:finish: {:?}", r1cs_info);
+    Ok(r1cs_info)
+}
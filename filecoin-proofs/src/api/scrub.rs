@@ -0,0 +1,366 @@
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use anyhow::{ensure, Context, Result};
+use bincode::deserialize;
+use filecoin_hashers::Hasher;
+use fr32::bytes_into_fr;
+use log::{info, trace};
+use merkletree::hash::Algorithm;
+use merkletree::merkle::get_merkle_tree_cache_size;
+use merkletree::store::{ExternalReader, Store, StoreConfig};
+use rand::{seq::index::sample, Rng};
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use storage_proofs_core::{
+    cache_key::CacheKey,
+    merkle::{get_base_tree_count, split_config_and_replica, LCStore, MerkleTreeTrait},
+    util::{default_rows_to_discard_with_context, NODE_SIZE},
+};
+use storage_proofs_porep::stacked::PersistentAux;
+use typenum::Unsigned;
+
+use crate::{
+    api::util::{get_base_tree_leafs, get_base_tree_size},
+    types::{ProofsContext, SectorSize},
+};
+
+/// The number of subtrees read and hashed at once.
+const SCRUB_BATCH_SUBTREES: usize = 1024;
+
+/// A range of replica nodes, from `start` up to but excluding `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// The outcome of scrubbing a replica.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrubReport {
+    /// The number of replica nodes which were checked.
+    pub nodes_checked: u64,
+    /// The nodes whose subtree does not hash to the row stored in the `tree_r_last` cache, in
+    /// ascending order with adjacent ranges merged.
+    pub corrupted_nodes: Vec<NodeRange>,
+    /// Whether the rows stored in the `tree_r_last` cache hash up to the `comm_r_last` of
+    /// `p_aux`. If not, the cache itself is damaged, and corrupted nodes may be false positives.
+    pub cache_consistent: bool,
+}
+
+impl ScrubReport {
+    pub fn is_intact(&self) -> bool {
+        self.corrupted_nodes.is_empty() && self.cache_consistent
+    }
+}
+
+/// Checks every node of a sealed replica against its `tree_r_last`, to detect corruption before
+/// a PoSt challenge hits it.
+///
+/// The replica is streamed and hashed up to the lowest row of `tree_r_last` kept in the cache,
+/// whose nodes are compared with the stored ones. For small sectors, where the cache only keeps
+/// the root, the root is recomputed. A mismatch marks all replica nodes below the stored node as
+/// corrupted.
+pub fn scrub_replica<Tree: 'static + MerkleTreeTrait>(
+    sector_size: SectorSize,
+    cache_path: &Path,
+    replica_path: &Path,
+) -> Result<ScrubReport> {
+    scrub_replica_with_context::<Tree>(
+        &ProofsContext::default(),
+        sector_size,
+        cache_path,
+        replica_path,
+    )
+}
+
+/// Same as `scrub_replica`, for a replica whose `tree_r_last` was cached with the rows to discard
+/// configured in `ctx`.
+pub fn scrub_replica_with_context<Tree: 'static + MerkleTreeTrait>(
+    ctx: &ProofsContext,
+    sector_size: SectorSize,
+    cache_path: &Path,
+    replica_path: &Path,
+) -> Result<ScrubReport> {
+    info!("scrub_replica:start");
+    let result = scrub::<Tree, _>(
+        ctx,
+        sector_size,
+        cache_path,
+        replica_path,
+        |subtree_count| (0..subtree_count).collect(),
+    );
+    info!("scrub_replica:finish");
+
+    result
+}
+
+/// Same as `scrub_replica`, but only checks the nodes below `sample_count` randomly chosen nodes
+/// of the lowest stored row of `tree_r_last`. `sample_count` must be at least 1.
+pub fn scrub_replica_sampled<R: Rng, Tree: 'static + MerkleTreeTrait>(
+    rng: &mut R,
+    sector_size: SectorSize,
+    cache_path: &Path,
+    replica_path: &Path,
+    sample_count: usize,
+) -> Result<ScrubReport> {
+    scrub_replica_sampled_with_context::<R, Tree>(
+        &ProofsContext::default(),
+        rng,
+        sector_size,
+        cache_path,
+        replica_path,
+        sample_count,
+    )
+}
+
+/// Same as `scrub_replica_sampled`. `ctx` has to configure the rows to discard the replica was
+/// sealed with.
+pub fn scrub_replica_sampled_with_context<R: Rng, Tree: 'static + MerkleTreeTrait>(
+    ctx: &ProofsContext,
+    rng: &mut R,
+    sector_size: SectorSize,
+    cache_path: &Path,
+    replica_path: &Path,
+    sample_count: usize,
+) -> Result<ScrubReport> {
+    info!("scrub_replica_sampled:start");
+    ensure!(sample_count > 0, "sample_count must be at least 1");
+
+    let result = scrub::<Tree, _>(
+        ctx,
+        sector_size,
+        cache_path,
+        replica_path,
+        |subtree_count| {
+            let mut subtrees =
+                sample(rng, subtree_count, sample_count.min(subtree_count)).into_vec();
+            subtrees.sort_unstable();
+            subtrees
+        },
+    );
+    info!("scrub_replica_sampled:finish");
+
+    result
+}
+
+/// Hashes `row` up to a single node, `height` being the height of `row` in the tree.
+fn hash_rows<H: Hasher>(mut row: Vec<H::Domain>, arity: usize, mut height: usize) -> H::Domain {
+    let mut hasher = H::Function::default();
+    while row.len() > 1 {
+        row = row
+            .chunks(arity)
+            .map(|children| {
+                hasher.reset();
+                hasher.multi_node(children, height)
+            })
+            .collect();
+        height += 1;
+    }
+
+    row[0]
+}
+
+/// The root of the subtree over `leaves`, or `None` if a leaf is not a valid field element.
+fn subtree_root<H: Hasher>(leaves: &[u8], arity: usize) -> Option<H::Domain> {
+    let row = leaves
+        .chunks(NODE_SIZE)
+        .map(|leaf| bytes_into_fr(leaf).ok().map(H::Domain::from))
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(hash_rows::<H>(row, arity, 0))
+}
+
+/// Combines the roots of the base trees into the root of the whole tree.
+fn tree_root<Tree: MerkleTreeTrait>(
+    base_roots: Vec<<Tree::Hasher as Hasher>::Domain>,
+) -> <Tree::Hasher as Hasher>::Domain {
+    let mut hasher = <Tree::Hasher as Hasher>::Function::default();
+    let mut hash = |children: &[<Tree::Hasher as Hasher>::Domain]| {
+        hasher.reset();
+        hasher.multi_node(children, 0)
+    };
+
+    let sub_arity = Tree::SubTreeArity::to_usize();
+    let top_arity = Tree::TopTreeArity::to_usize();
+    if sub_arity == 0 {
+        base_roots[0]
+    } else if top_arity == 0 {
+        hash(&base_roots)
+    } else {
+        let sub_roots: Vec<_> = base_roots.chunks(sub_arity).map(&mut hash).collect();
+        hash(&sub_roots)
+    }
+}
+
+/// Merges adjacent ranges of `ranges`, which must be sorted.
+fn merge_ranges(ranges: Vec<NodeRange>) -> Vec<NodeRange> {
+    let mut merged: Vec<NodeRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if last.end == range.start => last.end = range.end,
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
+
+/// Scrubs the subtrees returned by `select_subtrees`, which is given the number of subtrees and
+/// must return a sorted selection of them.
+fn scrub<Tree: 'static + MerkleTreeTrait, F: FnOnce(usize) -> Vec<usize>>(
+    ctx: &ProofsContext,
+    sector_size: SectorSize,
+    cache_path: &Path,
+    replica_path: &Path,
+    select_subtrees: F,
+) -> Result<ScrubReport> {
+    let arity = Tree::Arity::to_usize();
+    let base_tree_size = get_base_tree_size::<Tree>(sector_size)?;
+    let base_tree_leafs = get_base_tree_leafs::<Tree>(base_tree_size)?;
+    let tree_count = get_base_tree_count::<Tree>();
+    let rows_to_discard = default_rows_to_discard_with_context(ctx, base_tree_leafs, arity);
+
+    let replica_len = fs::metadata(replica_path)
+        .with_context(|| format!("could not read replica {:?}", replica_path))?
+        .len();
+    ensure!(
+        replica_len == u64::from(sector_size),
+        "replica {:?} has {} bytes instead of {}",
+        replica_path,
+        replica_len,
+        u64::from(sector_size)
+    );
+
+    let p_aux: PersistentAux<<Tree::Hasher as Hasher>::Domain> = {
+        let p_aux_path = cache_path.join(CacheKey::PAux.to_string());
+        let p_aux_bytes = fs::read(&p_aux_path)
+            .with_context(|| format!("could not read file p_aux={:?}", p_aux_path))?;
+
+        deserialize(&p_aux_bytes)
+    }?;
+
+    let mut config = StoreConfig::new(
+        cache_path,
+        CacheKey::CommRLastTree.to_string(),
+        rows_to_discard,
+    );
+    config.size = Some(base_tree_size);
+    let (configs, replica_config) = split_config_and_replica(
+        config,
+        replica_path.to_path_buf(),
+        base_tree_leafs,
+        tree_count,
+    )?;
+
+    // The lowest stored row follows the leaves and the discarded rows.
+    let subtree_leafs = arity.pow(rows_to_discard as u32 + 1);
+    let stored_row_len = base_tree_leafs / subtree_leafs;
+    let stored_row_start: usize = (0..=rows_to_discard)
+        .map(|row| base_tree_leafs / arity.pow(row as u32))
+        .sum();
+    // Trees built with other settings keep a different number of rows.
+    let cache_size = get_merkle_tree_cache_size(base_tree_leafs, arity, rows_to_discard)?;
+    for config in &configs {
+        let path = StoreConfig::data_path(&config.path, &config.id);
+        let len = fs::metadata(&path)
+            .with_context(|| format!("could not read tree_r_last {:?}", path))?
+            .len();
+        ensure!(
+            len == (cache_size * NODE_SIZE) as u64,
+            "tree_r_last {:?} has {} bytes instead of {}, it was not built with {} rows to discard",
+            path,
+            len,
+            cache_size * NODE_SIZE,
+            rows_to_discard
+        );
+    }
+    trace!(
+        "scrub: {} base trees of {} leafs, comparing subtrees of {} leafs",
+        tree_count,
+        base_tree_leafs,
+        subtree_leafs
+    );
+
+    let mut stored_rows = Vec::with_capacity(tree_count);
+    let mut base_roots = Vec::with_capacity(tree_count);
+    let mut cache_consistent = true;
+    for (i, config) in configs.iter().enumerate() {
+        let store = LCStore::<<Tree::Hasher as Hasher>::Domain>::new_from_disk_with_reader(
+            base_tree_size,
+            arity,
+            config,
+            ExternalReader::new_from_config(&replica_config, i)?,
+        )
+        .with_context(|| format!("could not open tree_r_last {}/{}", i + 1, tree_count))?;
+
+        let stored_row = store.read_range(stored_row_start..stored_row_start + stored_row_len)?;
+        let stored_root = store.last()?;
+        cache_consistent &=
+            hash_rows::<Tree::Hasher>(stored_row.clone(), arity, rows_to_discard + 1)
+                == stored_root;
+
+        stored_rows.push(stored_row);
+        base_roots.push(stored_root);
+    }
+    cache_consistent &= tree_root::<Tree>(base_roots) == p_aux.comm_r_last;
+
+    let subtrees = select_subtrees(tree_count * stored_row_len);
+
+    let subtree_bytes = subtree_leafs * NODE_SIZE;
+    let mut replica = BufReader::new(
+        File::open(replica_path)
+            .with_context(|| format!("could not open replica {:?}", replica_path))?,
+    );
+    let mut position = 0;
+    let mut corrupted_nodes = Vec::new();
+    for batch in subtrees.chunks(SCRUB_BATCH_SUBTREES) {
+        let mut leaves = Vec::with_capacity(batch.len());
+        for &subtree in batch {
+            let offset = (subtree * subtree_bytes) as u64;
+            if offset != position {
+                replica.seek(SeekFrom::Start(offset))?;
+            }
+            let mut buf = vec![0u8; subtree_bytes];
+            replica.read_exact(&mut buf)?;
+            position = offset + subtree_bytes as u64;
+            leaves.push((subtree, buf));
+        }
+
+        let mut corrupted: Vec<NodeRange> = leaves
+            .par_iter()
+            .filter(|(subtree, buf)| {
+                let stored = stored_rows[subtree / stored_row_len][subtree % stored_row_len];
+                subtree_root::<Tree::Hasher>(buf, arity) != Some(stored)
+            })
+            .map(|(subtree, _)| NodeRange {
+                start: (subtree * subtree_leafs) as u64,
+                end: ((subtree + 1) * subtree_leafs) as u64,
+            })
+            .collect();
+        corrupted_nodes.append(&mut corrupted);
+    }
+
+    Ok(ScrubReport {
+        nodes_checked: (subtrees.len() * subtree_leafs) as u64,
+        corrupted_nodes: merge_ranges(corrupted_nodes),
+        cache_consistent,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_ranges() {
+        let range = |start, end| NodeRange { start, end };
+
+        assert_eq!(merge_ranges(vec![]), vec![]);
+        assert_eq!(
+            merge_ranges(vec![range(0, 8), range(8, 16), range(24, 32)]),
+            vec![range(0, 16), range(24, 32)]
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use std::fs::{self, metadata, File, OpenOptions};
-use std::io::Write;
+use std::io::{ErrorKind, Write};
 use std::path::{Path, PathBuf};
 
 use anyhow::{ensure, Context, Result};
@@ -28,17 +28,20 @@ use storage_proofs_porep::stacked::{
 
 use crate::{
     api::{as_safe_commitment, commitment_from_fr, get_base_tree_leafs, get_base_tree_size},
-    caches::{get_stacked_params, get_stacked_verifying_key},
+    caches::{
+        get_stacked_params_with_context, get_stacked_verifying_key,
+        get_stacked_verifying_key_with_context, Bls12PreparedVerifyingKey,
+    },
     constants::{
-        DefaultBinaryTree, DefaultPieceDomain, DefaultPieceHasher, POREP_MINIMUM_CHALLENGES,
-        SINGLE_PARTITION_PROOF_LEN,
+        DefaultBinaryTree, DefaultPieceDomain, DefaultPieceHasher, SINGLE_PARTITION_PROOF_LEN,
     },
     parameters::setup_params,
     pieces::{self, verify_pieces},
+    registry::sector_shape,
     types::{
-        Commitment, PaddedBytesAmount, PieceInfo, PoRepConfig, PoRepProofPartitions, ProverId,
-        SealCommitOutput, SealCommitPhase1Output, SealPreCommitOutput, SealPreCommitPhase1Output,
-        SectorSize, Ticket, BINARY_ARITY,
+        Commitment, PaddedBytesAmount, PieceInfo, PoRepConfig, PoRepProofPartitions, ProofsContext,
+        ProverId, SealCommitOutput, SealCommitPhase1Output, SealPreCommitOutput,
+        SealPreCommitPhase1Output, SectorSize, Ticket, BINARY_ARITY,
     },
 };
 
@@ -53,6 +56,38 @@ pub fn seal_pre_commit_phase1<R, S, T, Tree: 'static + MerkleTreeTrait>(
     ticket: Ticket,
     piece_infos: &[PieceInfo],
 ) -> Result<SealPreCommitPhase1Output<Tree>>
+where
+    R: AsRef<Path>,
+    S: AsRef<Path>,
+    T: AsRef<Path>,
+{
+    seal_pre_commit_phase1_with_context::<R, S, T, Tree>(
+        &ProofsContext::default(),
+        porep_config,
+        cache_path,
+        in_path,
+        out_path,
+        prover_id,
+        sector_id,
+        ticket,
+        piece_infos,
+    )
+}
+
+/// Same as `seal_pre_commit_phase1`, labeling with the parent cache and multicore SDR settings
+/// of `ctx`.
+#[allow(clippy::too_many_arguments)]
+pub fn seal_pre_commit_phase1_with_context<R, S, T, Tree: 'static + MerkleTreeTrait>(
+    ctx: &ProofsContext,
+    porep_config: PoRepConfig,
+    cache_path: R,
+    in_path: S,
+    out_path: T,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    piece_infos: &[PieceInfo],
+) -> Result<SealPreCommitPhase1Output<Tree>>
 where
     R: AsRef<Path>,
     S: AsRef<Path>,
@@ -176,6 +211,7 @@ where
     );
 
     let labels = StackedDrg::<Tree, DefaultPieceHasher>::replicate_phase1(
+        ctx,
         &compound_public_params.vanilla_params,
         &replica_id,
         config.clone(),
@@ -198,6 +234,29 @@ pub fn seal_pre_commit_phase2<R, S, Tree: 'static + MerkleTreeTrait>(
     cache_path: S,
     replica_path: R,
 ) -> Result<SealPreCommitOutput>
+where
+    R: AsRef<Path>,
+    S: AsRef<Path>,
+{
+    seal_pre_commit_phase2_with_context::<R, S, Tree>(
+        &ProofsContext::default(),
+        porep_config,
+        phase1_output,
+        cache_path,
+        replica_path,
+    )
+}
+
+/// Same as `seal_pre_commit_phase2`, building the trees with the GPU and batch size settings of
+/// `ctx` and caching them with its rows to discard.
+#[allow(clippy::too_many_arguments)]
+pub fn seal_pre_commit_phase2_with_context<R, S, Tree: 'static + MerkleTreeTrait>(
+    ctx: &ProofsContext,
+    porep_config: PoRepConfig,
+    phase1_output: SealPreCommitPhase1Output<Tree>,
+    cache_path: S,
+    replica_path: R,
+) -> Result<SealPreCommitOutput>
 where
     R: AsRef<Path>,
     S: AsRef<Path>,
@@ -282,6 +341,7 @@ where
     >>::setup(&compound_setup_params)?;
 
     let (tau, (p_aux, t_aux)) = StackedDrg::<Tree, DefaultPieceHasher>::replicate_phase2(
+        ctx,
         &compound_public_params.vanilla_params,
         labels,
         data,
@@ -315,6 +375,118 @@ where
     Ok(out)
 }
 
+/// Re-seals a sector from its unsealed data, e.g. to repair a corrupted replica, and checks that
+/// the result matches the commitments of the original seal.
+///
+/// Runs both pre-commit phases into `replica_path` and `cache_path`, which must be an empty
+/// directory. Sealing is deterministic, so with the original prover id, sector id, ticket and
+/// pieces, the recomputed `comm_d` and `comm_r` equal the on-chain values. If they do not, an
+/// error is returned, and the replica and cache must not be used.
+///
+/// # Arguments
+///
+/// * `porep_config` - porep configuration containing the sector size.
+/// * `cache_path` - an empty directory the sector's Merkle Trees are written to.
+/// * `unsealed_path` - the unsealed sector data.
+/// * `replica_path` - the file the new replica is written to, which must not exist or be empty.
+/// * `prover_id` - the prover-id that sealed the sector.
+/// * `sector_id` - the sector-id of the sealed sector.
+/// * `ticket` - the ticket that was used to generate the sector's replica-id.
+/// * `piece_infos` - the pieces of the sector.
+/// * `expected_comm_d` - the commitment to the sector's data of the original seal.
+/// * `expected_comm_r` - the commitment to the replica of the original seal.
+#[allow(clippy::too_many_arguments)]
+pub fn reseal_sector<R, S, T, Tree: 'static + MerkleTreeTrait>(
+    porep_config: PoRepConfig,
+    cache_path: R,
+    unsealed_path: S,
+    replica_path: T,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    piece_infos: &[PieceInfo],
+    expected_comm_d: Commitment,
+    expected_comm_r: Commitment,
+) -> Result<SealPreCommitOutput>
+where
+    R: AsRef<Path>,
+    S: AsRef<Path>,
+    T: AsRef<Path>,
+{
+    info!("reseal_sector:start: {:?}", sector_id);
+
+    ensure!(
+        fs::read_dir(cache_path.as_ref())
+            .with_context(|| format!(
+                "could not read cache_path={:?}",
+                cache_path.as_ref().display()
+            ))?
+            .next()
+            .is_none(),
+        "cache_path={:?} must be an empty directory",
+        cache_path.as_ref().display()
+    );
+    // Never overwrite a replica, like the corrupted one being repaired.
+    match metadata(replica_path.as_ref()) {
+        Ok(metadata) => ensure!(
+            metadata.is_file() && metadata.len() == 0,
+            "replica_path={:?} must not exist or be an empty file",
+            replica_path.as_ref().display()
+        ),
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => {
+            return Err(err).with_context(|| {
+                format!(
+                    "could not read replica_path={:?}",
+                    replica_path.as_ref().display()
+                )
+            })
+        }
+    }
+
+    // Catch a mismatch of the pieces before spending the time on labeling.
+    let comm_d = compute_comm_d(porep_config.sector_size, piece_infos)?;
+    ensure!(
+        comm_d == expected_comm_d,
+        "pieces have comm_d {}, but the sector has comm_d {}",
+        hex::encode(&comm_d),
+        hex::encode(&expected_comm_d)
+    );
+
+    let phase1_output = seal_pre_commit_phase1::<_, _, _, Tree>(
+        porep_config,
+        cache_path.as_ref(),
+        unsealed_path,
+        replica_path.as_ref(),
+        prover_id,
+        sector_id,
+        ticket,
+        piece_infos,
+    )?;
+    ensure!(
+        phase1_output.comm_d == expected_comm_d,
+        "unsealed data has comm_d {}, but the sector has comm_d {}",
+        hex::encode(&phase1_output.comm_d),
+        hex::encode(&expected_comm_d)
+    );
+
+    let out = seal_pre_commit_phase2::<_, _, Tree>(
+        porep_config,
+        phase1_output,
+        cache_path,
+        replica_path,
+    )?;
+    ensure!(
+        out.comm_r == expected_comm_r,
+        "resealed replica has comm_r {}, but the sector has comm_r {}",
+        hex::encode(&out.comm_r),
+        hex::encode(&expected_comm_r)
+    );
+
+    info!("reseal_sector:finish: {:?}", sector_id);
+    Ok(out)
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn seal_commit_phase1<T: AsRef<Path>, Tree: 'static + MerkleTreeTrait>(
     porep_config: PoRepConfig,
@@ -326,6 +498,38 @@ pub fn seal_commit_phase1<T: AsRef<Path>, Tree: 'static + MerkleTreeTrait>(
     seed: Ticket,
     pre_commit: SealPreCommitOutput,
     piece_infos: &[PieceInfo],
+) -> Result<SealCommitPhase1Output<Tree>> {
+    seal_commit_phase1_with_context::<T, Tree>(
+        &ProofsContext::default(),
+        porep_config,
+        cache_path,
+        replica_path,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        pre_commit,
+        piece_infos,
+    )
+}
+
+/// Same as `seal_commit_phase1`.
+///
+/// The trees are reopened from the configs stored in the cache, so no setting of `ctx` currently
+/// changes the output of this phase. It is accepted so that a sector can be sealed with one
+/// context throughout.
+#[allow(clippy::too_many_arguments)]
+pub fn seal_commit_phase1_with_context<T: AsRef<Path>, Tree: 'static + MerkleTreeTrait>(
+    _ctx: &ProofsContext,
+    porep_config: PoRepConfig,
+    cache_path: T,
+    replica_path: T,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    pre_commit: SealPreCommitOutput,
+    piece_infos: &[PieceInfo],
 ) -> Result<SealCommitPhase1Output<Tree>> {
     info!("seal_commit_phase1:start: {:?}", sector_id);
 
@@ -449,6 +653,24 @@ pub fn seal_commit_phase2<Tree: 'static + MerkleTreeTrait>(
     phase1_output: SealCommitPhase1Output<Tree>,
     prover_id: ProverId,
     sector_id: SectorId,
+) -> Result<SealCommitOutput> {
+    seal_commit_phase2_with_context::<Tree>(
+        &ProofsContext::default(),
+        porep_config,
+        phase1_output,
+        prover_id,
+        sector_id,
+    )
+}
+
+/// Same as `seal_commit_phase2`, loading the Groth parameters and verifying key from the
+/// parameter cache of `ctx`.
+pub fn seal_commit_phase2_with_context<Tree: 'static + MerkleTreeTrait>(
+    ctx: &ProofsContext,
+    porep_config: PoRepConfig,
+    phase1_output: SealCommitPhase1Output<Tree>,
+    prover_id: ProverId,
+    sector_id: SectorId,
 ) -> Result<SealCommitOutput> {
     info!("seal_commit_phase2:start: {:?}", sector_id);
 
@@ -477,7 +699,7 @@ pub fn seal_commit_phase2<Tree: 'static + MerkleTreeTrait>(
         seed,
     };
 
-    let groth_params = get_stacked_params::<Tree>(porep_config)?;
+    let groth_params = get_stacked_params_with_context::<Tree>(ctx, porep_config)?;
 
     info!(
         "got groth params ({}) while sealing",
@@ -520,8 +742,10 @@ pub fn seal_commit_phase2<Tree: 'static + MerkleTreeTrait>(
 
     // Verification is cheap when parameters are cached,
     // and it is never correct to return a proof which does not verify.
-    verify_seal::<Tree>(
+    let verifying_key = get_stacked_verifying_key_with_context::<Tree>(ctx, porep_config)?;
+    verify_seal_with_vk::<Tree>(
         porep_config,
+        &verifying_key,
         comm_r,
         comm_d,
         prover_id,
@@ -576,70 +800,65 @@ pub fn verify_seal<Tree: 'static + MerkleTreeTrait>(
     seed: Ticket,
     proof_vec: &[u8],
 ) -> Result<bool> {
-    info!("verify_seal:start: {:?}", sector_id);
-    ensure!(comm_d_in != [0; 32], "Invalid all zero commitment (comm_d)");
-    ensure!(comm_r_in != [0; 32], "Invalid all zero commitment (comm_r)");
-
-    let comm_r: <Tree::Hasher as Hasher>::Domain = as_safe_commitment(&comm_r_in, "comm_r")?;
-    let comm_d: DefaultPieceDomain = as_safe_commitment(&comm_d_in, "comm_d")?;
-
-    let replica_id = generate_replica_id::<Tree::Hasher, _>(
-        &prover_id,
-        sector_id.into(),
-        &ticket,
-        comm_d,
-        &porep_config.porep_id,
+    let verifying_key = get_stacked_verifying_key::<Tree>(porep_config)?;
+    info!(
+        "got verifying key ({}) while verifying seal",
+        u64::from(PaddedBytesAmount::from(porep_config))
     );
 
-    let compound_setup_params = compound_proof::SetupParams {
-        vanilla_params: setup_params(
-            PaddedBytesAmount::from(porep_config),
-            usize::from(PoRepProofPartitions::from(porep_config)),
-            porep_config.porep_id,
-            porep_config.api_version,
-        )?,
-        partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
-        priority: false,
-    };
+    verify_seal_with_vk::<Tree>(
+        porep_config,
+        &verifying_key,
+        comm_r_in,
+        comm_d_in,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        proof_vec,
+    )
+}
 
-    let compound_public_params: compound_proof::PublicParams<
-        '_,
-        StackedDrg<'_, Tree, DefaultPieceHasher>,
-    > = StackedCompound::setup(&compound_setup_params)?;
+/// Verifies the output of some previously-run seal operation against the provided verifying key,
+/// rather than the one in the parameter cache. See `read_porep_verifying_key` for loading it.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_seal_with_vk<Tree: 'static + MerkleTreeTrait>(
+    porep_config: PoRepConfig,
+    verifying_key: &Bls12PreparedVerifyingKey,
+    comm_r_in: Commitment,
+    comm_d_in: Commitment,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+    proof_vec: &[u8],
+) -> Result<bool> {
+    info!("verify_seal:start: {:?}", sector_id);
 
-    let public_inputs =
-        stacked::PublicInputs::<<Tree::Hasher as Hasher>::Domain, DefaultPieceDomain> {
-            replica_id,
-            tau: Some(Tau { comm_r, comm_d }),
-            seed,
-            k: None,
-        };
+    let statement = SealStatement::<Tree>::new(
+        porep_config,
+        comm_r_in,
+        comm_d_in,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+    )?;
 
     let result = {
-        let sector_bytes = PaddedBytesAmount::from(porep_config);
-        let verifying_key = get_stacked_verifying_key::<Tree>(porep_config)?;
-
-        info!(
-            "got verifying key ({}) while verifying seal",
-            u64::from(sector_bytes)
-        );
-
         let proof = MultiProof::new_from_reader(
             Some(usize::from(PoRepProofPartitions::from(porep_config))),
             proof_vec,
-            &verifying_key,
+            verifying_key,
         )?;
 
         StackedCompound::verify(
-            &compound_public_params,
-            &public_inputs,
+            &statement.compound_public_params,
+            &statement.public_inputs,
             &proof,
             &ChallengeRequirements {
-                minimum_challenges: *POREP_MINIMUM_CHALLENGES
-                    .read()
-                    .expect("POREP_MINIMUM_CHALLENGES poisoned")
-                    .get(&u64::from(SectorSize::from(porep_config)))
-                    .expect("unknown sector size") as usize,
+                minimum_challenges: sector_shape(u64::from(SectorSize::from(porep_config)))?
+                    .porep_minimum_challenges as usize,
             },
         )
     };
@@ -648,6 +867,110 @@ pub fn verify_seal<Tree: 'static + MerkleTreeTrait>(
     result
 }
 
+/// Returns the public inputs of the Groth16 proof of each partition of a seal, in the order of the
+/// proofs within `proof_vec` as accepted by `verify_seal`. Together with the verifying key, this
+/// is everything needed to verify the proofs outside of this crate. See `export_public_inputs`.
+///
+/// # Arguments
+///
+/// * `porep_config` - this sector's porep config that contains the number of bytes in this sector.
+/// * `comm_r_in` - commitment to the sector's replica (`comm_r`).
+/// * `comm_d_in` - commitment to the sector's data (`comm_d`).
+/// * `prover_id` - the prover-id that sealed this sector.
+/// * `sector_id` - this sector's sector-id.
+/// * `ticket` - the ticket that was used to generate this sector's replica-id.
+/// * `seed` - the seed used to derive the porep challenges.
+pub fn seal_public_inputs<Tree: 'static + MerkleTreeTrait>(
+    porep_config: PoRepConfig,
+    comm_r_in: Commitment,
+    comm_d_in: Commitment,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: Ticket,
+    seed: Ticket,
+) -> Result<Vec<Vec<Fr>>> {
+    let statement = SealStatement::<Tree>::new(
+        porep_config,
+        comm_r_in,
+        comm_d_in,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+    )?;
+
+    (0..usize::from(PoRepProofPartitions::from(porep_config)))
+        .map(|k| {
+            StackedCompound::<Tree, DefaultPieceHasher>::generate_public_inputs(
+                &statement.public_inputs,
+                &statement.compound_public_params.vanilla_params,
+                Some(k),
+            )
+        })
+        .collect()
+}
+
+/// The public parameters and inputs of the seal proof of one sector.
+struct SealStatement<'a, Tree: 'static + MerkleTreeTrait> {
+    compound_public_params:
+        compound_proof::PublicParams<'a, StackedDrg<'a, Tree, DefaultPieceHasher>>,
+    public_inputs: stacked::PublicInputs<<Tree::Hasher as Hasher>::Domain, DefaultPieceDomain>,
+}
+
+impl<'a, Tree: 'static + MerkleTreeTrait> SealStatement<'a, Tree> {
+    fn new(
+        porep_config: PoRepConfig,
+        comm_r_in: Commitment,
+        comm_d_in: Commitment,
+        prover_id: ProverId,
+        sector_id: SectorId,
+        ticket: Ticket,
+        seed: Ticket,
+    ) -> Result<Self> {
+        ensure!(comm_d_in != [0; 32], "Invalid all zero commitment (comm_d)");
+        ensure!(comm_r_in != [0; 32], "Invalid all zero commitment (comm_r)");
+
+        let comm_r: <Tree::Hasher as Hasher>::Domain = as_safe_commitment(&comm_r_in, "comm_r")?;
+        let comm_d: DefaultPieceDomain = as_safe_commitment(&comm_d_in, "comm_d")?;
+
+        let replica_id = generate_replica_id::<Tree::Hasher, _>(
+            &prover_id,
+            sector_id.into(),
+            &ticket,
+            comm_d,
+            &porep_config.porep_id,
+        );
+
+        let compound_setup_params = compound_proof::SetupParams {
+            vanilla_params: setup_params(
+                PaddedBytesAmount::from(porep_config),
+                usize::from(PoRepProofPartitions::from(porep_config)),
+                porep_config.porep_id,
+                porep_config.api_version,
+            )?,
+            partitions: Some(usize::from(PoRepProofPartitions::from(porep_config))),
+            priority: false,
+        };
+
+        let compound_public_params: compound_proof::PublicParams<
+            '_,
+            StackedDrg<'_, Tree, DefaultPieceHasher>,
+        > = StackedCompound::setup(&compound_setup_params)?;
+
+        let public_inputs = stacked::PublicInputs {
+            replica_id,
+            tau: Some(Tau { comm_r, comm_d }),
+            seed,
+            k: None,
+        };
+
+        Ok(SealStatement {
+            compound_public_params,
+            public_inputs,
+        })
+    }
+}
+
 /// Verifies a batch of outputs of some previously-run seal operations.
 ///
 /// # Arguments
@@ -670,6 +993,39 @@ pub fn verify_batch_seal<Tree: 'static + MerkleTreeTrait>(
     tickets: &[Ticket],
     seeds: &[Ticket],
     proof_vecs: &[&[u8]],
+) -> Result<bool> {
+    let verifying_key = get_stacked_verifying_key::<Tree>(porep_config)?;
+    info!(
+        "got verifying key ({}) while verifying seal",
+        u64::from(PaddedBytesAmount::from(porep_config))
+    );
+
+    verify_batch_seal_with_vk::<Tree>(
+        porep_config,
+        &verifying_key,
+        comm_r_ins,
+        comm_d_ins,
+        prover_ids,
+        sector_ids,
+        tickets,
+        seeds,
+        proof_vecs,
+    )
+}
+
+/// Verifies a batch of outputs of some previously-run seal operations against the provided
+/// verifying key, rather than the one in the parameter cache.
+#[allow(clippy::too_many_arguments)]
+pub fn verify_batch_seal_with_vk<Tree: 'static + MerkleTreeTrait>(
+    porep_config: PoRepConfig,
+    verifying_key: &Bls12PreparedVerifyingKey,
+    comm_r_ins: &[Commitment],
+    comm_d_ins: &[Commitment],
+    prover_ids: &[ProverId],
+    sector_ids: &[SectorId],
+    tickets: &[Ticket],
+    seeds: &[Ticket],
+    proof_vecs: &[&[u8]],
 ) -> Result<bool> {
     info!("verify_batch_seal:start");
     ensure!(!comm_r_ins.is_empty(), "Cannot prove empty batch");
@@ -695,14 +1051,6 @@ pub fn verify_batch_seal<Tree: 'static + MerkleTreeTrait>(
         );
     }
 
-    let sector_bytes = PaddedBytesAmount::from(porep_config);
-
-    let verifying_key = get_stacked_verifying_key::<Tree>(porep_config)?;
-    info!(
-        "got verifying key ({}) while verifying seal",
-        u64::from(sector_bytes)
-    );
-
     let compound_setup_params = compound_proof::SetupParams {
         vanilla_params: setup_params(
             PaddedBytesAmount::from(porep_config),
@@ -746,7 +1094,7 @@ pub fn verify_batch_seal<Tree: 'static + MerkleTreeTrait>(
         proofs.push(MultiProof::new_from_reader(
             Some(usize::from(PoRepProofPartitions::from(porep_config))),
             proof_vecs[i],
-            &verifying_key,
+            verifying_key,
         )?);
     }
 
@@ -755,11 +1103,8 @@ pub fn verify_batch_seal<Tree: 'static + MerkleTreeTrait>(
         &public_inputs,
         &proofs,
         &ChallengeRequirements {
-            minimum_challenges: *POREP_MINIMUM_CHALLENGES
-                .read()
-                .expect("POREP_MINIMUM_CHALLENGES poisoned")
-                .get(&u64::from(SectorSize::from(porep_config)))
-                .expect("unknown sector size") as usize,
+            minimum_challenges: sector_shape(u64::from(SectorSize::from(porep_config)))?
+                .porep_minimum_challenges as usize,
         },
     )
     .map_err(Into::into);
@@ -0,0 +1,74 @@
+use anyhow::{ensure, Context, Result};
+use bellperson::{bls::Bls12, groth16};
+use log::info;
+use storage_proofs_core::{
+    merkle::MerkleTreeTrait,
+    parameter_cache::{get_verifying_key_data, verifying_key_id},
+};
+
+use crate::{
+    param::get_digest_for_bytes,
+    types::{PoRepConfig, PoStConfig},
+};
+
+pub use crate::caches::Bls12PreparedVerifyingKey;
+pub use bellperson::groth16::prepare_verifying_key;
+
+pub type Bls12VerifyingKey = groth16::VerifyingKey<Bls12>;
+
+/// Reads a verifying key in the format of a `.vk` file, without checking it against
+/// `parameters.json`.
+pub fn read_verifying_key(bytes: &[u8]) -> Result<Bls12VerifyingKey> {
+    groth16::VerifyingKey::read(bytes).context("failed to read verifying key")
+}
+
+/// Reads and prepares the contents of the `.vk` file for `porep_config`, for use with
+/// `verify_seal_with_vk` and `verify_batch_seal_with_vk`.
+///
+/// Fails unless the digest of `bytes` matches the one recorded in `parameters.json`.
+pub fn read_porep_verifying_key<Tree: 'static + MerkleTreeTrait>(
+    porep_config: PoRepConfig,
+    bytes: &[u8],
+) -> Result<Bls12PreparedVerifyingKey> {
+    let cache_id = porep_config.get_cache_identifier::<Tree>()?;
+    read_production_verifying_key(&cache_id, bytes)
+}
+
+/// Reads and prepares the contents of the `.vk` file for `post_config`, for use with
+/// `verify_winning_post_with_vk` and `verify_window_post_with_vk`.
+///
+/// Fails unless the digest of `bytes` matches the one recorded in `parameters.json`.
+pub fn read_post_verifying_key<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    bytes: &[u8],
+) -> Result<Bls12PreparedVerifyingKey> {
+    let cache_id = post_config.get_cache_identifier::<Tree>()?;
+    read_production_verifying_key(&cache_id, bytes)
+}
+
+fn read_production_verifying_key(
+    cache_id: &str,
+    bytes: &[u8],
+) -> Result<Bls12PreparedVerifyingKey> {
+    let filename = verifying_key_id(cache_id);
+    let data = get_verifying_key_data(cache_id).with_context(|| {
+        format!(
+            "verifying key is not listed in parameters.json: {}",
+            filename
+        )
+    })?;
+
+    let digest = get_digest_for_bytes(bytes);
+    ensure!(
+        digest == data.digest,
+        "invalid verifying key {}: expected digest {}, got {}",
+        filename,
+        data.digest,
+        digest
+    );
+    info!("verified digest of verifying key {}", filename);
+
+    let vk = read_verifying_key(bytes)?;
+
+    Ok(prepare_verifying_key(&vk))
+}
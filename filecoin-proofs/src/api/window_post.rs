@@ -1,8 +1,13 @@
 use std::collections::BTreeMap;
 
-use anyhow::{ensure, Context, Result};
+use anyhow::{anyhow, ensure, Context, Result};
+use bellperson::{
+    bls::Fr,
+    groth16::{create_random_proof_batch, create_random_proof_batch_in_priority},
+};
 use filecoin_hashers::Hasher;
-use log::info;
+use log::{info, warn};
+use rand::rngs::OsRng;
 use storage_proofs_core::{
     compound_proof::{self, CompoundProof},
     merkle::MerkleTreeTrait,
@@ -14,12 +19,19 @@ use storage_proofs_post::fallback::{
 };
 
 use crate::{
-    api::{as_safe_commitment, get_partitions_for_window_post, partition_vanilla_proofs},
-    caches::{get_post_params, get_post_verifying_key},
+    api::{
+        as_safe_commitment, batch_verify_posts, get_partitions_for_window_post,
+        partition_vanilla_proofs, BatchPoSt,
+    },
+    caches::{
+        get_post_params, get_post_params_with_context, get_post_verifying_key,
+        Bls12PreparedVerifyingKey,
+    },
+    constants::SINGLE_PARTITION_PROOF_LEN,
     parameters::window_post_setup_params,
     types::{
-        ChallengeSeed, FallbackPoStSectorProof, PoStConfig, PrivateReplicaInfo, ProverId,
-        PublicReplicaInfo, SnarkProof,
+        ChallengeSeed, FallbackPoStSectorProof, PoStConfig, PrivateReplicaInfo, ProofsContext,
+        ProverId, PublicReplicaInfo, SnarkProof,
     },
     PoStType,
 };
@@ -95,12 +107,200 @@ pub fn generate_window_post_with_vanilla<Tree: 'static + MerkleTreeTrait>(
     Ok(proof.to_vec()?)
 }
 
+/// Generates the Groth16 proof of partition `partition_index` of a Window proof-of-spacetime over
+/// all sectors in `replicas`, from the vanilla proofs of the sectors in that partition. This lets
+/// the partitions of a deadline be proven on different machines. See
+/// `aggregate_window_post_partition_proofs` for combining the results.
+pub fn generate_window_post_partition_with_vanilla<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    prover_id: ProverId,
+    replicas: &BTreeMap<SectorId, PublicReplicaInfo>,
+    partition_index: usize,
+    vanilla_proofs: &[FallbackPoStSectorProof<Tree>],
+) -> Result<SnarkProof> {
+    info!(
+        "generate_window_post_partition_with_vanilla:start: {}",
+        partition_index
+    );
+    ensure!(
+        post_config.typ == PoStType::Window,
+        "invalid post config type"
+    );
+    ensure!(!replicas.is_empty(), "no replicas provided");
+
+    let randomness_safe = as_safe_commitment(randomness, "randomness")?;
+    let prover_id_safe = as_safe_commitment(&prover_id, "prover_id")?;
+
+    let vanilla_params = window_post_setup_params(&post_config);
+    let partitions = get_partitions_for_window_post(replicas.len(), &post_config);
+    let partition_count = partitions.unwrap_or(1);
+    ensure!(
+        partition_index < partition_count,
+        "invalid partition {}, proof has {} partitions",
+        partition_index,
+        partition_count
+    );
+
+    let setup_params = compound_proof::SetupParams {
+        vanilla_params,
+        partitions,
+        priority: post_config.priority,
+    };
+    let pub_params: compound_proof::PublicParams<'_, FallbackPoSt<'_, Tree>> =
+        FallbackPoStCompound::setup(&setup_params)?;
+    let groth_params = get_post_params::<Tree>(&post_config)?;
+
+    let pub_sectors: Vec<_> = replicas
+        .iter()
+        .map(|(sector_id, replica)| {
+            let comm_r = replica.safe_comm_r().with_context(|| {
+                format!(
+                    "generate_window_post_partition_with_vanilla: safe_comm_r failed: {:?}",
+                    sector_id
+                )
+            })?;
+            Ok(PublicSector {
+                id: *sector_id,
+                comm_r,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    let num_sectors_per_chunk = pub_params.vanilla_params.sector_count;
+    let partition_sectors = pub_sectors
+        .chunks(num_sectors_per_chunk)
+        .nth(partition_index)
+        .expect("partition index was checked");
+    ensure!(
+        vanilla_proofs.len() == partition_sectors.len(),
+        "partition {} has {} sectors, but {} vanilla proofs were provided",
+        partition_index,
+        partition_sectors.len(),
+        vanilla_proofs.len()
+    );
+
+    let mut sector_proofs = Vec::with_capacity(num_sectors_per_chunk);
+    for pub_sector in partition_sectors {
+        let vanilla_proof = vanilla_proofs
+            .iter()
+            .find(|proof| proof.sector_id == pub_sector.id)
+            .ok_or_else(|| anyhow!("missing vanilla proof for {:?}", pub_sector.id))?;
+        ensure!(
+            vanilla_proof.comm_r == pub_sector.comm_r,
+            "vanilla proof for {:?} does not match its comm_r",
+            pub_sector.id
+        );
+        sector_proofs.extend(vanilla_proof.vanilla_proof.sectors.clone());
+    }
+
+    // Pad the partition by duplicating the last sector, as in `partition_vanilla_proofs`.
+    while sector_proofs.len() < num_sectors_per_chunk {
+        sector_proofs.push(sector_proofs[sector_proofs.len() - 1].clone());
+    }
+
+    let pub_inputs = fallback::PublicInputs {
+        randomness: randomness_safe,
+        prover_id: prover_id_safe,
+        sectors: &pub_sectors,
+        k: None,
+    };
+
+    let circuit = FallbackPoStCompound::<Tree>::circuit(
+        &pub_inputs,
+        Default::default(),
+        &fallback::Proof {
+            sectors: sector_proofs,
+        },
+        &pub_params.vanilla_params,
+        Some(partition_index),
+    )?;
+
+    let groth_proofs = if post_config.priority {
+        create_random_proof_batch_in_priority(vec![circuit], &*groth_params, &mut OsRng)?
+    } else {
+        create_random_proof_batch(vec![circuit], &*groth_params, &mut OsRng)?
+    };
+
+    let mut proof = Vec::with_capacity(SINGLE_PARTITION_PROOF_LEN);
+    for groth_proof in &groth_proofs {
+        groth_proof.write(&mut proof)?;
+    }
+
+    info!(
+        "generate_window_post_partition_with_vanilla:finish: {}",
+        partition_index
+    );
+
+    Ok(proof)
+}
+
+/// Concatenates the proofs of all partitions of a Window proof-of-spacetime, as returned by
+/// `generate_window_post_partition_with_vanilla` and ordered by partition index, into the proof
+/// accepted by `verify_window_post`. Fails unless the resulting proof verifies.
+pub fn aggregate_window_post_partition_proofs<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &BTreeMap<SectorId, PublicReplicaInfo>,
+    prover_id: ProverId,
+    partition_proofs: &[SnarkProof],
+) -> Result<SnarkProof> {
+    info!("aggregate_window_post_partition_proofs:start");
+    ensure!(!replicas.is_empty(), "no replicas provided");
+
+    let partition_count = get_partitions_for_window_post(replicas.len(), &post_config).unwrap_or(1);
+    ensure!(
+        partition_proofs.len() == partition_count,
+        "expected {} partition proofs, got {}",
+        partition_count,
+        partition_proofs.len()
+    );
+
+    let mut proof = Vec::with_capacity(partition_count * SINGLE_PARTITION_PROOF_LEN);
+    for (k, partition_proof) in partition_proofs.iter().enumerate() {
+        ensure!(
+            partition_proof.len() == SINGLE_PARTITION_PROOF_LEN,
+            "invalid length of partition proof {}: {}",
+            k,
+            partition_proof.len()
+        );
+        proof.extend_from_slice(partition_proof);
+    }
+
+    ensure!(
+        verify_window_post::<Tree>(post_config, randomness, replicas, prover_id, &proof)?,
+        "aggregated window post proof failed to verify"
+    );
+
+    info!("aggregate_window_post_partition_proofs:finish");
+
+    Ok(proof)
+}
+
 /// Generates a Window proof-of-spacetime.
 pub fn generate_window_post<Tree: 'static + MerkleTreeTrait>(
     post_config: &PoStConfig,
     randomness: &ChallengeSeed,
     replicas: &BTreeMap<SectorId, PrivateReplicaInfo<Tree>>,
     prover_id: ProverId,
+) -> Result<SnarkProof> {
+    generate_window_post_with_context::<Tree>(
+        &ProofsContext::default(),
+        post_config,
+        randomness,
+        replicas,
+        prover_id,
+    )
+}
+
+/// Same as `generate_window_post`, loading the Groth parameters from the parameter cache of `ctx`
+/// and opening trees cached with its rows to discard.
+pub fn generate_window_post_with_context<Tree: 'static + MerkleTreeTrait>(
+    ctx: &ProofsContext,
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &BTreeMap<SectorId, PrivateReplicaInfo<Tree>>,
+    prover_id: ProverId,
 ) -> Result<SnarkProof> {
     info!("generate_window_post:start");
     ensure!(
@@ -123,13 +323,13 @@ pub fn generate_window_post<Tree: 'static + MerkleTreeTrait>(
 
     let pub_params: compound_proof::PublicParams<'_, FallbackPoSt<'_, Tree>> =
         FallbackPoStCompound::setup(&setup_params)?;
-    let groth_params = get_post_params::<Tree>(&post_config)?;
+    let groth_params = get_post_params_with_context::<Tree>(ctx, &post_config)?;
 
     let trees: Vec<_> = replicas
         .iter()
         .map(|(sector_id, replica)| {
             replica
-                .merkle_tree(post_config.sector_size)
+                .merkle_tree_with_context(ctx, post_config.sector_size)
                 .with_context(|| {
                     format!("generate_window_post: merkle_tree failed: {:?}", sector_id)
                 })
@@ -168,69 +368,163 @@ pub fn generate_window_post<Tree: 'static + MerkleTreeTrait>(
         sectors: &priv_sectors,
     };
 
-    let proof = FallbackPoStCompound::prove(&pub_params, &pub_inputs, &priv_inputs, &groth_params)?;
+    let proof = FallbackPoStCompound::prove_with_context(
+        ctx,
+        &pub_params,
+        &pub_inputs,
+        &priv_inputs,
+        &groth_params,
+    )?;
 
     info!("generate_window_post:finish");
 
     Ok(proof.to_vec()?)
 }
 
-/// Verifies a window proof-of-spacetime.
-pub fn verify_window_post<Tree: 'static + MerkleTreeTrait>(
+/// Returns the public inputs of the Groth16 proof of each partition of a window
+/// proof-of-spacetime, in the order of the proofs within `proof` as accepted by
+/// `verify_window_post`. See `export_public_inputs`.
+pub fn window_post_public_inputs<Tree: 'static + MerkleTreeTrait>(
     post_config: &PoStConfig,
     randomness: &ChallengeSeed,
     replicas: &BTreeMap<SectorId, PublicReplicaInfo>,
     prover_id: ProverId,
-    proof: &[u8],
-) -> Result<bool> {
-    info!("verify_window_post:start");
-
-    ensure!(
-        post_config.typ == PoStType::Window,
-        "invalid post config type"
-    );
-
-    let randomness_safe = as_safe_commitment(randomness, "randomness")?;
-    let prover_id_safe = as_safe_commitment(&prover_id, "prover_id")?;
+) -> Result<Vec<Vec<Fr>>> {
+    let statement = WindowPoStStatement::<Tree>::new(
+        post_config,
+        randomness,
+        replicas,
+        prover_id,
+        "window_post_public_inputs",
+    )?;
 
-    let vanilla_params = window_post_setup_params(&post_config);
-    let partitions = get_partitions_for_window_post(replicas.len(), &post_config);
+    (0..statement.pub_params.partitions.unwrap_or(1))
+        .map(|k| {
+            FallbackPoStCompound::<Tree>::generate_public_inputs(
+                &statement.pub_inputs(),
+                &statement.pub_params.vanilla_params,
+                Some(k),
+            )
+        })
+        .collect()
+}
 
-    let setup_params = compound_proof::SetupParams {
-        vanilla_params,
-        partitions,
-        priority: false,
-    };
-    let pub_params: compound_proof::PublicParams<'_, FallbackPoSt<'_, Tree>> =
-        FallbackPoStCompound::setup(&setup_params)?;
+/// The public parameters and sectors of a window proof-of-spacetime.
+struct WindowPoStStatement<'a, Tree: 'static + MerkleTreeTrait> {
+    pub_params: compound_proof::PublicParams<'a, FallbackPoSt<'a, Tree>>,
+    randomness: <Tree::Hasher as Hasher>::Domain,
+    prover_id: <Tree::Hasher as Hasher>::Domain,
+    pub_sectors: Vec<PublicSector<<Tree::Hasher as Hasher>::Domain>>,
+}
 
-    let pub_sectors: Vec<_> = replicas
-        .iter()
-        .map(|(sector_id, replica)| {
-            let comm_r = replica.safe_comm_r().with_context(|| {
-                format!("verify_window_post: safe_comm_r failed: {:?}", sector_id)
-            })?;
-            Ok(PublicSector {
-                id: *sector_id,
-                comm_r,
+impl<'a, Tree: 'static + MerkleTreeTrait> WindowPoStStatement<'a, Tree> {
+    fn new(
+        post_config: &PoStConfig,
+        randomness: &ChallengeSeed,
+        replicas: &BTreeMap<SectorId, PublicReplicaInfo>,
+        prover_id: ProverId,
+        caller: &str,
+    ) -> Result<Self> {
+        ensure!(
+            post_config.typ == PoStType::Window,
+            "invalid post config type"
+        );
+
+        let randomness_safe = as_safe_commitment(randomness, "randomness")?;
+        let prover_id_safe = as_safe_commitment(&prover_id, "prover_id")?;
+
+        let vanilla_params = window_post_setup_params(&post_config);
+        let partitions = get_partitions_for_window_post(replicas.len(), &post_config);
+
+        let setup_params = compound_proof::SetupParams {
+            vanilla_params,
+            partitions,
+            priority: false,
+        };
+        let pub_params: compound_proof::PublicParams<'_, FallbackPoSt<'_, Tree>> =
+            FallbackPoStCompound::setup(&setup_params)?;
+
+        let pub_sectors: Vec<_> = replicas
+            .iter()
+            .map(|(sector_id, replica)| {
+                let comm_r = replica
+                    .safe_comm_r()
+                    .with_context(|| format!("{}: safe_comm_r failed: {:?}", caller, sector_id))?;
+                Ok(PublicSector {
+                    id: *sector_id,
+                    comm_r,
+                })
             })
+            .collect::<Result<_>>()?;
+
+        Ok(WindowPoStStatement {
+            pub_params,
+            randomness: randomness_safe,
+            prover_id: prover_id_safe,
+            pub_sectors,
         })
-        .collect::<Result<_>>()?;
+    }
 
-    let pub_inputs = fallback::PublicInputs {
-        randomness: randomness_safe,
-        prover_id: prover_id_safe,
-        sectors: &pub_sectors,
-        k: None,
-    };
+    fn pub_inputs(&self) -> fallback::PublicInputs<'_, <Tree::Hasher as Hasher>::Domain> {
+        fallback::PublicInputs {
+            randomness: self.randomness,
+            prover_id: self.prover_id,
+            sectors: &self.pub_sectors,
+            k: None,
+        }
+    }
+}
+
+/// Verifies a window proof-of-spacetime.
+pub fn verify_window_post<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &BTreeMap<SectorId, PublicReplicaInfo>,
+    prover_id: ProverId,
+    proof: &[u8],
+) -> Result<bool> {
+    let verifying_key = get_post_verifying_key::<Tree>(&post_config)?;
+
+    verify_window_post_with_vk::<Tree>(
+        post_config,
+        &verifying_key,
+        randomness,
+        replicas,
+        prover_id,
+        proof,
+    )
+}
+
+/// Verifies a window proof-of-spacetime against the provided verifying key, rather than the one in
+/// the parameter cache. See `read_post_verifying_key` for loading it.
+pub fn verify_window_post_with_vk<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    verifying_key: &Bls12PreparedVerifyingKey,
+    randomness: &ChallengeSeed,
+    replicas: &BTreeMap<SectorId, PublicReplicaInfo>,
+    prover_id: ProverId,
+    proof: &[u8],
+) -> Result<bool> {
+    info!("verify_window_post:start");
+
+    let statement = WindowPoStStatement::<Tree>::new(
+        post_config,
+        randomness,
+        replicas,
+        prover_id,
+        "verify_window_post",
+    )?;
 
     let is_valid = {
-        let verifying_key = get_post_verifying_key::<Tree>(&post_config)?;
-        let multi_proof = MultiProof::new_from_reader(partitions, &proof[..], &verifying_key)?;
+        let multi_proof = MultiProof::new_from_reader(
+            statement.pub_params.partitions,
+            &proof[..],
+            verifying_key,
+        )?;
 
         FallbackPoStCompound::verify(
-            &pub_params,
-            &pub_inputs,
+            &statement.pub_params,
+            &statement.pub_inputs(),
             &multi_proof,
             &fallback::ChallengeRequirements {
                 minimum_challenge_count: post_config.challenge_count * post_config.sector_count,
@@ -245,3 +539,98 @@ pub fn verify_window_post<Tree: 'static + MerkleTreeTrait>(
 
     Ok(true)
 }
+
+/// The randomness, prover id, sectors and proof of a single window proof-of-spacetime.
+pub type WindowPoStBatchItem<'a> = (
+    ChallengeSeed,
+    ProverId,
+    &'a BTreeMap<SectorId, PublicReplicaInfo>,
+    &'a [u8],
+);
+
+/// Verifies many window proofs-of-spacetime for the same `post_config` at once, and returns
+/// whether each of them is valid.
+///
+/// Items whose inputs are malformed, e.g. a truncated proof, are reported as invalid rather than
+/// failing the whole batch.
+pub fn verify_window_post_batch<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    items: &[WindowPoStBatchItem<'_>],
+) -> Result<Vec<bool>> {
+    let verifying_key = get_post_verifying_key::<Tree>(&post_config)?;
+
+    verify_window_post_batch_with_vk::<Tree>(post_config, &verifying_key, items)
+}
+
+/// Same as `verify_window_post_batch`, against the provided verifying key rather than the one in
+/// the parameter cache.
+pub fn verify_window_post_batch_with_vk<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    verifying_key: &Bls12PreparedVerifyingKey,
+    items: &[WindowPoStBatchItem<'_>],
+) -> Result<Vec<bool>> {
+    info!("verify_window_post_batch:start");
+
+    ensure!(
+        post_config.typ == PoStType::Window,
+        "invalid post config type"
+    );
+    ensure!(!items.is_empty(), "Cannot verify empty batch");
+
+    // Proofs over different numbers of sectors have different numbers of partitions, so they
+    // are verified in separate batches.
+    let mut batches: BTreeMap<Option<usize>, Vec<usize>> = BTreeMap::new();
+    for (i, (_, _, replicas, _)) in items.iter().enumerate() {
+        batches
+            .entry(get_partitions_for_window_post(replicas.len(), &post_config))
+            .or_default()
+            .push(i);
+    }
+
+    let requirements = fallback::ChallengeRequirements {
+        minimum_challenge_count: post_config.challenge_count * post_config.sector_count,
+    };
+
+    let mut valid = vec![false; items.len()];
+    for (partitions, indices) in batches {
+        let setup_params = compound_proof::SetupParams {
+            vanilla_params: window_post_setup_params(&post_config),
+            partitions,
+            priority: false,
+        };
+        let pub_params: compound_proof::PublicParams<'_, FallbackPoSt<'_, Tree>> =
+            FallbackPoStCompound::setup(&setup_params)?;
+
+        let mut batch_indices = Vec::with_capacity(indices.len());
+        let mut posts = Vec::with_capacity(indices.len());
+        for i in indices {
+            let (randomness, prover_id, replicas, proof) = &items[i];
+            let sectors = replicas
+                .iter()
+                .map(|(sector_id, replica)| (*sector_id, replica));
+            match BatchPoSt::<Tree>::new(
+                randomness,
+                *prover_id,
+                sectors,
+                partitions,
+                proof,
+                verifying_key,
+            ) {
+                Ok(post) => {
+                    batch_indices.push(i);
+                    posts.push(post);
+                }
+                Err(err) => warn!("verify_window_post_batch: invalid item {}: {:#}", i, err),
+            }
+        }
+
+        let batch_valid = batch_verify_posts::<Tree>(&pub_params, posts, &requirements)?;
+        for (i, is_valid) in batch_indices.into_iter().zip(batch_valid) {
+            valid[i] = is_valid;
+        }
+    }
+
+    info!("verify_window_post_batch:finish");
+
+    Ok(valid)
+}
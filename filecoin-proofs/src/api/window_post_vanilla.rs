@@ -0,0 +1,464 @@
+use std::cmp::{max, min};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{
+    mpsc::{self, RecvTimeoutError},
+    Arc, Mutex,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{ensure, Context, Result};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use storage_proofs_core::{merkle::MerkleTreeTrait, sector::SectorId};
+
+use crate::{
+    api::{generate_fallback_sector_challenges, generate_single_vanilla_proof_with_context},
+    types::{
+        ChallengeSeed, FallbackPoStSectorProof, PoStConfig, PrivateReplicaInfo, ProofsContext,
+        ProverId,
+    },
+    PoStType,
+};
+
+/// Why a sector could not be proven.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FaultReason {
+    /// Reading the sector did not finish within `window_post_sector_read_timeout_secs`.
+    Timeout,
+    /// Reading another sector from the same device timed out, so the sector was not read.
+    DeviceTimeout,
+    /// Reading the sector or generating its vanilla proof failed.
+    Error(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FaultySector {
+    pub sector_id: SectorId,
+    pub reason: FaultReason,
+}
+
+/// The vanilla proofs of a Window proof-of-spacetime, and the sectors which could not be proven.
+#[derive(Debug)]
+pub struct WindowPoStVanillaProofs<Tree: MerkleTreeTrait> {
+    /// Sorted by sector id.
+    pub vanilla_proofs: Vec<FallbackPoStSectorProof<Tree>>,
+    /// Sorted by sector id.
+    pub faulty_sectors: Vec<FaultySector>,
+}
+
+type Job<Tree> = (SectorId, PrivateReplicaInfo<Tree>, Vec<u64>);
+
+/// The number of threads the devices of the replicas are looked up on.
+const DEVICE_ID_LOOKUP_THREADS: usize = 16;
+
+/// Generates the vanilla proofs of all `replicas` for a Window proof-of-spacetime, for use with
+/// `generate_window_post_with_vanilla`.
+///
+/// Unlike `generate_window_post`, a sector which cannot be read does not fail or stall the whole
+/// proof, but is reported in `faulty_sectors`. Since the challenges depend on the set of proven
+/// sectors, the vanilla proofs must be regenerated without the faulty sectors if there are any.
+pub fn generate_window_post_vanilla_proofs<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &BTreeMap<SectorId, PrivateReplicaInfo<Tree>>,
+    prover_id: ProverId,
+) -> Result<WindowPoStVanillaProofs<Tree>> {
+    generate_window_post_vanilla_proofs_with_context::<Tree>(
+        &ProofsContext::default(),
+        post_config,
+        randomness,
+        replicas,
+        prover_id,
+    )
+}
+
+/// Same as `generate_window_post_vanilla_proofs`, with the read limits and rows to discard
+/// configured in `ctx`.
+///
+/// Sectors are grouped by the device their replica is stored on, and at most
+/// `window_post_max_reads_per_device` sectors of each device are read at a time. A sector whose
+/// vanilla proof is not generated within `window_post_sector_read_timeout_secs` is reported as
+/// faulty. The thread reading it is abandoned, as a blocked read cannot be interrupted, and the
+/// remaining sectors of its device are reported as faulty without being read.
+pub fn generate_window_post_vanilla_proofs_with_context<Tree: 'static + MerkleTreeTrait>(
+    ctx: &ProofsContext,
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &BTreeMap<SectorId, PrivateReplicaInfo<Tree>>,
+    prover_id: ProverId,
+) -> Result<WindowPoStVanillaProofs<Tree>> {
+    info!("generate_window_post_vanilla_proofs:start");
+    ensure!(
+        post_config.typ == PoStType::Window,
+        "invalid post config type"
+    );
+
+    let sector_ids: Vec<SectorId> = replicas.keys().copied().collect();
+    let mut challenges = generate_fallback_sector_challenges::<Tree>(
+        post_config,
+        randomness,
+        &sector_ids,
+        prover_id,
+    )?;
+
+    let settings = ctx.settings();
+    let max_reads_per_device = max(settings.window_post_max_reads_per_device, 1);
+    let timeout = match settings.window_post_sector_read_timeout_secs {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    };
+
+    // Looking up the device touches the disk as well, so it is subject to the timeout. All
+    // lookups share one deadline, so that a hung device doesn't stall them one by one.
+    let mut device_ids = device_ids(
+        replicas
+            .iter()
+            .map(|(sector_id, replica)| (*sector_id, replica.replica_path().to_path_buf()))
+            .collect(),
+        timeout,
+    );
+
+    let mut faulty_sectors = Vec::new();
+    let mut devices: BTreeMap<u64, VecDeque<Job<Tree>>> = BTreeMap::new();
+    for (sector_id, replica) in replicas {
+        let sector_challenges = challenges
+            .remove(sector_id)
+            .with_context(|| format!("missing challenges for {:?}", sector_id))?;
+
+        match device_ids
+            .remove(sector_id)
+            .unwrap_or(Err(FaultReason::Timeout))
+        {
+            Ok(device) => devices.entry(device).or_default().push_back((
+                *sector_id,
+                replica.clone(),
+                sector_challenges,
+            )),
+            Err(reason) => faulty_sectors.push(FaultySector {
+                sector_id: *sector_id,
+                reason,
+            }),
+        }
+    }
+
+    let (results_tx, results_rx) = mpsc::channel();
+    let mut workers = Vec::new();
+    for (device, jobs) in devices {
+        let worker_count = min(max_reads_per_device, jobs.len());
+        info!(
+            "reading {} sectors from device {} with {} workers",
+            jobs.len(),
+            device,
+            worker_count
+        );
+
+        let jobs = Arc::new(Mutex::new(jobs));
+        let mut spawned_count = 0;
+        let mut spawn_error = None;
+        for _ in 0..worker_count {
+            let worker_jobs = jobs.clone();
+            let results_tx = results_tx.clone();
+            let ctx = ctx.clone();
+            let post_config = post_config.clone();
+
+            let spawned = thread::Builder::new()
+                .name(format!("window-post-reader-{}", device))
+                .spawn(move || loop {
+                    // Release the lock before reading.
+                    let job = worker_jobs.lock().expect("jobs poisoned").pop_front();
+                    let (sector_id, replica, challenges) = match job {
+                        Some(job) => job,
+                        None => break,
+                    };
+
+                    let ctx = ctx.clone();
+                    let post_config = post_config.clone();
+                    let result = with_deadline(timeout, move || {
+                        generate_single_vanilla_proof_with_context::<Tree>(
+                            &ctx,
+                            &post_config,
+                            sector_id,
+                            &replica,
+                            &challenges,
+                        )
+                    });
+
+                    // The abandoned read still occupies the device, and further reads from it are
+                    // likely to hang as well, so the remaining sectors of the device are not read.
+                    let timed_out = matches!(result, Err(FaultReason::Timeout));
+                    let skipped: Vec<_> = if timed_out {
+                        warn!("reading sector {:?} timed out, skipping device", sector_id);
+                        worker_jobs
+                            .lock()
+                            .expect("jobs poisoned")
+                            .drain(..)
+                            .collect()
+                    } else {
+                        Vec::new()
+                    };
+
+                    if results_tx.send((sector_id, result)).is_err() {
+                        break;
+                    }
+                    for (sector_id, _, _) in skipped {
+                        if results_tx
+                            .send((sector_id, Err(FaultReason::DeviceTimeout)))
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                    // The abandoned read takes over this worker's share of the device.
+                    if timed_out {
+                        break;
+                    }
+                });
+            match spawned {
+                Ok(worker) => {
+                    workers.push(worker);
+                    spawned_count += 1;
+                }
+                Err(err) => {
+                    warn!("could not spawn reader for device {}: {}", device, err);
+                    spawn_error = Some(err);
+                    break;
+                }
+            }
+        }
+
+        // Without any worker, nobody reads the sectors of the device.
+        if let (0, Some(err)) = (spawned_count, spawn_error) {
+            let reason = FaultReason::Error(format!("could not spawn reader: {}", err));
+            for (sector_id, _, _) in jobs.lock().expect("jobs poisoned").drain(..) {
+                faulty_sectors.push(FaultySector {
+                    sector_id,
+                    reason: reason.clone(),
+                });
+            }
+        }
+    }
+    drop(results_tx);
+
+    let mut vanilla_proofs = Vec::with_capacity(replicas.len());
+    for (sector_id, result) in results_rx {
+        match result {
+            Ok(vanilla_proof) => vanilla_proofs.push(vanilla_proof),
+            Err(reason) => {
+                warn!("sector {:?} is faulty: {:?}", sector_id, reason);
+                faulty_sectors.push(FaultySector { sector_id, reason });
+            }
+        }
+    }
+    for worker in workers {
+        worker.join().expect("window post reader panicked");
+    }
+
+    vanilla_proofs.sort_by_key(|proof| proof.sector_id);
+    faulty_sectors.sort_by_key(|faulty| faulty.sector_id);
+
+    info!(
+        "generate_window_post_vanilla_proofs:finish: {} proven, {} faulty",
+        vanilla_proofs.len(),
+        faulty_sectors.len()
+    );
+
+    Ok(WindowPoStVanillaProofs {
+        vanilla_proofs,
+        faulty_sectors,
+    })
+}
+
+/// Runs `f` on a separate thread and waits for at most `timeout` for its result. Without a
+/// timeout, `f` runs on the calling thread.
+fn with_deadline<T, F>(timeout: Option<Duration>, f: F) -> std::result::Result<T, FaultReason>
+where
+    T: 'static + Send,
+    F: 'static + Send + FnOnce() -> Result<T>,
+{
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return f().map_err(|err| FaultReason::Error(format!("{:#}", err))),
+    };
+
+    let (tx, rx) = mpsc::channel();
+    thread::Builder::new()
+        .name("window-post-read".to_string())
+        .spawn(move || {
+            // The receiver is gone if the deadline passed.
+            let _ = tx.send(f());
+        })
+        .map_err(|err| FaultReason::Error(format!("could not spawn reader: {}", err)))?;
+
+    let result = rx.recv_timeout(timeout).map_err(|err| match err {
+        RecvTimeoutError::Timeout => FaultReason::Timeout,
+        RecvTimeoutError::Disconnected => FaultReason::Error("reader panicked".to_string()),
+    })?;
+
+    result.map_err(|err| FaultReason::Error(format!("{:#}", err)))
+}
+
+/// Looks up the device of each path on at most `DEVICE_ID_LOOKUP_THREADS` threads, waiting for
+/// at most `timeout` for all of them. Paths whose lookup did not finish in time are missing from
+/// the result.
+fn device_ids(
+    paths: Vec<(SectorId, PathBuf)>,
+    timeout: Option<Duration>,
+) -> BTreeMap<SectorId, std::result::Result<u64, FaultReason>> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let count = paths.len();
+    let thread_count = min(DEVICE_ID_LOOKUP_THREADS, count);
+    let paths = Arc::new(Mutex::new(VecDeque::from(paths)));
+
+    let (tx, rx) = mpsc::channel();
+    let mut spawned_count = 0;
+    let mut spawn_error = None;
+    for i in 0..thread_count {
+        let paths = paths.clone();
+        let tx = tx.clone();
+        let spawned = thread::Builder::new()
+            .name(format!("window-post-device-id-{}", i))
+            .spawn(move || loop {
+                let (sector_id, path) = match paths.lock().expect("paths poisoned").pop_front() {
+                    Some(path) => path,
+                    None => break,
+                };
+                // The receiver is gone if the deadline passed.
+                if tx.send((sector_id, device_id(&path))).is_err() {
+                    break;
+                }
+            });
+        match spawned {
+            Ok(_) => spawned_count += 1,
+            Err(err) => {
+                warn!("could not spawn device id lookup: {}", err);
+                spawn_error = Some(err);
+                break;
+            }
+        }
+    }
+    drop(tx);
+
+    let mut device_ids = BTreeMap::new();
+    // Without any thread, none of the paths are looked up.
+    if let (0, Some(err)) = (spawned_count, spawn_error) {
+        let reason = FaultReason::Error(format!("could not spawn device id lookup: {}", err));
+        for (sector_id, _) in paths.lock().expect("paths poisoned").drain(..) {
+            device_ids.insert(sector_id, Err(reason.clone()));
+        }
+        return device_ids;
+    }
+
+    while device_ids.len() < count {
+        let received = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    break;
+                }
+                rx.recv_timeout(deadline - now).ok()
+            }
+            None => rx.recv().ok(),
+        };
+        match received {
+            Some((sector_id, result)) => {
+                let result = result.map_err(|err| FaultReason::Error(format!("{:#}", err)));
+                device_ids.insert(sector_id, result);
+            }
+            None => break,
+        }
+    }
+
+    device_ids
+}
+
+#[cfg(unix)]
+fn device_id(path: &Path) -> Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = std::fs::metadata(path).with_context(|| format!("could not stat {:?}", path))?;
+    Ok(metadata.dev())
+}
+
+/// Without device ids, all sectors are treated as being on the same device.
+#[cfg(not(unix))]
+fn device_id(path: &Path) -> Result<u64> {
+    std::fs::metadata(path).with_context(|| format!("could not stat {:?}", path))?;
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_deadline() {
+        assert_eq!(with_deadline(None, || Ok(1)), Ok(1));
+        assert_eq!(
+            with_deadline(Some(Duration::from_secs(10)), || Ok(1)),
+            Ok(1)
+        );
+
+        let timed_out = with_deadline(Some(Duration::from_millis(10)), || {
+            thread::sleep(Duration::from_secs(1));
+            Ok(1)
+        });
+        assert_eq!(timed_out, Err(FaultReason::Timeout));
+
+        let failed: std::result::Result<(), _> =
+            with_deadline(None, || Err(anyhow::anyhow!("disk error")));
+        assert_eq!(failed, Err(FaultReason::Error("disk error".to_string())));
+    }
+
+    #[test]
+    fn test_device_id() {
+        let dir = tempfile::tempdir().expect("tempdir failure");
+        let a = dir.path().join("a");
+        let b = dir.path().join("b");
+        std::fs::write(&a, b"a").expect("write failure");
+        std::fs::write(&b, b"b").expect("write failure");
+
+        assert_eq!(
+            device_id(&a).expect("device_id failure"),
+            device_id(&b).expect("device_id failure")
+        );
+        assert!(device_id(&dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_device_ids() {
+        let dir = tempfile::tempdir().expect("tempdir failure");
+        let a = dir.path().join("a");
+        std::fs::write(&a, b"a").expect("write failure");
+
+        let paths = vec![
+            (SectorId::from(1), a.clone()),
+            (SectorId::from(2), dir.path().join("missing")),
+        ];
+        let device_ids = device_ids(paths, Some(Duration::from_secs(10)));
+        assert_eq!(device_ids.len(), 2);
+        assert_eq!(
+            device_ids[&SectorId::from(1)],
+            Ok(device_id(&a).expect("device_id failure"))
+        );
+        assert!(matches!(
+            device_ids[&SectorId::from(2)],
+            Err(FaultReason::Error(_))
+        ));
+    }
+
+    #[test]
+    fn test_device_ids_more_paths_than_threads() {
+        let dir = tempfile::tempdir().expect("tempdir failure");
+        let a = dir.path().join("a");
+        std::fs::write(&a, b"a").expect("write failure");
+        let device = device_id(&a).expect("device_id failure");
+
+        let count = 3 * DEVICE_ID_LOOKUP_THREADS as u64;
+        let paths = (0..count).map(|i| (SectorId::from(i), a.clone())).collect();
+        let device_ids = device_ids(paths, Some(Duration::from_secs(10)));
+        assert_eq!(device_ids.len(), count as usize);
+        assert!(device_ids.values().all(|result| *result == Ok(device)));
+    }
+}
@@ -1,6 +1,7 @@
-use anyhow::{ensure, Context, Result};
+use anyhow::{anyhow, ensure, Context, Result};
+use bellperson::bls::Fr;
 use filecoin_hashers::Hasher;
-use log::info;
+use log::{info, warn};
 use storage_proofs_core::{
     compound_proof::{self, CompoundProof},
     merkle::MerkleTreeTrait,
@@ -13,12 +14,15 @@ use storage_proofs_post::fallback::{
 };
 
 use crate::{
-    api::{as_safe_commitment, partition_vanilla_proofs},
-    caches::{get_post_params, get_post_verifying_key},
+    api::{as_safe_commitment, batch_verify_posts, partition_vanilla_proofs, BatchPoSt},
+    caches::{
+        get_post_params, get_post_params_with_context, get_post_verifying_key,
+        Bls12PreparedVerifyingKey,
+    },
     parameters::winning_post_setup_params,
     types::{
         ChallengeSeed, Commitment, FallbackPoStSectorProof, PoStConfig, PrivateReplicaInfo,
-        ProverId, PublicReplicaInfo, SnarkProof,
+        ProofsContext, ProverId, PublicReplicaInfo, SnarkProof,
     },
     PoStType,
 };
@@ -103,6 +107,25 @@ pub fn generate_winning_post<Tree: 'static + MerkleTreeTrait>(
     randomness: &ChallengeSeed,
     replicas: &[(SectorId, PrivateReplicaInfo<Tree>)],
     prover_id: ProverId,
+) -> Result<SnarkProof> {
+    generate_winning_post_with_context::<Tree>(
+        &ProofsContext::default(),
+        post_config,
+        randomness,
+        replicas,
+        prover_id,
+    )
+}
+
+/// Same as `generate_winning_post`, loading the Groth parameters from the parameter cache of
+/// `ctx`. The trees are opened with its rows to discard and kept open between calls if its
+/// tree handle cache is enabled.
+pub fn generate_winning_post_with_context<Tree: 'static + MerkleTreeTrait>(
+    ctx: &ProofsContext,
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &[(SectorId, PrivateReplicaInfo<Tree>)],
+    prover_id: ProverId,
 ) -> Result<SnarkProof> {
     info!("generate_winning_post:start");
     ensure!(
@@ -130,13 +153,15 @@ pub fn generate_winning_post<Tree: 'static + MerkleTreeTrait>(
     };
     let pub_params: compound_proof::PublicParams<'_, FallbackPoSt<'_, Tree>> =
         FallbackPoStCompound::setup(&setup_params)?;
-    let groth_params = get_post_params::<Tree>(&post_config)?;
+    let groth_params = get_post_params_with_context::<Tree>(ctx, &post_config)?;
 
+    // Opening the trees dominates the time of a single sector proof, so they are shared between
+    // proofs if the tree handle cache is enabled.
     let trees = replicas
         .iter()
         .map(|(sector_id, replica)| {
             replica
-                .merkle_tree(post_config.sector_size)
+                .shared_merkle_tree_with_context(ctx, post_config.sector_size)
                 .with_context(|| {
                     format!("generate_winning_post: merkle_tree failed: {:?}", sector_id)
                 })
@@ -159,7 +184,7 @@ pub fn generate_winning_post<Tree: 'static + MerkleTreeTrait>(
                 comm_r,
             });
             priv_sectors.push(PrivateSector {
-                tree,
+                tree: tree.as_ref(),
                 comm_c,
                 comm_r_last,
             });
@@ -177,8 +202,13 @@ pub fn generate_winning_post<Tree: 'static + MerkleTreeTrait>(
         sectors: &priv_sectors,
     };
 
-    let proof =
-        FallbackPoStCompound::<Tree>::prove(&pub_params, &pub_inputs, &priv_inputs, &groth_params)?;
+    let proof = FallbackPoStCompound::<Tree>::prove_with_context(
+        ctx,
+        &pub_params,
+        &pub_inputs,
+        &priv_inputs,
+        &groth_params,
+    )?;
     let proof = proof.to_vec()?;
 
     info!("generate_winning_post:finish");
@@ -220,6 +250,104 @@ pub fn generate_winning_post_sector_challenge<Tree: MerkleTreeTrait>(
     result
 }
 
+/// Returns the public inputs of the single Groth16 proof of a winning proof-of-spacetime, as
+/// accepted by `verify_winning_post`. See `export_public_inputs`.
+pub fn winning_post_public_inputs<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    randomness: &ChallengeSeed,
+    replicas: &[(SectorId, PublicReplicaInfo)],
+    prover_id: ProverId,
+) -> Result<Vec<Vec<Fr>>> {
+    let statement = WinningPoStStatement::<Tree>::new(
+        post_config,
+        randomness,
+        replicas,
+        prover_id,
+        "winning_post_public_inputs",
+    )?;
+
+    let inputs = FallbackPoStCompound::<Tree>::generate_public_inputs(
+        &statement.pub_inputs(),
+        &statement.pub_params.vanilla_params,
+        Some(0),
+    )?;
+
+    Ok(vec![inputs])
+}
+
+/// The public parameters and sectors of a winning proof-of-spacetime, with the challenged sectors
+/// repeated to fill the sector count of the parameters.
+struct WinningPoStStatement<'a, Tree: 'static + MerkleTreeTrait> {
+    pub_params: compound_proof::PublicParams<'a, FallbackPoSt<'a, Tree>>,
+    randomness: <Tree::Hasher as Hasher>::Domain,
+    prover_id: <Tree::Hasher as Hasher>::Domain,
+    pub_sectors: Vec<PublicSector<<Tree::Hasher as Hasher>::Domain>>,
+}
+
+impl<'a, Tree: 'static + MerkleTreeTrait> WinningPoStStatement<'a, Tree> {
+    fn new(
+        post_config: &PoStConfig,
+        randomness: &ChallengeSeed,
+        replicas: &[(SectorId, PublicReplicaInfo)],
+        prover_id: ProverId,
+        caller: &str,
+    ) -> Result<Self> {
+        ensure!(
+            post_config.typ == PoStType::Winning,
+            "invalid post config type"
+        );
+        ensure!(
+            post_config.sector_count == replicas.len(),
+            "invalid amount of replicas provided"
+        );
+
+        let randomness_safe: <Tree::Hasher as Hasher>::Domain =
+            as_safe_commitment(randomness, "randomness")?;
+        let prover_id_safe: <Tree::Hasher as Hasher>::Domain =
+            as_safe_commitment(&prover_id, "prover_id")?;
+
+        let vanilla_params = winning_post_setup_params(&post_config)?;
+        let param_sector_count = vanilla_params.sector_count;
+
+        let setup_params = compound_proof::SetupParams {
+            vanilla_params,
+            partitions: None,
+            priority: false,
+        };
+        let pub_params: compound_proof::PublicParams<'_, FallbackPoSt<'_, Tree>> =
+            FallbackPoStCompound::setup(&setup_params)?;
+
+        let mut pub_sectors = Vec::with_capacity(param_sector_count);
+        for _ in 0..param_sector_count {
+            for (sector_id, replica) in replicas.iter() {
+                let comm_r = replica
+                    .safe_comm_r()
+                    .with_context(|| format!("{}: safe_comm_r failed: {:?}", caller, sector_id))?;
+                pub_sectors.push(PublicSector {
+                    id: *sector_id,
+                    comm_r,
+                });
+            }
+        }
+
+        Ok(WinningPoStStatement {
+            pub_params,
+            randomness: randomness_safe,
+            prover_id: prover_id_safe,
+            pub_sectors,
+        })
+    }
+
+    fn pub_inputs(&self) -> fallback::PublicInputs<'_, <Tree::Hasher as Hasher>::Domain> {
+        fallback::PublicInputs {
+            randomness: self.randomness,
+            prover_id: self.prover_id,
+            sectors: &self.pub_sectors,
+            k: None,
+        }
+    }
+}
+
 /// Verifies a winning proof-of-spacetime.
 ///
 /// The provided `replicas` must be the same ones as passed to `generate_winning_post`, and be based on
@@ -231,22 +359,100 @@ pub fn verify_winning_post<Tree: 'static + MerkleTreeTrait>(
     replicas: &[(SectorId, PublicReplicaInfo)],
     prover_id: ProverId,
     proof: &[u8],
+) -> Result<bool> {
+    let verifying_key = get_post_verifying_key::<Tree>(&post_config)?;
+
+    verify_winning_post_with_vk::<Tree>(
+        post_config,
+        &verifying_key,
+        randomness,
+        replicas,
+        prover_id,
+        proof,
+    )
+}
+
+/// Verifies a winning proof-of-spacetime against the provided verifying key, rather than the one
+/// in the parameter cache. See `read_post_verifying_key` for loading it.
+pub fn verify_winning_post_with_vk<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    verifying_key: &Bls12PreparedVerifyingKey,
+    randomness: &ChallengeSeed,
+    replicas: &[(SectorId, PublicReplicaInfo)],
+    prover_id: ProverId,
+    proof: &[u8],
 ) -> Result<bool> {
     info!("verify_winning_post:start");
 
+    let statement = WinningPoStStatement::<Tree>::new(
+        post_config,
+        randomness,
+        replicas,
+        prover_id,
+        "verify_winning_post",
+    )?;
+
+    let is_valid = {
+        let single_proof = MultiProof::new_from_reader(None, &proof[..], verifying_key)?;
+        if single_proof.len() != 1 {
+            return Ok(false);
+        }
+
+        FallbackPoStCompound::verify(
+            &statement.pub_params,
+            &statement.pub_inputs(),
+            &single_proof,
+            &fallback::ChallengeRequirements {
+                minimum_challenge_count: post_config.challenge_count * post_config.sector_count,
+            },
+        )?
+    };
+
+    if !is_valid {
+        return Ok(false);
+    }
+
+    info!("verify_winning_post:finish");
+
+    Ok(true)
+}
+
+/// The randomness, prover id, sectors and proof of a single winning proof-of-spacetime.
+pub type WinningPoStBatchItem<'a> = (
+    ChallengeSeed,
+    ProverId,
+    &'a [(SectorId, PublicReplicaInfo)],
+    &'a [u8],
+);
+
+/// Verifies many winning proofs-of-spacetime for the same `post_config` at once, and returns
+/// whether each of them is valid.
+///
+/// Items whose inputs are malformed, e.g. a truncated proof, are reported as invalid rather than
+/// failing the whole batch.
+pub fn verify_winning_post_batch<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    items: &[WinningPoStBatchItem<'_>],
+) -> Result<Vec<bool>> {
+    let verifying_key = get_post_verifying_key::<Tree>(&post_config)?;
+
+    verify_winning_post_batch_with_vk::<Tree>(post_config, &verifying_key, items)
+}
+
+/// Same as `verify_winning_post_batch`, against the provided verifying key rather than the one
+/// in the parameter cache.
+pub fn verify_winning_post_batch_with_vk<Tree: 'static + MerkleTreeTrait>(
+    post_config: &PoStConfig,
+    verifying_key: &Bls12PreparedVerifyingKey,
+    items: &[WinningPoStBatchItem<'_>],
+) -> Result<Vec<bool>> {
+    info!("verify_winning_post_batch:start");
+
     ensure!(
         post_config.typ == PoStType::Winning,
         "invalid post config type"
     );
-    ensure!(
-        post_config.sector_count == replicas.len(),
-        "invalid amount of replicas provided"
-    );
-
-    let randomness_safe: <Tree::Hasher as Hasher>::Domain =
-        as_safe_commitment(randomness, "randomness")?;
-    let prover_id_safe: <Tree::Hasher as Hasher>::Domain =
-        as_safe_commitment(&prover_id, "prover_id")?;
+    ensure!(!items.is_empty(), "Cannot verify empty batch");
 
     let vanilla_params = winning_post_setup_params(&post_config)?;
     let param_sector_count = vanilla_params.sector_count;
@@ -259,49 +465,39 @@ pub fn verify_winning_post<Tree: 'static + MerkleTreeTrait>(
     let pub_params: compound_proof::PublicParams<'_, FallbackPoSt<'_, Tree>> =
         FallbackPoStCompound::setup(&setup_params)?;
 
-    let mut pub_sectors = Vec::with_capacity(param_sector_count);
-    for _ in 0..param_sector_count {
-        for (sector_id, replica) in replicas.iter() {
-            let comm_r = replica.safe_comm_r().with_context(|| {
-                format!("verify_winning_post: safe_comm_r failed: {:?}", sector_id)
-            })?;
-            pub_sectors.push(PublicSector {
-                id: *sector_id,
-                comm_r,
+    let mut batch_indices = Vec::with_capacity(items.len());
+    let mut posts = Vec::with_capacity(items.len());
+    for (i, (randomness, prover_id, replicas, proof)) in items.iter().enumerate() {
+        let post = if replicas.len() == post_config.sector_count {
+            let sectors = (0..param_sector_count).flat_map(|_| {
+                replicas
+                    .iter()
+                    .map(|(sector_id, replica)| (*sector_id, replica))
             });
+            BatchPoSt::<Tree>::new(randomness, *prover_id, sectors, None, proof, verifying_key)
+        } else {
+            Err(anyhow!("invalid amount of replicas provided"))
+        };
+
+        match post {
+            Ok(post) => {
+                batch_indices.push(i);
+                posts.push(post);
+            }
+            Err(err) => warn!("verify_winning_post_batch: invalid item {}: {:#}", i, err),
         }
     }
 
-    let pub_inputs = fallback::PublicInputs {
-        randomness: randomness_safe,
-        prover_id: prover_id_safe,
-        sectors: &pub_sectors,
-        k: None,
+    let mut valid = vec![false; items.len()];
+    let requirements = fallback::ChallengeRequirements {
+        minimum_challenge_count: post_config.challenge_count * post_config.sector_count,
     };
-
-    let is_valid = {
-        let verifying_key = get_post_verifying_key::<Tree>(&post_config)?;
-
-        let single_proof = MultiProof::new_from_reader(None, &proof[..], &verifying_key)?;
-        if single_proof.len() != 1 {
-            return Ok(false);
-        }
-
-        FallbackPoStCompound::verify(
-            &pub_params,
-            &pub_inputs,
-            &single_proof,
-            &fallback::ChallengeRequirements {
-                minimum_challenge_count: post_config.challenge_count * post_config.sector_count,
-            },
-        )?
-    };
-
-    if !is_valid {
-        return Ok(false);
+    let batch_valid = batch_verify_posts::<Tree>(&pub_params, posts, &requirements)?;
+    for (i, is_valid) in batch_indices.into_iter().zip(batch_valid) {
+        valid[i] = is_valid;
     }
 
-    info!("verify_winning_post:finish");
+    info!("verify_winning_post_batch:finish");
 
-    Ok(true)
+    Ok(valid)
 }
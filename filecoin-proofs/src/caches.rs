@@ -1,34 +1,210 @@
+use std::any::Any;
 use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use bellperson::{
     bls::Bls12,
     groth16::{self, prepare_verifying_key},
 };
 use lazy_static::lazy_static;
-use log::info;
+use log::{info, warn};
 use rand::rngs::OsRng;
-use storage_proofs_core::{compound_proof::CompoundProof, merkle::MerkleTreeTrait};
+use storage_proofs_core::{
+    compound_proof::CompoundProof,
+    merkle::MerkleTreeTrait,
+    parameter_cache::{get_parameter_data_from_id, verify_production_params},
+    settings::SETTINGS,
+};
 use storage_proofs_porep::stacked::{StackedCompound, StackedDrg};
 use storage_proofs_post::fallback::{FallbackPoSt, FallbackPoStCircuit, FallbackPoStCompound};
 
 use crate::{
     constants::DefaultPieceHasher,
     parameters::{public_params, window_post_public_params, winning_post_public_params},
-    types::{PaddedBytesAmount, PoRepConfig, PoRepProofPartitions, PoStConfig, PoStType},
+    registry::sector_shape,
+    types::{
+        PaddedBytesAmount, PoRepConfig, PoRepProofPartitions, PoStConfig, PoStType, ProofsContext,
+    },
+    with_shape,
 };
 
 type Bls12GrothParams = groth16::MappedParameters<Bls12>;
 pub type Bls12PreparedVerifyingKey = groth16::PreparedVerifyingKey<Bls12>;
 
-type Cache<G> = HashMap<String, Arc<G>>;
 type GrothMemCache = Cache<Bls12GrothParams>;
 type VerifyingKeyMemCache = Cache<Bls12PreparedVerifyingKey>;
+type TreeHandleCache = Cache<TreeHandle>;
 
 lazy_static! {
-    static ref GROTH_PARAM_MEMORY_CACHE: Mutex<GrothMemCache> = Default::default();
-    static ref VERIFYING_KEY_MEMORY_CACHE: Mutex<VerifyingKeyMemCache> = Default::default();
+    static ref GROTH_PARAM_MEMORY_CACHE: Mutex<GrothMemCache> =
+        Mutex::new(Cache::new(SETTINGS.groth_param_memory_cache_size));
+    // Verifying keys are small, so they are never unloaded to stay within a budget.
+    static ref VERIFYING_KEY_MEMORY_CACHE: Mutex<VerifyingKeyMemCache> = Mutex::new(Cache::new(0));
+    // The budget is set from the context of each lookup, see `get_tree_handle`.
+    static ref TREE_HANDLE_CACHE: Mutex<TreeHandleCache> = Mutex::new(Cache::new(0));
+}
+
+/// The size an entry of a memory cache is accounted for, in the unit of the cache's budget.
+pub trait CacheEntrySize {
+    fn cache_entry_size(&self) -> u64;
+}
+
+impl CacheEntrySize for Bls12GrothParams {
+    fn cache_entry_size(&self) -> u64 {
+        // The parameters file is mapped into memory in its entirety.
+        self.params.len() as u64
+    }
+}
+
+impl CacheEntrySize for Bls12PreparedVerifyingKey {
+    fn cache_entry_size(&self) -> u64 {
+        std::mem::size_of::<Self>() as u64
+    }
+}
+
+/// An opened tree kept by the tree handle cache, together with the state of the files it was
+/// opened from.
+pub(crate) struct TreeHandle {
+    tree: Arc<dyn Any + Send + Sync>,
+    files: Vec<FileState>,
+    open_files: u64,
+}
+
+/// The size and modification time of a file, to detect that it was replaced or modified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileState {
+    len: u64,
+    modified: SystemTime,
+}
+
+impl FileState {
+    fn read(path: &Path) -> Result<Self> {
+        let metadata = fs::metadata(path).with_context(|| format!("could not stat {:?}", path))?;
+        Ok(FileState {
+            len: metadata.len(),
+            modified: metadata.modified()?,
+        })
+    }
+}
+
+impl CacheEntrySize for TreeHandle {
+    fn cache_entry_size(&self) -> u64 {
+        self.open_files
+    }
+}
+
+struct CacheEntry<G> {
+    value: Arc<G>,
+    size: u64,
+    last_used: u64,
+}
+
+/// A memory cache which unloads the least recently used entries once the total size of its
+/// entries exceeds a budget, e.g. a number of bytes or of open files.
+///
+/// Unloading an entry only drops the cache's reference to it, so the memory is released once
+/// all proofs which are still using it are done.
+pub struct Cache<G> {
+    entries: HashMap<String, CacheEntry<G>>,
+    /// The maximum total size of all entries, or 0 for no limit.
+    budget: u64,
+    size: u64,
+    clock: u64,
+}
+
+impl<G: CacheEntrySize> Cache<G> {
+    pub fn new(budget: u64) -> Self {
+        Cache {
+            entries: HashMap::new(),
+            budget,
+            size: 0,
+            clock: 0,
+        }
+    }
+
+    /// Returns the entry for `identifier` and marks it as most recently used.
+    pub fn get(&mut self, identifier: &str) -> Option<Arc<G>> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.entries.get_mut(identifier).map(|entry| {
+            entry.last_used = clock;
+            entry.value.clone()
+        })
+    }
+
+    /// Inserts an entry, first unloading the least recently used entries as needed to stay within
+    /// the budget. An entry which exceeds the budget on its own is still inserted.
+    pub fn insert(&mut self, identifier: String, value: Arc<G>) {
+        self.remove(&identifier);
+
+        let size = value.cache_entry_size();
+        if self.budget > 0 {
+            while !self.entries.is_empty() && self.size + size > self.budget {
+                let lru = self
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(identifier, _)| identifier.clone())
+                    .expect("cache is not empty");
+                info!("unloading {} from memory cache", &lru);
+                self.remove(&lru);
+            }
+        }
+
+        self.clock += 1;
+        self.size += size;
+        self.entries.insert(
+            identifier,
+            CacheEntry {
+                value,
+                size,
+                last_used: self.clock,
+            },
+        );
+    }
+
+    /// Sets the budget used by subsequent inserts. Entries already in the cache are unloaded
+    /// as needed on the next insert.
+    pub fn set_budget(&mut self, budget: u64) {
+        self.budget = budget;
+    }
+
+    /// Removes the entry for `identifier`, returning whether it was present.
+    pub fn remove(&mut self, identifier: &str) -> bool {
+        match self.entries.remove(identifier) {
+            Some(entry) => {
+                self.size -= entry.size;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.size = 0;
+    }
+
+    pub fn contains(&self, identifier: &str) -> bool {
+        self.entries.contains_key(identifier)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The total size of all entries.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
 }
 
 pub fn cache_lookup<F, G>(
@@ -38,15 +214,15 @@ pub fn cache_lookup<F, G>(
 ) -> Result<Arc<G>>
 where
     F: FnOnce() -> Result<G>,
-    G: Send + Sync,
+    G: CacheEntrySize + Send + Sync,
 {
     info!("trying parameters memory cache for: {}", &identifier);
     {
-        let cache = (*cache_ref).lock().expect("poisoned cache");
+        let mut cache = (*cache_ref).lock().expect("poisoned cache");
 
         if let Some(entry) = cache.get(&identifier) {
             info!("found params in memory cache for {}", &identifier);
-            return Ok(entry.clone());
+            return Ok(entry);
         }
     }
 
@@ -62,6 +238,306 @@ where
     Ok(res)
 }
 
+/// Returns the tree cached for `identifier`, opening it with `open` if it is not cached or any of
+/// `files`, i.e. the replica and the tree files, changed size or modification time since.
+///
+/// `open_files` is the number of files the opened tree keeps open. If opening fails, all cached
+/// trees are closed and it is tried once more, as the process may have run out of file
+/// descriptors.
+///
+/// The cache is shared by the whole process. It is kept within the
+/// `tree_handle_cache_max_open_files` budget of `ctx` whenever a tree is opened, so a context
+/// with a smaller budget closes trees opened under a larger one.
+pub(crate) fn get_tree_handle<T, F>(
+    ctx: &ProofsContext,
+    identifier: String,
+    files: &[PathBuf],
+    open_files: u64,
+    open: F,
+) -> Result<Arc<T>>
+where
+    T: 'static + Send + Sync,
+    F: Fn() -> Result<T>,
+{
+    tree_handle_lookup(
+        &TREE_HANDLE_CACHE,
+        identifier,
+        files,
+        open_files,
+        ctx.settings().tree_handle_cache_max_open_files,
+        open,
+    )
+}
+
+fn tree_handle_lookup<T, F>(
+    cache_ref: &Mutex<TreeHandleCache>,
+    identifier: String,
+    files: &[PathBuf],
+    open_files: u64,
+    max_open_files: u64,
+    open: F,
+) -> Result<Arc<T>>
+where
+    T: 'static + Send + Sync,
+    F: Fn() -> Result<T>,
+{
+    let file_states = files
+        .iter()
+        .map(|path| FileState::read(path))
+        .collect::<Result<Vec<_>>>()?;
+
+    {
+        let mut cache = cache_ref.lock().expect("poisoned cache");
+        if let Some(handle) = cache.get(&identifier) {
+            if handle.files == file_states {
+                if let Ok(tree) = handle.tree.clone().downcast::<T>() {
+                    return Ok(tree);
+                }
+            }
+            info!("files of cached tree changed: {}", &identifier);
+            cache.remove(&identifier);
+        }
+    }
+
+    info!("opening tree for {}", &identifier);
+    let tree = match open() {
+        Ok(tree) => tree,
+        Err(err) => {
+            let mut cache = cache_ref.lock().expect("poisoned cache");
+            if cache.is_empty() {
+                return Err(err);
+            }
+            warn!(
+                "opening tree failed, closing {} cached trees and retrying: {:#}",
+                cache.len(),
+                err
+            );
+            cache.clear();
+            drop(cache);
+
+            open()?
+        }
+    };
+
+    let tree = Arc::new(tree);
+    let handle = TreeHandle {
+        tree: tree.clone(),
+        files: file_states,
+        open_files,
+    };
+    let mut cache = cache_ref.lock().expect("poisoned cache");
+    cache.set_budget(max_open_files);
+    cache.insert(identifier, Arc::new(handle));
+
+    Ok(tree)
+}
+
+/// Closes all trees kept open by the tree handle cache. Trees which are still in use are closed
+/// once the proofs using them are done.
+pub fn clear_tree_handle_cache() {
+    info!("clearing tree handle cache");
+    TREE_HANDLE_CACHE.lock().expect("poisoned cache").clear();
+}
+
+fn porep_identifier(porep_config: &PoRepConfig) -> String {
+    format!(
+        "STACKED[{}]",
+        usize::from(PaddedBytesAmount::from(*porep_config))
+    )
+}
+
+fn post_identifier(post_config: &PoStConfig) -> String {
+    match post_config.typ {
+        PoStType::Winning => format!(
+            "WINNING_POST[{}]",
+            usize::from(post_config.padded_sector_size())
+        ),
+        PoStType::Window => format!(
+            "WINDOW_POST[{}]",
+            usize::from(post_config.padded_sector_size())
+        ),
+    }
+}
+
+/// Keys parameters loaded from a parameter cache other than the default one by their directory
+/// as well, so that contexts with different caches don't share entries. Parameters from the
+/// default cache keep the plain identifier, `unload_parameters_with_context` adds the directory
+/// for the others.
+fn context_identifier(ctx: &ProofsContext, identifier: String) -> String {
+    let parameter_cache = &ctx.settings().parameter_cache;
+    if *parameter_cache == SETTINGS.parameter_cache {
+        identifier
+    } else {
+        format!("{}@{}", identifier, parameter_cache)
+    }
+}
+
+fn verifying_key_identifier(identifier: &str) -> String {
+    format!("{}-verifying-key", identifier)
+}
+
+/// A set of Groth parameters, along with its verifying key, as used by the proving and
+/// verification APIs.
+#[derive(Debug, Clone)]
+pub enum ParameterSet {
+    PoRep(PoRepConfig),
+    PoSt(PoStConfig),
+}
+
+impl ParameterSet {
+    /// The identifier of this set in the memory caches, as accepted by `unload_parameters`.
+    pub fn identifier(&self) -> String {
+        match self {
+            ParameterSet::PoRep(porep_config) => porep_identifier(porep_config),
+            ParameterSet::PoSt(post_config) => post_identifier(post_config),
+        }
+    }
+
+    fn sector_size(&self) -> u64 {
+        match self {
+            ParameterSet::PoRep(porep_config) => u64::from(porep_config.sector_size),
+            ParameterSet::PoSt(post_config) => u64::from(post_config.sector_size),
+        }
+    }
+}
+
+/// Unloads the Groth parameters and verifying key with the given identifier (see
+/// `ParameterSet::identifier`) from memory. Returns whether anything was loaded.
+pub fn unload_parameters(identifier: &str) -> bool {
+    unload_parameters_with_context(&ProofsContext::default(), identifier)
+}
+
+/// Same as `unload_parameters`, for parameters loaded from the parameter cache of `ctx`.
+pub fn unload_parameters_with_context(ctx: &ProofsContext, identifier: &str) -> bool {
+    let identifier = context_identifier(ctx, identifier.to_string());
+    info!("unloading params from memory cache for {}", identifier);
+    let params_removed = GROTH_PARAM_MEMORY_CACHE
+        .lock()
+        .expect("poisoned cache")
+        .remove(&identifier);
+    let vk_removed = VERIFYING_KEY_MEMORY_CACHE
+        .lock()
+        .expect("poisoned cache")
+        .remove(&verifying_key_identifier(&identifier));
+
+    params_removed || vk_removed
+}
+
+/// Unloads all Groth parameters and verifying keys from memory.
+pub fn clear_parameter_caches() {
+    info!("clearing params memory caches");
+    GROTH_PARAM_MEMORY_CACHE
+        .lock()
+        .expect("poisoned cache")
+        .clear();
+    VERIFYING_KEY_MEMORY_CACHE
+        .lock()
+        .expect("poisoned cache")
+        .clear();
+}
+
+/// Loads the Groth parameters and verifying keys of `parameter_sets` into memory, so that the
+/// first proof using them doesn't have to.
+///
+/// Parameters listed in `parameters.json` are verified against their digest first. Parameters
+/// which are not listed, e.g. for test sector sizes, are loaded without verification.
+pub fn preload_parameters(parameter_sets: &[ParameterSet]) -> Result<()> {
+    preload_parameters_with_context(&ProofsContext::default(), parameter_sets)
+}
+
+/// Same as `preload_parameters`, loading the parameters from the parameter cache of `ctx`.
+pub fn preload_parameters_with_context(
+    ctx: &ProofsContext,
+    parameter_sets: &[ParameterSet],
+) -> Result<()> {
+    for parameter_set in parameter_sets {
+        let sector_size = parameter_set.sector_size();
+        sector_shape(sector_size)?;
+        with_shape!(sector_size, preload_parameter_set, ctx, parameter_set)?;
+    }
+
+    Ok(())
+}
+
+fn preload_parameter_set<Tree: 'static + MerkleTreeTrait>(
+    ctx: &ProofsContext,
+    parameter_set: &ParameterSet,
+) -> Result<()> {
+    info!("preloading params for {}", parameter_set.identifier());
+
+    let params_path = match parameter_set {
+        ParameterSet::PoRep(porep_config) => porep_config.get_cache_params_path::<Tree>(ctx)?,
+        ParameterSet::PoSt(post_config) => post_config.get_cache_params_path::<Tree>(ctx)?,
+    };
+    let params_filename = params_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    if get_parameter_data_from_id(params_filename).is_some() {
+        verify_production_params(&params_path)?;
+    } else {
+        warn!(
+            "params are not listed in parameters.json, skipping verification: {:?}",
+            params_path
+        );
+    }
+
+    match parameter_set {
+        ParameterSet::PoRep(porep_config) => {
+            get_stacked_params_with_context::<Tree>(ctx, *porep_config)?;
+            get_stacked_verifying_key_with_context::<Tree>(ctx, *porep_config)?;
+        }
+        ParameterSet::PoSt(post_config) => {
+            get_post_params_with_context::<Tree>(ctx, post_config)?;
+            get_post_verifying_key_with_context::<Tree>(ctx, post_config)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Loads and prepares only the verifying keys of `parameter_sets`, so that the first
+/// verification using them doesn't have to. Unlike `preload_parameters`, the Groth parameters
+/// are not mapped into memory.
+pub fn preload_verifying_keys(parameter_sets: &[ParameterSet]) -> Result<()> {
+    preload_verifying_keys_with_context(&ProofsContext::default(), parameter_sets)
+}
+
+/// Same as `preload_verifying_keys`, loading the keys from the parameter cache of `ctx`.
+pub fn preload_verifying_keys_with_context(
+    ctx: &ProofsContext,
+    parameter_sets: &[ParameterSet],
+) -> Result<()> {
+    for parameter_set in parameter_sets {
+        let sector_size = parameter_set.sector_size();
+        sector_shape(sector_size)?;
+        with_shape!(sector_size, preload_verifying_key, ctx, parameter_set)?;
+    }
+
+    Ok(())
+}
+
+fn preload_verifying_key<Tree: 'static + MerkleTreeTrait>(
+    ctx: &ProofsContext,
+    parameter_set: &ParameterSet,
+) -> Result<()> {
+    info!(
+        "preloading verifying key for {}",
+        parameter_set.identifier()
+    );
+
+    match parameter_set {
+        ParameterSet::PoRep(porep_config) => {
+            get_stacked_verifying_key_with_context::<Tree>(ctx, *porep_config)?;
+        }
+        ParameterSet::PoSt(post_config) => {
+            get_post_verifying_key_with_context::<Tree>(ctx, post_config)?;
+        }
+    }
+
+    Ok(())
+}
+
 #[inline]
 pub fn lookup_groth_params<F>(identifier: String, generator: F) -> Result<Arc<Bls12GrothParams>>
 where
@@ -78,12 +554,20 @@ pub fn lookup_verifying_key<F>(
 where
     F: FnOnce() -> Result<Bls12PreparedVerifyingKey>,
 {
-    let vk_identifier = format!("{}-verifying-key", &identifier);
+    let vk_identifier = verifying_key_identifier(&identifier);
     cache_lookup(&*VERIFYING_KEY_MEMORY_CACHE, vk_identifier, generator)
 }
 
 pub fn get_stacked_params<Tree: 'static + MerkleTreeTrait>(
     porep_config: PoRepConfig,
+) -> Result<Arc<Bls12GrothParams>> {
+    get_stacked_params_with_context::<Tree>(&ProofsContext::default(), porep_config)
+}
+
+/// Same as `get_stacked_params`, loading the parameters from the parameter cache of `ctx`.
+pub fn get_stacked_params_with_context<Tree: 'static + MerkleTreeTrait>(
+    ctx: &ProofsContext,
+    porep_config: PoRepConfig,
 ) -> Result<Arc<Bls12GrothParams>> {
     let public_params = public_params::<Tree>(
         PaddedBytesAmount::from(porep_config),
@@ -96,15 +580,12 @@ pub fn get_stacked_params<Tree: 'static + MerkleTreeTrait>(
         <StackedCompound<Tree, DefaultPieceHasher> as CompoundProof<
             StackedDrg<'_, Tree, DefaultPieceHasher>,
             _,
-        >>::groth_params::<OsRng>(None, &public_params)
+        >>::groth_params_with_context::<OsRng>(ctx, None, &public_params)
         .map_err(Into::into)
     };
 
     Ok(lookup_groth_params(
-        format!(
-            "STACKED[{}]",
-            usize::from(PaddedBytesAmount::from(porep_config))
-        ),
+        context_identifier(ctx, porep_identifier(&porep_config)),
         parameters_generator,
     )?)
 }
@@ -112,50 +593,43 @@ pub fn get_stacked_params<Tree: 'static + MerkleTreeTrait>(
 pub fn get_post_params<Tree: 'static + MerkleTreeTrait>(
     post_config: &PoStConfig,
 ) -> Result<Arc<Bls12GrothParams>> {
-    match post_config.typ {
-        PoStType::Winning => {
-            let post_public_params = winning_post_public_params::<Tree>(post_config)?;
-
-            let parameters_generator = || {
-                <FallbackPoStCompound<Tree> as CompoundProof<
-                    FallbackPoSt<'_, Tree>,
-                    FallbackPoStCircuit<Tree>,
-                >>::groth_params::<OsRng>(None, &post_public_params)
-                .map_err(Into::into)
-            };
-
-            Ok(lookup_groth_params(
-                format!(
-                    "WINNING_POST[{}]",
-                    usize::from(post_config.padded_sector_size())
-                ),
-                parameters_generator,
-            )?)
-        }
-        PoStType::Window => {
-            let post_public_params = window_post_public_params::<Tree>(post_config)?;
-
-            let parameters_generator = || {
-                <FallbackPoStCompound<Tree> as CompoundProof<
-                    FallbackPoSt<'_, Tree>,
-                    FallbackPoStCircuit<Tree>,
-                >>::groth_params::<OsRng>(None, &post_public_params)
-                .map_err(Into::into)
-            };
-
-            Ok(lookup_groth_params(
-                format!(
-                    "Window_POST[{}]",
-                    usize::from(post_config.padded_sector_size())
-                ),
-                parameters_generator,
-            )?)
-        }
-    }
+    get_post_params_with_context::<Tree>(&ProofsContext::default(), post_config)
+}
+
+/// Same as `get_post_params`, loading the parameters from the parameter cache of `ctx`.
+pub fn get_post_params_with_context<Tree: 'static + MerkleTreeTrait>(
+    ctx: &ProofsContext,
+    post_config: &PoStConfig,
+) -> Result<Arc<Bls12GrothParams>> {
+    let post_public_params = match post_config.typ {
+        PoStType::Winning => winning_post_public_params::<Tree>(post_config)?,
+        PoStType::Window => window_post_public_params::<Tree>(post_config)?,
+    };
+
+    let parameters_generator = || {
+        <FallbackPoStCompound<Tree> as CompoundProof<
+            FallbackPoSt<'_, Tree>,
+            FallbackPoStCircuit<Tree>,
+        >>::groth_params_with_context::<OsRng>(ctx, None, &post_public_params)
+        .map_err(Into::into)
+    };
+
+    Ok(lookup_groth_params(
+        context_identifier(ctx, post_identifier(post_config)),
+        parameters_generator,
+    )?)
 }
 
 pub fn get_stacked_verifying_key<Tree: 'static + MerkleTreeTrait>(
     porep_config: PoRepConfig,
+) -> Result<Arc<Bls12PreparedVerifyingKey>> {
+    get_stacked_verifying_key_with_context::<Tree>(&ProofsContext::default(), porep_config)
+}
+
+/// Same as `get_stacked_verifying_key`, loading the key from the parameter cache of `ctx`.
+pub fn get_stacked_verifying_key_with_context<Tree: 'static + MerkleTreeTrait>(
+    ctx: &ProofsContext,
+    porep_config: PoRepConfig,
 ) -> Result<Arc<Bls12PreparedVerifyingKey>> {
     let public_params = public_params(
         PaddedBytesAmount::from(porep_config),
@@ -168,15 +642,12 @@ pub fn get_stacked_verifying_key<Tree: 'static + MerkleTreeTrait>(
         let vk = <StackedCompound<Tree, DefaultPieceHasher> as CompoundProof<
             StackedDrg<'_, Tree, DefaultPieceHasher>,
             _,
-        >>::verifying_key::<OsRng>(None, &public_params)?;
+        >>::verifying_key_with_context::<OsRng>(ctx, None, &public_params)?;
         Ok(prepare_verifying_key(&vk))
     };
 
     Ok(lookup_verifying_key(
-        format!(
-            "STACKED[{}]",
-            usize::from(PaddedBytesAmount::from(porep_config))
-        ),
+        context_identifier(ctx, porep_identifier(&porep_config)),
         vk_generator,
     )?)
 }
@@ -184,44 +655,198 @@ pub fn get_stacked_verifying_key<Tree: 'static + MerkleTreeTrait>(
 pub fn get_post_verifying_key<Tree: 'static + MerkleTreeTrait>(
     post_config: &PoStConfig,
 ) -> Result<Arc<Bls12PreparedVerifyingKey>> {
-    match post_config.typ {
-        PoStType::Winning => {
-            let post_public_params = winning_post_public_params::<Tree>(post_config)?;
-
-            let vk_generator = || {
-                let vk = <FallbackPoStCompound<Tree> as CompoundProof<
-                    FallbackPoSt<'_, Tree>,
-                    FallbackPoStCircuit<Tree>,
-                >>::verifying_key::<OsRng>(None, &post_public_params)?;
-                Ok(prepare_verifying_key(&vk))
-            };
-
-            Ok(lookup_verifying_key(
-                format!(
-                    "WINNING_POST[{}]",
-                    usize::from(post_config.padded_sector_size())
-                ),
-                vk_generator,
-            )?)
+    get_post_verifying_key_with_context::<Tree>(&ProofsContext::default(), post_config)
+}
+
+/// Same as `get_post_verifying_key`, loading the key from the parameter cache of `ctx`.
+pub fn get_post_verifying_key_with_context<Tree: 'static + MerkleTreeTrait>(
+    ctx: &ProofsContext,
+    post_config: &PoStConfig,
+) -> Result<Arc<Bls12PreparedVerifyingKey>> {
+    let post_public_params = match post_config.typ {
+        PoStType::Winning => winning_post_public_params::<Tree>(post_config)?,
+        PoStType::Window => window_post_public_params::<Tree>(post_config)?,
+    };
+
+    let vk_generator = || {
+        let vk = <FallbackPoStCompound<Tree> as CompoundProof<
+            FallbackPoSt<'_, Tree>,
+            FallbackPoStCircuit<Tree>,
+        >>::verifying_key_with_context::<OsRng>(ctx, None, &post_public_params)?;
+        Ok(prepare_verifying_key(&vk))
+    };
+
+    Ok(lookup_verifying_key(
+        context_identifier(ctx, post_identifier(post_config)),
+        vk_generator,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Entry(u64);
+
+    impl CacheEntrySize for Entry {
+        fn cache_entry_size(&self) -> u64 {
+            self.0
         }
-        PoStType::Window => {
-            let post_public_params = window_post_public_params::<Tree>(post_config)?;
-
-            let vk_generator = || {
-                let vk = <FallbackPoStCompound<Tree> as CompoundProof<
-                    FallbackPoSt<'_, Tree>,
-                    FallbackPoStCircuit<Tree>,
-                >>::verifying_key::<OsRng>(None, &post_public_params)?;
-                Ok(prepare_verifying_key(&vk))
-            };
-
-            Ok(lookup_verifying_key(
-                format!(
-                    "WINDOW_POST[{}]",
-                    usize::from(post_config.padded_sector_size())
-                ),
-                vk_generator,
-            )?)
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let mut cache = Cache::new(100);
+        cache.insert("a".to_string(), Arc::new(Entry(40)));
+        cache.insert("b".to_string(), Arc::new(Entry(40)));
+        assert_eq!(cache.size(), 80);
+
+        // Using `a` makes `b` the least recently used entry.
+        assert!(cache.get("a").is_some());
+        cache.insert("c".to_string(), Arc::new(Entry(40)));
+
+        assert!(cache.contains("a"));
+        assert!(!cache.contains("b"));
+        assert!(cache.contains("c"));
+        assert_eq!(cache.size(), 80);
+    }
+
+    #[test]
+    fn test_cache_keeps_oversized_entry() {
+        let mut cache = Cache::new(100);
+        cache.insert("a".to_string(), Arc::new(Entry(40)));
+        cache.insert("b".to_string(), Arc::new(Entry(150)));
+
+        assert!(!cache.contains("a"));
+        assert!(cache.contains("b"));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.size(), 150);
+    }
+
+    #[test]
+    fn test_cache_without_budget() {
+        let mut cache = Cache::new(0);
+        for i in 0..10 {
+            cache.insert(i.to_string(), Arc::new(Entry(1 << 40)));
         }
+        assert_eq!(cache.len(), 10);
+
+        assert!(cache.remove("3"));
+        assert!(!cache.remove("3"));
+        assert_eq!(cache.size(), 9 << 40);
+
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.size(), 0);
+    }
+
+    #[test]
+    fn test_tree_handle_lookup() {
+        let dir = tempfile::tempdir().expect("tempdir failure");
+        let replica_path = dir.path().join("replica");
+        let tree_path = dir.path().join("tree-r-last");
+        fs::write(&replica_path, b"replica").expect("write failure");
+        fs::write(&tree_path, b"tree").expect("write failure");
+        let files = [replica_path.clone(), tree_path.clone()];
+
+        let cache = Mutex::new(Cache::new(0));
+        let opened = std::cell::Cell::new(0);
+        let open = || {
+            opened.set(opened.get() + 1);
+            Ok(opened.get())
+        };
+
+        let first = tree_handle_lookup(&cache, "a".to_string(), &files, 2, 4, open)
+            .expect("lookup failure");
+        let second = tree_handle_lookup(&cache, "a".to_string(), &files, 2, 4, open)
+            .expect("lookup failure");
+        assert_eq!((*first, *second), (1, 1));
+        assert_eq!(cache.lock().expect("poisoned cache").size(), 2);
+
+        // A replica of a different size is reopened.
+        fs::write(&replica_path, b"resealed replica").expect("write failure");
+        let third = tree_handle_lookup(&cache, "a".to_string(), &files, 2, 4, open)
+            .expect("lookup failure");
+        assert_eq!(*third, 2);
+        assert_eq!(cache.lock().expect("poisoned cache").len(), 1);
+
+        // So is a tree whose tree file changed.
+        fs::write(&tree_path, b"rebuilt tree").expect("write failure");
+        let fourth = tree_handle_lookup(&cache, "a".to_string(), &files, 2, 4, open)
+            .expect("lookup failure");
+        assert_eq!(*fourth, 3);
+
+        // A missing tree file fails the lookup.
+        fs::remove_file(&tree_path).expect("remove failure");
+        assert!(tree_handle_lookup(&cache, "a".to_string(), &files, 2, 4, open).is_err());
+        fs::write(&tree_path, b"rebuilt tree").expect("write failure");
+        tree_handle_lookup(&cache, "a".to_string(), &files, 2, 4, open).expect("lookup failure");
+
+        // Exceeding the open files budget closes the least recently used tree.
+        tree_handle_lookup(&cache, "b".to_string(), &files, 2, 4, open).expect("lookup failure");
+        tree_handle_lookup(&cache, "c".to_string(), &files, 2, 4, open).expect("lookup failure");
+        {
+            let cache_guard = cache.lock().expect("poisoned cache");
+            assert!(!cache_guard.contains("a"));
+            assert_eq!(cache_guard.size(), 4);
+        }
+
+        // A lookup with a smaller budget shrinks the cache to it.
+        tree_handle_lookup(&cache, "d".to_string(), &files, 2, 2, open).expect("lookup failure");
+        let cache_guard = cache.lock().expect("poisoned cache");
+        assert_eq!(cache_guard.len(), 1);
+        assert!(cache_guard.contains("d"));
+    }
+
+    #[test]
+    fn test_tree_handle_lookup_retries_after_clearing() {
+        let dir = tempfile::tempdir().expect("tempdir failure");
+        let replica_path = dir.path().join("replica");
+        fs::write(&replica_path, b"replica").expect("write failure");
+        let files = [replica_path];
+
+        let cache = Mutex::new(Cache::new(0));
+        tree_handle_lookup(&cache, "a".to_string(), &files, 2, 0, || Ok(0))
+            .expect("lookup failure");
+
+        // Fails while any other tree is open, like a process out of file descriptors.
+        let open = || {
+            if cache
+                .try_lock()
+                .map(|cache| cache.is_empty())
+                .unwrap_or(false)
+            {
+                Ok(1)
+            } else {
+                Err(anyhow::anyhow!("too many open files"))
+            }
+        };
+        let tree = tree_handle_lookup(&cache, "b".to_string(), &files, 2, 0, open)
+            .expect("lookup failure");
+        assert_eq!(*tree, 1);
+
+        let cache_guard = cache.lock().expect("poisoned cache");
+        assert!(!cache_guard.contains("a"));
+        assert!(cache_guard.contains("b"));
+
+        assert!(tree_handle_lookup::<u64, _>(
+            &Mutex::new(Cache::new(0)),
+            "c".to_string(),
+            &files,
+            2,
+            0,
+            || Err(anyhow::anyhow!("corrupt tree"))
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_cache_replaces_entry() {
+        let mut cache = Cache::new(100);
+        cache.insert("a".to_string(), Arc::new(Entry(60)));
+        cache.insert("a".to_string(), Arc::new(Entry(80)));
+
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.size(), 80);
     }
 }
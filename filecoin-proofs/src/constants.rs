@@ -1,11 +1,7 @@
-use std::collections::HashMap;
-use std::sync::RwLock;
-
 pub use storage_proofs_core::drgraph::BASE_DEGREE as DRG_DEGREE;
 pub use storage_proofs_porep::stacked::EXP_DEGREE;
 
 use filecoin_hashers::{poseidon::PoseidonHasher, sha256::Sha256Hasher, Hasher};
-use lazy_static::lazy_static;
 use storage_proofs_core::{
     merkle::{BinaryMerkleTree, LCTree, OctLCMerkleTree, OctMerkleTree},
     util::NODE_SIZE,
@@ -13,7 +9,10 @@ use storage_proofs_core::{
 };
 use typenum::{U0, U2, U8};
 
-use crate::types::UnpaddedBytesAmount;
+use crate::{
+    registry::{get_sector_shape, SectorShapeConfig, SectorShapeTable, TreeShape},
+    types::UnpaddedBytesAmount,
+};
 
 pub const SECTOR_SIZE_2_KIB: u64 = 1 << 11;
 pub const SECTOR_SIZE_4_KIB: u64 = 1 << 12;
@@ -47,78 +46,57 @@ pub const PUBLISHED_SECTOR_SIZES: [u64; 10] = [
     SECTOR_SIZE_64_GIB,
 ];
 
-lazy_static! {
-    pub static ref POREP_MINIMUM_CHALLENGES: RwLock<HashMap<u64, u64>> = RwLock::new(
-        [
-            (SECTOR_SIZE_2_KIB, 2),
-            (SECTOR_SIZE_4_KIB, 2),
-            (SECTOR_SIZE_16_KIB, 2),
-            (SECTOR_SIZE_32_KIB, 2),
-            (SECTOR_SIZE_8_MIB, 2),
-            (SECTOR_SIZE_16_MIB, 2),
-            (SECTOR_SIZE_512_MIB, 2),
-            (SECTOR_SIZE_1_GIB, 2),
-            (SECTOR_SIZE_32_GIB, 176),
-            (SECTOR_SIZE_64_GIB, 176),
-        ]
-        .iter()
-        .copied()
-        .collect()
-    );
-    pub static ref POREP_PARTITIONS: RwLock<HashMap<u64, u8>> = RwLock::new(
-        [
-            (SECTOR_SIZE_2_KIB, 1),
-            (SECTOR_SIZE_4_KIB, 1),
-            (SECTOR_SIZE_16_KIB, 1),
-            (SECTOR_SIZE_32_KIB, 1),
-            (SECTOR_SIZE_8_MIB, 1),
-            (SECTOR_SIZE_16_MIB, 1),
-            (SECTOR_SIZE_512_MIB, 1),
-            (SECTOR_SIZE_1_GIB, 1),
-            (SECTOR_SIZE_32_GIB, 10),
-            (SECTOR_SIZE_64_GIB, 10),
-        ]
-        .iter()
-        .copied()
-        .collect()
-    );
-    pub static ref LAYERS: RwLock<HashMap<u64, usize>> = RwLock::new(
-        [
-            (SECTOR_SIZE_2_KIB, 2),
-            (SECTOR_SIZE_4_KIB, 2),
-            (SECTOR_SIZE_16_KIB, 2),
-            (SECTOR_SIZE_32_KIB, 2),
-            (SECTOR_SIZE_8_MIB, 2),
-            (SECTOR_SIZE_16_MIB, 2),
-            (SECTOR_SIZE_512_MIB, 2),
-            (SECTOR_SIZE_1_GIB, 2),
-            (SECTOR_SIZE_32_GIB, 11),
-            (SECTOR_SIZE_64_GIB, 11),
-        ]
-        .iter()
-        .copied()
-        .collect()
-    );
-    // These numbers must match those used for Window PoSt scheduling in the miner actor.
-    // Please coordinate changes with actor code.
-    // https://github.com/filecoin-project/specs-actors/blob/master/actors/abi/sector.go
-    pub static ref WINDOW_POST_SECTOR_COUNT: RwLock<HashMap<u64, usize>> = RwLock::new(
-        [
-            (SECTOR_SIZE_2_KIB, 2),
-            (SECTOR_SIZE_4_KIB, 2),
-            (SECTOR_SIZE_16_KIB, 2),
-            (SECTOR_SIZE_32_KIB, 2),
-            (SECTOR_SIZE_8_MIB, 2),
-            (SECTOR_SIZE_16_MIB, 2),
-            (SECTOR_SIZE_512_MIB, 2),
-            (SECTOR_SIZE_1_GIB, 2),
-            (SECTOR_SIZE_32_GIB, 2349), // this gives 125,279,217 constraints, fitting in a single partition
-            (SECTOR_SIZE_64_GIB, 2300), // this gives 129,887,900 constraints, fitting in a single partition
-        ]
-        .iter()
-        .copied()
-        .collect()
-    );
+#[deprecated(note = "use `sector_shape(sector_size)?.porep_minimum_challenges` instead")]
+pub static POREP_MINIMUM_CHALLENGES: SectorShapeTable<u64> = SectorShapeTable {
+    field: porep_minimum_challenges,
+    set_field: set_porep_minimum_challenges,
+};
+#[deprecated(note = "use `sector_shape(sector_size)?.porep_partitions` instead")]
+pub static POREP_PARTITIONS: SectorShapeTable<u8> = SectorShapeTable {
+    field: porep_partitions,
+    set_field: set_porep_partitions,
+};
+#[deprecated(note = "use `sector_shape(sector_size)?.layers` instead")]
+pub static LAYERS: SectorShapeTable<usize> = SectorShapeTable {
+    field: layers,
+    set_field: set_layers,
+};
+#[deprecated(note = "use `sector_shape(sector_size)?.window_post_sector_count` instead")]
+pub static WINDOW_POST_SECTOR_COUNT: SectorShapeTable<usize> = SectorShapeTable {
+    field: window_post_sector_count,
+    set_field: set_window_post_sector_count,
+};
+
+fn porep_minimum_challenges(shape: &SectorShapeConfig) -> u64 {
+    shape.porep_minimum_challenges
+}
+
+fn set_porep_minimum_challenges(shape: &mut SectorShapeConfig, value: u64) {
+    shape.porep_minimum_challenges = value;
+}
+
+fn porep_partitions(shape: &SectorShapeConfig) -> u8 {
+    shape.porep_partitions
+}
+
+fn set_porep_partitions(shape: &mut SectorShapeConfig, value: u8) {
+    shape.porep_partitions = value;
+}
+
+fn layers(shape: &SectorShapeConfig) -> usize {
+    shape.layers
+}
+
+fn set_layers(shape: &mut SectorShapeConfig, value: usize) {
+    shape.layers = value;
+}
+
+fn window_post_sector_count(shape: &SectorShapeConfig) -> usize {
+    shape.window_post_sector_count
+}
+
+fn set_window_post_sector_count(shape: &mut SectorShapeConfig, value: usize) {
+    shape.window_post_sector_count = value;
 }
 
 /// The size of a single snark proof.
@@ -166,76 +144,51 @@ pub type SectorShape32GiB = SectorShapeSub8;
 pub type SectorShape32KiB = SectorShapeTop2;
 pub type SectorShape64GiB = SectorShapeTop2;
 
+fn registered_tree_shape(sector_size: u64) -> Option<TreeShape> {
+    get_sector_shape(sector_size).map(|shape| shape.tree_shape())
+}
+
 pub fn is_sector_shape_base(sector_size: u64) -> bool {
-    match sector_size {
-        SECTOR_SIZE_2_KIB | SECTOR_SIZE_8_MIB | SECTOR_SIZE_512_MIB => true,
-        _ => false,
-    }
+    registered_tree_shape(sector_size) == Some(TreeShape::Base)
 }
 
 pub fn is_sector_shape_sub2(sector_size: u64) -> bool {
-    match sector_size {
-        SECTOR_SIZE_4_KIB | SECTOR_SIZE_16_MIB | SECTOR_SIZE_1_GIB => true,
-        _ => false,
-    }
+    registered_tree_shape(sector_size) == Some(TreeShape::Sub2)
 }
 
 pub fn is_sector_shape_sub8(sector_size: u64) -> bool {
-    match sector_size {
-        SECTOR_SIZE_16_KIB | SECTOR_SIZE_32_GIB => true,
-        _ => false,
-    }
+    registered_tree_shape(sector_size) == Some(TreeShape::Sub8)
 }
 
 pub fn is_sector_shape_top2(sector_size: u64) -> bool {
-    match sector_size {
-        SECTOR_SIZE_32_KIB | SECTOR_SIZE_64_GIB => true,
-        _ => false,
-    }
+    registered_tree_shape(sector_size) == Some(TreeShape::Top2)
 }
 
-/// Calls a function with the type hint of the sector shape matching the provided sector.
-/// Panics if provided with an unknown sector size.
+/// Calls a function with the type hint of the sector shape registered for the provided sector
+/// size. Panics if provided with an unknown sector size.
 #[macro_export]
 macro_rules! with_shape {
     ($size:expr, $f:ident) => {
         with_shape!($size, $f,)
     };
-    ($size:expr, $f:ident, $($args:expr,)*) => {
-        match $size {
-            _x if $size == $crate::constants::SECTOR_SIZE_2_KIB => {
-              $f::<$crate::constants::SectorShape2KiB>($($args),*)
-            },
-            _x if $size == $crate::constants::SECTOR_SIZE_4_KIB => {
-              $f::<$crate::constants::SectorShape4KiB>($($args),*)
-            },
-            _x if $size == $crate::constants::SECTOR_SIZE_16_KIB => {
-              $f::<$crate::constants::SectorShape16KiB>($($args),*)
-            },
-            _x if $size == $crate::constants::SECTOR_SIZE_32_KIB => {
-              $f::<$crate::constants::SectorShape32KiB>($($args),*)
-            },
-            _xx if $size == $crate::constants::SECTOR_SIZE_8_MIB => {
-              $f::<$crate::constants::SectorShape8MiB>($($args),*)
-            },
-            _xx if $size == $crate::constants::SECTOR_SIZE_16_MIB => {
-              $f::<$crate::constants::SectorShape16MiB>($($args),*)
-            },
-            _x if $size == $crate::constants::SECTOR_SIZE_512_MIB => {
-              $f::<$crate::constants::SectorShape512MiB>($($args),*)
-            },
-            _x if $size == $crate::constants::SECTOR_SIZE_1_GIB => {
-              $f::<$crate::constants::SectorShape1GiB>($($args),*)
-            },
-            _x if $size == $crate::constants::SECTOR_SIZE_32_GIB => {
-              $f::<$crate::constants::SectorShape32GiB>($($args),*)
-            },
-            _x if $size == $crate::constants::SECTOR_SIZE_64_GIB => {
-              $f::<$crate::constants::SectorShape64GiB>($($args),*)
-            },
-            _ => panic!("unsupported sector size: {}", $size),
+    ($size:expr, $f:ident, $($args:expr,)*) => {{
+        let size: u64 = $size;
+        match $crate::registry::get_sector_shape(size).map(|shape| shape.tree_shape()) {
+            Some($crate::registry::TreeShape::Base) => {
+                $f::<$crate::constants::SectorShapeBase>($($args),*)
+            }
+            Some($crate::registry::TreeShape::Sub2) => {
+                $f::<$crate::constants::SectorShapeSub2>($($args),*)
+            }
+            Some($crate::registry::TreeShape::Sub8) => {
+                $f::<$crate::constants::SectorShapeSub8>($($args),*)
+            }
+            Some($crate::registry::TreeShape::Top2) => {
+                $f::<$crate::constants::SectorShapeTop2>($($args),*)
+            }
+            None => panic!("unsupported sector size: {}", size),
         }
-    };
+    }};
     ($size:expr, $f:ident, $($args:expr),*) => {
         with_shape!($size, $f, $($args,)*)
     };
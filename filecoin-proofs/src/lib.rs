@@ -5,6 +5,7 @@ pub mod constants;
 pub mod param;
 pub mod parameters;
 pub mod pieces;
+pub mod registry;
 pub mod types;
 
 mod api;
@@ -12,6 +13,12 @@ mod caches;
 mod commitment_reader;
 
 pub use api::*;
+pub use caches::{
+    clear_parameter_caches, clear_tree_handle_cache, preload_parameters,
+    preload_parameters_with_context, preload_verifying_keys, preload_verifying_keys_with_context,
+    unload_parameters, unload_parameters_with_context, ParameterSet,
+};
 pub use commitment_reader::*;
 pub use constants::*;
+pub use registry::*;
 pub use types::*;
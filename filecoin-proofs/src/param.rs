@@ -6,21 +6,27 @@ use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use blake2b_simd::State as Blake2b;
-use storage_proofs_core::parameter_cache::{
-    parameter_cache_dir, CacheEntryMetadata, PARAMETER_METADATA_EXT,
+use storage_proofs_core::{
+    parameter_cache::{parameter_cache_dir, CacheEntryMetadata, PARAMETER_METADATA_EXT},
+    settings::ProofsContext,
 };
 
 // Produces an absolute path to a file within the cache
-pub fn get_full_path_for_file_within_cache(filename: &str) -> PathBuf {
-    let mut path = parameter_cache_dir();
+pub fn get_full_path_for_file_within_cache(ctx: &ProofsContext, filename: &str) -> PathBuf {
+    let mut path = parameter_cache_dir(ctx);
     path.push(filename);
     path
 }
 
 // Produces a BLAKE2b checksum for a file within the cache
-pub fn get_digest_for_file_within_cache(filename: &str) -> Result<String> {
-    let path = get_full_path_for_file_within_cache(filename);
-    let mut file = File::open(&path).with_context(|| format!("could not open path={:?}", path))?;
+pub fn get_digest_for_file_within_cache(ctx: &ProofsContext, filename: &str) -> Result<String> {
+    let path = get_full_path_for_file_within_cache(ctx, filename);
+    get_digest_for_file(&path)
+}
+
+// Produces a BLAKE2b checksum for a file, as recorded in parameters.json
+pub fn get_digest_for_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_context(|| format!("could not open path={:?}", path))?;
     let mut hasher = Blake2b::new();
 
     io::copy(&mut file, &mut hasher)?;
@@ -28,6 +34,14 @@ pub fn get_digest_for_file_within_cache(filename: &str) -> Result<String> {
     Ok(hasher.finalize().to_hex()[..32].into())
 }
 
+// Produces a BLAKE2b checksum for in-memory file contents, as recorded in parameters.json
+pub fn get_digest_for_bytes(bytes: &[u8]) -> String {
+    let mut hasher = Blake2b::new();
+    hasher.update(bytes);
+
+    hasher.finalize().to_hex()[..32].into()
+}
+
 // Predicate which matches the provided extension against the given filename
 pub fn has_extension<S: AsRef<str>, P: AsRef<Path>>(filename: P, ext: S) -> bool {
     filename
@@ -45,13 +59,14 @@ pub fn add_extension(filename: &str, ext: &str) -> String {
 
 /// Builds a map from a parameter_id (file in cache) to metadata.
 pub fn parameter_id_to_metadata_map(
+    ctx: &ProofsContext,
     parameter_ids: &[String],
 ) -> Result<BTreeMap<String, CacheEntryMetadata>> {
     let mut map: BTreeMap<String, CacheEntryMetadata> = Default::default();
 
     for parameter_id in parameter_ids {
         let filename = add_extension(parameter_id, PARAMETER_METADATA_EXT);
-        let file_path = get_full_path_for_file_within_cache(&filename);
+        let file_path = get_full_path_for_file_within_cache(ctx, &filename);
         let file = File::open(&file_path)
             .with_context(|| format!("could not open path={:?}", file_path))?;
 
@@ -4,7 +4,8 @@ use storage_proofs_porep::stacked::{self, LayerChallenges, StackedDrg};
 use storage_proofs_post::fallback::{self, FallbackPoSt};
 
 use crate::{
-    constants::{DefaultPieceHasher, DRG_DEGREE, EXP_DEGREE, LAYERS, POREP_MINIMUM_CHALLENGES},
+    constants::{DefaultPieceHasher, DRG_DEGREE, EXP_DEGREE},
+    registry::sector_shape,
     types::{MerkleTreeTrait, PaddedBytesAmount, PoStConfig},
 };
 
@@ -80,18 +81,11 @@ pub fn setup_params(
     porep_id: [u8; 32],
     api_version: ApiVersion,
 ) -> Result<stacked::SetupParams> {
+    let shape = sector_shape(u64::from(sector_bytes))?;
     let layer_challenges = select_challenges(
         partitions,
-        *POREP_MINIMUM_CHALLENGES
-            .read()
-            .expect("POREP_MINIMUM_CHALLENGES poisoned")
-            .get(&u64::from(sector_bytes))
-            .expect("unknown sector size") as usize,
-        *LAYERS
-            .read()
-            .expect("LAYERS poisoned")
-            .get(&u64::from(sector_bytes))
-            .expect("unknown sector size"),
+        shape.porep_minimum_challenges as usize,
+        shape.layers,
     )?;
     let sector_bytes = u64::from(sector_bytes);
 
@@ -0,0 +1,284 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{LockResult, RwLock};
+
+use anyhow::{bail, ensure, Result};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use storage_proofs_core::util::NODE_SIZE;
+
+use crate::constants::{
+    SECTOR_SIZE_16_KIB, SECTOR_SIZE_16_MIB, SECTOR_SIZE_1_GIB, SECTOR_SIZE_2_KIB,
+    SECTOR_SIZE_32_GIB, SECTOR_SIZE_32_KIB, SECTOR_SIZE_4_KIB, SECTOR_SIZE_512_MIB,
+    SECTOR_SIZE_64_GIB, SECTOR_SIZE_8_MIB,
+};
+
+/// The merkle tree shapes sectors can be dispatched to, i.e. the `SectorShape*` types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TreeShape {
+    /// `SectorShapeBase`, a single oct tree.
+    Base,
+    /// `SectorShapeSub2`, two oct trees under a binary sub tree.
+    Sub2,
+    /// `SectorShapeSub8`, eight oct trees under an oct sub tree.
+    Sub8,
+    /// `SectorShapeTop2`, two `Sub8` trees under a binary top tree.
+    Top2,
+}
+
+impl TreeShape {
+    /// Returns the shape with the given base, sub and top tree arities, if it is supported.
+    pub fn from_arities(base: usize, sub: usize, top: usize) -> Option<Self> {
+        match (base, sub, top) {
+            (8, 0, 0) => Some(TreeShape::Base),
+            (8, 2, 0) => Some(TreeShape::Sub2),
+            (8, 8, 0) => Some(TreeShape::Sub8),
+            (8, 8, 2) => Some(TreeShape::Top2),
+            _ => None,
+        }
+    }
+
+    /// Returns the base, sub and top tree arities of this shape.
+    pub fn arities(self) -> (usize, usize, usize) {
+        match self {
+            TreeShape::Base => (8, 0, 0),
+            TreeShape::Sub2 => (8, 2, 0),
+            TreeShape::Sub8 => (8, 8, 0),
+            TreeShape::Top2 => (8, 8, 2),
+        }
+    }
+}
+
+/// Everything that is configured per sector size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SectorShapeConfig {
+    pub sector_size: u64,
+    pub base_arity: usize,
+    pub sub_arity: usize,
+    pub top_arity: usize,
+    pub layers: usize,
+    pub porep_partitions: u8,
+    pub porep_minimum_challenges: u64,
+    // These numbers must match those used for Window PoSt scheduling in the miner actor.
+    // Please coordinate changes with actor code.
+    // https://github.com/filecoin-project/specs-actors/blob/master/actors/abi/sector.go
+    pub window_post_sector_count: usize,
+}
+
+impl SectorShapeConfig {
+    /// Returns the tree shape matching the configured arities.
+    ///
+    /// Panics if the arities are not supported, which `register_sector_shape` rules out for
+    /// all registered shapes.
+    pub fn tree_shape(&self) -> TreeShape {
+        TreeShape::from_arities(self.base_arity, self.sub_arity, self.top_arity)
+            .expect("unsupported tree arities")
+    }
+
+    fn validate(&self) -> Result<()> {
+        ensure!(
+            TreeShape::from_arities(self.base_arity, self.sub_arity, self.top_arity).is_some(),
+            "unsupported tree arities: base {}, sub {}, top {}",
+            self.base_arity,
+            self.sub_arity,
+            self.top_arity
+        );
+        ensure!(
+            self.sector_size.is_power_of_two() && self.sector_size >= SECTOR_SIZE_2_KIB,
+            "sector size {} must be a power of two of at least {} bytes",
+            self.sector_size,
+            SECTOR_SIZE_2_KIB
+        );
+        ensure!(self.layers > 0, "layers must be positive");
+        ensure!(
+            self.porep_partitions > 0,
+            "porep partitions must be positive"
+        );
+        ensure!(
+            self.porep_minimum_challenges > 0,
+            "porep minimum challenges must be positive"
+        );
+        ensure!(
+            self.window_post_sector_count > 0,
+            "window post sector count must be positive"
+        );
+
+        // Each base tree must be a complete oct tree.
+        let nodes = self.sector_size / NODE_SIZE as u64;
+        let base_tree_count = (self.sub_arity.max(1) * self.top_arity.max(1)) as u64;
+        ensure!(
+            nodes % base_tree_count == 0,
+            "sector size {} cannot be split into {} base trees",
+            self.sector_size,
+            base_tree_count
+        );
+        let base_nodes = nodes / base_tree_count;
+        let log_base = (self.base_arity as u64).trailing_zeros();
+        ensure!(
+            base_nodes > 1 && base_nodes.trailing_zeros() % log_base == 0,
+            "sector size {} does not fit tree shape {:?}",
+            self.sector_size,
+            self.tree_shape()
+        );
+
+        Ok(())
+    }
+}
+
+const fn production_shape(
+    sector_size: u64,
+    arities: (usize, usize, usize),
+    layers: usize,
+    porep_partitions: u8,
+    porep_minimum_challenges: u64,
+    window_post_sector_count: usize,
+) -> SectorShapeConfig {
+    SectorShapeConfig {
+        sector_size,
+        base_arity: arities.0,
+        sub_arity: arities.1,
+        top_arity: arities.2,
+        layers,
+        porep_partitions,
+        porep_minimum_challenges,
+        window_post_sector_count,
+    }
+}
+
+const BASE: (usize, usize, usize) = (8, 0, 0);
+const SUB2: (usize, usize, usize) = (8, 2, 0);
+const SUB8: (usize, usize, usize) = (8, 8, 0);
+const TOP2: (usize, usize, usize) = (8, 8, 2);
+
+/// The sector shapes for which parameters have been published.
+const PRODUCTION_SHAPES: [SectorShapeConfig; 10] = [
+    production_shape(SECTOR_SIZE_2_KIB, BASE, 2, 1, 2, 2),
+    production_shape(SECTOR_SIZE_4_KIB, SUB2, 2, 1, 2, 2),
+    production_shape(SECTOR_SIZE_16_KIB, SUB8, 2, 1, 2, 2),
+    production_shape(SECTOR_SIZE_32_KIB, TOP2, 2, 1, 2, 2),
+    production_shape(SECTOR_SIZE_8_MIB, BASE, 2, 1, 2, 2),
+    production_shape(SECTOR_SIZE_16_MIB, SUB2, 2, 1, 2, 2),
+    production_shape(SECTOR_SIZE_512_MIB, BASE, 2, 1, 2, 2),
+    production_shape(SECTOR_SIZE_1_GIB, SUB2, 2, 1, 2, 2),
+    // this gives 125,279,217 constraints, fitting in a single partition
+    production_shape(SECTOR_SIZE_32_GIB, SUB8, 11, 10, 176, 2349),
+    // this gives 129,887,900 constraints, fitting in a single partition
+    production_shape(SECTOR_SIZE_64_GIB, TOP2, 11, 10, 176, 2300),
+];
+
+lazy_static! {
+    static ref SECTOR_SHAPES: RwLock<BTreeMap<u64, SectorShapeConfig>> = RwLock::new(
+        PRODUCTION_SHAPES
+            .iter()
+            .map(|shape| (shape.sector_size, *shape))
+            .collect()
+    );
+}
+
+/// Returns the registered shape for `sector_size`, if any.
+pub fn get_sector_shape(sector_size: u64) -> Option<SectorShapeConfig> {
+    SECTOR_SHAPES
+        .read()
+        .expect("SECTOR_SHAPES poisoned")
+        .get(&sector_size)
+        .copied()
+}
+
+/// Returns the registered shape for `sector_size`, or an error if the size is unknown.
+pub fn sector_shape(sector_size: u64) -> Result<SectorShapeConfig> {
+    match get_sector_shape(sector_size) {
+        Some(shape) => Ok(shape),
+        None => bail!("unknown sector size: {}", sector_size),
+    }
+}
+
+/// Registers a sector shape, e.g. an additional test size for a devnet.
+///
+/// The shapes of the production sizes are consensus critical and cannot be replaced, shapes of
+/// other sizes can be registered again.
+pub fn register_sector_shape(shape: SectorShapeConfig) -> Result<()> {
+    ensure!(
+        !PRODUCTION_SHAPES
+            .iter()
+            .any(|production| production.sector_size == shape.sector_size),
+        "sector size {} is built in and cannot be registered",
+        shape.sector_size
+    );
+
+    override_sector_shape(shape)
+}
+
+/// Registers a sector shape, replacing the shape of a production size if needed.
+///
+/// This only exists for benchmarks evaluating other layer, partition or challenge counts.
+/// Proofs generated for a replaced production shape are not valid on chain.
+pub fn override_sector_shape(shape: SectorShapeConfig) -> Result<()> {
+    shape.validate()?;
+
+    SECTOR_SHAPES
+        .write()
+        .expect("SECTOR_SHAPES poisoned")
+        .insert(shape.sector_size, shape);
+
+    Ok(())
+}
+
+/// Returns all registered sector sizes, in ascending order.
+pub fn registered_sector_sizes() -> Vec<u64> {
+    SECTOR_SHAPES
+        .read()
+        .expect("SECTOR_SHAPES poisoned")
+        .keys()
+        .copied()
+        .collect()
+}
+
+/// A view of one setting of all registered sector shapes, keyed by sector size.
+///
+/// This backs the deprecated per-size statics in `constants`, which used to be
+/// `RwLock<HashMap>`s, so that e.g. `LAYERS.read()` and `LAYERS.write()?.insert(..)` keep
+/// working.
+pub struct SectorShapeTable<T> {
+    pub(crate) field: fn(&SectorShapeConfig) -> T,
+    pub(crate) set_field: fn(&mut SectorShapeConfig, T),
+}
+
+impl<T> SectorShapeTable<T> {
+    /// Returns the setting of every registered sector size. Never fails, the `LockResult`
+    /// only matches the former `RwLock::read`.
+    pub fn read(&self) -> LockResult<HashMap<u64, T>> {
+        let shapes = SECTOR_SHAPES.read().expect("SECTOR_SHAPES poisoned");
+
+        Ok(shapes
+            .iter()
+            .map(|(sector_size, shape)| (*sector_size, (self.field)(shape)))
+            .collect())
+    }
+
+    /// Returns a writer changing the setting of registered sector sizes. Never fails, the
+    /// `LockResult` only matches the former `RwLock::write`.
+    pub fn write(&self) -> LockResult<SectorShapeTableWriter<'_, T>> {
+        Ok(SectorShapeTableWriter { table: self })
+    }
+}
+
+/// Changes one setting of registered sector shapes, see `SectorShapeTable::write`.
+pub struct SectorShapeTableWriter<'a, T> {
+    table: &'a SectorShapeTable<T>,
+}
+
+impl<'a, T> SectorShapeTableWriter<'a, T> {
+    /// Replaces the setting of `sector_size` through `override_sector_shape` and returns the
+    /// previous value, like the former `HashMap::insert`.
+    ///
+    /// Panics if `sector_size` is not registered, as there is no complete shape to change, or
+    /// if the changed shape is invalid. Use `register_sector_shape` or `override_sector_shape`
+    /// to handle these errors.
+    pub fn insert(&mut self, sector_size: u64, value: T) -> Option<T> {
+        let mut shape = sector_shape(sector_size).expect("sector size is not registered");
+        let previous = (self.table.field)(&shape);
+        (self.table.set_field)(&mut shape, value);
+        override_sector_shape(shape).expect("invalid sector shape");
+
+        Some(previous)
+    }
+}
@@ -1,5 +1,8 @@
 pub use merkletree::store::StoreConfig;
-pub use storage_proofs_core::merkle::{MerkleProof, MerkleTreeTrait};
+pub use storage_proofs_core::{
+    merkle::{MerkleProof, MerkleTreeTrait},
+    settings::{ProofsContext, Settings},
+};
 pub use storage_proofs_porep::stacked::{Labels, PersistentAux, TemporaryAux};
 
 use filecoin_hashers::Hasher;
@@ -8,6 +8,7 @@ use storage_proofs_core::{
         parameter_cache_metadata_path, parameter_cache_params_path,
         parameter_cache_verifying_key_path, CacheableParameters,
     },
+    settings::ProofsContext,
 };
 use storage_proofs_post::fallback::{FallbackPoStCircuit, FallbackPoStCompound};
 
@@ -78,18 +79,27 @@ impl PoStConfig {
         }
     }
 
-    pub fn get_cache_metadata_path<Tree: 'static + MerkleTreeTrait>(&self) -> Result<PathBuf> {
+    pub fn get_cache_metadata_path<Tree: 'static + MerkleTreeTrait>(
+        &self,
+        ctx: &ProofsContext,
+    ) -> Result<PathBuf> {
         let id = self.get_cache_identifier::<Tree>()?;
-        Ok(parameter_cache_metadata_path(&id))
+        Ok(parameter_cache_metadata_path(ctx, &id))
     }
 
-    pub fn get_cache_verifying_key_path<Tree: 'static + MerkleTreeTrait>(&self) -> Result<PathBuf> {
+    pub fn get_cache_verifying_key_path<Tree: 'static + MerkleTreeTrait>(
+        &self,
+        ctx: &ProofsContext,
+    ) -> Result<PathBuf> {
         let id = self.get_cache_identifier::<Tree>()?;
-        Ok(parameter_cache_verifying_key_path(&id))
+        Ok(parameter_cache_verifying_key_path(ctx, &id))
     }
 
-    pub fn get_cache_params_path<Tree: 'static + MerkleTreeTrait>(&self) -> Result<PathBuf> {
+    pub fn get_cache_params_path<Tree: 'static + MerkleTreeTrait>(
+        &self,
+        ctx: &ProofsContext,
+    ) -> Result<PathBuf> {
         let id = self.get_cache_identifier::<Tree>()?;
-        Ok(parameter_cache_params_path(&id))
+        Ok(parameter_cache_params_path(ctx, &id))
     }
 }
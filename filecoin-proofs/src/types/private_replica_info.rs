@@ -1,26 +1,30 @@
+use std::any::type_name;
 use std::cmp::Ordering;
 use std::fs;
 use std::hash::{Hash, Hasher as StdHasher};
 use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use anyhow::{ensure, Context, Result};
 use bincode::deserialize;
 use filecoin_hashers::Hasher;
 use generic_array::typenum::Unsigned;
 use log::trace;
-use merkletree::store::StoreConfig;
+use merkletree::store::{ReplicaConfig, StoreConfig};
 use storage_proofs_core::{
     cache_key::CacheKey,
     merkle::{
         create_tree, get_base_tree_count, split_config_and_replica, MerkleTreeTrait,
         MerkleTreeWrapper,
     },
-    util::default_rows_to_discard,
+    settings::ProofsContext,
+    util::default_rows_to_discard_with_context,
 };
 
 use crate::{
     api::{as_safe_commitment, get_base_tree_leafs, get_base_tree_size},
+    caches::get_tree_handle,
     types::{Commitment, PersistentAux, SectorSize},
 };
 
@@ -139,6 +143,24 @@ impl<Tree: 'static + MerkleTreeTrait> PrivateReplicaInfo<Tree> {
             Tree::SubTreeArity,
             Tree::TopTreeArity,
         >,
+    > {
+        self.merkle_tree_with_context(&ProofsContext::default(), sector_size)
+    }
+
+    /// Same as `merkle_tree`, for a `tree_r_last` cached with the rows to discard configured in
+    /// `ctx`.
+    pub fn merkle_tree_with_context(
+        &self,
+        ctx: &ProofsContext,
+        sector_size: SectorSize,
+    ) -> Result<
+        MerkleTreeWrapper<
+            Tree::Hasher,
+            Tree::Store,
+            Tree::Arity,
+            Tree::SubTreeArity,
+            Tree::TopTreeArity,
+        >,
     > {
         let base_tree_size = get_base_tree_size::<Tree>(sector_size)?;
         let base_tree_leafs = get_base_tree_leafs::<Tree>(base_tree_size)?;
@@ -146,27 +168,89 @@ impl<Tree: 'static + MerkleTreeTrait> PrivateReplicaInfo<Tree> {
             "post: base tree size {}, base tree leafs {}, rows_to_discard {}, arities [{}, {}, {}]",
             base_tree_size,
             base_tree_leafs,
-            default_rows_to_discard(base_tree_leafs, Tree::Arity::to_usize()),
+            default_rows_to_discard_with_context(ctx, base_tree_leafs, Tree::Arity::to_usize()),
             Tree::Arity::to_usize(),
             Tree::SubTreeArity::to_usize(),
             Tree::TopTreeArity::to_usize(),
         );
 
+        let (configs, replica_config) = self.tree_r_last_configs(ctx, sector_size)?;
+
+        create_tree::<Tree>(base_tree_size, &configs, Some(&replica_config))
+    }
+
+    /// The configs of the base trees of `tree_r_last` and of the replica they are built over.
+    fn tree_r_last_configs(
+        &self,
+        ctx: &ProofsContext,
+        sector_size: SectorSize,
+    ) -> Result<(Vec<StoreConfig>, ReplicaConfig)> {
+        let base_tree_size = get_base_tree_size::<Tree>(sector_size)?;
+        let base_tree_leafs = get_base_tree_leafs::<Tree>(base_tree_size)?;
+
         let mut config = StoreConfig::new(
             self.cache_dir_path(),
             CacheKey::CommRLastTree.to_string(),
-            default_rows_to_discard(base_tree_leafs, Tree::Arity::to_usize()),
+            default_rows_to_discard_with_context(ctx, base_tree_leafs, Tree::Arity::to_usize()),
         );
         config.size = Some(base_tree_size);
 
         let tree_count = get_base_tree_count::<Tree>();
-        let (configs, replica_config) = split_config_and_replica(
+        split_config_and_replica(
             config,
             self.replica_path().to_path_buf(),
             base_tree_leafs,
             tree_count,
-        )?;
+        )
+    }
 
-        create_tree::<Tree>(base_tree_size, &configs, Some(&replica_config))
+    /// Same as `merkle_tree_with_context`, but shares the opened tree between calls if the
+    /// `use_tree_handle_cache` setting is enabled.
+    pub fn shared_merkle_tree_with_context(
+        &self,
+        ctx: &ProofsContext,
+        sector_size: SectorSize,
+    ) -> Result<
+        Arc<
+            MerkleTreeWrapper<
+                Tree::Hasher,
+                Tree::Store,
+                Tree::Arity,
+                Tree::SubTreeArity,
+                Tree::TopTreeArity,
+            >,
+        >,
+    > {
+        if !ctx.settings().use_tree_handle_cache {
+            return Ok(Arc::new(self.merkle_tree_with_context(ctx, sector_size)?));
+        }
+
+        let base_tree_size = get_base_tree_size::<Tree>(sector_size)?;
+        let base_tree_leafs = get_base_tree_leafs::<Tree>(base_tree_size)?;
+        let rows_to_discard =
+            default_rows_to_discard_with_context(ctx, base_tree_leafs, Tree::Arity::to_usize());
+        let identifier = format!(
+            "{}[{:?}, {:?}, {}, {}]",
+            type_name::<Tree>(),
+            self.replica,
+            self.cache_dir,
+            hex::encode(&self.comm_r),
+            rows_to_discard
+        );
+
+        // Each base tree keeps its tree file and the replica open.
+        let open_files = 2 * get_base_tree_count::<Tree>() as u64;
+
+        let (configs, _) = self.tree_r_last_configs(ctx, sector_size)?;
+        let mut files = vec![self.replica_path().to_path_buf()];
+        files.extend(
+            configs
+                .iter()
+                .map(|config| StoreConfig::data_path(&config.path, &config.id)),
+        );
+
+        get_tree_handle(ctx, identifier, &files, open_files, || {
+            self.merkle_tree_with_context(ctx, sector_size)
+        })
     }
 }
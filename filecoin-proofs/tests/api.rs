@@ -1,29 +1,43 @@
 use std::collections::BTreeMap;
-use std::fs::{read_dir, remove_file};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::fs::{read, read_dir, remove_file, OpenOptions};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Once;
 
 use anyhow::Result;
-use bellperson::bls::Fr;
+use bellperson::{
+    bls::{Bls12, Fr},
+    groth16,
+};
 use ff::Field;
 use filecoin_hashers::Hasher;
 use filecoin_proofs::{
-    add_piece, clear_cache, compute_comm_d, fauxrep_aux, generate_fallback_sector_challenges,
+    add_piece, aggregate_window_post_partition_proofs, clear_cache, compute_comm_d, export_proofs,
+    export_public_inputs, export_verifying_key, fauxrep_aux, generate_fallback_sector_challenges,
     generate_piece_commitment, generate_single_vanilla_proof, generate_window_post,
+    generate_window_post_partition_with_vanilla, generate_window_post_vanilla_proofs,
     generate_window_post_with_vanilla, generate_winning_post,
-    generate_winning_post_sector_challenge, generate_winning_post_with_vanilla, seal_commit_phase1,
-    seal_commit_phase2, seal_pre_commit_phase1, seal_pre_commit_phase2, unseal_range,
-    validate_cache_for_commit, validate_cache_for_precommit_phase2, verify_seal,
-    verify_window_post, verify_winning_post, Commitment, DefaultTreeDomain, MerkleTreeTrait,
-    PaddedBytesAmount, PieceInfo, PoRepConfig, PoRepProofPartitions, PoStConfig, PoStType,
-    PrivateReplicaInfo, ProverId, PublicReplicaInfo, SealPreCommitOutput,
-    SealPreCommitPhase1Output, SectorShape16KiB, SectorShape2KiB, SectorShape32KiB,
-    SectorShape4KiB, SectorSize, UnpaddedByteIndex, UnpaddedBytesAmount, POREP_PARTITIONS,
-    SECTOR_SIZE_16_KIB, SECTOR_SIZE_2_KIB, SECTOR_SIZE_32_KIB, SECTOR_SIZE_4_KIB,
-    WINDOW_POST_CHALLENGE_COUNT, WINDOW_POST_SECTOR_COUNT, WINNING_POST_CHALLENGE_COUNT,
-    WINNING_POST_SECTOR_COUNT,
+    generate_winning_post_sector_challenge, generate_winning_post_with_vanilla, import_proofs,
+    import_public_inputs, import_verifying_key, preload_verifying_keys_with_context,
+    prepare_verifying_key, read_porep_verifying_key, read_verifying_key, reseal_sector,
+    scrub_replica, scrub_replica_sampled, scrub_replica_with_context, seal_commit_phase1,
+    seal_commit_phase2, seal_pre_commit_phase1, seal_pre_commit_phase2, seal_public_inputs,
+    sector_shape, unload_parameters_with_context, unseal_range, validate_cache_for_commit,
+    validate_cache_for_precommit_phase2, verify_batch_seal_with_vk, verify_seal,
+    verify_seal_with_vk, verify_window_post, verify_window_post_batch,
+    verify_window_post_batch_with_vk, verify_window_post_with_vk, verify_winning_post,
+    verify_winning_post_batch, verify_winning_post_batch_with_vk, verify_winning_post_with_vk,
+    window_post_public_inputs, winning_post_public_inputs, write_porep_r1cs, write_seal_witness,
+    Commitment, DefaultTreeDomain, FaultReason, MerkleTreeTrait, NodeRange, PaddedBytesAmount,
+    ParameterSet, PieceInfo, PoRepConfig, PoRepProofPartitions, PoStConfig, PoStType,
+    PrivateReplicaInfo, ProofsContext, ProverId, PublicReplicaInfo, SealCommitPhase1Output,
+    SealPreCommitOutput, SealPreCommitPhase1Output, SectorShape16KiB, SectorShape2KiB,
+    SectorShape32KiB, SectorShape4KiB, SectorShape8MiB, SectorSize, Settings, UnpaddedByteIndex,
+    UnpaddedBytesAmount, SECTOR_SIZE_16_KIB, SECTOR_SIZE_2_KIB, SECTOR_SIZE_32_KIB,
+    SECTOR_SIZE_4_KIB, SECTOR_SIZE_8_MIB, WINDOW_POST_CHALLENGE_COUNT,
+    WINNING_POST_CHALLENGE_COUNT, WINNING_POST_SECTOR_COUNT,
 };
+use fr32::bytes_into_fr;
 use rand::{random, Rng, SeedableRng};
 use rand_xorshift::XorShiftRng;
 use storage_proofs_core::{api_version::ApiVersion, is_legacy_porep_id, sector::SectorId};
@@ -276,6 +290,143 @@ fn test_resumable_seal_v1_1() {
     run_resumable_seal::<SectorShape2KiB>(false, 1, &porep_id, ApiVersion::V1_1_0);
 }
 
+#[test]
+fn test_reseal_sector_2kib_base_8() -> Result<()> {
+    init_logger();
+
+    let sector_size = SECTOR_SIZE_2_KIB;
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let prover_fr: DefaultTreeDomain = Fr::random(rng).into();
+    let mut prover_id = [0u8; 32];
+    prover_id.copy_from_slice(AsRef::<[u8]>::as_ref(&prover_fr));
+
+    let config = porep_config(sector_size, ARBITRARY_POREP_ID_V1_1_0, ApiVersion::V1_1_0);
+    let ticket = rng.gen();
+    let sector_id = rng.gen::<u64>().into();
+
+    let (mut piece_file, _) = generate_piece_file(sector_size)?;
+    let number_of_bytes_in_piece = UnpaddedBytesAmount::from(PaddedBytesAmount(sector_size));
+    let piece_info = generate_piece_commitment(piece_file.as_file_mut(), number_of_bytes_in_piece)?;
+    piece_file.as_file_mut().seek(SeekFrom::Start(0))?;
+    let mut unsealed_file = NamedTempFile::new()?;
+    add_piece(
+        &mut piece_file,
+        &mut unsealed_file,
+        number_of_bytes_in_piece,
+        &[],
+    )?;
+    let piece_infos = vec![piece_info];
+
+    let sealed_sector_file = NamedTempFile::new()?;
+    let cache_dir = tempdir()?;
+    let phase1_output = seal_pre_commit_phase1::<_, _, _, SectorShape2KiB>(
+        config,
+        cache_dir.path(),
+        unsealed_file.path(),
+        sealed_sector_file.path(),
+        prover_id,
+        sector_id,
+        ticket,
+        &piece_infos,
+    )?;
+    let SealPreCommitOutput { comm_r, comm_d } = seal_pre_commit_phase2(
+        config,
+        phase1_output,
+        cache_dir.path(),
+        sealed_sector_file.path(),
+    )?;
+
+    let reseal = |expected_comm_r: Commitment| -> Result<(NamedTempFile, SealPreCommitOutput)> {
+        let resealed_file = NamedTempFile::new()?;
+        let cache_dir = tempdir()?;
+        let out = reseal_sector::<_, _, _, SectorShape2KiB>(
+            config,
+            cache_dir.path(),
+            unsealed_file.path(),
+            resealed_file.path(),
+            prover_id,
+            sector_id,
+            ticket,
+            &piece_infos,
+            comm_d,
+            expected_comm_r,
+        )?;
+
+        Ok((resealed_file, out))
+    };
+
+    let (resealed_file, out) = reseal(comm_r)?;
+    assert_eq!(out.comm_r, comm_r);
+    assert_eq!(out.comm_d, comm_d);
+    assert_eq!(
+        read(resealed_file.path())?,
+        read(sealed_sector_file.path())?
+    );
+
+    let mut wrong_comm_r = comm_r;
+    wrong_comm_r[0] ^= 1;
+    assert!(reseal(wrong_comm_r).is_err(), "accepted a wrong comm_r");
+
+    // Pieces not matching comm_d are rejected before anything is sealed.
+    let mut wrong_comm_d = comm_d;
+    wrong_comm_d[0] ^= 1;
+    let resealed_file = NamedTempFile::new()?;
+    let empty_cache_dir = tempdir()?;
+    assert!(
+        reseal_sector::<_, _, _, SectorShape2KiB>(
+            config,
+            empty_cache_dir.path(),
+            unsealed_file.path(),
+            resealed_file.path(),
+            prover_id,
+            sector_id,
+            ticket,
+            &piece_infos,
+            wrong_comm_d,
+            comm_r,
+        )
+        .is_err(),
+        "accepted a wrong comm_d"
+    );
+    assert_eq!(read_dir(empty_cache_dir.path())?.count(), 0);
+    assert_eq!(resealed_file.as_file().metadata()?.len(), 0);
+
+    // An existing replica is not overwritten.
+    let sealed = read(sealed_sector_file.path())?;
+    assert!(reseal_sector::<_, _, _, SectorShape2KiB>(
+        config,
+        empty_cache_dir.path(),
+        unsealed_file.path(),
+        sealed_sector_file.path(),
+        prover_id,
+        sector_id,
+        ticket,
+        &piece_infos,
+        comm_d,
+        comm_r,
+    )
+    .is_err());
+    assert_eq!(read(sealed_sector_file.path())?, sealed);
+
+    // The cache directory must be empty.
+    let resealed_file = NamedTempFile::new()?;
+    assert!(reseal_sector::<_, _, _, SectorShape2KiB>(
+        config,
+        cache_dir.path(),
+        unsealed_file.path(),
+        resealed_file.path(),
+        prover_id,
+        sector_id,
+        ticket,
+        &piece_infos,
+        comm_d,
+        comm_r,
+    )
+    .is_err());
+
+    Ok(())
+}
+
 /// Create a seal, delete a layer and resume
 ///
 /// The current code works on two layers only. The `layer_to_delete` specifies (zero-based) which
@@ -557,6 +708,762 @@ fn winning_post<Tree: 'static + MerkleTreeTrait>(
         verify_winning_post::<Tree>(&config, &randomness, &pub_replicas[..], prover_id, &proof)?;
     assert!(valid, "proof did not verify");
 
+    // Batch verification reports the proof checked against other randomness as invalid.
+    let mut other_randomness = randomness;
+    other_randomness[0] ^= 1;
+    let valid = verify_winning_post_batch::<Tree>(
+        &config,
+        &[
+            (randomness, prover_id, &pub_replicas[..], &proof[..]),
+            (other_randomness, prover_id, &pub_replicas[..], &proof[..]),
+            (randomness, prover_id, &pub_replicas[..], &proof[..]),
+        ],
+    )?;
+    assert_eq!(valid, vec![true, false, true]);
+
+    // Verifying against the cached verifying key passed in explicitly is equivalent.
+    let vk_path = config.get_cache_verifying_key_path::<Tree>(&ProofsContext::default())?;
+    let raw_verifying_key = read_verifying_key(&read(vk_path)?)?;
+    let verifying_key = prepare_verifying_key(&raw_verifying_key);
+    let valid = verify_winning_post_with_vk::<Tree>(
+        &config,
+        &verifying_key,
+        &randomness,
+        &pub_replicas[..],
+        prover_id,
+        &proof,
+    )?;
+    assert!(valid, "proof did not verify with explicit verifying key");
+
+    // The verifying key, proof and public inputs survive a round trip through their JSON export.
+    let vk_json = export_verifying_key(&raw_verifying_key);
+    let public_inputs =
+        winning_post_public_inputs::<Tree>(&config, &randomness, &pub_replicas[..], prover_id)?;
+    assert_eq!(public_inputs.len(), 1);
+    assert_eq!(vk_json.ic.len(), public_inputs[0].len() + 1);
+    assert_eq!(
+        import_public_inputs(&export_public_inputs(&public_inputs))?,
+        public_inputs
+    );
+    verify_exported_proofs(&raw_verifying_key, &proof, &public_inputs)?;
+
+    let imported_vk =
+        import_verifying_key(&serde_json::from_str(&serde_json::to_string(&vk_json)?)?)?;
+    let imported_proof = import_proofs(&export_proofs(&proof)?)?;
+    assert_eq!(imported_proof, proof);
+    let valid = verify_winning_post_with_vk::<Tree>(
+        &config,
+        &prepare_verifying_key(&imported_vk),
+        &randomness,
+        &pub_replicas[..],
+        prover_id,
+        &imported_proof,
+    )?;
+    assert!(valid, "proof did not verify with imported verifying key");
+
+    Ok(())
+}
+
+/// Verifies each partition proof within `proof` against the corresponding `public_inputs` with
+/// plain Groth16 verification, as a verifier which only has the JSON exports would.
+fn verify_exported_proofs(
+    verifying_key: &groth16::VerifyingKey<Bls12>,
+    proof: &[u8],
+    public_inputs: &[Vec<Fr>],
+) -> Result<()> {
+    let verifying_key = import_verifying_key(&export_verifying_key(verifying_key))?;
+    let pvk = groth16::prepare_verifying_key(&verifying_key);
+    let proofs = export_proofs(proof)?;
+    let public_inputs = import_public_inputs(&export_public_inputs(public_inputs))?;
+    assert_eq!(proofs.len(), public_inputs.len());
+
+    for (proof, inputs) in proofs.iter().zip(&public_inputs) {
+        assert_eq!(verifying_key.ic.len(), inputs.len() + 1);
+        let proof = import_proofs(std::slice::from_ref(proof))?;
+        let proof = groth16::Proof::<Bls12>::read(&proof[..])?;
+        assert!(
+            groth16::verify_proof(&pvk, &proof, inputs)?,
+            "proof did not verify against its exported public inputs"
+        );
+
+        let mut wrong_inputs = inputs.clone();
+        wrong_inputs[0].add_assign(&Fr::one());
+        assert!(!groth16::verify_proof(&pvk, &proof, &wrong_inputs)?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_scrub_replica_2kib_base_8() -> Result<()> {
+    scrub_replica_detects_corruption::<SectorShape2KiB>(SECTOR_SIZE_2_KIB, 10, 0..64)
+}
+
+#[test]
+fn test_scrub_replica_4kib_sub_8_2() -> Result<()> {
+    scrub_replica_detects_corruption::<SectorShape4KiB>(SECTOR_SIZE_4_KIB, 70, 64..128)
+}
+
+#[test]
+fn test_scrub_replica_16kib_sub_8_8() -> Result<()> {
+    scrub_replica_detects_corruption::<SectorShape16KiB>(SECTOR_SIZE_16_KIB, 300, 256..320)
+}
+
+#[test]
+fn test_scrub_replica_32kib_top_8_8_2() -> Result<()> {
+    scrub_replica_detects_corruption::<SectorShape32KiB>(SECTOR_SIZE_32_KIB, 1000, 960..1024)
+}
+
+#[test]
+#[ignore]
+fn test_scrub_replica_8mib_base_8() -> Result<()> {
+    scrub_replica_detects_corruption::<SectorShape8MiB>(SECTOR_SIZE_8_MIB, 1000, 512..1024)
+}
+
+fn scrub_replica_detects_corruption<Tree: 'static + MerkleTreeTrait>(
+    sector_size: u64,
+    corrupted_node: u64,
+    expected_range: std::ops::Range<u64>,
+) -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let (_, replica, _, cache_dir) = create_fake_seal::<_, Tree>(
+        rng,
+        sector_size,
+        &ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    )?;
+    let nodes = sector_size / 32;
+    let subtree_count = (nodes / (expected_range.end - expected_range.start)) as usize;
+
+    let report = scrub_replica::<Tree>(sector_size.into(), cache_dir.path(), replica.path())?;
+    assert!(report.is_intact(), "fresh replica reported as corrupted");
+    assert_eq!(report.nodes_checked, nodes);
+
+    // Flip a bit of a single node, keeping it a valid field element.
+    let mut file = OpenOptions::new().write(true).open(replica.path())?;
+    file.seek(SeekFrom::Start(corrupted_node * 32))?;
+    file.write_all(&[1])?;
+    file.sync_all()?;
+
+    let expected = vec![NodeRange {
+        start: expected_range.start,
+        end: expected_range.end,
+    }];
+
+    let report = scrub_replica::<Tree>(sector_size.into(), cache_dir.path(), replica.path())?;
+    assert!(!report.is_intact(), "corruption not detected");
+    assert!(report.cache_consistent);
+    assert_eq!(report.corrupted_nodes, expected);
+
+    let report = scrub_replica_sampled::<_, Tree>(
+        rng,
+        sector_size.into(),
+        cache_dir.path(),
+        replica.path(),
+        subtree_count,
+    )?;
+    assert_eq!(report.nodes_checked, nodes);
+    assert_eq!(report.corrupted_nodes, expected);
+
+    // Sampling nothing would report the replica as intact without checking it.
+    assert!(scrub_replica_sampled::<_, Tree>(
+        rng,
+        sector_size.into(),
+        cache_dir.path(),
+        replica.path(),
+        0,
+    )
+    .is_err());
+
+    // Sampling fewer subtrees only checks the nodes below them.
+    if subtree_count > 1 {
+        let sample_count = subtree_count / 2;
+        let report = scrub_replica_sampled::<_, Tree>(
+            rng,
+            sector_size.into(),
+            cache_dir.path(),
+            replica.path(),
+            sample_count,
+        )?;
+        assert_eq!(
+            report.nodes_checked,
+            sample_count as u64 * (expected_range.end - expected_range.start)
+        );
+        assert!(report.corrupted_nodes.is_empty() || report.corrupted_nodes == expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_scrub_replica_with_context_8mib_base_8() -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let (_, replica, _, cache_dir) = create_fake_seal::<_, SectorShape8MiB>(
+        rng,
+        SECTOR_SIZE_8_MIB,
+        &ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    )?;
+
+    // The replica was sealed with the default settings.
+    let sealed_with = ProofsContext::default();
+    let report = scrub_replica_with_context::<SectorShape8MiB>(
+        &sealed_with,
+        SECTOR_SIZE_8_MIB.into(),
+        cache_dir.path(),
+        replica.path(),
+    )?;
+    assert!(report.is_intact());
+
+    // A tree_r_last of other settings is rejected, rather than compared with the wrong row.
+    let other = ProofsContext::new(Settings {
+        rows_to_discard: sealed_with.settings().rows_to_discard + 1,
+        ..sealed_with.settings().clone()
+    });
+    assert!(scrub_replica_with_context::<SectorShape8MiB>(
+        &other,
+        SECTOR_SIZE_8_MIB.into(),
+        cache_dir.path(),
+        replica.path(),
+    )
+    .is_err());
+
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_unload_parameters_with_context_2kib() -> Result<()> {
+    let default = ProofsContext::default();
+    // The same directory, named differently, so that the keys are loaded a second time under
+    // the identifier of a non-default cache.
+    let other = ProofsContext::new(Settings {
+        parameter_cache: format!("{}/", default.settings().parameter_cache),
+        ..default.settings().clone()
+    });
+
+    let parameter_set = ParameterSet::PoRep(porep_config(
+        SECTOR_SIZE_2_KIB,
+        ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    ));
+    preload_verifying_keys_with_context(&other, &[parameter_set.clone()])?;
+
+    let identifier = parameter_set.identifier();
+    assert!(unload_parameters_with_context(&other, &identifier));
+    assert!(!unload_parameters_with_context(&other, &identifier));
+
+    Ok(())
+}
+
+#[test]
+fn test_read_porep_verifying_key_rejects_invalid_digest() {
+    let config = porep_config(
+        SECTOR_SIZE_2_KIB,
+        ARBITRARY_POREP_ID_V1_0_0,
+        ApiVersion::V1_0_0,
+    );
+    assert!(
+        read_porep_verifying_key::<SectorShape2KiB>(config, b"not a verifying key").is_err(),
+        "accepted verifying key with invalid digest"
+    );
+}
+
+/// A 2KiB sector sealed with a proof, along with everything its verification takes.
+struct SealProof {
+    config: PoRepConfig,
+    comm_r: Commitment,
+    comm_d: Commitment,
+    prover_id: ProverId,
+    sector_id: SectorId,
+    ticket: [u8; 32],
+    seed: [u8; 32],
+    proof: Vec<u8>,
+}
+
+fn seal_commit_phase1_2kib() -> Result<(
+    PoRepConfig,
+    ProverId,
+    SectorId,
+    SealCommitPhase1Output<SectorShape2KiB>,
+)> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let prover_fr: DefaultTreeDomain = Fr::random(rng).into();
+    let mut prover_id = [0u8; 32];
+    prover_id.copy_from_slice(AsRef::<[u8]>::as_ref(&prover_fr));
+
+    let (mut piece_file, _) = generate_piece_file(SECTOR_SIZE_2_KIB)?;
+    let sealed_sector_file = NamedTempFile::new()?;
+    let cache_dir = tempdir()?;
+
+    let config = porep_config(
+        SECTOR_SIZE_2_KIB,
+        ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    );
+    let ticket = rng.gen();
+    let seed = rng.gen();
+    let sector_id = rng.gen::<u64>().into();
+
+    let (piece_infos, phase1_output) = run_seal_pre_commit_phase1::<SectorShape2KiB>(
+        config,
+        prover_id,
+        sector_id,
+        ticket,
+        &cache_dir,
+        &mut piece_file,
+        &sealed_sector_file,
+    )?;
+    let pre_commit_output = seal_pre_commit_phase2(
+        config,
+        phase1_output,
+        cache_dir.path(),
+        sealed_sector_file.path(),
+    )?;
+
+    let phase1_output = seal_commit_phase1::<_, SectorShape2KiB>(
+        config,
+        cache_dir.path(),
+        sealed_sector_file.path(),
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        pre_commit_output,
+        &piece_infos,
+    )?;
+
+    Ok((config, prover_id, sector_id, phase1_output))
+}
+
+fn seal_proof_2kib() -> Result<SealProof> {
+    let (config, prover_id, sector_id, phase1_output) = seal_commit_phase1_2kib()?;
+    let comm_r = phase1_output.comm_r;
+    let comm_d = phase1_output.comm_d;
+    let ticket = phase1_output.ticket;
+    let seed = phase1_output.seed;
+    let commit_output = seal_commit_phase2(config, phase1_output, prover_id, sector_id)?;
+
+    Ok(SealProof {
+        config,
+        comm_r,
+        comm_d,
+        prover_id,
+        sector_id,
+        ticket,
+        seed,
+        proof: commit_output.proof,
+    })
+}
+
+/// Returns `verifying_key` with its delta replaced by its gamma, so that it rejects every proof
+/// while accepting the same number of public inputs.
+fn wrong_verifying_key(
+    verifying_key: &groth16::VerifyingKey<Bls12>,
+) -> groth16::PreparedVerifyingKey<Bls12> {
+    let mut wrong = verifying_key.clone();
+    wrong.delta_g2 = wrong.gamma_g2;
+    prepare_verifying_key(&wrong)
+}
+
+fn porep_verifying_key_2kib(config: PoRepConfig) -> Result<groth16::VerifyingKey<Bls12>> {
+    let vk_path =
+        config.get_cache_verifying_key_path::<SectorShape2KiB>(&ProofsContext::default())?;
+    read_verifying_key(&read(vk_path)?)
+}
+
+fn verify_seal_proof_with_vk(
+    sealed: &SealProof,
+    verifying_key: &groth16::PreparedVerifyingKey<Bls12>,
+) -> Result<bool> {
+    verify_seal_with_vk::<SectorShape2KiB>(
+        sealed.config,
+        verifying_key,
+        sealed.comm_r,
+        sealed.comm_d,
+        sealed.prover_id,
+        sealed.sector_id,
+        sealed.ticket,
+        sealed.seed,
+        &sealed.proof,
+    )
+}
+
+fn verify_batch_seal_proof_with_vk(
+    sealed: &SealProof,
+    verifying_key: &groth16::PreparedVerifyingKey<Bls12>,
+) -> Result<bool> {
+    verify_batch_seal_with_vk::<SectorShape2KiB>(
+        sealed.config,
+        verifying_key,
+        &[sealed.comm_r, sealed.comm_r],
+        &[sealed.comm_d, sealed.comm_d],
+        &[sealed.prover_id, sealed.prover_id],
+        &[sealed.sector_id, sealed.sector_id],
+        &[sealed.ticket, sealed.ticket],
+        &[sealed.seed, sealed.seed],
+        &[&sealed.proof[..], &sealed.proof[..]],
+    )
+}
+
+#[test]
+#[ignore]
+fn test_verify_seal_with_vk_2kib_base_8() -> Result<()> {
+    let sealed = seal_proof_2kib()?;
+    let verifying_key = porep_verifying_key_2kib(sealed.config)?;
+
+    assert!(verify_seal_proof_with_vk(
+        &sealed,
+        &prepare_verifying_key(&verifying_key)
+    )?);
+
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_verify_seal_with_vk_rejects_wrong_key_2kib_base_8() -> Result<()> {
+    let sealed = seal_proof_2kib()?;
+    let verifying_key = porep_verifying_key_2kib(sealed.config)?;
+
+    assert!(!verify_seal_proof_with_vk(
+        &sealed,
+        &wrong_verifying_key(&verifying_key)
+    )?);
+
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_verify_batch_seal_with_vk_2kib_base_8() -> Result<()> {
+    let sealed = seal_proof_2kib()?;
+    let verifying_key = porep_verifying_key_2kib(sealed.config)?;
+
+    assert!(verify_batch_seal_proof_with_vk(
+        &sealed,
+        &prepare_verifying_key(&verifying_key)
+    )?);
+
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_verify_batch_seal_with_vk_rejects_wrong_key_2kib_base_8() -> Result<()> {
+    let sealed = seal_proof_2kib()?;
+    let verifying_key = porep_verifying_key_2kib(sealed.config)?;
+
+    assert!(!verify_batch_seal_proof_with_vk(
+        &sealed,
+        &wrong_verifying_key(&verifying_key)
+    )?);
+
+    Ok(())
+}
+
+fn read_u32_le(bytes: &[u8], offset: &mut usize) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&bytes[*offset..*offset + 4]);
+    *offset += 4;
+    u32::from_le_bytes(buf)
+}
+
+fn read_u64_le(bytes: &[u8], offset: &mut usize) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&bytes[*offset..*offset + 8]);
+    *offset += 8;
+    u64::from_le_bytes(buf)
+}
+
+fn read_fr(bytes: &[u8], offset: &mut usize) -> Fr {
+    let fr = bytes_into_fr(&bytes[*offset..*offset + 32]).expect("invalid field element");
+    *offset += 32;
+    fr
+}
+
+/// Reads a linear combination of an exported constraint and evaluates it for `values`, the
+/// inputs followed by the aux values of a witness.
+fn eval_exported_lc(bytes: &[u8], offset: &mut usize, values: &[Fr], num_inputs: usize) -> Fr {
+    let mut sum = Fr::zero();
+    for _ in 0..read_u32_le(bytes, offset) {
+        let variable = read_u64_le(bytes, offset);
+        let mut term = read_fr(bytes, offset);
+        let index = if variable & (1 << 63) == 0 {
+            variable as usize
+        } else {
+            num_inputs + (variable & !(1 << 63)) as usize
+        };
+        term.mul_assign(&values[index]);
+        sum.add_assign(&term);
+    }
+    sum
+}
+
+#[test]
+fn test_write_seal_witness_satisfies_porep_r1cs_2kib_base_8() -> Result<()> {
+    init_logger();
+
+    let (config, _, _, phase1_output) = seal_commit_phase1_2kib()?;
+
+    let mut r1cs = Cursor::new(Vec::new());
+    let r1cs_info = write_porep_r1cs::<SectorShape2KiB, _, Vec<u8>>(config, &mut r1cs, None)?;
+    let mut witness = Vec::new();
+    let witness_info = write_seal_witness(config, &phase1_output, 0, &mut witness)?;
+    assert_eq!(witness_info, r1cs_info);
+
+    let r1cs = r1cs.into_inner();
+    let mut offset = 48;
+    let num_inputs = read_u64_le(&r1cs, &mut offset) as usize;
+    let num_aux = read_u64_le(&r1cs, &mut offset) as usize;
+    let num_constraints = read_u64_le(&r1cs, &mut offset);
+    assert_eq!(num_inputs as u64, r1cs_info.num_inputs);
+    assert_eq!(num_aux as u64, r1cs_info.num_aux);
+    assert_eq!(num_constraints, r1cs_info.num_constraints);
+
+    let mut witness_offset = 32;
+    let values: Vec<Fr> = (0..num_inputs + num_aux)
+        .map(|_| read_fr(&witness, &mut witness_offset))
+        .collect();
+    assert_eq!(witness_offset, witness.len());
+    assert_eq!(values[0], Fr::one());
+
+    for i in 0..num_constraints {
+        let mut a = eval_exported_lc(&r1cs, &mut offset, &values, num_inputs);
+        let b = eval_exported_lc(&r1cs, &mut offset, &values, num_inputs);
+        let c = eval_exported_lc(&r1cs, &mut offset, &values, num_inputs);
+        a.mul_assign(&b);
+        assert_eq!(a, c, "constraint {} is not satisfied", i);
+    }
+    assert_eq!(offset, r1cs.len());
+
+    Ok(())
+}
+
+/// A window proof-of-spacetime of fake sealed 2KiB sectors, along with everything its
+/// verification takes.
+struct WindowPoStProof {
+    config: PoStConfig,
+    randomness: [u8; 32],
+    replicas: BTreeMap<SectorId, PublicReplicaInfo>,
+    prover_id: ProverId,
+    proof: Vec<u8>,
+}
+
+fn window_post_proof_2kib() -> Result<WindowPoStProof> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let sector_count = sector_shape(SECTOR_SIZE_2_KIB)?.window_post_sector_count;
+
+    let mut sectors = Vec::with_capacity(sector_count);
+    let mut pub_replicas = BTreeMap::new();
+    let mut priv_replicas = BTreeMap::new();
+    for _ in 0..sector_count {
+        let (sector_id, replica, comm_r, cache_dir) = create_fake_seal::<_, SectorShape2KiB>(
+            rng,
+            SECTOR_SIZE_2_KIB,
+            &ARBITRARY_POREP_ID_V1_1_0,
+            ApiVersion::V1_1_0,
+        )?;
+        priv_replicas.insert(
+            sector_id,
+            PrivateReplicaInfo::new(replica.path().into(), comm_r, cache_dir.path().into())?,
+        );
+        pub_replicas.insert(sector_id, PublicReplicaInfo::new(comm_r)?);
+        sectors.push((replica, cache_dir));
+    }
+
+    let prover_fr: DefaultTreeDomain = Fr::random(rng).into();
+    let mut prover_id = [0u8; 32];
+    prover_id.copy_from_slice(AsRef::<[u8]>::as_ref(&prover_fr));
+    let random_fr: DefaultTreeDomain = Fr::random(rng).into();
+    let mut randomness = [0u8; 32];
+    randomness.copy_from_slice(AsRef::<[u8]>::as_ref(&random_fr));
+
+    let config = PoStConfig {
+        sector_size: SECTOR_SIZE_2_KIB.into(),
+        sector_count,
+        challenge_count: WINDOW_POST_CHALLENGE_COUNT,
+        typ: PoStType::Window,
+        priority: false,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let proof =
+        generate_window_post::<SectorShape2KiB>(&config, &randomness, &priv_replicas, prover_id)?;
+
+    Ok(WindowPoStProof {
+        config,
+        randomness,
+        replicas: pub_replicas,
+        prover_id,
+        proof,
+    })
+}
+
+fn post_verifying_key_2kib(config: &PoStConfig) -> Result<groth16::VerifyingKey<Bls12>> {
+    let vk_path =
+        config.get_cache_verifying_key_path::<SectorShape2KiB>(&ProofsContext::default())?;
+    read_verifying_key(&read(vk_path)?)
+}
+
+#[test]
+#[ignore]
+fn test_verify_window_post_with_vk_2kib_base_8() -> Result<()> {
+    let post = window_post_proof_2kib()?;
+    let verifying_key = post_verifying_key_2kib(&post.config)?;
+
+    assert!(verify_window_post_with_vk::<SectorShape2KiB>(
+        &post.config,
+        &prepare_verifying_key(&verifying_key),
+        &post.randomness,
+        &post.replicas,
+        post.prover_id,
+        &post.proof,
+    )?);
+
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_verify_window_post_with_vk_rejects_wrong_key_2kib_base_8() -> Result<()> {
+    let post = window_post_proof_2kib()?;
+    let verifying_key = post_verifying_key_2kib(&post.config)?;
+
+    assert!(!verify_window_post_with_vk::<SectorShape2KiB>(
+        &post.config,
+        &wrong_verifying_key(&verifying_key),
+        &post.randomness,
+        &post.replicas,
+        post.prover_id,
+        &post.proof,
+    )?);
+
+    Ok(())
+}
+
+#[test]
+#[ignore]
+fn test_verify_window_post_batch_with_vk_rejects_empty_replicas_2kib_base_8() -> Result<()> {
+    let post = window_post_proof_2kib()?;
+    let verifying_key = prepare_verifying_key(&post_verifying_key_2kib(&post.config)?);
+    let no_replicas = BTreeMap::new();
+
+    let valid = verify_window_post_batch_with_vk::<SectorShape2KiB>(
+        &post.config,
+        &verifying_key,
+        &[
+            (
+                post.randomness,
+                post.prover_id,
+                &post.replicas,
+                &post.proof[..],
+            ),
+            (
+                post.randomness,
+                post.prover_id,
+                &no_replicas,
+                &post.proof[..],
+            ),
+            (
+                post.randomness,
+                post.prover_id,
+                &post.replicas,
+                &post.proof[..],
+            ),
+        ],
+    )?;
+    assert_eq!(valid, vec![true, false, true]);
+
+    Ok(())
+}
+
+/// A winning proof-of-spacetime of a fake sealed 2KiB sector, along with everything its
+/// verification takes.
+struct WinningPoStProof {
+    config: PoStConfig,
+    randomness: [u8; 32],
+    replicas: Vec<(SectorId, PublicReplicaInfo)>,
+    prover_id: ProverId,
+    proof: Vec<u8>,
+}
+
+fn winning_post_proof_2kib() -> Result<WinningPoStProof> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let (sector_id, replica, comm_r, cache_dir) = create_fake_seal::<_, SectorShape2KiB>(
+        rng,
+        SECTOR_SIZE_2_KIB,
+        &ARBITRARY_POREP_ID_V1_1_0,
+        ApiVersion::V1_1_0,
+    )?;
+    let priv_replicas = vec![(
+        sector_id,
+        PrivateReplicaInfo::new(replica.path().into(), comm_r, cache_dir.path().into())?,
+    )];
+    let pub_replicas = vec![(sector_id, PublicReplicaInfo::new(comm_r)?)];
+
+    let prover_fr: DefaultTreeDomain = Fr::random(rng).into();
+    let mut prover_id = [0u8; 32];
+    prover_id.copy_from_slice(AsRef::<[u8]>::as_ref(&prover_fr));
+    let random_fr: DefaultTreeDomain = Fr::random(rng).into();
+    let mut randomness = [0u8; 32];
+    randomness.copy_from_slice(AsRef::<[u8]>::as_ref(&random_fr));
+
+    let config = PoStConfig {
+        sector_size: SECTOR_SIZE_2_KIB.into(),
+        sector_count: WINNING_POST_SECTOR_COUNT,
+        challenge_count: WINNING_POST_CHALLENGE_COUNT,
+        typ: PoStType::Winning,
+        priority: false,
+        api_version: ApiVersion::V1_1_0,
+    };
+    let proof =
+        generate_winning_post::<SectorShape2KiB>(&config, &randomness, &priv_replicas, prover_id)?;
+
+    Ok(WinningPoStProof {
+        config,
+        randomness,
+        replicas: pub_replicas,
+        prover_id,
+        proof,
+    })
+}
+
+#[test]
+#[ignore]
+fn test_verify_winning_post_batch_with_vk_rejects_empty_replicas_2kib_base_8() -> Result<()> {
+    let post = winning_post_proof_2kib()?;
+    let verifying_key = prepare_verifying_key(&post_verifying_key_2kib(&post.config)?);
+    let no_replicas: Vec<(SectorId, PublicReplicaInfo)> = Vec::new();
+
+    let valid = verify_winning_post_batch_with_vk::<SectorShape2KiB>(
+        &post.config,
+        &verifying_key,
+        &[
+            (
+                post.randomness,
+                post.prover_id,
+                &post.replicas[..],
+                &post.proof[..],
+            ),
+            (
+                post.randomness,
+                post.prover_id,
+                &no_replicas[..],
+                &post.proof[..],
+            ),
+            (
+                post.randomness,
+                post.prover_id,
+                &post.replicas[..],
+                &post.proof[..],
+            ),
+        ],
+    )?;
+    assert_eq!(valid, vec![true, false, true]);
+
     Ok(())
 }
 
@@ -564,11 +1471,9 @@ fn winning_post<Tree: 'static + MerkleTreeTrait>(
 #[ignore]
 fn test_window_post_single_partition_smaller_2kib_base_8() -> Result<()> {
     let sector_size = SECTOR_SIZE_2_KIB;
-    let sector_count = *WINDOW_POST_SECTOR_COUNT
-        .read()
-        .expect("WINDOW_POST_SECTOR_COUNT poisoned")
-        .get(&sector_size)
-        .expect("unknown sector size");
+    let sector_count = sector_shape(sector_size)
+        .expect("unknown sector size")
+        .window_post_sector_count;
 
     window_post::<SectorShape2KiB>(
         sector_size,
@@ -604,11 +1509,9 @@ fn test_window_post_single_partition_smaller_2kib_base_8() -> Result<()> {
 #[ignore]
 fn test_window_post_two_partitions_matching_2kib_base_8() -> Result<()> {
     let sector_size = SECTOR_SIZE_2_KIB;
-    let sector_count = *WINDOW_POST_SECTOR_COUNT
-        .read()
-        .expect("WINDOW_POST_SECTOR_COUNT poisoned")
-        .get(&sector_size)
-        .expect("unknown sector size");
+    let sector_count = sector_shape(sector_size)
+        .expect("unknown sector size")
+        .window_post_sector_count;
 
     window_post::<SectorShape2KiB>(
         sector_size,
@@ -644,11 +1547,9 @@ fn test_window_post_two_partitions_matching_2kib_base_8() -> Result<()> {
 #[ignore]
 fn test_window_post_two_partitions_matching_4kib_sub_8_2() -> Result<()> {
     let sector_size = SECTOR_SIZE_4_KIB;
-    let sector_count = *WINDOW_POST_SECTOR_COUNT
-        .read()
-        .expect("WINDOW_POST_SECTOR_COUNT poisoned")
-        .get(&sector_size)
-        .expect("unknown sector size");
+    let sector_count = sector_shape(sector_size)
+        .expect("unknown sector size")
+        .window_post_sector_count;
 
     window_post::<SectorShape4KiB>(
         sector_size,
@@ -684,11 +1585,9 @@ fn test_window_post_two_partitions_matching_4kib_sub_8_2() -> Result<()> {
 #[ignore]
 fn test_window_post_two_partitions_matching_16kib_sub_8_8() -> Result<()> {
     let sector_size = SECTOR_SIZE_16_KIB;
-    let sector_count = *WINDOW_POST_SECTOR_COUNT
-        .read()
-        .expect("WINDOW_POST_SECTOR_COUNT poisoned")
-        .get(&sector_size)
-        .expect("unknown sector size");
+    let sector_count = sector_shape(sector_size)
+        .expect("unknown sector size")
+        .window_post_sector_count;
 
     window_post::<SectorShape16KiB>(
         sector_size,
@@ -724,11 +1623,9 @@ fn test_window_post_two_partitions_matching_16kib_sub_8_8() -> Result<()> {
 #[ignore]
 fn test_window_post_two_partitions_matching_32kib_top_8_8_2() -> Result<()> {
     let sector_size = SECTOR_SIZE_32_KIB;
-    let sector_count = *WINDOW_POST_SECTOR_COUNT
-        .read()
-        .expect("WINDOW_POST_SECTOR_COUNT poisoned")
-        .get(&sector_size)
-        .expect("unknown sector size");
+    let sector_count = sector_shape(sector_size)
+        .expect("unknown sector size")
+        .window_post_sector_count;
 
     window_post::<SectorShape32KiB>(
         sector_size,
@@ -764,11 +1661,9 @@ fn test_window_post_two_partitions_matching_32kib_top_8_8_2() -> Result<()> {
 #[ignore]
 fn test_window_post_two_partitions_smaller_2kib_base_8() -> Result<()> {
     let sector_size = SECTOR_SIZE_2_KIB;
-    let sector_count = *WINDOW_POST_SECTOR_COUNT
-        .read()
-        .expect("WINDOW_POST_SECTOR_COUNT poisoned")
-        .get(&sector_size)
-        .expect("unknown sector size");
+    let sector_count = sector_shape(sector_size)
+        .expect("unknown sector size")
+        .window_post_sector_count;
 
     window_post::<SectorShape2KiB>(
         sector_size,
@@ -804,11 +1699,9 @@ fn test_window_post_two_partitions_smaller_2kib_base_8() -> Result<()> {
 #[ignore]
 fn test_window_post_single_partition_matching_2kib_base_8() -> Result<()> {
     let sector_size = SECTOR_SIZE_2_KIB;
-    let sector_count = *WINDOW_POST_SECTOR_COUNT
-        .read()
-        .expect("WINDOW_POST_SECTOR_COUNT poisoned")
-        .get(&sector_size)
-        .expect("unknown sector size");
+    let sector_count = sector_shape(sector_size)
+        .expect("unknown sector size")
+        .window_post_sector_count;
 
     window_post::<SectorShape2KiB>(
         sector_size,
@@ -840,6 +1733,105 @@ fn test_window_post_single_partition_matching_2kib_base_8() -> Result<()> {
     )
 }
 
+#[test]
+#[ignore]
+fn test_window_post_vanilla_proofs_with_missing_replica_2kib_base_8() -> Result<()> {
+    window_post_with_missing_replica::<SectorShape2KiB>(SECTOR_SIZE_2_KIB, ApiVersion::V1_1_0)
+}
+
+/// Seals three sectors, removes the replica of one of them and checks that it is reported as
+/// faulty while the others are still proven, and that the proof over the others verifies.
+fn window_post_with_missing_replica<Tree: 'static + MerkleTreeTrait>(
+    sector_size: u64,
+    api_version: ApiVersion,
+) -> Result<()> {
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let prover_fr: <Tree::Hasher as Hasher>::Domain = Fr::random(rng).into();
+    let mut prover_id = [0u8; 32];
+    prover_id.copy_from_slice(AsRef::<[u8]>::as_ref(&prover_fr));
+
+    let mut sectors = Vec::new();
+    let mut pub_replicas = BTreeMap::new();
+    let mut priv_replicas = BTreeMap::new();
+    for _ in 0..3 {
+        let (sector_id, replica, comm_r, cache_dir) = create_seal::<_, Tree>(
+            rng,
+            sector_size,
+            prover_id,
+            true,
+            &ARBITRARY_POREP_ID_V1_1_0,
+            api_version,
+        )?;
+        priv_replicas.insert(
+            sector_id,
+            PrivateReplicaInfo::new(replica.path().into(), comm_r, cache_dir.path().into())?,
+        );
+        pub_replicas.insert(sector_id, PublicReplicaInfo::new(comm_r)?);
+        sectors.push((sector_id, replica, cache_dir));
+    }
+
+    let missing_sector = sectors[1].0;
+    remove_file(sectors[1].1.path())?;
+
+    let random_fr: <Tree::Hasher as Hasher>::Domain = Fr::random(rng).into();
+    let mut randomness = [0u8; 32];
+    randomness.copy_from_slice(AsRef::<[u8]>::as_ref(&random_fr));
+
+    let config = PoStConfig {
+        sector_size: sector_size.into(),
+        sector_count: 3,
+        challenge_count: WINDOW_POST_CHALLENGE_COUNT,
+        typ: PoStType::Window,
+        priority: false,
+        api_version,
+    };
+
+    let scheduled = generate_window_post_vanilla_proofs::<Tree>(
+        &config,
+        &randomness,
+        &priv_replicas,
+        prover_id,
+    )?;
+    assert_eq!(scheduled.faulty_sectors.len(), 1);
+    assert_eq!(scheduled.faulty_sectors[0].sector_id, missing_sector);
+    assert!(matches!(
+        scheduled.faulty_sectors[0].reason,
+        FaultReason::Error(_)
+    ));
+    assert_eq!(
+        scheduled
+            .vanilla_proofs
+            .iter()
+            .map(|proof| proof.sector_id)
+            .collect::<Vec<_>>(),
+        vec![sectors[0].0, sectors[2].0]
+    );
+
+    // The challenges depend on the proven sectors, so the proofs are regenerated without the
+    // faulty one.
+    priv_replicas.remove(&missing_sector);
+    pub_replicas.remove(&missing_sector);
+    let scheduled = generate_window_post_vanilla_proofs::<Tree>(
+        &config,
+        &randomness,
+        &priv_replicas,
+        prover_id,
+    )?;
+    assert!(scheduled.faulty_sectors.is_empty());
+
+    let proof = generate_window_post_with_vanilla::<Tree>(
+        &config,
+        &randomness,
+        prover_id,
+        scheduled.vanilla_proofs,
+    )?;
+    let valid = verify_window_post::<Tree>(&config, &randomness, &pub_replicas, prover_id, &proof)?;
+    assert!(valid, "proof did not verify");
+
+    Ok(())
+}
+
 fn window_post<Tree: 'static + MerkleTreeTrait>(
     sector_size: u64,
     total_sector_count: usize,
@@ -900,6 +1892,15 @@ fn window_post<Tree: 'static + MerkleTreeTrait>(
     let valid = verify_window_post::<Tree>(&config, &randomness, &pub_replicas, prover_id, &proof)?;
     assert!(valid, "proof did not verify");
 
+    let public_inputs =
+        window_post_public_inputs::<Tree>(&config, &randomness, &pub_replicas, prover_id)?;
+    let vk_path = config.get_cache_verifying_key_path::<Tree>(&ProofsContext::default())?;
+    verify_exported_proofs(
+        &read_verifying_key(&read(vk_path)?)?,
+        &proof,
+        &public_inputs,
+    )?;
+
     // 2)
     let replica_sectors = priv_replicas
         .iter()
@@ -923,13 +1924,103 @@ fn window_post<Tree: 'static + MerkleTreeTrait>(
         vanilla_proofs.push(single_proof);
     }
 
+    let scheduled = generate_window_post_vanilla_proofs::<Tree>(
+        &config,
+        &randomness,
+        &priv_replicas,
+        prover_id,
+    )?;
+    assert!(scheduled.faulty_sectors.is_empty());
+    assert_eq!(
+        scheduled
+            .vanilla_proofs
+            .iter()
+            .map(|proof| proof.sector_id)
+            .collect::<Vec<_>>(),
+        replica_sectors
+    );
+
+    // 3)
+    let partition_proofs = replica_sectors
+        .chunks(sector_count)
+        .enumerate()
+        .map(|(k, partition_sectors)| {
+            let partition_vanilla_proofs: Vec<_> = vanilla_proofs
+                .iter()
+                .filter(|proof| partition_sectors.contains(&proof.sector_id))
+                .cloned()
+                .collect();
+            generate_window_post_partition_with_vanilla::<Tree>(
+                &config,
+                &randomness,
+                prover_id,
+                &pub_replicas,
+                k,
+                &partition_vanilla_proofs,
+            )
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let partition_proof = aggregate_window_post_partition_proofs::<Tree>(
+        &config,
+        &randomness,
+        &pub_replicas,
+        prover_id,
+        &partition_proofs,
+    )?;
+
+    let mut reversed_proofs = partition_proofs.clone();
+    reversed_proofs.reverse();
+    if reversed_proofs != partition_proofs {
+        assert!(aggregate_window_post_partition_proofs::<Tree>(
+            &config,
+            &randomness,
+            &pub_replicas,
+            prover_id,
+            &reversed_proofs,
+        )
+        .is_err());
+    }
+
+    // Partitions over no replicas at all are rejected.
+    let no_replicas = BTreeMap::new();
+    assert!(generate_window_post_partition_with_vanilla::<Tree>(
+        &config,
+        &randomness,
+        prover_id,
+        &no_replicas,
+        0,
+        &[],
+    )
+    .is_err());
+    assert!(aggregate_window_post_partition_proofs::<Tree>(
+        &config,
+        &randomness,
+        &no_replicas,
+        prover_id,
+        &[],
+    )
+    .is_err());
+
     let proof =
         generate_window_post_with_vanilla::<Tree>(&config, &randomness, prover_id, vanilla_proofs)?;
+    assert_eq!(proof.len(), partition_proof.len());
     /////////////////////////////////////////////
 
     let valid = verify_window_post::<Tree>(&config, &randomness, &pub_replicas, prover_id, &proof)?;
     assert!(valid, "proof did not verify");
 
+    // Batch verification reports the truncated proof as invalid.
+    let valid = verify_window_post_batch::<Tree>(
+        &config,
+        &[
+            (randomness, prover_id, &pub_replicas, &proof[..]),
+            (randomness, prover_id, &pub_replicas, &proof[1..]),
+            (randomness, prover_id, &pub_replicas, &partition_proof[..]),
+        ],
+    )?;
+    assert_eq!(valid, vec![true, false, true]);
+
     Ok(())
 }
 
@@ -952,11 +2043,9 @@ fn porep_config(sector_size: u64, porep_id: [u8; 32], api_version: ApiVersion) -
     PoRepConfig {
         sector_size: SectorSize(sector_size),
         partitions: PoRepProofPartitions(
-            *POREP_PARTITIONS
-                .read()
-                .expect("POREP_PARTITIONS poisoned")
-                .get(&sector_size)
-                .expect("unknown sector size"),
+            sector_shape(sector_size)
+                .expect("unknown sector size")
+                .porep_partitions,
         ),
         porep_id,
         api_version,
@@ -1081,6 +2170,20 @@ fn proof_and_unseal<Tree: 'static + MerkleTreeTrait>(
         &commit_output.proof,
     )?;
     assert!(verified, "failed to verify valid seal");
+
+    let public_inputs =
+        seal_public_inputs::<Tree>(config, comm_r, comm_d, prover_id, sector_id, ticket, seed)?;
+    assert_eq!(
+        public_inputs.len(),
+        usize::from(PoRepProofPartitions::from(config))
+    );
+    let vk_path = config.get_cache_verifying_key_path::<Tree>(&ProofsContext::default())?;
+    verify_exported_proofs(
+        &read_verifying_key(&read(vk_path)?)?,
+        &commit_output.proof,
+        &public_inputs,
+    )?;
+
     Ok(())
 }
 
@@ -1,6 +1,12 @@
 use filecoin_proofs::{
-    with_shape, SECTOR_SIZE_16_MIB, SECTOR_SIZE_1_GIB, SECTOR_SIZE_2_KIB, SECTOR_SIZE_32_GIB,
-    SECTOR_SIZE_4_KIB, SECTOR_SIZE_512_MIB, SECTOR_SIZE_64_GIB, SECTOR_SIZE_8_MIB,
+    get_sector_shape, register_sector_shape, registered_sector_sizes, sector_shape, with_shape,
+    SectorShapeConfig, TreeShape, PUBLISHED_SECTOR_SIZES, SECTOR_SIZE_16_MIB, SECTOR_SIZE_1_GIB,
+    SECTOR_SIZE_2_KIB, SECTOR_SIZE_32_GIB, SECTOR_SIZE_4_KIB, SECTOR_SIZE_512_MIB,
+    SECTOR_SIZE_64_GIB, SECTOR_SIZE_8_MIB,
+};
+#[allow(deprecated)]
+use filecoin_proofs::{
+    LAYERS, POREP_MINIMUM_CHALLENGES, POREP_PARTITIONS, WINDOW_POST_SECTOR_COUNT,
 };
 use generic_array::typenum::Unsigned;
 use storage_proofs_core::merkle::MerkleTreeTrait;
@@ -83,3 +89,119 @@ fn test_with_shape_macro_aux(sector_size: u64) {
         sector_size, arities, expected
     );
 }
+
+#[test]
+fn test_registered_sector_shapes() {
+    for &sector_size in PUBLISHED_SECTOR_SIZES.iter() {
+        let shape = sector_shape(sector_size).expect("sector_shape failure");
+        assert_eq!(shape.sector_size, sector_size);
+        assert_eq!(
+            (shape.base_arity, shape.sub_arity, shape.top_arity),
+            shape.tree_shape().arities()
+        );
+        assert!(registered_sector_sizes().contains(&sector_size));
+    }
+    assert!(sector_shape(SECTOR_SIZE_2_KIB + 1).is_err());
+}
+
+#[test]
+fn test_register_sector_shape() {
+    let sector_size_128_mib = 1 << 27;
+    assert!(get_sector_shape(sector_size_128_mib).is_none());
+
+    let shape = SectorShapeConfig {
+        sector_size: sector_size_128_mib,
+        base_arity: 8,
+        sub_arity: 2,
+        top_arity: 0,
+        layers: 2,
+        porep_partitions: 1,
+        porep_minimum_challenges: 2,
+        window_post_sector_count: 2,
+    };
+
+    // The base trees of a 128 MiB sector cannot be oct trees without a binary sub tree.
+    let invalid = SectorShapeConfig {
+        sub_arity: 0,
+        ..shape
+    };
+    assert!(register_sector_shape(invalid).is_err());
+    let invalid = SectorShapeConfig {
+        base_arity: 4,
+        ..shape
+    };
+    assert!(register_sector_shape(invalid).is_err());
+    assert!(get_sector_shape(sector_size_128_mib).is_none());
+
+    register_sector_shape(shape).expect("register_sector_shape failure");
+    assert_eq!(get_sector_shape(sector_size_128_mib), Some(shape));
+    assert_eq!(shape.tree_shape(), TreeShape::Sub2);
+    test_with_shape_macro_aux(sector_size_128_mib);
+}
+
+#[test]
+fn test_register_sector_shape_rejects_production_sizes() {
+    for &sector_size in PUBLISHED_SECTOR_SIZES.iter() {
+        let shape = sector_shape(sector_size).expect("sector_shape failure");
+        let changed = SectorShapeConfig {
+            layers: shape.layers + 1,
+            ..shape
+        };
+        assert!(register_sector_shape(changed).is_err());
+        assert_eq!(get_sector_shape(sector_size), Some(shape));
+    }
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_deprecated_shape_tables() {
+    let layers = LAYERS.read().expect("LAYERS poisoned");
+    let partitions = POREP_PARTITIONS.read().expect("POREP_PARTITIONS poisoned");
+    let challenges = POREP_MINIMUM_CHALLENGES
+        .read()
+        .expect("POREP_MINIMUM_CHALLENGES poisoned");
+    let sector_counts = WINDOW_POST_SECTOR_COUNT
+        .read()
+        .expect("WINDOW_POST_SECTOR_COUNT poisoned");
+
+    for &sector_size in PUBLISHED_SECTOR_SIZES.iter() {
+        let shape = sector_shape(sector_size).expect("sector_shape failure");
+        assert_eq!(layers[&sector_size], shape.layers);
+        assert_eq!(partitions[&sector_size], shape.porep_partitions);
+        assert_eq!(challenges[&sector_size], shape.porep_minimum_challenges);
+        assert_eq!(sector_counts[&sector_size], shape.window_post_sector_count);
+    }
+    assert_eq!(layers[&SECTOR_SIZE_32_GIB], 11);
+    assert_eq!(sector_counts[&SECTOR_SIZE_64_GIB], 2300);
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_deprecated_shape_tables_write() {
+    let sector_size_64_mib = 1 << 26;
+    let shape = SectorShapeConfig {
+        sector_size: sector_size_64_mib,
+        base_arity: 8,
+        sub_arity: 0,
+        top_arity: 0,
+        layers: 2,
+        porep_partitions: 1,
+        porep_minimum_challenges: 2,
+        window_post_sector_count: 2,
+    };
+    register_sector_shape(shape).expect("register_sector_shape failure");
+
+    let previous = LAYERS
+        .write()
+        .expect("LAYERS poisoned")
+        .insert(sector_size_64_mib, 4);
+    assert_eq!(previous, Some(2));
+    assert_eq!(
+        LAYERS.read().expect("LAYERS poisoned")[&sector_size_64_mib],
+        4
+    );
+    assert_eq!(
+        get_sector_shape(sector_size_64_mib),
+        Some(SectorShapeConfig { layers: 4, ..shape })
+    );
+}
@@ -1,8 +1,8 @@
 use bellperson::bls::Fr;
 use ff::Field;
 use filecoin_proofs::{
-    as_safe_commitment, verify_seal, DefaultOctLCTree, DefaultTreeDomain, PoRepConfig,
-    PoRepProofPartitions, SectorSize, POREP_PARTITIONS, SECTOR_SIZE_2_KIB, TEST_SEED,
+    as_safe_commitment, sector_shape, verify_seal, DefaultOctLCTree, DefaultTreeDomain,
+    PoRepConfig, PoRepProofPartitions, SectorSize, SECTOR_SIZE_2_KIB, TEST_SEED,
 };
 use fr32::bytes_into_fr;
 use rand::SeedableRng;
@@ -25,11 +25,9 @@ fn test_verify_seal_fr32_validation() {
             PoRepConfig {
                 sector_size: SectorSize(SECTOR_SIZE_2_KIB),
                 partitions: PoRepProofPartitions(
-                    *POREP_PARTITIONS
-                        .read()
-                        .expect("POREP_PARTITIONS poisoned")
-                        .get(&SECTOR_SIZE_2_KIB)
-                        .expect("unknown sector size"),
+                    sector_shape(SECTOR_SIZE_2_KIB)
+                        .expect("unknown sector size")
+                        .porep_partitions,
                 ),
                 porep_id: arbitrary_porep_id,
                 api_version: ApiVersion::V1_1_0,
@@ -61,11 +59,9 @@ fn test_verify_seal_fr32_validation() {
             PoRepConfig {
                 sector_size: SectorSize(SECTOR_SIZE_2_KIB),
                 partitions: PoRepProofPartitions(
-                    *POREP_PARTITIONS
-                        .read()
-                        .expect("POREP_PARTITIONS poisoned")
-                        .get(&SECTOR_SIZE_2_KIB)
-                        .expect("unknown sector size"),
+                    sector_shape(SECTOR_SIZE_2_KIB)
+                        .expect("unknown sector size")
+                        .porep_partitions,
                 ),
                 porep_id: arbitrary_porep_id,
                 api_version: ApiVersion::V1_1_0,
@@ -10,6 +10,8 @@ pub use digest::Digest;
 mod consts;
 mod platform;
 mod sha256;
+#[cfg(target_arch = "x86_64")]
+mod sha256_avx2;
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 mod sha256_intrinsics;
 mod sha256_utils;
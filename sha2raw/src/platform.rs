@@ -1,3 +1,6 @@
+use crate::consts::STATE_LEN;
+#[cfg(target_arch = "x86_64")]
+use crate::sha256_avx2;
 use crate::{sha256_intrinsics, sha256_utils};
 
 #[allow(dead_code)]
@@ -85,3 +88,82 @@ impl Implementation {
         }
     }
 }
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum MultiPlatform {
+    Scalar,
+    #[cfg(target_arch = "x86_64")]
+    Avx2,
+}
+
+/// Dispatch for hashing several independent messages at once.
+#[derive(Clone, Copy, Debug)]
+pub struct MultiImplementation(MultiPlatform);
+
+impl MultiImplementation {
+    pub fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if let Some(avx2_impl) = Self::avx2_if_supported() {
+                return avx2_impl;
+            }
+        }
+
+        Self::scalar()
+    }
+
+    /// Hash each message on its own, using the single message `Implementation`.
+    pub fn scalar() -> Self {
+        MultiImplementation(MultiPlatform::Scalar)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub fn avx2_if_supported() -> Option<Self> {
+        if cpuid_bool::cpuid_bool!("avx2") {
+            return Some(MultiImplementation(MultiPlatform::Avx2));
+        }
+
+        None
+    }
+
+    /// The number of messages compressed together per call to `compress256_lanes`.
+    pub fn lanes(self) -> usize {
+        match self.0 {
+            MultiPlatform::Scalar => 1,
+            #[cfg(target_arch = "x86_64")]
+            MultiPlatform::Avx2 => sha256_avx2::LANES,
+        }
+    }
+
+    /// Compress one 64 byte block, given as two 32 byte halves, into each of `states`.
+    /// `states` and `blocks` must both have exactly `lanes()` entries.
+    #[inline]
+    pub fn compress256_lanes(
+        self,
+        fallback: Implementation,
+        states: &mut [[u32; STATE_LEN]],
+        blocks: &[[&[u8]; 2]],
+    ) {
+        assert_eq!(states.len(), self.lanes(), "invalid number of states");
+        assert_eq!(blocks.len(), self.lanes(), "invalid number of blocks");
+
+        match self.0 {
+            MultiPlatform::Scalar => {
+                for (state, block) in states.iter_mut().zip(blocks.iter()) {
+                    fallback.compress256(state, &block[..]);
+                }
+            }
+            #[cfg(target_arch = "x86_64")]
+            MultiPlatform::Avx2 => {
+                use std::convert::TryInto;
+
+                let states: &mut [[u32; STATE_LEN]; sha256_avx2::LANES] =
+                    states.try_into().expect("invalid number of states");
+                let blocks: &[[&[u8]; 2]; sha256_avx2::LANES] =
+                    blocks.try_into().expect("invalid number of blocks");
+                unsafe { sha256_avx2::compress256_x8(states, blocks) };
+            }
+        }
+    }
+}
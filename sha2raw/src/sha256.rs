@@ -1,10 +1,14 @@
 use byteorder::{ByteOrder, BE};
 use lazy_static::lazy_static;
 
-use crate::{consts::H256, platform::Implementation};
+use crate::{
+    consts::H256,
+    platform::{Implementation, MultiImplementation},
+};
 
 lazy_static! {
     static ref IMPL: Implementation = Implementation::detect();
+    static ref MULTI_IMPL: MultiImplementation = MultiImplementation::detect();
 }
 
 #[derive(Clone)]
@@ -33,6 +37,15 @@ impl Sha256 {
         sha.finish()
     }
 
+    /// Hash many independent messages, each given as 32 byte chunks like in `digest`.
+    ///
+    /// Where supported, messages are hashed in groups using multi-buffer SIMD compression,
+    /// otherwise this falls back to hashing each message on its own. The results are always
+    /// identical to calling `digest` on each message.
+    pub fn digest_many(messages: &[&[&[u8]]]) -> Vec<[u8; 32]> {
+        digest_many_with(*MULTI_IMPL, messages)
+    }
+
     pub fn input(&mut self, blocks: &[&[u8]]) {
         debug_assert_eq!(blocks.len() % 2, 0, "invalid block length");
 
@@ -81,6 +94,68 @@ impl Sha256 {
 
 opaque_debug::implement!(Sha256);
 
+fn digest_many_with(multi_impl: MultiImplementation, messages: &[&[&[u8]]]) -> Vec<[u8; 32]> {
+    let lanes = multi_impl.lanes();
+    if lanes == 1 {
+        return messages
+            .iter()
+            .map(|blocks| Sha256::digest(blocks))
+            .collect();
+    }
+
+    let mut out = Vec::with_capacity(messages.len());
+    for group in messages.chunks(lanes) {
+        if group.len() < lanes {
+            out.extend(group.iter().map(|blocks| Sha256::digest(blocks)));
+            continue;
+        }
+
+        // The final block of each message, holding the padding and the message length.
+        let padding: Vec<[[u8; 32]; 2]> = group
+            .iter()
+            .map(|blocks| {
+                debug_assert_eq!(blocks.len() % 2, 0, "invalid block length");
+
+                let mut padding = [[0u8; 32]; 2];
+                padding[0][0] = 0b1000_0000;
+                let l = (blocks.len() as u64) << 8;
+                padding[1][32 - 8..].copy_from_slice(&l.to_be_bytes()[..]);
+                padding
+            })
+            .collect();
+        let block_at = |lane: usize, index: usize| -> [&[u8]; 2] {
+            let blocks = group[lane];
+            if 2 * index < blocks.len() {
+                [blocks[2 * index], blocks[2 * index + 1]]
+            } else {
+                [&padding[lane][0][..], &padding[lane][1][..]]
+            }
+        };
+
+        let block_counts: Vec<usize> = group.iter().map(|blocks| blocks.len() / 2 + 1).collect();
+        let common = *block_counts.iter().min().expect("empty group");
+
+        // Compress the blocks all lanes have in common together, then finish each lane alone.
+        let mut states = vec![H256; lanes];
+        for index in 0..common {
+            let blocks: Vec<[&[u8]; 2]> = (0..lanes).map(|lane| block_at(lane, index)).collect();
+            multi_impl.compress256_lanes(*IMPL, &mut states, &blocks);
+        }
+
+        for (lane, state) in states.iter_mut().enumerate() {
+            for index in common..block_counts[lane] {
+                IMPL.compress256(state, &block_at(lane, index)[..]);
+            }
+
+            let mut digest = [0u8; 32];
+            BE::write_u32_into(&state[..], &mut digest);
+            out.push(digest);
+        }
+    }
+
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -100,6 +175,48 @@ mod tests {
         fuzz(1_000);
     }
 
+    #[test]
+    fn test_digest_many() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        // Mix equal and unequal message lengths, and a trailing partial group.
+        for &count in &[1, 7, 8, 16, 21] {
+            for &uniform in &[true, false] {
+                let inputs: Vec<Vec<u8>> = (0..count)
+                    .map(|i| {
+                        let k = if uniform { 2 } else { 1 + (i % 5) };
+                        let mut input = vec![0u8; 64 * k];
+                        rng.fill_bytes(&mut input);
+                        input
+                    })
+                    .collect();
+                let chunked: Vec<Vec<&[u8]>> = inputs
+                    .iter()
+                    .map(|input| input.chunks(32).collect())
+                    .collect();
+                let messages: Vec<&[&[u8]]> = chunked.iter().map(|c| &c[..]).collect();
+
+                let expected: Vec<[u8; 32]> = inputs
+                    .iter()
+                    .map(|input| {
+                        let mut digest = [0u8; 32];
+                        digest.copy_from_slice(&Original::digest(input)[..]);
+                        digest
+                    })
+                    .collect();
+
+                assert_eq!(Sha256::digest_many(&messages), expected);
+                assert_eq!(
+                    digest_many_with(MultiImplementation::scalar(), &messages),
+                    expected
+                );
+            }
+        }
+    }
+
     fn fuzz(n: usize) {
         let rng = &mut XorShiftRng::from_seed([
             0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
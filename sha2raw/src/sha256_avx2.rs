@@ -0,0 +1,131 @@
+#![allow(clippy::many_single_char_names)]
+#![allow(clippy::cast_ptr_alignment)] // Safe to cast without alignment checks as the stores do not require alignment.
+
+use std::arch::x86_64::{
+    __m256i, _mm256_add_epi32, _mm256_and_si256, _mm256_andnot_si256, _mm256_or_si256,
+    _mm256_set1_epi32, _mm256_set_epi32, _mm256_slli_epi32, _mm256_srli_epi32, _mm256_storeu_si256,
+    _mm256_xor_si256,
+};
+
+use crate::consts::{K32, STATE_LEN};
+
+/// Number of messages processed in parallel, one per 32-bit lane of a 256-bit register.
+pub const LANES: usize = 8;
+
+macro_rules! rotr {
+    ($x:expr, $n:literal, $m:literal) => {
+        _mm256_or_si256(_mm256_srli_epi32($x, $n), _mm256_slli_epi32($x, $m))
+    };
+}
+
+#[inline(always)]
+unsafe fn xor3(a: __m256i, b: __m256i, c: __m256i) -> __m256i {
+    _mm256_xor_si256(_mm256_xor_si256(a, b), c)
+}
+
+#[inline(always)]
+unsafe fn add4(a: __m256i, b: __m256i, c: __m256i, d: __m256i) -> __m256i {
+    _mm256_add_epi32(_mm256_add_epi32(a, b), _mm256_add_epi32(c, d))
+}
+
+/// Loads the big endian word at `index` of each lane's 64 byte block, given as two 32 byte halves.
+#[inline(always)]
+unsafe fn load_word(blocks: &[[&[u8]; 2]; LANES], index: usize) -> __m256i {
+    let half = index / 8;
+    let offset = (index % 8) * 4;
+    let word = |lane: usize| {
+        let bytes = &blocks[lane][half][offset..offset + 4];
+        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as i32
+    };
+
+    _mm256_set_epi32(
+        word(7),
+        word(6),
+        word(5),
+        word(4),
+        word(3),
+        word(2),
+        word(1),
+        word(0),
+    )
+}
+
+/// Process one 64 byte block for each of `LANES` independent SHA-256 states, interleaving the
+/// lanes in AVX2 registers. `blocks[lane]` holds the block of lane `lane` as two 32 byte halves.
+#[target_feature(enable = "avx2")]
+pub unsafe fn compress256_x8(states: &mut [[u32; STATE_LEN]; LANES], blocks: &[[&[u8]; 2]; LANES]) {
+    for lane in blocks.iter() {
+        assert_eq!(lane[0].len(), 32);
+        assert_eq!(lane[1].len(), 32);
+    }
+
+    // Transpose the states, so that each register holds one state word of every lane.
+    let mut s = [_mm256_set1_epi32(0); STATE_LEN];
+    for (i, word) in s.iter_mut().enumerate() {
+        *word = _mm256_set_epi32(
+            states[7][i] as i32,
+            states[6][i] as i32,
+            states[5][i] as i32,
+            states[4][i] as i32,
+            states[3][i] as i32,
+            states[2][i] as i32,
+            states[1][i] as i32,
+            states[0][i] as i32,
+        );
+    }
+
+    let mut w = [_mm256_set1_epi32(0); 64];
+    for (i, word) in w.iter_mut().enumerate().take(16) {
+        *word = load_word(blocks, i);
+    }
+    for i in 16..64 {
+        let s0 = xor3(
+            rotr!(w[i - 15], 7, 25),
+            rotr!(w[i - 15], 18, 14),
+            _mm256_srli_epi32(w[i - 15], 3),
+        );
+        let s1 = xor3(
+            rotr!(w[i - 2], 17, 15),
+            rotr!(w[i - 2], 19, 13),
+            _mm256_srli_epi32(w[i - 2], 10),
+        );
+        w[i] = add4(w[i - 16], s0, w[i - 7], s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = s;
+    for i in 0..64 {
+        let big_s1 = xor3(rotr!(e, 6, 26), rotr!(e, 11, 21), rotr!(e, 25, 7));
+        let ch = _mm256_xor_si256(_mm256_and_si256(e, f), _mm256_andnot_si256(e, g));
+        let t1 = _mm256_add_epi32(add4(h, big_s1, ch, _mm256_set1_epi32(K32[i] as i32)), w[i]);
+
+        let big_s0 = xor3(rotr!(a, 2, 30), rotr!(a, 13, 19), rotr!(a, 22, 10));
+        let maj = xor3(
+            _mm256_and_si256(a, b),
+            _mm256_and_si256(a, c),
+            _mm256_and_si256(b, c),
+        );
+        let t2 = _mm256_add_epi32(big_s0, maj);
+
+        h = g;
+        g = f;
+        f = e;
+        e = _mm256_add_epi32(d, t1);
+        d = c;
+        c = b;
+        b = a;
+        a = _mm256_add_epi32(t1, t2);
+    }
+
+    let mut out = [[0u32; LANES]; STATE_LEN];
+    for (i, word) in [a, b, c, d, e, f, g, h].iter().enumerate() {
+        let sum = _mm256_add_epi32(s[i], *word);
+        _mm256_storeu_si256(out[i].as_mut_ptr() as *mut __m256i, sum);
+    }
+
+    // Transpose back into per lane states.
+    for (lane, state) in states.iter_mut().enumerate() {
+        for (i, word) in state.iter_mut().enumerate() {
+            *word = out[i][lane];
+        }
+    }
+}
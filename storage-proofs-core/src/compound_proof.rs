@@ -18,6 +18,7 @@ use crate::{
     parameter_cache::{CacheableParameters, ParameterSetMetadata},
     partitions::partition_count,
     proof::ProofScheme,
+    settings::ProofsContext,
 };
 
 #[derive(Clone)]
@@ -304,7 +305,21 @@ where
         rng: Option<&mut R>,
         public_params: &S::PublicParams,
     ) -> Result<groth16::MappedParameters<Bls12>> {
-        Self::get_groth_params(rng, Self::blank_circuit(public_params), public_params)
+        Self::groth_params_with_context(&ProofsContext::default(), rng, public_params)
+    }
+
+    /// Same as `groth_params`, see `CacheableParameters::get_groth_params_with_context`.
+    fn groth_params_with_context<R: RngCore>(
+        ctx: &ProofsContext,
+        rng: Option<&mut R>,
+        public_params: &S::PublicParams,
+    ) -> Result<groth16::MappedParameters<Bls12>> {
+        Self::get_groth_params_with_context(
+            ctx,
+            rng,
+            Self::blank_circuit(public_params),
+            public_params,
+        )
     }
 
     /// If the rng option argument is set, parameters will be
@@ -316,7 +331,21 @@ where
         rng: Option<&mut R>,
         public_params: &S::PublicParams,
     ) -> Result<groth16::VerifyingKey<Bls12>> {
-        Self::get_verifying_key(rng, Self::blank_circuit(public_params), public_params)
+        Self::verifying_key_with_context(&ProofsContext::default(), rng, public_params)
+    }
+
+    /// Same as `verifying_key`, using the parameter cache of `ctx`.
+    fn verifying_key_with_context<R: RngCore>(
+        ctx: &ProofsContext,
+        rng: Option<&mut R>,
+        public_params: &S::PublicParams,
+    ) -> Result<groth16::VerifyingKey<Bls12>> {
+        Self::get_verifying_key_with_context(
+            ctx,
+            rng,
+            Self::blank_circuit(public_params),
+            public_params,
+        )
     }
 
     fn circuit_for_test(
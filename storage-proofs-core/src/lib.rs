@@ -22,6 +22,7 @@ pub mod partitions;
 pub mod pieces;
 pub mod por;
 pub mod proof;
+pub mod r1cs;
 pub mod sector;
 pub mod settings;
 pub mod test_helper;
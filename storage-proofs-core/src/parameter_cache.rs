@@ -18,7 +18,7 @@ use sha2::{Digest, Sha256};
 
 use crate::{
     error::{Error, Result},
-    settings::SETTINGS,
+    settings::ProofsContext,
 };
 
 /// Bump this when circuits change to invalidate the cache.
@@ -142,32 +142,38 @@ impl Drop for LockedFile {
     }
 }
 
-pub fn parameter_cache_dir_name() -> String {
-    SETTINGS.parameter_cache.clone()
+pub fn parameter_cache_dir_name(ctx: &ProofsContext) -> String {
+    ctx.settings().parameter_cache.clone()
 }
 
-pub fn parameter_cache_dir() -> PathBuf {
-    Path::new(&parameter_cache_dir_name()).to_path_buf()
+pub fn parameter_cache_dir(ctx: &ProofsContext) -> PathBuf {
+    Path::new(&parameter_cache_dir_name(ctx)).to_path_buf()
 }
 
-pub fn parameter_cache_params_path(parameter_set_identifier: &str) -> PathBuf {
-    let dir = Path::new(&parameter_cache_dir_name()).to_path_buf();
+pub fn parameter_cache_params_path(ctx: &ProofsContext, parameter_set_identifier: &str) -> PathBuf {
+    let dir = parameter_cache_dir(ctx);
     dir.join(format!(
         "v{}-{}.{}",
         VERSION, parameter_set_identifier, GROTH_PARAMETER_EXT
     ))
 }
 
-pub fn parameter_cache_metadata_path(parameter_set_identifier: &str) -> PathBuf {
-    let dir = Path::new(&parameter_cache_dir_name()).to_path_buf();
+pub fn parameter_cache_metadata_path(
+    ctx: &ProofsContext,
+    parameter_set_identifier: &str,
+) -> PathBuf {
+    let dir = parameter_cache_dir(ctx);
     dir.join(format!(
         "v{}-{}.{}",
         VERSION, parameter_set_identifier, PARAMETER_METADATA_EXT
     ))
 }
 
-pub fn parameter_cache_verifying_key_path(parameter_set_identifier: &str) -> PathBuf {
-    let dir = Path::new(&parameter_cache_dir_name()).to_path_buf();
+pub fn parameter_cache_verifying_key_path(
+    ctx: &ProofsContext,
+    parameter_set_identifier: &str,
+) -> PathBuf {
+    let dir = parameter_cache_dir(ctx);
     dir.join(format!(
         "v{}-{}.{}",
         VERSION, parameter_set_identifier, VERIFYING_KEY_EXT
@@ -230,11 +236,20 @@ where
         )
     }
 
-    fn get_param_metadata(_circuit: C, pub_params: &P) -> Result<CacheEntryMetadata> {
+    fn get_param_metadata(circuit: C, pub_params: &P) -> Result<CacheEntryMetadata> {
+        Self::get_param_metadata_with_context(&ProofsContext::default(), circuit, pub_params)
+    }
+
+    /// Same as `get_param_metadata`, using the parameter cache of `ctx`.
+    fn get_param_metadata_with_context(
+        ctx: &ProofsContext,
+        _circuit: C,
+        pub_params: &P,
+    ) -> Result<CacheEntryMetadata> {
         let id = Self::cache_identifier(pub_params);
 
         // generate (or load) metadata
-        let meta_path = ensure_ancestor_dirs_exist(parameter_cache_metadata_path(&id))?;
+        let meta_path = ensure_ancestor_dirs_exist(parameter_cache_metadata_path(ctx, &id))?;
         read_cached_metadata(&meta_path)
             .or_else(|_| write_cached_metadata(&meta_path, Self::cache_meta(pub_params)))
             .map_err(Into::into)
@@ -249,9 +264,20 @@ where
         rng: Option<&mut R>,
         circuit: C,
         pub_params: &P,
+    ) -> Result<groth16::MappedParameters<Bls12>> {
+        Self::get_groth_params_with_context(&ProofsContext::default(), rng, circuit, pub_params)
+    }
+
+    /// Same as `get_groth_params`, using the parameter cache of `ctx` and checking cached
+    /// parameters against `parameters.json` if it sets `verify_production_params`.
+    fn get_groth_params_with_context<R: RngCore>(
+        ctx: &ProofsContext,
+        rng: Option<&mut R>,
+        circuit: C,
+        pub_params: &P,
     ) -> Result<groth16::MappedParameters<Bls12>> {
         let id = Self::cache_identifier(pub_params);
-        let cache_path = ensure_ancestor_dirs_exist(parameter_cache_params_path(&id))?;
+        let cache_path = ensure_ancestor_dirs_exist(parameter_cache_params_path(ctx, &id))?;
 
         let generate = || -> Result<_> {
             if let Some(rng) = rng {
@@ -274,20 +300,24 @@ where
         };
 
         // load or generate Groth parameter mappings
-        read_cached_params(&cache_path).or_else(|err| match err.downcast::<Error>() {
-            Ok(error @ Error::InvalidParameters(_)) => Err(error.into()),
-            _ => {
-                // if the file already exists, another process is already trying to generate these.
-                if !cache_path.exists() {
-                    match write_cached_params(&cache_path, generate()?) {
-                        Ok(_) => {}
-                        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
-                            // other thread just wrote it, do nothing
+        read_cached_params_with_context(ctx, &cache_path).or_else(|err| {
+            match err.downcast::<Error>() {
+                Ok(error @ Error::InvalidParameters(_)) => Err(error.into()),
+                _ => {
+                    // if the file already exists, another process is already trying to generate these.
+                    if !cache_path.exists() {
+                        match write_cached_params(&cache_path, generate()?) {
+                            Ok(_) => {}
+                            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                                // other thread just wrote it, do nothing
+                            }
+                            Err(e) => {
+                                panic!("{}: failed to write generated parameters to cache", e)
+                            }
                         }
-                        Err(e) => panic!("{}: failed to write generated parameters to cache", e),
                     }
+                    Ok(read_cached_params_with_context(ctx, &cache_path)?)
                 }
-                Ok(read_cached_params(&cache_path)?)
             }
         })
     }
@@ -301,17 +331,27 @@ where
         rng: Option<&mut R>,
         circuit: C,
         pub_params: &P,
+    ) -> Result<groth16::VerifyingKey<Bls12>> {
+        Self::get_verifying_key_with_context(&ProofsContext::default(), rng, circuit, pub_params)
+    }
+
+    /// Same as `get_verifying_key`, using the parameter cache of `ctx`.
+    fn get_verifying_key_with_context<R: RngCore>(
+        ctx: &ProofsContext,
+        rng: Option<&mut R>,
+        circuit: C,
+        pub_params: &P,
     ) -> Result<groth16::VerifyingKey<Bls12>> {
         let id = Self::cache_identifier(pub_params);
 
         let generate = || -> Result<groth16::VerifyingKey<Bls12>> {
-            let groth_params = Self::get_groth_params(rng, circuit, pub_params)?;
+            let groth_params = Self::get_groth_params_with_context(ctx, rng, circuit, pub_params)?;
             info!("Getting verifying key. (id: {})", &id);
             Ok(groth_params.vk)
         };
 
         // generate (or load) verifying key
-        let cache_path = ensure_ancestor_dirs_exist(parameter_cache_verifying_key_path(&id))?;
+        let cache_path = ensure_ancestor_dirs_exist(parameter_cache_verifying_key_path(ctx, &id))?;
         match read_cached_verifying_key(&cache_path) {
             Ok(key) => Ok(key),
             Err(_) => write_cached_verifying_key(&cache_path, generate()?).map_err(Into::into),
@@ -332,62 +372,22 @@ fn ensure_parent(path: &PathBuf) -> io::Result<()> {
 // Reads parameter mappings using mmap so that they can be lazily
 // loaded later.
 pub fn read_cached_params(cache_entry_path: &PathBuf) -> Result<groth16::MappedParameters<Bls12>> {
-    info!("checking cache_path: {:?} for parameters", cache_entry_path);
+    read_cached_params_with_context(&ProofsContext::default(), cache_entry_path)
+}
 
-    let verify_production_params = SETTINGS.verify_production_params;
+/// Same as `read_cached_params`, checking the file against `parameters.json` if `ctx` sets
+/// `verify_production_params`.
+pub fn read_cached_params_with_context(
+    ctx: &ProofsContext,
+    cache_entry_path: &PathBuf,
+) -> Result<groth16::MappedParameters<Bls12>> {
+    info!("checking cache_path: {:?} for parameters", cache_entry_path);
 
     // If the verify production params is set, we make sure that the path being accessed matches a
     // production cache key, found in the 'parameters.json' file. The parameter data file is also
     // hashed and matched against the hash in the `parameters.json` file.
-    if verify_production_params {
-        let cache_key = cache_entry_path
-            .file_name()
-            .expect("failed to get cached param filename")
-            .to_str()
-            .expect("failed to convert to str")
-            .to_string();
-
-        match get_parameter_data_from_id(&cache_key) {
-            Some(data) => {
-                // Verify the actual hash only once per parameters file
-                let not_yet_verified = VERIFIED_PARAMETERS
-                    .lock()
-                    .expect("verified parameters lock failed")
-                    .get(&cache_key)
-                    .is_none();
-                if not_yet_verified {
-                    info!("generating consistency digest for parameters");
-                    let hash = with_exclusive_read_lock::<_, io::Error, _>(
-                        cache_entry_path,
-                        |mut file| {
-                            let mut hasher = Blake2bParams::new().to_state();
-                            io::copy(&mut file, &mut hasher)
-                                .expect("copying file into hasher failed");
-                            Ok(hasher.finalize())
-                        },
-                    )?;
-                    info!("generated consistency digest for parameters");
-
-                    // The hash in the parameters file is truncated to 256 bits.
-                    let digest_hex = &hash.to_hex()[..32];
-
-                    if digest_hex != data.digest {
-                        return Err(Error::InvalidParameters(
-                            cache_entry_path.display().to_string(),
-                        )
-                        .into());
-                    }
-
-                    VERIFIED_PARAMETERS
-                        .lock()
-                        .expect("verified parameters lock failed")
-                        .insert(cache_key);
-                }
-            }
-            None => {
-                return Err(Error::InvalidParameters(cache_entry_path.display().to_string()).into())
-            }
-        }
+    if ctx.settings().verify_production_params {
+        verify_production_params(cache_entry_path)?;
     }
 
     with_exclusive_read_lock::<_, io::Error, _>(cache_entry_path, |_file| {
@@ -400,6 +400,57 @@ pub fn read_cached_params(cache_entry_path: &PathBuf) -> Result<groth16::MappedP
     .map_err(Into::into)
 }
 
+/// Checks that `cache_entry_path` names a production parameters file listed in `parameters.json`,
+/// and that its digest matches. Each file is only hashed once per process.
+pub fn verify_production_params(cache_entry_path: &PathBuf) -> Result<()> {
+    let cache_key = cache_entry_path
+        .file_name()
+        .expect("failed to get cached param filename")
+        .to_str()
+        .expect("failed to convert to str")
+        .to_string();
+
+    match get_parameter_data_from_id(&cache_key) {
+        Some(data) => {
+            // Verify the actual hash only once per parameters file
+            let not_yet_verified = VERIFIED_PARAMETERS
+                .lock()
+                .expect("verified parameters lock failed")
+                .get(&cache_key)
+                .is_none();
+            if not_yet_verified {
+                info!("generating consistency digest for parameters");
+                let hash =
+                    with_exclusive_read_lock::<_, io::Error, _>(cache_entry_path, |mut file| {
+                        let mut hasher = Blake2bParams::new().to_state();
+                        io::copy(&mut file, &mut hasher).expect("copying file into hasher failed");
+                        Ok(hasher.finalize())
+                    })?;
+                info!("generated consistency digest for parameters");
+
+                // The hash in the parameters file is truncated to 256 bits.
+                let digest_hex = &hash.to_hex()[..32];
+
+                if digest_hex != data.digest {
+                    return Err(
+                        Error::InvalidParameters(cache_entry_path.display().to_string()).into(),
+                    );
+                }
+
+                VERIFIED_PARAMETERS
+                    .lock()
+                    .expect("verified parameters lock failed")
+                    .insert(cache_key);
+            }
+        }
+        None => {
+            return Err(Error::InvalidParameters(cache_entry_path.display().to_string()).into())
+        }
+    }
+
+    Ok(())
+}
+
 fn read_cached_verifying_key(
     cache_entry_path: &PathBuf,
 ) -> io::Result<groth16::VerifyingKey<Bls12>> {
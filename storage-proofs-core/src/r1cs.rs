@@ -0,0 +1,468 @@
+//! Export of synthesized circuits as rank-1 constraint systems, and of their witnesses, for
+//! auditing and for use with alternative provers.
+//!
+//! All integers are little-endian. Field elements are 32 bytes, little-endian, in canonical
+//! (non-Montgomery) form.
+//!
+//! # R1CS format
+//!
+//! | Field             | Type       | Description                                             |
+//! |-------------------|------------|---------------------------------------------------------|
+//! | `magic`           | 8 bytes    | `FILR1CS\0`                                             |
+//! | `version`         | `u32`      | `1`                                                     |
+//! | `field_size`      | `u32`      | `32`, the size of a field element in bytes              |
+//! | `modulus`         | 32 bytes   | the modulus of the BLS12-381 scalar field               |
+//! | `num_inputs`      | `u64`      | number of public inputs, including the constant one     |
+//! | `num_aux`         | `u64`      | number of private (auxiliary) variables                 |
+//! | `num_constraints` | `u64`      | number of constraints                                   |
+//! | `constraints`     |            | `num_constraints` times the linear combinations A, B, C |
+//!
+//! Every constraint requires `A * B = C`. Each linear combination is a `u32` number of terms,
+//! followed by that many terms of a `u64` variable and a field element coefficient. Input `i` is
+//! the variable `i`, auxiliary variable `j` is the variable `2^63 + j`. Input 0 is always the
+//! constant one.
+//!
+//! # Annotations
+//!
+//! Optionally, the names of all variables and constraints, including the namespaces they were
+//! created in, are written as text lines of `<input|aux|constraint> <index> <name>`, where
+//! namespaces are separated by `/`.
+//!
+//! # Witness format
+//!
+//! | Field        | Type     | Description                                                    |
+//! |--------------|----------|----------------------------------------------------------------|
+//! | `magic`      | 8 bytes  | `FILWTNS\0`                                                    |
+//! | `version`    | `u32`    | `1`                                                            |
+//! | `field_size` | `u32`    | `32`                                                           |
+//! | `num_inputs` | `u64`    | number of public inputs, including the constant one            |
+//! | `num_aux`    | `u64`    | number of auxiliary variables                                  |
+//! | `values`     |          | `num_inputs` input values followed by `num_aux` aux values     |
+
+use std::io::{self, Seek, SeekFrom, Write};
+
+use anyhow::{Context, Result};
+use bellperson::{
+    bls::{Bls12, Fr},
+    Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable,
+};
+use byteorder::{LittleEndian, WriteBytesExt};
+use ff::Field;
+use fr32::fr_into_bytes;
+
+pub const R1CS_MAGIC: &[u8; 8] = b"FILR1CS\0";
+pub const WITNESS_MAGIC: &[u8; 8] = b"FILWTNS\0";
+pub const R1CS_FORMAT_VERSION: u32 = 1;
+
+const FR_BYTES: u32 = 32;
+
+/// The modulus of the BLS12-381 scalar field, little-endian.
+const FR_MODULUS: [u8; 32] = [
+    0x01, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xfe, 0x5b, 0xfe, 0xff, 0x02, 0xa4, 0xbd, 0x53,
+    0x05, 0xd8, 0xa1, 0x09, 0x08, 0xd8, 0x39, 0x33, 0x48, 0x7d, 0x9d, 0x29, 0x53, 0xa7, 0xed, 0x73,
+];
+
+/// Offset of `num_inputs` within the R1CS header.
+const COUNTS_OFFSET: u64 = 8 + 4 + 4 + 32;
+
+const AUX_FLAG: u64 = 1 << 63;
+
+/// The dimensions of an exported constraint system.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct R1csInfo {
+    pub num_inputs: u64,
+    pub num_aux: u64,
+    pub num_constraints: u64,
+}
+
+/// Synthesizes `circuit` and streams its constraints to `out`. Values are never assigned, so
+/// `circuit` is typically a blank circuit. If `annotations` is given, the names of all variables
+/// and constraints are written to it.
+pub fn write_r1cs<C, W, A>(circuit: C, out: W, annotations: Option<A>) -> Result<R1csInfo>
+where
+    C: Circuit<Bls12>,
+    W: Write + Seek + Send,
+    A: Write + Send,
+{
+    let mut cs = R1csWriter::new(out, annotations).context("failed to write r1cs header")?;
+    circuit
+        .synthesize(&mut cs)
+        .context("failed to synthesize circuit")?;
+    cs.finish()
+}
+
+/// Synthesizes `circuit`, which must have all values assigned, and writes the values of all its
+/// variables to `out`. The variables are indexed the same as by `write_r1cs`.
+pub fn write_witness<C, W>(circuit: C, mut out: W) -> Result<R1csInfo>
+where
+    C: Circuit<Bls12>,
+    W: Write,
+{
+    let mut cs = WitnessRecorder {
+        inputs: vec![Fr::one()],
+        aux: Vec::new(),
+        num_constraints: 0,
+    };
+    circuit
+        .synthesize(&mut cs)
+        .context("failed to synthesize circuit")?;
+
+    out.write_all(WITNESS_MAGIC)?;
+    out.write_u32::<LittleEndian>(R1CS_FORMAT_VERSION)?;
+    out.write_u32::<LittleEndian>(FR_BYTES)?;
+    out.write_u64::<LittleEndian>(cs.inputs.len() as u64)?;
+    out.write_u64::<LittleEndian>(cs.aux.len() as u64)?;
+    for value in cs.inputs.iter().chain(cs.aux.iter()) {
+        out.write_all(&fr_into_bytes(value))?;
+    }
+    out.flush()?;
+
+    Ok(R1csInfo {
+        num_inputs: cs.inputs.len() as u64,
+        num_aux: cs.aux.len() as u64,
+        num_constraints: cs.num_constraints,
+    })
+}
+
+fn write_counts<W: Write>(out: &mut W, info: &R1csInfo) -> io::Result<()> {
+    out.write_u64::<LittleEndian>(info.num_inputs)?;
+    out.write_u64::<LittleEndian>(info.num_aux)?;
+    out.write_u64::<LittleEndian>(info.num_constraints)
+}
+
+/// A constraint system which writes constraints as they are enforced, rather than keeping them in
+/// memory, as the production circuits have up to hundreds of millions of them.
+struct R1csWriter<W, A> {
+    out: W,
+    annotations: Option<A>,
+    namespace: Vec<String>,
+    info: R1csInfo,
+    /// `enforce` cannot fail, so the first write error is kept until `finish`.
+    error: Option<io::Error>,
+}
+
+impl<W: Write + Seek + Send, A: Write + Send> R1csWriter<W, A> {
+    fn new(mut out: W, annotations: Option<A>) -> io::Result<Self> {
+        out.write_all(R1CS_MAGIC)?;
+        out.write_u32::<LittleEndian>(R1CS_FORMAT_VERSION)?;
+        out.write_u32::<LittleEndian>(FR_BYTES)?;
+        out.write_all(&FR_MODULUS)?;
+        // The counts are only known once synthesis is done, and filled in by `finish`.
+        write_counts(&mut out, &R1csInfo::default())?;
+
+        let mut cs = R1csWriter {
+            out,
+            annotations,
+            namespace: Vec::new(),
+            info: R1csInfo {
+                num_inputs: 1,
+                ..Default::default()
+            },
+            error: None,
+        };
+        cs.annotate("input", 0, "ONE".to_string());
+
+        Ok(cs)
+    }
+
+    fn annotate(&mut self, kind: &str, index: u64, name: String) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Some(annotations) = self.annotations.as_mut() {
+            let res = if self.namespace.is_empty() {
+                writeln!(annotations, "{} {} {}", kind, index, name)
+            } else {
+                writeln!(
+                    annotations,
+                    "{} {} {}/{}",
+                    kind,
+                    index,
+                    self.namespace.join("/"),
+                    name
+                )
+            };
+            if let Err(err) = res {
+                self.error = Some(err);
+            }
+        }
+    }
+
+    fn write_lc(&mut self, lc: &LinearCombination<Bls12>) -> io::Result<()> {
+        let terms: Vec<(Variable, &Fr)> = lc.iter().collect();
+        self.out.write_u32::<LittleEndian>(terms.len() as u32)?;
+        for (variable, coeff) in terms {
+            let index = match variable.get_unchecked() {
+                Index::Input(i) => i as u64,
+                Index::Aux(i) => AUX_FLAG | i as u64,
+            };
+            self.out.write_u64::<LittleEndian>(index)?;
+            self.out.write_all(&fr_into_bytes(coeff))?;
+        }
+
+        Ok(())
+    }
+
+    fn write_constraint(
+        &mut self,
+        a: &LinearCombination<Bls12>,
+        b: &LinearCombination<Bls12>,
+        c: &LinearCombination<Bls12>,
+    ) -> io::Result<()> {
+        self.write_lc(a)?;
+        self.write_lc(b)?;
+        self.write_lc(c)
+    }
+
+    fn finish(mut self) -> Result<R1csInfo> {
+        if let Some(err) = self.error.take() {
+            return Err(err).context("failed to write r1cs");
+        }
+
+        self.out.seek(SeekFrom::Start(COUNTS_OFFSET))?;
+        write_counts(&mut self.out, &self.info)?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()?;
+        if let Some(mut annotations) = self.annotations {
+            annotations.flush()?;
+        }
+
+        Ok(self.info)
+    }
+}
+
+impl<W: Write + Seek + Send, A: Write + Send> ConstraintSystem<Bls12> for R1csWriter<W, A> {
+    type Root = Self;
+
+    fn alloc<F, AN, AR>(&mut self, annotation: AN, _f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Fr, SynthesisError>,
+        AN: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let index = self.info.num_aux;
+        self.info.num_aux += 1;
+        if self.annotations.is_some() {
+            self.annotate("aux", index, annotation().into());
+        }
+
+        Ok(Variable::new_unchecked(Index::Aux(index as usize)))
+    }
+
+    fn alloc_input<F, AN, AR>(&mut self, annotation: AN, _f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Fr, SynthesisError>,
+        AN: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let index = self.info.num_inputs;
+        self.info.num_inputs += 1;
+        if self.annotations.is_some() {
+            self.annotate("input", index, annotation().into());
+        }
+
+        Ok(Variable::new_unchecked(Index::Input(index as usize)))
+    }
+
+    fn enforce<AN, AR, LA, LB, LC>(&mut self, annotation: AN, a: LA, b: LB, c: LC)
+    where
+        AN: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<Bls12>) -> LinearCombination<Bls12>,
+        LB: FnOnce(LinearCombination<Bls12>) -> LinearCombination<Bls12>,
+        LC: FnOnce(LinearCombination<Bls12>) -> LinearCombination<Bls12>,
+    {
+        let index = self.info.num_constraints;
+        self.info.num_constraints += 1;
+        if self.annotations.is_some() {
+            self.annotate("constraint", index, annotation().into());
+        }
+
+        if self.error.is_none() {
+            let a = a(LinearCombination::zero());
+            let b = b(LinearCombination::zero());
+            let c = c(LinearCombination::zero());
+            if let Err(err) = self.write_constraint(&a, &b, &c) {
+                self.error = Some(err);
+            }
+        }
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        if self.annotations.is_some() {
+            self.namespace.push(name_fn().into());
+        }
+    }
+
+    fn pop_namespace(&mut self) {
+        if self.annotations.is_some() {
+            self.namespace.pop();
+        }
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+/// A constraint system which only records the assigned values.
+struct WitnessRecorder {
+    inputs: Vec<Fr>,
+    aux: Vec<Fr>,
+    num_constraints: u64,
+}
+
+impl ConstraintSystem<Bls12> for WitnessRecorder {
+    type Root = Self;
+
+    fn alloc<F, AN, AR>(&mut self, _annotation: AN, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Fr, SynthesisError>,
+        AN: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.aux.push(f()?);
+
+        Ok(Variable::new_unchecked(Index::Aux(self.aux.len() - 1)))
+    }
+
+    fn alloc_input<F, AN, AR>(&mut self, _annotation: AN, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Fr, SynthesisError>,
+        AN: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.inputs.push(f()?);
+
+        Ok(Variable::new_unchecked(Index::Input(self.inputs.len() - 1)))
+    }
+
+    fn enforce<AN, AR, LA, LB, LC>(&mut self, _annotation: AN, _a: LA, _b: LB, _c: LC)
+    where
+        AN: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<Bls12>) -> LinearCombination<Bls12>,
+        LB: FnOnce(LinearCombination<Bls12>) -> LinearCombination<Bls12>,
+        LC: FnOnce(LinearCombination<Bls12>) -> LinearCombination<Bls12>,
+    {
+        self.num_constraints += 1;
+    }
+
+    fn push_namespace<NR, N>(&mut self, _name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self) {}
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use byteorder::{ByteOrder, LittleEndian};
+    use fr32::bytes_into_fr;
+
+    /// Proves knowledge of `x` such that `x * x = y`, with `y` public.
+    struct Square(Option<Fr>);
+
+    impl Circuit<Bls12> for Square {
+        fn synthesize<CS: ConstraintSystem<Bls12>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let x_value = self.0;
+            let y_value = x_value.map(|mut x| {
+                x.square();
+                x
+            });
+
+            let x = cs.alloc(|| "x", || x_value.ok_or(SynthesisError::AssignmentMissing))?;
+            let y = cs
+                .namespace(|| "output")
+                .alloc_input(|| "y", || y_value.ok_or(SynthesisError::AssignmentMissing))?;
+            cs.enforce(|| "square", |lc| lc + x, |lc| lc + x, |lc| lc + y);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_r1cs() {
+        let mut out = Cursor::new(Vec::new());
+        let mut annotations = Vec::new();
+        let info =
+            write_r1cs(Square(None), &mut out, Some(&mut annotations)).expect("write_r1cs failure");
+
+        assert_eq!(
+            info,
+            R1csInfo {
+                num_inputs: 2,
+                num_aux: 1,
+                num_constraints: 1,
+            }
+        );
+        assert_eq!(
+            String::from_utf8(annotations).expect("invalid utf8"),
+            "input 0 ONE\naux 0 x\ninput 1 output/y\nconstraint 0 square\n"
+        );
+
+        let bytes = out.into_inner();
+        assert_eq!(&bytes[..8], R1CS_MAGIC);
+        assert_eq!(&bytes[16..48], &FR_MODULUS);
+        assert_eq!(LittleEndian::read_u64(&bytes[48..56]), 2);
+        assert_eq!(LittleEndian::read_u64(&bytes[56..64]), 1);
+        assert_eq!(LittleEndian::read_u64(&bytes[64..72]), 1);
+
+        // A = x, B = x and C = y, each a single term with coefficient one.
+        let term_len = 8 + 32;
+        assert_eq!(bytes.len(), 72 + 3 * (4 + term_len));
+        let expected_vars = [AUX_FLAG, AUX_FLAG, 1];
+        for (i, expected_var) in expected_vars.iter().enumerate() {
+            let lc = &bytes[72 + i * (4 + term_len)..];
+            assert_eq!(LittleEndian::read_u32(&lc[..4]), 1);
+            assert_eq!(LittleEndian::read_u64(&lc[4..12]), *expected_var);
+            assert_eq!(
+                bytes_into_fr(&lc[12..44]).expect("invalid coefficient"),
+                Fr::one()
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_witness() {
+        let mut x = Fr::one();
+        x.double();
+
+        let mut out = Vec::new();
+        let info = write_witness(Square(Some(x)), &mut out).expect("write_witness failure");
+        assert_eq!(info.num_inputs, 2);
+        assert_eq!(info.num_aux, 1);
+
+        assert_eq!(&out[..8], WITNESS_MAGIC);
+        let values: Vec<Fr> = out[32..]
+            .chunks(32)
+            .map(|chunk| bytes_into_fr(chunk).expect("invalid value"))
+            .collect();
+        let mut four = x;
+        four.double();
+        assert_eq!(values, vec![Fr::one(), four, x]);
+    }
+
+    #[test]
+    fn test_write_witness_requires_assignment() {
+        assert!(write_witness(Square(None), Vec::new()).is_err());
+    }
+}
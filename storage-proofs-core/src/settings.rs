@@ -1,4 +1,5 @@
 use std::env;
+use std::sync::Arc;
 
 use config::{Config, ConfigError, Environment, File};
 use lazy_static::lazy_static;
@@ -6,12 +7,13 @@ use serde::{Deserialize, Serialize};
 
 lazy_static! {
     pub static ref SETTINGS: Settings = Settings::new().expect("invalid configuration");
+    static ref DEFAULT_CONTEXT: ProofsContext = ProofsContext::new(SETTINGS.clone());
 }
 
 const SETTINGS_PATH: &str = "./rust-fil-proofs.config.toml";
 const PREFIX: &str = "FIL_PROOFS";
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Settings {
     pub verify_cache: bool,
@@ -19,11 +21,17 @@ pub struct Settings {
     pub use_gpu_column_builder: bool,
     pub max_gpu_column_batch_size: u32,
     pub column_write_batch_size: u32,
+    pub use_cpu_batch_tree_builder: bool,
     pub use_gpu_tree_builder: bool,
     pub max_gpu_tree_batch_size: u32,
     pub rows_to_discard: u32,
     pub sdr_parents_cache_size: u32,
     pub window_post_synthesis_num_cpus: u32,
+    pub window_post_max_reads_per_device: usize,
+    pub window_post_sector_read_timeout_secs: u64,
+    pub groth_param_memory_cache_size: u64,
+    pub use_tree_handle_cache: bool,
+    pub tree_handle_cache_max_open_files: u64,
     pub parameter_cache: String,
     pub parent_cache: String,
     pub use_multicore_sdr: bool,
@@ -40,11 +48,19 @@ impl Default for Settings {
             use_gpu_column_builder: false,
             max_gpu_column_batch_size: 400_000,
             column_write_batch_size: 262_144,
+            use_cpu_batch_tree_builder: false,
             use_gpu_tree_builder: false,
             max_gpu_tree_batch_size: 700_000,
             rows_to_discard: 2,
             sdr_parents_cache_size: 2_048,
             window_post_synthesis_num_cpus: num_cpus::get() as u32,
+            window_post_max_reads_per_device: 4,
+            // Zero disables the timeout.
+            window_post_sector_read_timeout_secs: 60,
+            // No limit.
+            groth_param_memory_cache_size: 0,
+            use_tree_handle_cache: false,
+            tree_handle_cache_max_open_files: 1_024,
             // `parameter_cache` does not use the cache() mechanism because it is now used
             // for durable, canonical Groth parameters and verifying keys.
             // The name is retained for backwards compatibility.
@@ -78,3 +94,33 @@ impl Settings {
         s.try_into()
     }
 }
+
+/// The settings a single proving call runs with.
+///
+/// `ProofsContext::default()` uses the global `SETTINGS`, read from the config file and the
+/// environment. A custom context allows running e.g. one sector with the multicore SDR and the
+/// GPU tree builder and another one without, in the same process. Process wide resources, i.e.
+/// the in-memory Groth parameter cache, the multicore SDR core groups and the Window PoSt
+/// synthesis thread count, always follow the global settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProofsContext {
+    settings: Arc<Settings>,
+}
+
+impl Default for ProofsContext {
+    fn default() -> Self {
+        DEFAULT_CONTEXT.clone()
+    }
+}
+
+impl ProofsContext {
+    pub fn new(settings: Settings) -> Self {
+        ProofsContext {
+            settings: Arc::new(settings),
+        }
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+}
@@ -8,7 +8,7 @@ use bellperson::{
 };
 use merkletree::merkle::get_merkle_tree_row_count;
 
-use crate::{error::Error, settings::SETTINGS};
+use crate::{error::Error, settings::ProofsContext};
 
 pub const NODE_SIZE: usize = 32;
 
@@ -150,6 +150,15 @@ pub fn reverse_bit_numbering(bits: Vec<Boolean>) -> Vec<Boolean> {
 
 // If the tree is large enough to use the default value (per-arity), use it.  If it's too small to cache anything (i.e. not enough rows), don't discard any.
 pub fn default_rows_to_discard(leafs: usize, arity: usize) -> usize {
+    default_rows_to_discard_with_context(&ProofsContext::default(), leafs, arity)
+}
+
+/// Same as `default_rows_to_discard`, using the configured oct-tree value of `ctx`.
+pub fn default_rows_to_discard_with_context(
+    ctx: &ProofsContext,
+    leafs: usize,
+    arity: usize,
+) -> usize {
     let row_count = get_merkle_tree_row_count(leafs, arity);
     if row_count <= 2 {
         // If a tree only has a root row and/or base, there is
@@ -166,7 +175,7 @@ pub fn default_rows_to_discard(leafs: usize, arity: usize) -> usize {
 
     // This configurable setting is for a default oct-tree
     // rows_to_discard value, which defaults to 2.
-    let rows_to_discard = SETTINGS.rows_to_discard as usize;
+    let rows_to_discard = ctx.settings().rows_to_discard as usize;
 
     // Discard at most 'constant value' rows (coded below,
     // differing by arity) while respecting the max number that
@@ -335,4 +344,21 @@ mod tests {
             "circuit and non circuit do not match"
         );
     }
+
+    #[test]
+    fn test_default_rows_to_discard_with_context() {
+        let mut settings = ProofsContext::default().settings().clone();
+        let leafs = 1 << 15;
+
+        settings.rows_to_discard = 1;
+        let ctx = ProofsContext::new(settings.clone());
+        assert_eq!(default_rows_to_discard_with_context(&ctx, leafs, 8), 1);
+
+        settings.rows_to_discard = 3;
+        let ctx = ProofsContext::new(settings);
+        assert_eq!(default_rows_to_discard_with_context(&ctx, leafs, 8), 3);
+
+        // Trees too small to cache anything discard nothing, regardless of the setting.
+        assert_eq!(default_rows_to_discard_with_context(&ctx, 8, 8), 0);
+    }
 }
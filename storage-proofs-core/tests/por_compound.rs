@@ -18,11 +18,17 @@ use storage_proofs_core::{
         create_base_merkle_tree, generate_tree, get_base_tree_count, MerkleTreeTrait,
         MerkleTreeWrapper, ResTree,
     },
+    parameter_cache::{
+        parameter_cache_metadata_path, parameter_cache_params_path,
+        parameter_cache_verifying_key_path, CacheableParameters,
+    },
     por,
     proof::NoRequirements,
+    settings::{ProofsContext, Settings},
     util::data_at_node,
     TEST_SEED,
 };
+use tempfile::tempdir;
 
 type TreeBase<H, A> = MerkleTreeWrapper<H, VecStore<<H as Hasher>::Domain>, A, U0, U0>;
 type TreeSub<H, A, B> = MerkleTreeWrapper<H, VecStore<<H as Hasher>::Domain>, A, B, U0>;
@@ -92,6 +98,82 @@ fn por_compound<Tree: 'static + MerkleTreeTrait>() {
     assert!(cs.verify(&inputs));
 }
 
+#[test]
+fn test_por_compound_parameter_cache_per_context() {
+    type Tree = TreeBase<PoseidonHasher, U2>;
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+
+    let setup_params = compound_proof::SetupParams {
+        vanilla_params: por::SetupParams {
+            leaves: 64,
+            private: false,
+        },
+        partitions: None,
+        priority: false,
+    };
+    let public_params = PoRCompound::<Tree>::setup(&setup_params).expect("setup failed");
+    let id = PoRCompound::<Tree>::cache_identifier(&public_params.vanilla_params);
+
+    let context_with_cache = |dir: &std::path::Path| {
+        ProofsContext::new(Settings {
+            parameter_cache: dir.to_string_lossy().into_owned(),
+            ..Settings::default()
+        })
+    };
+    let dir_a = tempdir().expect("tempdir failure");
+    let dir_b = tempdir().expect("tempdir failure");
+    let ctx_a = context_with_cache(dir_a.path());
+    let ctx_b = context_with_cache(dir_b.path());
+
+    PoRCompound::<Tree>::verifying_key_with_context(
+        &ctx_a,
+        Some(rng),
+        &public_params.vanilla_params,
+    )
+    .expect("failed to generate verifying key");
+    PoRCompound::<Tree>::get_param_metadata_with_context(
+        &ctx_a,
+        PoRCompound::<Tree>::blank_circuit(&public_params.vanilla_params),
+        &public_params.vanilla_params,
+    )
+    .expect("failed to write metadata");
+
+    for path in &[
+        parameter_cache_params_path(&ctx_a, &id),
+        parameter_cache_verifying_key_path(&ctx_a, &id),
+        parameter_cache_metadata_path(&ctx_a, &id),
+    ] {
+        assert!(path.starts_with(dir_a.path()));
+        assert!(path.exists(), "{:?} was not written", path);
+    }
+    assert_eq!(
+        std::fs::read_dir(dir_b.path())
+            .expect("read_dir failure")
+            .count(),
+        0,
+        "another context's parameter cache was written to"
+    );
+
+    // The parameters cached for `ctx_a` are not visible to `ctx_b`.
+    assert!(
+        PoRCompound::<Tree>::groth_params_with_context::<XorShiftRng>(
+            &ctx_b,
+            None,
+            &public_params.vanilla_params,
+        )
+        .is_err()
+    );
+    assert!(
+        PoRCompound::<Tree>::groth_params_with_context::<XorShiftRng>(
+            &ctx_a,
+            None,
+            &public_params.vanilla_params,
+        )
+        .is_ok()
+    );
+}
+
 #[ignore]
 #[test]
 fn test_por_compound_poseidon_base_2_private_root() {
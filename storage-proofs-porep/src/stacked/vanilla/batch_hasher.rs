@@ -0,0 +1,167 @@
+use std::cmp::max;
+
+use bellperson::bls::{Bls12, Fr};
+use ff::Field;
+use filecoin_hashers::PoseidonArity;
+use generic_array::typenum::Unsigned;
+use neptune::poseidon::Poseidon;
+use rayon::prelude::{IndexedParallelIterator, ParallelIterator, ParallelSlice, ParallelSliceMut};
+
+/// The minimum number of digests a single worker computes per task, so that the cost of setting
+/// up a `Poseidon` instance is amortized over many hashes.
+const MIN_TASK_SIZE: usize = 1024;
+
+fn task_size(len: usize) -> usize {
+    max(MIN_TASK_SIZE, (len + num_cpus::get() - 1) / num_cpus::get())
+}
+
+/// Hashes consecutive groups of `A::to_usize()` elements of `preimages`, writing one digest per
+/// group into `out`. Each worker reuses a single `Poseidon` instance across its whole chunk.
+pub fn hash_batch<A: PoseidonArity>(preimages: &[Fr], out: &mut [Fr]) {
+    let arity = A::to_usize();
+    assert_eq!(
+        preimages.len(),
+        out.len() * arity,
+        "invalid preimage length"
+    );
+
+    let task_size = task_size(out.len());
+    out.par_chunks_mut(task_size)
+        .zip(preimages.par_chunks(task_size * arity))
+        .for_each(|(digests, preimages)| {
+            let mut hasher = Poseidon::<Bls12, A>::new(A::PARAMETERS());
+            for (digest, preimage) in digests.iter_mut().zip(preimages.chunks_exact(arity)) {
+                hasher.reset();
+                for element in preimage {
+                    hasher.input(*element).expect("input failure");
+                }
+                *digest = hasher.hash();
+            }
+        });
+}
+
+/// Hashes the columns formed by `layers`, where `layers[l][i]` is the label of node `i` in layer
+/// `l + 1`. The result for node `i` is written to `out[i]` and matches `hash_single_column`.
+pub fn hash_columns<A: PoseidonArity>(layers: &[Vec<Fr>], out: &mut [Fr]) {
+    assert_eq!(layers.len(), A::to_usize(), "invalid column height");
+    assert!(
+        layers.iter().all(|layer| layer.len() == out.len()),
+        "invalid layer length"
+    );
+
+    let task_size = task_size(out.len());
+    out.par_chunks_mut(task_size)
+        .enumerate()
+        .for_each(|(chunk, digests)| {
+            let offset = chunk * task_size;
+            let mut hasher = Poseidon::<Bls12, A>::new(A::PARAMETERS());
+            for (i, digest) in digests.iter_mut().enumerate() {
+                hasher.reset();
+                for layer in layers {
+                    hasher.input(layer[offset + i]).expect("input failure");
+                }
+                *digest = hasher.hash();
+            }
+        });
+}
+
+/// Builds all rows of an `A`-ary merkle tree above the given `leaves`, returned as a single
+/// vector in the same row order `merkletree` uses on disk (lowest row first, root last).
+pub fn build_tree_rows<A: PoseidonArity>(leaves: &[Fr]) -> Vec<Fr> {
+    let arity = A::to_usize();
+    assert!(leaves.len() > 1, "tree must have more than one leaf");
+    assert_eq!(leaves.len() % arity, 0, "invalid number of leaves");
+
+    let mut rows = Vec::with_capacity(leaves.len() / (arity - 1));
+    let mut row_start = 0;
+    let mut row_len = leaves.len() / arity;
+
+    rows.resize(row_len, Fr::zero());
+    hash_batch::<A>(leaves, &mut rows[..]);
+
+    while row_len > 1 {
+        assert_eq!(row_len % arity, 0, "invalid row length");
+        let next_len = row_len / arity;
+        let next_start = row_start + row_len;
+        rows.resize(next_start + next_len, Fr::zero());
+
+        let (prev, next) = rows.split_at_mut(next_start);
+        hash_batch::<A>(&prev[row_start..], next);
+
+        row_start = next_start;
+        row_len = next_len;
+    }
+
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use filecoin_hashers::{
+        poseidon::{PoseidonDomain, PoseidonFunction},
+        Domain,
+    };
+    use generic_array::typenum::{U11, U2, U8};
+    use merkletree::{merkle::MerkleTree, store::VecStore};
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+    use storage_proofs_core::TEST_SEED;
+
+    use crate::stacked::vanilla::hash::hash_single_column;
+
+    #[test]
+    fn test_hash_columns_matches_single_column() {
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+        let nodes = 3 * MIN_TASK_SIZE + 7;
+
+        for &height in &[2, 11] {
+            let layers: Vec<Vec<Fr>> = (0..height)
+                .map(|_| (0..nodes).map(|_| Fr::random(rng)).collect())
+                .collect();
+
+            let mut batched = vec![Fr::zero(); nodes];
+            match height {
+                2 => hash_columns::<U2>(&layers, &mut batched),
+                11 => hash_columns::<U11>(&layers, &mut batched),
+                _ => unreachable!(),
+            }
+
+            for (i, digest) in batched.iter().enumerate() {
+                let column: Vec<Fr> = layers.iter().map(|layer| layer[i]).collect();
+                assert_eq!(digest, &hash_single_column(&column));
+            }
+        }
+    }
+
+    fn test_build_tree_rows<A: PoseidonArity>(leafs: usize) {
+        let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+        let leaves: Vec<PoseidonDomain> = (0..leafs).map(|_| PoseidonDomain::random(rng)).collect();
+
+        let tree = MerkleTree::<PoseidonDomain, PoseidonFunction, VecStore<_>, A>::new(
+            leaves.iter().copied(),
+        )
+        .expect("merkle tree new failure");
+
+        let frs: Vec<Fr> = leaves.iter().map(|leaf| (*leaf).into()).collect();
+        let rows = build_tree_rows::<A>(&frs);
+
+        assert_eq!(rows.len() + leafs, tree.len());
+        for (i, node) in rows.iter().enumerate() {
+            let expected = tree.read_at(leafs + i).expect("read_at failure");
+            assert_eq!(PoseidonDomain::from(*node), expected);
+        }
+        assert_eq!(PoseidonDomain::from(rows[rows.len() - 1]), tree.root());
+    }
+
+    #[test]
+    fn test_build_tree_rows_binary() {
+        test_build_tree_rows::<U2>(4096);
+    }
+
+    #[test]
+    fn test_build_tree_rows_oct() {
+        test_build_tree_rows::<U8>(4096);
+    }
+}
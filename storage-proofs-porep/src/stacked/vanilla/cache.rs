@@ -16,7 +16,7 @@ use storage_proofs_core::{
     drgraph::{Graph, BASE_DEGREE},
     error::Result,
     parameter_cache::{with_exclusive_lock, LockedFile, ParameterSetMetadata, VERSION},
-    settings::SETTINGS,
+    settings::ProofsContext,
     util::NODE_SIZE,
 };
 
@@ -149,14 +149,19 @@ impl CacheData {
 }
 
 impl ParentCache {
-    pub fn new<H, G>(len: u32, cache_entries: u32, graph: &StackedGraph<H, G>) -> Result<Self>
+    pub fn new<H, G>(
+        ctx: &ProofsContext,
+        len: u32,
+        cache_entries: u32,
+        graph: &StackedGraph<H, G>,
+    ) -> Result<Self>
     where
         H: Hasher,
         G: Graph<H> + ParameterSetMetadata + Send + Sync,
     {
-        let path = cache_path(cache_entries, graph);
+        let path = cache_path(ctx, cache_entries, graph);
         if path.exists() {
-            Self::open(len, cache_entries, graph, &path)
+            Self::open(ctx, len, cache_entries, graph, &path)
         } else {
             match Self::generate(len, cache_entries, graph, &path) {
                 Ok(c) => Ok(c),
@@ -164,7 +169,7 @@ impl ParentCache {
                     match err.downcast::<io::Error>() {
                         Ok(error) if error.kind() == io::ErrorKind::AlreadyExists => {
                             // cache was written from another process, just read it
-                            Self::open(len, cache_entries, graph, &path)
+                            Self::open(ctx, len, cache_entries, graph, &path)
                         }
                         Ok(error) => Err(error.into()),
                         Err(error) => Err(error),
@@ -179,6 +184,7 @@ impl ParentCache {
     /// hash file.  If the persisted hash file does not exist, we
     /// re-generate the cache file, which will create it.
     pub fn open<H, G>(
+        ctx: &ProofsContext,
         len: u32,
         cache_entries: u32,
         graph: &StackedGraph<H, G>,
@@ -200,14 +206,14 @@ impl ParentCache {
 
                     (
                         None,
-                        SETTINGS.verify_cache,
+                        ctx.settings().verify_cache,
                         false, // not production since not in manifest
                         "".to_string(),
                     )
                 }
                 Some(pcd) => (
                     Some(pcd),
-                    SETTINGS.verify_cache,
+                    ctx.settings().verify_cache,
                     true, // is_production since it exists in the manifest
                     pcd.digest.clone(),
                 ),
@@ -392,8 +398,8 @@ impl ParentCache {
     }
 }
 
-fn parent_cache_dir_name() -> String {
-    SETTINGS.parent_cache.clone()
+pub fn parent_cache_dir_name(ctx: &ProofsContext) -> String {
+    ctx.settings().parent_cache.clone()
 }
 
 fn parent_cache_id(path: &PathBuf) -> String {
@@ -410,7 +416,7 @@ fn get_parent_cache_data(path: &PathBuf) -> Option<&ParentCacheData> {
     PARENT_CACHE.get(&parent_cache_id(path))
 }
 
-fn cache_path<H, G>(cache_entries: u32, graph: &StackedGraph<H, G>) -> PathBuf
+fn cache_path<H, G>(ctx: &ProofsContext, cache_entries: u32, graph: &StackedGraph<H, G>) -> PathBuf
 where
     H: Hasher,
     G: Graph<H> + ParameterSetMetadata + Send + Sync,
@@ -424,7 +430,7 @@ where
     }
     hasher.update(cache_entries.to_le_bytes());
     let h = hasher.finalize();
-    PathBuf::from(parent_cache_dir_name()).join(format!(
+    PathBuf::from(parent_cache_dir_name(ctx)).join(format!(
         "v{}-sdr-parent-{}.cache",
         VERSION,
         hex::encode(h),
@@ -452,7 +458,8 @@ mod tests {
         )
         .expect("new_stacked failure");
 
-        let mut cache = ParentCache::new(nodes, nodes, &graph).expect("parent cache new failure");
+        let mut cache = ParentCache::new(&ProofsContext::default(), nodes, nodes, &graph)
+            .expect("parent cache new failure");
 
         for node in 0..nodes {
             let mut expected_parents = [0; DEGREE];
@@ -477,10 +484,11 @@ mod tests {
         )
         .expect("new_stacked failure");
 
-        let mut half_cache =
-            ParentCache::new(nodes / 2, nodes, &graph).expect("parent cache new failure");
+        let mut half_cache = ParentCache::new(&ProofsContext::default(), nodes / 2, nodes, &graph)
+            .expect("parent cache new failure");
         let mut quarter_cache =
-            ParentCache::new(nodes / 4, nodes, &graph).expect("parent cache new failure");
+            ParentCache::new(&ProofsContext::default(), nodes / 4, nodes, &graph)
+                .expect("parent cache new failure");
 
         for node in 0..nodes {
             let mut expected_parents = [0; DEGREE];
@@ -22,7 +22,7 @@ use storage_proofs_core::{
     cache_key::CacheKey,
     drgraph::{Graph, BASE_DEGREE},
     merkle::MerkleTreeTrait,
-    settings::SETTINGS,
+    settings::ProofsContext,
     util::NODE_SIZE,
 };
 
@@ -203,6 +203,7 @@ fn create_label_runner(
 }
 
 fn create_layer_labels(
+    ctx: &ProofsContext,
     parents_cache: &CacheReader<u32>,
     replica_id: &[u8],
     layer_labels: &mut MmapMut,
@@ -214,7 +215,7 @@ fn create_layer_labels(
     info!("Creating labels for layer {}", cur_layer);
     // num_producers is the number of producer threads
     let (lookahead, num_producers, producer_stride) = {
-        let settings = &SETTINGS;
+        let settings = ctx.settings();
         let lookahead = settings.multicore_sdr_lookahead;
         let num_producers = settings.multicore_sdr_producers;
         // NOTE: Stride must not exceed the number of nodes in parents_cache's window. If it does, the process will deadlock
@@ -424,6 +425,7 @@ fn create_layer_labels(
 
 #[allow(clippy::type_complexity)]
 pub fn create_labels_for_encoding<Tree: 'static + MerkleTreeTrait, T: AsRef<[u8]>>(
+    ctx: &ProofsContext,
     graph: &StackedBucketGraph<Tree::Hasher>,
     parents_cache: &ParentCache,
     layers: usize,
@@ -436,7 +438,7 @@ pub fn create_labels_for_encoding<Tree: 'static + MerkleTreeTrait, T: AsRef<[u8]
 
     let sector_size = graph.size() * NODE_SIZE;
     let node_count = graph.size() as u64;
-    let cache_window_nodes = SETTINGS.sdr_parents_cache_size as usize;
+    let cache_window_nodes = ctx.settings().sdr_parents_cache_size as usize;
 
     let default_cache_size = DEGREE * 4 * cache_window_nodes;
 
@@ -476,6 +478,7 @@ pub fn create_labels_for_encoding<Tree: 'static + MerkleTreeTrait, T: AsRef<[u8]
         }
 
         create_layer_labels(
+            ctx,
             &parents_cache,
             &replica_id.as_ref(),
             &mut layer_labels,
@@ -520,6 +523,7 @@ pub fn create_labels_for_encoding<Tree: 'static + MerkleTreeTrait, T: AsRef<[u8]
 
 #[allow(clippy::type_complexity)]
 pub fn create_labels_for_decoding<Tree: 'static + MerkleTreeTrait, T: AsRef<[u8]>>(
+    ctx: &ProofsContext,
     graph: &StackedBucketGraph<Tree::Hasher>,
     parents_cache: &ParentCache,
     layers: usize,
@@ -534,7 +538,7 @@ pub fn create_labels_for_decoding<Tree: 'static + MerkleTreeTrait, T: AsRef<[u8]
 
     let sector_size = graph.size() * NODE_SIZE;
     let node_count = graph.size() as u64;
-    let cache_window_nodes = (&SETTINGS.sdr_parents_cache_size / 2) as usize;
+    let cache_window_nodes = (ctx.settings().sdr_parents_cache_size / 2) as usize;
 
     let default_cache_size = DEGREE * 4 * cache_window_nodes;
 
@@ -566,6 +570,7 @@ pub fn create_labels_for_decoding<Tree: 'static + MerkleTreeTrait, T: AsRef<[u8]
         }
 
         create_layer_labels(
+            ctx,
             &parents_cache,
             &replica_id.as_ref(),
             &mut layer_labels,
@@ -727,10 +732,11 @@ mod tests {
             api_version,
         )
         .unwrap();
-        let cache = graph.parent_cache().unwrap();
+        let ctx = ProofsContext::default();
+        let cache = graph.parent_cache(&ctx).unwrap();
 
         let labels = create_labels_for_decoding::<LCTree<PoseidonHasher, U8, U0, U2>, _>(
-            &graph, &cache, layers, replica_id, config,
+            &ctx, &graph, &cache, layers, replica_id, config,
         )
         .unwrap();
 
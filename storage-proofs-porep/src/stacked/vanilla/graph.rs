@@ -16,7 +16,7 @@ use storage_proofs_core::{
     drgraph::{BucketGraph, Graph, BASE_DEGREE},
     error::Result,
     parameter_cache::ParameterSetMetadata,
-    settings::SETTINGS,
+    settings::ProofsContext,
     util::NODE_SIZE,
     PoRepID,
 };
@@ -133,15 +133,15 @@ where
     }
 
     /// Returns a reference to the parent cache.
-    pub fn parent_cache(&self) -> Result<ParentCache> {
+    pub fn parent_cache(&self, ctx: &ProofsContext) -> Result<ParentCache> {
         // Number of nodes to be cached in memory
-        let default_cache_size = SETTINGS.sdr_parents_cache_size;
+        let default_cache_size = ctx.settings().sdr_parents_cache_size;
         let cache_entries = self.size() as u32;
         let cache_size = cache_entries.min(default_cache_size);
 
         info!("using parent_cache[{} / {}]", cache_size, cache_entries);
 
-        ParentCache::new(cache_size, cache_entries, self)
+        ParentCache::new(ctx, cache_size, cache_entries, self)
     }
     pub fn copy_parents_data_exp(
         &self,
@@ -4,6 +4,7 @@ mod macros;
 pub mod create_label;
 pub(crate) mod hash;
 
+mod batch_hasher;
 mod cache;
 mod challenges;
 mod column;
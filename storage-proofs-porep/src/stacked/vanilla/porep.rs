@@ -5,6 +5,7 @@ use merkletree::store::StoreConfig;
 use storage_proofs_core::{
     error::Result,
     merkle::{BinaryMerkleTree, MerkleTreeTrait},
+    settings::ProofsContext,
     Data,
 };
 
@@ -34,6 +35,7 @@ impl<'a, 'c, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> PoRep<'a, Tre
         replica_path: PathBuf,
     ) -> Result<(Self::Tau, Self::ProverAux)> {
         let (tau, p_aux, t_aux) = Self::transform_and_replicate_layers(
+            &ProofsContext::default(),
             &pp.graph,
             &pp.layer_challenges,
             replica_id,
@@ -55,6 +57,7 @@ impl<'a, 'c, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> PoRep<'a, Tre
         let mut data = data.to_vec();
 
         Self::extract_and_invert_transform_layers(
+            &ProofsContext::default(),
             &pp.graph,
             &pp.layer_challenges,
             replica_id,
@@ -1,21 +1,28 @@
-use std::fs;
+use std::cmp::max;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
 use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
-use anyhow::Context;
+use anyhow::{ensure, Context};
+use bellperson::bls::Fr;
 use bincode::deserialize;
 use fdlimit::raise_fd_limit;
-use filecoin_hashers::{Domain, HashFunction, Hasher, PoseidonArity};
+use ff::Field;
+use filecoin_hashers::{poseidon::PoseidonHasher, Domain, HashFunction, Hasher, PoseidonArity};
+use fr32::fr_into_bytes;
 use generic_array::typenum::{Unsigned, U0, U11, U2, U8};
 use lazy_static::lazy_static;
 use log::{error, info, trace};
+use mapr::MmapOptions;
 use merkletree::{
-    merkle::{get_merkle_tree_len, is_merkle_tree_size_valid},
-    store::{Store, StoreConfig},
+    merkle::{get_merkle_tree_cache_size, get_merkle_tree_len, is_merkle_tree_size_valid},
+    store::{DiskStore, Store, StoreConfig},
 };
 use rayon::prelude::{
-    IndexedParallelIterator, IntoParallelIterator, ParallelIterator, ParallelSliceMut,
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
+    ParallelSlice, ParallelSliceMut,
 };
 use storage_proofs_core::{
     cache_key::CacheKey,
@@ -28,13 +35,14 @@ use storage_proofs_core::{
         split_config_and_replica, BinaryMerkleTree, DiskTree, LCTree, MerkleProofTrait, MerkleTree,
         MerkleTreeTrait,
     },
-    settings::SETTINGS,
-    util::{default_rows_to_discard, NODE_SIZE},
+    settings::ProofsContext,
+    util::{default_rows_to_discard_with_context, NODE_SIZE},
 };
 
 use crate::{
     encode::{decode, encode},
     stacked::vanilla::{
+        batch_hasher::{build_tree_rows, hash_batch, hash_columns},
         challenges::LayerChallenges,
         column::Column,
         create_label,
@@ -262,7 +270,8 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
             .collect()
     }
 
-    pub(crate) fn extract_and_invert_transform_layers(
+    pub fn extract_and_invert_transform_layers(
+        ctx: &ProofsContext,
         graph: &StackedBucketGraph<Tree::Hasher>,
         layer_challenges: &LayerChallenges,
         replica_id: &<Tree::Hasher as Hasher>::Domain,
@@ -275,7 +284,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         assert!(layers > 0);
 
         let labels =
-            Self::generate_labels_for_decoding(graph, layer_challenges, replica_id, config)?;
+            Self::generate_labels_for_decoding(ctx, graph, layer_challenges, replica_id, config)?;
 
         let last_layer_labels = labels.labels_for_last_layer()?;
         let size = Store::len(last_layer_labels);
@@ -298,16 +307,18 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
 
     /// Generates the layers as needed for encoding.
     pub fn generate_labels_for_encoding(
+        ctx: &ProofsContext,
         graph: &StackedBucketGraph<Tree::Hasher>,
         layer_challenges: &LayerChallenges,
         replica_id: &<Tree::Hasher as Hasher>::Domain,
         config: StoreConfig,
     ) -> Result<(Labels<Tree>, Vec<LayerState>)> {
-        let mut parent_cache = graph.parent_cache()?;
+        let mut parent_cache = graph.parent_cache(ctx)?;
 
-        if SETTINGS.use_multicore_sdr {
+        if ctx.settings().use_multicore_sdr {
             info!("multi core replication");
             create_label::multi::create_labels_for_encoding(
+                ctx,
                 graph,
                 &parent_cache,
                 layer_challenges.layers(),
@@ -328,16 +339,18 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
 
     /// Generates the layers, as needed for decoding.
     pub fn generate_labels_for_decoding(
+        ctx: &ProofsContext,
         graph: &StackedBucketGraph<Tree::Hasher>,
         layer_challenges: &LayerChallenges,
         replica_id: &<Tree::Hasher as Hasher>::Domain,
         config: StoreConfig,
     ) -> Result<LabelsCache<Tree>> {
-        let mut parent_cache = graph.parent_cache()?;
+        let mut parent_cache = graph.parent_cache(ctx)?;
 
-        if SETTINGS.use_multicore_sdr {
+        if ctx.settings().use_multicore_sdr {
             info!("multi core replication");
             create_label::multi::create_labels_for_decoding(
+                ctx,
                 graph,
                 &parent_cache,
                 layer_challenges.layers(),
@@ -377,6 +390,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
 
     #[cfg(any(feature = "gpu", feature = "gpu2"))]
     fn generate_tree_c<ColumnArity, TreeArity>(
+        ctx: &ProofsContext,
         layers: usize,
         nodes_count: usize,
         tree_count: usize,
@@ -387,8 +401,9 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         ColumnArity: 'static + PoseidonArity,
         TreeArity: PoseidonArity,
     {
-        if SETTINGS.use_gpu_column_builder {
+        if ctx.settings().use_gpu_column_builder {
             Self::generate_tree_c_gpu::<ColumnArity, TreeArity>(
+                ctx,
                 layers,
                 nodes_count,
                 tree_count,
@@ -397,6 +412,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
             )
         } else {
             Self::generate_tree_c_cpu::<ColumnArity, TreeArity>(
+                ctx,
                 layers,
                 nodes_count,
                 tree_count,
@@ -408,6 +424,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
 
     #[cfg(not(any(feature = "gpu", feature = "gpu2")))]
     fn generate_tree_c<ColumnArity, TreeArity>(
+        ctx: &ProofsContext,
         layers: usize,
         nodes_count: usize,
         tree_count: usize,
@@ -419,6 +436,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         TreeArity: PoseidonArity,
     {
         Self::generate_tree_c_cpu::<ColumnArity, TreeArity>(
+            ctx,
             layers,
             nodes_count,
             tree_count,
@@ -430,6 +448,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
     #[allow(clippy::needless_range_loop)]
     #[cfg(any(feature = "gpu", feature = "gpu2"))]
     fn generate_tree_c_gpu<ColumnArity, TreeArity>(
+        ctx: &ProofsContext,
         layers: usize,
         nodes_count: usize,
         tree_count: usize,
@@ -466,9 +485,9 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
             // Override these values with care using environment variables:
             // FIL_PROOFS_MAX_GPU_COLUMN_BATCH_SIZE, FIL_PROOFS_MAX_GPU_TREE_BATCH_SIZE, and
             // FIL_PROOFS_COLUMN_WRITE_BATCH_SIZE respectively.
-            let max_gpu_column_batch_size = SETTINGS.max_gpu_column_batch_size as usize;
-            let max_gpu_tree_batch_size = SETTINGS.max_gpu_tree_batch_size as usize;
-            let column_write_batch_size = SETTINGS.column_write_batch_size as usize;
+            let max_gpu_column_batch_size = ctx.settings().max_gpu_column_batch_size as usize;
+            let max_gpu_tree_batch_size = ctx.settings().max_gpu_tree_batch_size as usize;
+            let column_write_batch_size = ctx.settings().column_write_batch_size as usize;
 
             // This channel will receive batches of columns and add them to the ColumnTreeBuilder.
             let (builder_tx, builder_rx) = sync_channel(0);
@@ -730,6 +749,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
     }
 
     fn generate_tree_c_cpu<ColumnArity, TreeArity>(
+        ctx: &ProofsContext,
         layers: usize,
         nodes_count: usize,
         tree_count: usize,
@@ -740,6 +760,19 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         ColumnArity: PoseidonArity,
         TreeArity: PoseidonArity,
     {
+        if ctx.settings().use_cpu_batch_tree_builder
+            && Tree::Hasher::name() == PoseidonHasher::name()
+        {
+            return Self::generate_tree_c_cpu_batched::<ColumnArity, TreeArity>(
+                ctx,
+                layers,
+                nodes_count,
+                tree_count,
+                configs,
+                labels,
+            );
+        }
+
         info!("generating tree c using the CPU");
         measure_op(Operation::GenerateTreeC, || {
             info!("Building column hashes");
@@ -796,8 +829,92 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         })
     }
 
+    fn generate_tree_c_cpu_batched<ColumnArity, TreeArity>(
+        ctx: &ProofsContext,
+        layers: usize,
+        nodes_count: usize,
+        tree_count: usize,
+        configs: Vec<StoreConfig>,
+        labels: &LabelsCache<Tree>,
+    ) -> Result<DiskTree<Tree::Hasher, Tree::Arity, Tree::SubTreeArity, Tree::TopTreeArity>>
+    where
+        ColumnArity: PoseidonArity,
+        TreeArity: PoseidonArity,
+    {
+        info!("generating tree c using the CPU batch hasher");
+        measure_op(Operation::GenerateTreeC, || {
+            info!("Building column hashes");
+
+            // Columns are read and hashed in chunks of 'column_write_batch_size' nodes, so that
+            // each layer is read sequentially and the working set of a chunk stays small.
+            let column_write_batch_size = ctx.settings().column_write_batch_size as usize;
+
+            for (i, config) in configs.iter().enumerate() {
+                let mut base_data = vec![Fr::zero(); nodes_count];
+
+                for (chunk, hashes) in base_data.chunks_mut(column_write_batch_size).enumerate() {
+                    let start = (i * nodes_count) + (chunk * column_write_batch_size);
+                    let end = start + hashes.len();
+                    trace!(
+                        "hashing columns for config {}/{} [{}-{}]",
+                        i + 1,
+                        tree_count,
+                        start,
+                        end
+                    );
+
+                    let layer_data = (1..=layers)
+                        .map(|layer| {
+                            let elements: Vec<<Tree::Hasher as Hasher>::Domain> =
+                                labels.labels_for_layer(layer).read_range(start..end)?;
+                            Ok(elements.into_iter().map(Into::into).collect())
+                        })
+                        .collect::<Result<Vec<Vec<Fr>>>>()?;
+
+                    hash_columns::<ColumnArity>(&layer_data, hashes);
+                }
+
+                info!("building base tree_c {}/{}", i + 1, tree_count);
+                let tree_data = build_tree_rows::<TreeArity>(&base_data);
+                let tree_len = base_data.len() + tree_data.len();
+                ensure!(
+                    tree_len == config.size.expect("config size failure"),
+                    "invalid tree_c length {}",
+                    tree_len
+                );
+
+                let mut tree_c_store =
+                    DiskStore::<<Tree::Hasher as Hasher>::Domain>::new_with_config(
+                        tree_len,
+                        Tree::Arity::to_usize(),
+                        config.clone(),
+                    )?;
+
+                trace!(
+                    "persisting base tree_c {}/{} of length {}",
+                    i + 1,
+                    tree_count,
+                    tree_len
+                );
+                for (data, offset) in &[(&base_data, 0), (&tree_data, base_data.len())] {
+                    for (index, elements) in data.chunks(column_write_batch_size).enumerate() {
+                        let buf: Vec<u8> = elements.par_iter().flat_map(fr_into_bytes).collect();
+                        tree_c_store
+                            .copy_from_slice(&buf, offset + (index * column_write_batch_size))?;
+                    }
+                }
+                tree_c_store.sync()?;
+            }
+
+            create_disk_tree::<
+                DiskTree<Tree::Hasher, Tree::Arity, Tree::SubTreeArity, Tree::TopTreeArity>,
+            >(configs[0].size.expect("config size failure"), &configs)
+        })
+    }
+
     #[cfg(any(feature = "gpu", feature = "gpu2"))]
     fn generate_tree_r_last<TreeArity>(
+        ctx: &ProofsContext,
         data: &mut Data<'_>,
         nodes_count: usize,
         tree_count: usize,
@@ -808,8 +925,9 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
     where
         TreeArity: PoseidonArity,
     {
-        if SETTINGS.use_gpu_tree_builder {
+        if ctx.settings().use_gpu_tree_builder {
             Self::generate_tree_r_last_gpu::<TreeArity>(
+                ctx,
                 data,
                 nodes_count,
                 tree_count,
@@ -819,6 +937,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
             )
         } else {
             Self::generate_tree_r_last_cpu::<TreeArity>(
+                ctx,
                 data,
                 nodes_count,
                 tree_count,
@@ -831,6 +950,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
 
     #[cfg(not(any(feature = "gpu", feature = "gpu2")))]
     fn generate_tree_r_last<TreeArity>(
+        ctx: &ProofsContext,
         data: &mut Data<'_>,
         nodes_count: usize,
         tree_count: usize,
@@ -842,6 +962,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         TreeArity: PoseidonArity,
     {
         Self::generate_tree_r_last_cpu::<TreeArity>(
+            ctx,
             data,
             nodes_count,
             tree_count,
@@ -853,6 +974,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
 
     #[cfg(any(feature = "gpu", feature = "gpu2"))]
     fn generate_tree_r_last_gpu<TreeArity>(
+        ctx: &ProofsContext,
         data: &mut Data<'_>,
         nodes_count: usize,
         tree_count: usize,
@@ -887,7 +1009,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         let last_layer_labels = labels.labels_for_last_layer()?;
 
         info!("generating tree r last using the GPU");
-        let max_gpu_tree_batch_size = SETTINGS.max_gpu_tree_batch_size as usize;
+        let max_gpu_tree_batch_size = ctx.settings().max_gpu_tree_batch_size as usize;
 
         // This channel will receive batches of leaf nodes and add them to the TreeBuilder.
         let (builder_tx, builder_rx) = sync_channel::<(Vec<Fr>, bool)>(0);
@@ -1087,6 +1209,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
     }
 
     fn generate_tree_r_last_cpu<TreeArity>(
+        ctx: &ProofsContext,
         data: &mut Data<'_>,
         nodes_count: usize,
         tree_count: usize,
@@ -1097,6 +1220,20 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
     where
         TreeArity: PoseidonArity,
     {
+        if ctx.settings().use_cpu_batch_tree_builder
+            && Tree::Hasher::name() == PoseidonHasher::name()
+        {
+            return Self::generate_tree_r_last_cpu_batched::<TreeArity>(
+                ctx,
+                data,
+                nodes_count,
+                tree_count,
+                tree_r_last_config,
+                replica_path,
+                labels,
+            );
+        }
+
         let (configs, replica_config) = split_config_and_replica(
             tree_r_last_config.clone(),
             replica_path,
@@ -1152,7 +1289,118 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         )
     }
 
+    fn generate_tree_r_last_cpu_batched<TreeArity>(
+        ctx: &ProofsContext,
+        data: &mut Data<'_>,
+        nodes_count: usize,
+        tree_count: usize,
+        tree_r_last_config: StoreConfig,
+        replica_path: PathBuf,
+        labels: &LabelsCache<Tree>,
+    ) -> Result<LCTree<Tree::Hasher, Tree::Arity, Tree::SubTreeArity, Tree::TopTreeArity>>
+    where
+        TreeArity: PoseidonArity,
+    {
+        let (configs, replica_config) = split_config_and_replica(
+            tree_r_last_config.clone(),
+            replica_path,
+            nodes_count,
+            tree_count,
+        )?;
+
+        data.ensure_data()?;
+        let last_layer_labels = labels.labels_for_last_layer()?;
+
+        info!("generating tree r last using the CPU batch hasher");
+        let arity = TreeArity::to_usize();
+        ensure!(
+            nodes_count % arity == 0,
+            "invalid nodes count {}",
+            nodes_count
+        );
+
+        // Leaves are encoded and hashed into the first tree row in chunks, which must be a
+        // multiple of the arity so that no parent spans two chunks.
+        let batch_size = max(ctx.settings().column_write_batch_size as usize / arity, 1) * arity;
+
+        for (i, config) in configs.iter().enumerate() {
+            let mut first_row = vec![Fr::zero(); nodes_count / arity];
+
+            for (chunk, parents) in first_row.chunks_mut(batch_size / arity).enumerate() {
+                let start = (i * nodes_count) + (chunk * batch_size);
+                let end = start + (parents.len() * arity);
+
+                let encoded: Vec<Fr> = last_layer_labels
+                    .read_range(start..end)?
+                    .into_par_iter()
+                    .zip(
+                        data.as_mut()[(start * NODE_SIZE)..(end * NODE_SIZE)]
+                            .par_chunks_mut(NODE_SIZE),
+                    )
+                    .map(|(key, data_node_bytes)| {
+                        let data_node =
+                            <Tree::Hasher as Hasher>::Domain::try_from_bytes(data_node_bytes)
+                                .expect("try from bytes failed");
+                        let encoded_node =
+                            encode::<<Tree::Hasher as Hasher>::Domain>(key, data_node);
+                        data_node_bytes.copy_from_slice(AsRef::<[u8]>::as_ref(&encoded_node));
+
+                        encoded_node.into()
+                    })
+                    .collect();
+
+                hash_batch::<TreeArity>(&encoded, parents);
+            }
+
+            info!(
+                "building base tree_r_last with CPU batch hasher {}/{}",
+                i + 1,
+                tree_count
+            );
+            let mut tree_data = first_row;
+            if tree_data.len() > 1 {
+                let upper_rows = build_tree_rows::<TreeArity>(&tree_data);
+                tree_data.extend(upper_rows);
+            }
+
+            // Only the rows above those discarded are persisted, i.e. the tail of the tree.
+            let cache_size =
+                get_merkle_tree_cache_size(nodes_count, arity, config.rows_to_discard)?;
+            ensure!(
+                cache_size <= tree_data.len(),
+                "invalid tree_r_last cache size {}",
+                cache_size
+            );
+            let flat_tree_data: Vec<u8> = tree_data[tree_data.len() - cache_size..]
+                .par_iter()
+                .flat_map(fr_into_bytes)
+                .collect();
+
+            let tree_r_last_path = StoreConfig::data_path(&config.path, &config.id);
+            trace!(
+                "persisting tree r of len {} with {} rows to discard at path {:?}",
+                cache_size,
+                config.rows_to_discard,
+                tree_r_last_path
+            );
+            let mut f = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .open(&tree_r_last_path)
+                .with_context(|| format!("could not open {:?}", tree_r_last_path))?;
+            f.write_all(&flat_tree_data)
+                .with_context(|| format!("failed tree_r_last CPU {}/{}", i + 1, tree_count))?;
+        }
+
+        create_lc_tree::<LCTree<Tree::Hasher, Tree::Arity, Tree::SubTreeArity, Tree::TopTreeArity>>(
+            tree_r_last_config.size.expect("config size failure"),
+            &configs,
+            &replica_config,
+        )
+    }
+
     pub(crate) fn transform_and_replicate_layers(
+        ctx: &ProofsContext,
         graph: &StackedBucketGraph<Tree::Hasher>,
         layer_challenges: &LayerChallenges,
         replica_id: &<Tree::Hasher as Hasher>::Domain,
@@ -1163,12 +1411,19 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
     ) -> Result<TransformedLayers<Tree, G>> {
         // Generate key layers.
         let labels = measure_op(Operation::EncodeWindowTimeAll, || {
-            Self::generate_labels_for_encoding(graph, layer_challenges, replica_id, config.clone())
-                .context("failed to generate labels")
+            Self::generate_labels_for_encoding(
+                ctx,
+                graph,
+                layer_challenges,
+                replica_id,
+                config.clone(),
+            )
+            .context("failed to generate labels")
         })?
         .0;
 
         Self::transform_and_replicate_layers_inner(
+            ctx,
             graph,
             layer_challenges,
             data,
@@ -1181,6 +1436,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
     }
 
     pub(crate) fn transform_and_replicate_layers_inner(
+        ctx: &ProofsContext,
         graph: &StackedBucketGraph<Tree::Hasher>,
         layer_challenges: &LayerChallenges,
         mut data: Data<'_>,
@@ -1225,7 +1481,8 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
             CacheKey::CommDTree.to_string(),
             Some(get_merkle_tree_len(nodes_count, BINARY_ARITY)?),
         );
-        tree_d_config.rows_to_discard = default_rows_to_discard(nodes_count, BINARY_ARITY);
+        tree_d_config.rows_to_discard =
+            default_rows_to_discard_with_context(ctx, nodes_count, BINARY_ARITY);
 
         let mut tree_r_last_config = StoreConfig::from_config(
             &config,
@@ -1239,7 +1496,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         // through merkle_light, merkle_light now does a check that does not allow us to discard more rows than is possible
         // to discard.
         tree_r_last_config.rows_to_discard =
-            default_rows_to_discard(nodes_count, Tree::Arity::to_usize());
+            default_rows_to_discard_with_context(ctx, nodes_count, Tree::Arity::to_usize());
         trace!(
             "tree_r_last using rows_to_discard={}",
             tree_r_last_config.rows_to_discard
@@ -1251,7 +1508,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
             Some(get_merkle_tree_len(nodes_count, Tree::Arity::to_usize())?),
         );
         tree_c_config.rows_to_discard =
-            default_rows_to_discard(nodes_count, Tree::Arity::to_usize());
+            default_rows_to_discard_with_context(ctx, nodes_count, Tree::Arity::to_usize());
 
         let labels =
             LabelsCache::<Tree>::new(&label_configs).context("failed to create labels cache")?;
@@ -1267,6 +1524,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         let tree_c_root = match layers {
             2 => {
                 let tree_c = Self::generate_tree_c::<U2, Tree::Arity>(
+                    ctx,
                     layers,
                     nodes_count,
                     tree_count,
@@ -1277,6 +1535,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
             }
             8 => {
                 let tree_c = Self::generate_tree_c::<U8, Tree::Arity>(
+                    ctx,
                     layers,
                     nodes_count,
                     tree_count,
@@ -1287,6 +1546,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
             }
             11 => {
                 let tree_c = Self::generate_tree_c::<U11, Tree::Arity>(
+                    ctx,
                     layers,
                     nodes_count,
                     tree_count,
@@ -1327,6 +1587,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         info!("building tree_r_last");
         let tree_r_last = measure_op(Operation::GenerateTreeRLast, || {
             Self::generate_tree_r_last::<Tree::Arity>(
+                ctx,
                 &mut data,
                 nodes_count,
                 tree_count,
@@ -1368,6 +1629,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
 
     /// Phase1 of replication.
     pub fn replicate_phase1(
+        ctx: &ProofsContext,
         pp: &'a PublicParams<Tree>,
         replica_id: &<Tree::Hasher as Hasher>::Domain,
         config: StoreConfig,
@@ -1375,7 +1637,13 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         info!("replicate_phase1");
 
         let labels = measure_op(Operation::EncodeWindowTimeAll, || {
-            Self::generate_labels_for_encoding(&pp.graph, &pp.layer_challenges, replica_id, config)
+            Self::generate_labels_for_encoding(
+                ctx,
+                &pp.graph,
+                &pp.layer_challenges,
+                replica_id,
+                config,
+            )
         })?
         .0;
 
@@ -1385,6 +1653,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
     /// Phase2 of replication.
     #[allow(clippy::type_complexity)]
     pub fn replicate_phase2(
+        ctx: &ProofsContext,
         pp: &'a PublicParams<Tree>,
         labels: Labels<Tree>,
         data: Data<'a>,
@@ -1398,6 +1667,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         info!("replicate_phase2");
 
         let (tau, paux, taux) = Self::transform_and_replicate_layers_inner(
+            ctx,
             &pp.graph,
             &pp.layer_challenges,
             data,
@@ -1415,6 +1685,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
     // Instead new zeros are provided (hence the need for replica to be all zeros).
     #[cfg(any(feature = "gpu", feature = "gpu2"))]
     fn generate_fake_tree_r_last<TreeArity>(
+        ctx: &ProofsContext,
         nodes_count: usize,
         tree_count: usize,
         tree_r_last_config: StoreConfig,
@@ -1442,9 +1713,9 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
             tree_count,
         )?;
 
-        if SETTINGS.use_gpu_tree_builder {
+        if ctx.settings().use_gpu_tree_builder {
             info!("generating tree r last using the GPU");
-            let max_gpu_tree_batch_size = SETTINGS.max_gpu_tree_batch_size as usize;
+            let max_gpu_tree_batch_size = ctx.settings().max_gpu_tree_batch_size as usize;
 
             let _gpu_lock = GPU_LOCK.lock().unwrap();
             let mut tree_builder = TreeBuilder::<Tree::Arity>::new(
@@ -1548,6 +1819,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
     // Instead new zeros are provided (hence the need for replica to be all zeros).
     #[cfg(not(any(feature = "gpu", feature = "gpu2")))]
     fn generate_fake_tree_r_last<TreeArity>(
+        ctx: &ProofsContext,
         nodes_count: usize,
         tree_count: usize,
         tree_r_last_config: StoreConfig,
@@ -1586,6 +1858,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
     }
 
     pub fn fake_replicate_phase2<R: AsRef<Path>, S: AsRef<Path>>(
+        ctx: &ProofsContext,
         tree_c_root: <Tree::Hasher as Hasher>::Domain,
         replica_path: R,
         cache_path: S,
@@ -1603,7 +1876,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         let config = StoreConfig::new(
             cache_path.as_ref(),
             CacheKey::CommRLastTree.to_string(),
-            default_rows_to_discard(nodes_count, Tree::Arity::to_usize()),
+            default_rows_to_discard_with_context(ctx, nodes_count, Tree::Arity::to_usize()),
         );
         let tree_r_last_config = StoreConfig::from_config(
             &config,
@@ -1614,6 +1887,7 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         // Encode original data into the last layer.
         info!("building tree_r_last");
         let tree_r_last = Self::generate_fake_tree_r_last::<Tree::Arity>(
+            ctx,
             nodes_count,
             tree_count,
             tree_r_last_config,
@@ -1636,6 +1910,93 @@ impl<'a, Tree: 'static + MerkleTreeTrait, G: 'static + Hasher> StackedDrg<'a, Tr
         Ok((comm_r, p_aux))
     }
 
+    /// Same as `fake_replicate_phase2`, but builds `tree_r_last` over the data of the replica,
+    /// which must consist of valid field elements, rather than assuming it is all zeros.
+    pub fn fake_replicate_phase2_with_replica<R: AsRef<Path>, S: AsRef<Path>>(
+        ctx: &ProofsContext,
+        tree_c_root: <Tree::Hasher as Hasher>::Domain,
+        replica_path: R,
+        cache_path: S,
+        sector_size: usize,
+    ) -> Result<(
+        <Tree::Hasher as Hasher>::Domain,
+        PersistentAux<<Tree::Hasher as Hasher>::Domain>,
+    )> {
+        let leaf_count = sector_size / NODE_SIZE;
+        assert_eq!(0, sector_size % NODE_SIZE);
+        let tree_count = get_base_tree_count::<Tree>();
+        let nodes_count = leaf_count / tree_count;
+
+        let replica = OpenOptions::new()
+            .read(true)
+            .open(replica_path.as_ref())
+            .with_context(|| format!("could not open replica {:?}", replica_path.as_ref()))?;
+        ensure!(
+            replica.metadata()?.len() == sector_size as u64,
+            "replica {:?} is not of the sector size",
+            replica_path.as_ref()
+        );
+
+        let config = StoreConfig::new(
+            cache_path.as_ref(),
+            CacheKey::CommRLastTree.to_string(),
+            default_rows_to_discard_with_context(ctx, nodes_count, Tree::Arity::to_usize()),
+        );
+        let tree_r_last_config = StoreConfig::from_config(
+            &config,
+            CacheKey::CommRLastTree.to_string(),
+            Some(get_merkle_tree_len(nodes_count, Tree::Arity::to_usize())?),
+        );
+        let (configs, replica_config) = split_config_and_replica(
+            tree_r_last_config.clone(),
+            PathBuf::from(replica_path.as_ref()),
+            nodes_count,
+            tree_count,
+        )?;
+
+        info!("building tree_r_last over the replica");
+        let base_tree_bytes = nodes_count * NODE_SIZE;
+        for (i, config) in configs.iter().enumerate() {
+            let data = unsafe {
+                MmapOptions::new()
+                    .offset((i * base_tree_bytes) as u64)
+                    .len(base_tree_bytes)
+                    .map(&replica)
+            }
+            .with_context(|| format!("could not mmap replica {:?}", replica_path.as_ref()))?;
+
+            LCTree::<Tree::Hasher, Tree::Arity, U0, U0>::from_par_iter_with_config(
+                data.par_chunks(NODE_SIZE).map(|node| {
+                    <Tree::Hasher as Hasher>::Domain::try_from_bytes(node)
+                        .expect("node of NODE_SIZE bytes")
+                }),
+                config.clone(),
+            )?;
+        }
+        let tree_r_last = create_lc_tree::<
+            LCTree<Tree::Hasher, Tree::Arity, Tree::SubTreeArity, Tree::TopTreeArity>,
+        >(
+            tree_r_last_config.size.expect("config size failure"),
+            &configs,
+            &replica_config,
+        )?;
+        info!("tree_r_last done");
+
+        let tree_r_last_root = tree_r_last.root();
+        drop(tree_r_last);
+
+        // comm_r = H(comm_c || comm_r_last)
+        let comm_r: <Tree::Hasher as Hasher>::Domain =
+            <Tree::Hasher as Hasher>::Function::hash2(&tree_c_root, &tree_r_last_root);
+
+        let p_aux = PersistentAux {
+            comm_c: tree_c_root,
+            comm_r_last: tree_r_last_root,
+        };
+
+        Ok((comm_r, p_aux))
+    }
+
     pub fn fake_comm_r<R: AsRef<Path>>(
         tree_c_root: <Tree::Hasher as Hasher>::Domain,
         existing_p_aux_path: R,
@@ -1,4 +1,4 @@
-use std::fs::remove_file;
+use std::fs::{read, remove_file};
 
 use bellperson::bls::{Fr, FrRepr};
 use ff::{Field, PrimeField};
@@ -15,8 +15,11 @@ use storage_proofs_core::{
     api_version::ApiVersion,
     cache_key::CacheKey,
     drgraph::BASE_DEGREE,
-    merkle::{get_base_tree_count, DiskTree, MerkleTreeTrait},
+    merkle::{
+        create_base_merkle_tree, get_base_tree_count, BinaryMerkleTree, DiskTree, MerkleTreeTrait,
+    },
     proof::ProofScheme,
+    settings::{ProofsContext, Settings},
     table_tests,
     test_helper::setup_replica,
     util::{default_rows_to_discard, NODE_SIZE},
@@ -133,6 +136,7 @@ fn test_extract_all<Tree: 'static + MerkleTreeTrait>() {
     // not do any work.
 
     let (_, label_states) = StackedDrg::<Tree, Blake2sHasher>::generate_labels_for_encoding(
+        &ProofsContext::default(),
         &pp.graph,
         &layer_challenges,
         &replica_id,
@@ -151,6 +155,7 @@ fn test_extract_all<Tree: 'static + MerkleTreeTrait>() {
     }
 
     let (_, label_states) = StackedDrg::<Tree, Blake2sHasher>::generate_labels_for_encoding(
+        &ProofsContext::default(),
         &pp.graph,
         &layer_challenges,
         &replica_id,
@@ -264,6 +269,7 @@ fn test_stacked_porep_resume_seal() {
 
     // delete last 2 layers
     let (_, label_states) = StackedDrg::<Tree, Blake2sHasher>::generate_labels_for_encoding(
+        &ProofsContext::default(),
         &pp.graph,
         &layer_challenges,
         &replica_id,
@@ -307,6 +313,103 @@ fn test_stacked_porep_resume_seal() {
     cache_dir.close().expect("Failed to remove cache dir");
 }
 
+#[test]
+fn test_stacked_porep_cpu_batched_tree_builders_2kib() {
+    test_cpu_batched_tree_builders(64);
+}
+
+#[test]
+#[ignore]
+fn test_stacked_porep_cpu_batched_tree_builders_8mib() {
+    test_cpu_batched_tree_builders(1 << 18);
+}
+
+/// Replicates the same data with the regular and the batched CPU tree builders, which must
+/// produce identical replicas, commitments and tree_c and tree_r_last files.
+fn test_cpu_batched_tree_builders(nodes: usize) {
+    type Tree = DiskTree<PoseidonHasher, U8, U0, U0>;
+
+    let rng = &mut XorShiftRng::from_seed(TEST_SEED);
+    let replica_id = <PoseidonHasher as Hasher>::Domain::random(rng);
+    let data: Vec<u8> = (0..nodes)
+        .flat_map(|_| fr_into_bytes(&Fr::random(rng)))
+        .collect();
+
+    let sp = SetupParams {
+        nodes,
+        degree: BASE_DEGREE,
+        expansion_degree: EXP_DEGREE,
+        porep_id: [32; 32],
+        layer_challenges: LayerChallenges::new(2, 5),
+        api_version: ApiVersion::V1_1_0,
+    };
+    let pp = StackedDrg::<Tree, Blake2sHasher>::setup(&sp).expect("setup failed");
+
+    let replicate = |use_cpu_batch_tree_builder: bool| {
+        let ctx = ProofsContext::new(Settings {
+            use_cpu_batch_tree_builder,
+            ..Settings::default()
+        });
+
+        let cache_dir = tempdir().expect("tempdir failure");
+        let replica_path = cache_dir.path().join("replica-path");
+        let mut mmapped_data = setup_replica(&data, &replica_path);
+
+        let mut config = StoreConfig::new(
+            cache_dir.path(),
+            CacheKey::CommDTree.to_string(),
+            default_rows_to_discard(nodes, BINARY_ARITY),
+        );
+        let data_tree = create_base_merkle_tree::<BinaryMerkleTree<Blake2sHasher>>(
+            Some(config.clone()),
+            nodes,
+            &data,
+        )
+        .expect("failed to build tree_d");
+        config.size = Some(data_tree.len());
+
+        let labels = StackedDrg::<Tree, Blake2sHasher>::replicate_phase1(
+            &ctx,
+            &pp,
+            &replica_id,
+            config.clone(),
+        )
+        .expect("replication phase 1 failed");
+        let (tau, (p_aux, _)) = StackedDrg::<Tree, Blake2sHasher>::replicate_phase2(
+            &ctx,
+            &pp,
+            labels,
+            (mmapped_data.as_mut()).into(),
+            data_tree,
+            config,
+            replica_path,
+        )
+        .expect("replication phase 2 failed");
+
+        let tree_files: Vec<Vec<u8>> = [CacheKey::CommCTree, CacheKey::CommRLastTree]
+            .iter()
+            .flat_map(|key| {
+                let pattern = format!("{}/*{}*.dat", cache_dir.path().to_string_lossy(), key);
+                glob(&pattern).expect("invalid glob pattern")
+            })
+            .map(|path| {
+                read(path.expect("failed to read glob entry")).expect("failed to read tree")
+            })
+            .collect();
+        assert!(!tree_files.is_empty(), "no tree files written");
+
+        (tau, p_aux, mmapped_data.to_vec(), tree_files)
+    };
+
+    let (tau, p_aux, replica, tree_files) = replicate(false);
+    let (batched_tau, batched_p_aux, batched_replica, batched_tree_files) = replicate(true);
+
+    assert_eq!(tau, batched_tau);
+    assert_eq!(p_aux, batched_p_aux);
+    assert!(replica == batched_replica, "replicas differ");
+    assert!(tree_files == batched_tree_files, "tree files differ");
+}
+
 table_tests! {
     test_prove_verify_fixed {
        test_stacked_porep_prove_verify(64);
@@ -567,6 +670,7 @@ fn test_generate_labels_aux(
         DiskTree<PoseidonHasher, U8, U8, U2>,
         Sha256Hasher,
     >::generate_labels_for_decoding(
+        &ProofsContext::default(),
         &graph,
         &unused_layer_challenges,
         &<PoseidonHasher as Hasher>::Domain::try_from_bytes(&replica_id).unwrap(),
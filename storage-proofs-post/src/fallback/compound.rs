@@ -3,22 +3,27 @@ use std::marker::PhantomData;
 use anyhow::{anyhow, ensure};
 use bellperson::{
     bls::{Bls12, Fr},
-    Circuit,
+    groth16, Circuit,
 };
 use filecoin_hashers::Hasher;
 use sha2::{Digest, Sha256};
 use storage_proofs_core::{
-    compound_proof::{CircuitComponent, CompoundProof},
+    compound_proof::{self, CircuitComponent, CompoundProof},
     error::Result,
     gadgets::por::PoRCompound,
     merkle::MerkleTreeTrait,
+    multi_proof::MultiProof,
     parameter_cache::{CacheableParameters, ParameterSetMetadata},
     por,
     proof::ProofScheme,
+    settings::ProofsContext,
     util::NODE_SIZE,
 };
 
-use crate::fallback::{generate_leaf_challenge_inner, FallbackPoSt, FallbackPoStCircuit, Sector};
+use crate::fallback::{
+    generate_leaf_challenge_inner, FallbackPoSt, FallbackPoStCircuit, PrivateInputs, PublicInputs,
+    Sector,
+};
 
 pub struct FallbackPoStCompound<Tree>
 where
@@ -35,6 +40,40 @@ impl<C: Circuit<Bls12>, P: ParameterSetMetadata, Tree: MerkleTreeTrait> Cacheabl
     }
 }
 
+impl<Tree: 'static + MerkleTreeTrait> FallbackPoStCompound<Tree> {
+    /// Same as `CompoundProof::prove`, for trees cached with the rows to discard configured in
+    /// `ctx`.
+    pub fn prove_with_context<'a, 'b>(
+        ctx: &ProofsContext,
+        pub_params: &compound_proof::PublicParams<'a, FallbackPoSt<'a, Tree>>,
+        pub_in: &PublicInputs<'a, <Tree::Hasher as Hasher>::Domain>,
+        priv_in: &PrivateInputs<'a, Tree>,
+        groth_params: &'b groth16::MappedParameters<Bls12>,
+    ) -> Result<MultiProof<'b>> {
+        let partition_count = Self::partition_count(pub_params);
+
+        // This will always run at least once, since there cannot be zero partitions.
+        ensure!(partition_count > 0, "There must be partitions");
+
+        let vanilla_proofs = FallbackPoSt::<'a, Tree>::prove_all_partitions_with_context(
+            ctx,
+            &pub_params.vanilla_params,
+            pub_in,
+            priv_in,
+            partition_count,
+        )?;
+
+        let sanity_check = FallbackPoSt::<'a, Tree>::verify_all_partitions(
+            &pub_params.vanilla_params,
+            pub_in,
+            &vanilla_proofs,
+        )?;
+        ensure!(sanity_check, "sanity check failed");
+
+        Self::prove_with_vanilla(pub_params, pub_in, vanilla_proofs, groth_params)
+    }
+}
+
 impl<'a, Tree: 'static + MerkleTreeTrait>
     CompoundProof<'a, FallbackPoSt<'a, Tree>, FallbackPoStCircuit<Tree>>
     for FallbackPoStCompound<Tree>
@@ -19,7 +19,8 @@ use storage_proofs_core::{
     parameter_cache::ParameterSetMetadata,
     proof::ProofScheme,
     sector::SectorId,
-    util::{default_rows_to_discard, NODE_SIZE},
+    settings::ProofsContext,
+    util::{default_rows_to_discard_with_context, NODE_SIZE},
 };
 
 #[derive(Debug, Clone)]
@@ -169,6 +170,167 @@ where
     _t: PhantomData<&'a Tree>,
 }
 
+impl<'a, Tree: 'a + MerkleTreeTrait> FallbackPoSt<'a, Tree> {
+    /// Same as `ProofScheme::prove_all_partitions`, for trees cached with the rows to discard
+    /// configured in `ctx`.
+    pub fn prove_all_partitions_with_context(
+        ctx: &ProofsContext,
+        pub_params: &PublicParams,
+        pub_inputs: &PublicInputs<'a, <Tree::Hasher as Hasher>::Domain>,
+        priv_inputs: &PrivateInputs<'a, Tree>,
+        partition_count: usize,
+    ) -> Result<Vec<Proof<Tree::Proof>>> {
+        ensure!(
+            priv_inputs.sectors.len() == pub_inputs.sectors.len(),
+            "inconsistent number of private and public sectors {} != {}",
+            priv_inputs.sectors.len(),
+            pub_inputs.sectors.len(),
+        );
+
+        let num_sectors_per_chunk = pub_params.sector_count;
+        let num_sectors = pub_inputs.sectors.len();
+
+        ensure!(
+            num_sectors <= partition_count * num_sectors_per_chunk,
+            "cannot prove the provided number of sectors: {} > {} * {}",
+            num_sectors,
+            partition_count,
+            num_sectors_per_chunk,
+        );
+
+        let mut partition_proofs = Vec::new();
+
+        // Use `BTreeSet` so failure result will be canonically ordered (sorted).
+        let mut faulty_sectors = BTreeSet::new();
+
+        for (j, (pub_sectors_chunk, priv_sectors_chunk)) in pub_inputs
+            .sectors
+            .chunks(num_sectors_per_chunk)
+            .zip(priv_inputs.sectors.chunks(num_sectors_per_chunk))
+            .enumerate()
+        {
+            let (mut proofs, mut faults) = pub_sectors_chunk
+                .par_iter()
+                .zip(priv_sectors_chunk.par_iter())
+                .enumerate()
+                .map(|(i, (pub_sector, priv_sector))| {
+                    let sector_id = pub_sector.id;
+                    let tree = priv_sector.tree;
+                    let tree_leafs = tree.leafs();
+                    let rows_to_discard = default_rows_to_discard_with_context(
+                        ctx,
+                        tree_leafs,
+                        Tree::Arity::to_usize(),
+                    );
+
+                    trace!(
+                        "Generating proof for tree leafs {} and arity {}",
+                        tree_leafs,
+                        Tree::Arity::to_usize(),
+                    );
+
+                    // avoid rehashing fixed inputs
+                    let mut challenge_hasher = Sha256::new();
+                    challenge_hasher.update(AsRef::<[u8]>::as_ref(&pub_inputs.randomness));
+                    challenge_hasher.update(&u64::from(sector_id).to_le_bytes()[..]);
+
+                    let (inclusion_proofs, faults) = (0..pub_params.challenge_count)
+                        .into_par_iter()
+                        .fold(
+                            || (Vec::new(), BTreeSet::new()),
+                            |(mut inclusion_proofs, mut faults), n| {
+                                let challenge_index =
+                                    ((j * num_sectors_per_chunk + i) * pub_params.challenge_count
+                                        + n) as u64;
+                                let challenged_leaf = generate_leaf_challenge_inner::<
+                                    <Tree::Hasher as Hasher>::Domain,
+                                >(
+                                    challenge_hasher.clone(),
+                                    pub_params,
+                                    challenge_index,
+                                );
+                                let proof = tree.gen_cached_proof(
+                                    challenged_leaf as usize,
+                                    Some(rows_to_discard),
+                                );
+
+                                match proof {
+                                    Ok(proof) => {
+                                        if proof.validate(challenged_leaf as usize)
+                                            && proof.root() == priv_sector.comm_r_last
+                                            && pub_sector.comm_r
+                                                == <Tree::Hasher as Hasher>::Function::hash2(
+                                                    &priv_sector.comm_c,
+                                                    &priv_sector.comm_r_last,
+                                                )
+                                        {
+                                            inclusion_proofs.push(proof);
+                                        } else {
+                                            error!("faulty sector: {:?}", sector_id);
+                                            faults.insert(sector_id);
+                                        }
+                                    }
+                                    Err(err) => {
+                                        error!("faulty sector: {:?} ({:?})", sector_id, err);
+                                        faults.insert(sector_id);
+                                    }
+                                }
+                                (inclusion_proofs, faults)
+                            },
+                        )
+                        .reduce(
+                            || (Vec::new(), BTreeSet::new()),
+                            |(mut inclusion_proofs, mut faults), (p, f)| {
+                                inclusion_proofs.extend(p);
+                                faults.extend(f);
+                                (inclusion_proofs, faults)
+                            },
+                        );
+
+                    (
+                        SectorProof {
+                            inclusion_proofs,
+                            comm_c: priv_sector.comm_c,
+                            comm_r_last: priv_sector.comm_r_last,
+                        },
+                        faults,
+                    )
+                })
+                .fold(
+                    || (Vec::new(), BTreeSet::new()),
+                    |(mut sector_proofs, mut sector_faults), (sector_proof, mut faults)| {
+                        sector_faults.append(&mut faults);
+                        sector_proofs.push(sector_proof);
+                        (sector_proofs, sector_faults)
+                    },
+                )
+                .reduce(
+                    || (Vec::new(), BTreeSet::new()),
+                    |(mut sector_proofs, mut sector_faults), (proofs, mut faults)| {
+                        sector_proofs.extend(proofs);
+                        sector_faults.append(&mut faults);
+                        (sector_proofs, sector_faults)
+                    },
+                );
+
+            // If there were less than the required number of sectors provided, we duplicate the last one
+            // to pad the proof out, such that it works in the circuit part.
+            while proofs.len() < num_sectors_per_chunk {
+                proofs.push(proofs[proofs.len() - 1].clone());
+            }
+
+            partition_proofs.push(Proof { sectors: proofs });
+            faulty_sectors.append(&mut faults);
+        }
+
+        if faulty_sectors.is_empty() {
+            Ok(partition_proofs)
+        } else {
+            Err(Error::FaultySectors(faulty_sectors.into_iter().collect()).into())
+        }
+    }
+}
+
 pub fn generate_sector_challenges<T: Domain>(
     randomness: T,
     challenge_count: usize,
@@ -254,6 +416,21 @@ pub fn vanilla_proof<Tree: MerkleTreeTrait>(
     sector_id: SectorId,
     priv_inputs: &PrivateInputs<'_, Tree>,
     challenges: &[u64],
+) -> Result<Proof<Tree::Proof>> {
+    vanilla_proof_with_context(
+        &ProofsContext::default(),
+        sector_id,
+        priv_inputs,
+        challenges,
+    )
+}
+
+/// Same as `vanilla_proof`, for a tree cached with the rows to discard configured in `ctx`.
+pub fn vanilla_proof_with_context<Tree: MerkleTreeTrait>(
+    ctx: &ProofsContext,
+    sector_id: SectorId,
+    priv_inputs: &PrivateInputs<'_, Tree>,
+    challenges: &[u64],
 ) -> Result<Proof<Tree::Proof>> {
     ensure!(
         priv_inputs.sectors.len() == 1,
@@ -266,7 +443,8 @@ pub fn vanilla_proof<Tree: MerkleTreeTrait>(
     let tree = priv_sector.tree;
 
     let tree_leafs = tree.leafs();
-    let rows_to_discard = default_rows_to_discard(tree_leafs, Tree::Arity::to_usize());
+    let rows_to_discard =
+        default_rows_to_discard_with_context(ctx, tree_leafs, Tree::Arity::to_usize());
 
     trace!(
         "Generating proof for tree leafs {} and arity {}",
@@ -343,151 +521,13 @@ impl<'a, Tree: 'a + MerkleTreeTrait> ProofScheme<'a> for FallbackPoSt<'a, Tree>
         priv_inputs: &'b Self::PrivateInputs,
         partition_count: usize,
     ) -> Result<Vec<Self::Proof>> {
-        ensure!(
-            priv_inputs.sectors.len() == pub_inputs.sectors.len(),
-            "inconsistent number of private and public sectors {} != {}",
-            priv_inputs.sectors.len(),
-            pub_inputs.sectors.len(),
-        );
-
-        let num_sectors_per_chunk = pub_params.sector_count;
-        let num_sectors = pub_inputs.sectors.len();
-
-        ensure!(
-            num_sectors <= partition_count * num_sectors_per_chunk,
-            "cannot prove the provided number of sectors: {} > {} * {}",
-            num_sectors,
+        Self::prove_all_partitions_with_context(
+            &ProofsContext::default(),
+            pub_params,
+            pub_inputs,
+            priv_inputs,
             partition_count,
-            num_sectors_per_chunk,
-        );
-
-        let mut partition_proofs = Vec::new();
-
-        // Use `BTreeSet` so failure result will be canonically ordered (sorted).
-        let mut faulty_sectors = BTreeSet::new();
-
-        for (j, (pub_sectors_chunk, priv_sectors_chunk)) in pub_inputs
-            .sectors
-            .chunks(num_sectors_per_chunk)
-            .zip(priv_inputs.sectors.chunks(num_sectors_per_chunk))
-            .enumerate()
-        {
-            let (mut proofs, mut faults) = pub_sectors_chunk
-                .par_iter()
-                .zip(priv_sectors_chunk.par_iter())
-                .enumerate()
-                .map(|(i, (pub_sector, priv_sector))| {
-                    let sector_id = pub_sector.id;
-                    let tree = priv_sector.tree;
-                    let tree_leafs = tree.leafs();
-                    let rows_to_discard =
-                        default_rows_to_discard(tree_leafs, Tree::Arity::to_usize());
-
-                    trace!(
-                        "Generating proof for tree leafs {} and arity {}",
-                        tree_leafs,
-                        Tree::Arity::to_usize(),
-                    );
-
-                    // avoid rehashing fixed inputs
-                    let mut challenge_hasher = Sha256::new();
-                    challenge_hasher.update(AsRef::<[u8]>::as_ref(&pub_inputs.randomness));
-                    challenge_hasher.update(&u64::from(sector_id).to_le_bytes()[..]);
-
-                    let (inclusion_proofs, faults) = (0..pub_params.challenge_count)
-                        .into_par_iter()
-                        .fold(
-                            || (Vec::new(), BTreeSet::new()),
-                            |(mut inclusion_proofs, mut faults), n| {
-                                let challenge_index =
-                                    ((j * num_sectors_per_chunk + i) * pub_params.challenge_count
-                                        + n) as u64;
-                                let challenged_leaf = generate_leaf_challenge_inner::<
-                                    <Tree::Hasher as Hasher>::Domain,
-                                >(
-                                    challenge_hasher.clone(),
-                                    pub_params,
-                                    challenge_index,
-                                );
-                                let proof = tree.gen_cached_proof(
-                                    challenged_leaf as usize,
-                                    Some(rows_to_discard),
-                                );
-
-                                match proof {
-                                    Ok(proof) => {
-                                        if proof.validate(challenged_leaf as usize)
-                                            && proof.root() == priv_sector.comm_r_last
-                                            && pub_sector.comm_r
-                                                == <Tree::Hasher as Hasher>::Function::hash2(
-                                                    &priv_sector.comm_c,
-                                                    &priv_sector.comm_r_last,
-                                                )
-                                        {
-                                            inclusion_proofs.push(proof);
-                                        } else {
-                                            error!("faulty sector: {:?}", sector_id);
-                                            faults.insert(sector_id);
-                                        }
-                                    }
-                                    Err(err) => {
-                                        error!("faulty sector: {:?} ({:?})", sector_id, err);
-                                        faults.insert(sector_id);
-                                    }
-                                }
-                                (inclusion_proofs, faults)
-                            },
-                        )
-                        .reduce(
-                            || (Vec::new(), BTreeSet::new()),
-                            |(mut inclusion_proofs, mut faults), (p, f)| {
-                                inclusion_proofs.extend(p);
-                                faults.extend(f);
-                                (inclusion_proofs, faults)
-                            },
-                        );
-
-                    (
-                        SectorProof {
-                            inclusion_proofs,
-                            comm_c: priv_sector.comm_c,
-                            comm_r_last: priv_sector.comm_r_last,
-                        },
-                        faults,
-                    )
-                })
-                .fold(
-                    || (Vec::new(), BTreeSet::new()),
-                    |(mut sector_proofs, mut sector_faults), (sector_proof, mut faults)| {
-                        sector_faults.append(&mut faults);
-                        sector_proofs.push(sector_proof);
-                        (sector_proofs, sector_faults)
-                    },
-                )
-                .reduce(
-                    || (Vec::new(), BTreeSet::new()),
-                    |(mut sector_proofs, mut sector_faults), (proofs, mut faults)| {
-                        sector_proofs.extend(proofs);
-                        sector_faults.append(&mut faults);
-                        (sector_proofs, sector_faults)
-                    },
-                );
-
-            // If there were less than the required number of sectors provided, we duplicate the last one
-            // to pad the proof out, such that it works in the circuit part.
-            while proofs.len() < num_sectors_per_chunk {
-                proofs.push(proofs[proofs.len() - 1].clone());
-            }
-
-            partition_proofs.push(Proof { sectors: proofs });
-            faulty_sectors.append(&mut faults);
-        }
-
-        if faulty_sectors.is_empty() {
-            Ok(partition_proofs)
-        } else {
-            Err(Error::FaultySectors(faulty_sectors.into_iter().collect()).into())
-        }
+        )
     }
 
     fn verify_all_partitions(